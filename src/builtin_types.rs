@@ -1,9 +1,13 @@
 use crate::event::*;
 use crate::generator::{GenModFun, Generator};
+use crate::generator_link::GeneratorLink;
 use crate::generator_processor::GeneratorProcessor;
+use crate::music_theory::{Scale, Tuning};
 use crate::parameter::*;
+use crate::real_time_streaming::RecordingOptions;
 use dashmap::DashMap;
 use std::collections::{BTreeSet, HashMap};
+use std::sync;
 
 use ruffbox_synth::building_blocks::SynthParameterLabel;
 
@@ -21,6 +25,8 @@ pub enum ConfigParameter {
     Numeric(f32),
     Dynamic(DynVal),
     Symbolic(String),
+    Scale(sync::Arc<Scale>),
+    Tuning(sync::Arc<Tuning>),
 }
 
 // only one so far
@@ -30,33 +36,53 @@ pub enum BuiltinGlobalParameters {
     GlobalTimeModifier,
     GlobalLatency, // latency between language and dsp
     DefaultDuration,
+    GlobalScale,
+    GlobalTuning,
+    UserDefined(String), // set with (global 'name value)
 }
 
 pub type GlobalParameters = DashMap<BuiltinGlobalParameters, ConfigParameter>;
 
+// the ruffbox buffers a wavematrix can be captured from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BufferSource {
+    Live(usize),
+    Freeze(usize),
+}
+
 #[derive(Clone)]
 pub enum Command {
     Clear,                                                               // clear the entire session
-    Tmod(DynVal),         // set global time mod parameter
-    Latency(DynVal),      // set global latency parameter
-    Bpm(f32),             // set default tempo in bpm
-    DefaultDuration(f32), // set default duration in milliseconds
-    GlobRes(f32),         // global resources for lifemodel algorithm
+    Tmod(DynVal),                                    // set global time mod parameter
+    Latency(DynVal),                                 // set global latency parameter
+    Bpm(f32),                                        // set default tempo in bpm
+    DefaultDuration(f32),                            // set default duration in milliseconds
+    Scale(Scale),                                    // set global scale
+    Tuning(Tuning),                                  // set global tuning
+    LoadTuning(String, Option<String>, Option<f32>), // scl path, kbm path, reference frequency
+    GlobRes(f32),                                    // global resources for lifemodel algorithm
+    SetGlobal(String, f32),                          // set user-defined global value
     GlobalRuffboxParams(HashMap<SynthParameterLabel, ParameterValue>), // global ruffbox params
     LoadSampleAsWavematrix(String, String, String, (usize, usize), f32), // key, path, method, matrix size, start
-    LoadSample(String, Vec<String>, String, bool), // set (events), keyword, path, downmix_stereo
-    LoadSampleSet(String, bool),                   // set path, downmix stereo
-    LoadSampleSets(String, bool),                  // top level sets set path
-    LoadPart((String, Part)),                      // set (events), keyword, path
-    StepPart(String),                              // step through specified path
-    FreezeBuffer(usize, usize),                    // freeze live buffer
-    ExportDotStatic((String, Generator)),          // filename, generator
-    ExportDotRunning((String, BTreeSet<String>)),  // filename, generator id
-    ExportDotPart((String, String)),               // filename, part name
-    Once((Vec<StaticEvent>, Vec<ControlEvent>)),   // execute event(s) once
-    ConnectVisualizer,                             // connect visualizer
-    StartRecording(Option<String>, bool),          // start recording, prefix, input
-    StopRecording,                                 // stop recording ...
+    CaptureWavematrix(String, BufferSource, String, (usize, usize), f32), // key, buffer, method, matrix size, start
+    LoadSample(String, Vec<String>, String, bool, Option<usize>), // set (events), keyword, path, downmix_stereo, max duration
+    LoadSampleSet(String, bool, Option<usize>), // set path, downmix stereo, max duration
+    LoadSampleSets(String, bool, Option<usize>), // top level sets set path, downmix stereo, max duration
+    UnloadSampleSet(String),                     // set name
+    ReloadSampleSet(String),                     // set name
+    LoadPart((String, Part)),                    // set (events), keyword, path
+    StepPart(String),                            // step through specified path
+    FreezeBuffer(usize, usize),                  // freeze live buffer
+    ExportDotStatic((String, Generator)),        // filename, generator
+    ExportDotRunning((String, BTreeSet<String>)), // filename, generator id
+    ExportDotPart((String, String)),             // filename, part name
+    Once((Vec<StaticEvent>, Vec<ControlEvent>)), // execute event(s) once
+    ConnectVisualizer,                           // connect visualizer
+    StartRecording(Option<String>, RecordingOptions), // start recording, prefix, options
+    StopRecording,                               // stop recording ...
+    SaveLast(f32, Option<String>), // save the last seconds from the retro buffer, prefix
+    Link(GeneratorLink),           // link a generator to another generator or context
+    Unlink(String),                // remove all links from a generator
 }
 
 #[derive(Clone)]
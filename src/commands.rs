@@ -2,8 +2,9 @@ use parking_lot::Mutex;
 use std::{
     collections::{BTreeSet, HashMap, HashSet},
     fs,
-    path::Path,
-    sync,
+    path::{Path, PathBuf},
+    sync, thread,
+    time::{Duration, SystemTime},
 };
 use vom_rs::pfa;
 
@@ -16,59 +17,61 @@ use crate::builtin_types::*;
 use crate::event::*;
 use crate::event_helpers::*;
 use crate::generator::*;
+use crate::generator_link::{add_link, remove_links, GeneratorLink};
+use crate::live_buffer::LiveBufferMirror;
 use crate::load_audio_file;
+use crate::messages;
+use crate::music_theory::{Scale, Tuning};
+use crate::parameter::modifier::EvalContext;
 use crate::parameter::*;
 use crate::parser::eval;
-use crate::parser::FunctionMap;
+use crate::parser::{FunctionCategory, FunctionMap};
 use crate::real_time_streaming;
-use crate::sample_set::SampleAndWavematrixSet;
+use crate::sample_set::{SampleAndWavematrixSet, SampleInfo, DEFAULT_MAX_SAMPLE_DURATION};
 use crate::session::*;
+use crate::standard_library::{SAMPLE_KEYWORDS, SOUND_KEYWORDS};
+use crate::stream_player;
 use chrono::Local;
-use directories_next::ProjectDirs;
 use std::sync::atomic::Ordering;
 
 pub fn freeze_buffer<const BUFSIZE: usize, const NCHAN: usize>(
     ruffbox: &sync::Arc<RuffboxControls<BUFSIZE, NCHAN>>,
+    live_buffer: &Option<sync::Arc<LiveBufferMirror>>,
     freezbuf: usize,
     inbuf: usize,
 ) {
     ruffbox.freeze_buffer(freezbuf, inbuf);
+    // keep the megra-side copy in sync
+    if let Some(lb) = live_buffer {
+        lb.freeze(freezbuf, inbuf);
+    }
 }
 
-pub fn load_sample_as_wavematrix(
+/// turn a (mono) buffer into a wavematrix and store it under the given key
+fn insert_wavematrix(
     sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
     key: String,
-    path: String,
+    sample_buffer: &[f32],
     method: &str,
     matrix_size: (usize, usize),
     start: f32,
 ) {
-    let mut sample_buffer: Vec<f32> = Vec::new();
-    let mut reader = claxon::FlacReader::open(path).unwrap();
-
-    // decode to f32
-    let max_val = (i32::MAX >> (32 - reader.streaminfo().bits_per_sample)) as f32;
-    for sample in reader.samples() {
-        let s = sample.unwrap() as f32 / max_val;
-        sample_buffer.push(s);
-    }
-
     let wavematrix_raw = match method {
-        "raw" => wavetableize(&sample_buffer, matrix_size, start, WavetableizeMethod::Raw),
+        "raw" => wavetableize(sample_buffer, matrix_size, start, WavetableizeMethod::Raw),
         "smooth" => wavetableize(
-            &sample_buffer,
+            sample_buffer,
             matrix_size,
             start,
             WavetableizeMethod::Smooth,
         ),
         "supersmooth" => wavetableize(
-            &sample_buffer,
+            sample_buffer,
             matrix_size,
             start,
             WavetableizeMethod::Supersmooth,
         ),
         _ => wavetableize(
-            &sample_buffer,
+            sample_buffer,
             matrix_size,
             start,
             WavetableizeMethod::Supersmooth,
@@ -88,6 +91,84 @@ pub fn load_sample_as_wavematrix(
     sample_set.lock().insert_wavematrix(key, wavematrix);
 }
 
+pub fn load_sample_as_wavematrix(
+    sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    key: String,
+    path: String,
+    method: &str,
+    matrix_size: (usize, usize),
+    start: f32,
+    samplerate: f32,
+) {
+    if let Some((_, _, channels, sample_buffer)) =
+        load_audio_file::load_audio_file(&path, samplerate)
+    {
+        // wavematrices are always mono
+        let mono_buffer: Vec<f32> = if channels > 1 {
+            sample_buffer
+                .chunks(channels as usize)
+                .map(|x| x.iter().sum::<f32>() / channels as f32)
+                .collect()
+        } else {
+            sample_buffer
+        };
+        insert_wavematrix(sample_set, key, &mono_buffer, method, matrix_size, start);
+    } else {
+        messages::warning(format!("can't load wavematrix from {}", path));
+    }
+}
+
+/// Capture a wavematrix from the megra-side copy of a live or freeze buffer.
+pub fn capture_wavematrix(
+    live_buffer: &Option<sync::Arc<LiveBufferMirror>>,
+    sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    key: String,
+    source: BufferSource,
+    method: &str,
+    matrix_size: (usize, usize),
+    start: f32,
+) {
+    let maybe_content = live_buffer.as_ref().and_then(|lb| match source {
+        BufferSource::Live(n) => lb.contents(n),
+        BufferSource::Freeze(n) => lb.frozen_contents(n),
+    });
+    if let Some(content) = maybe_content {
+        insert_wavematrix(sample_set, key, &content, method, matrix_size, start);
+    } else {
+        match source {
+            BufferSource::Live(n) => messages::warning(format!("there's no live buffer {}", n + 1)),
+            BufferSource::Freeze(n) => messages::warning(format!(
+                "there's no freeze buffer {}, or nothing has been frozen into it yet",
+                n + 1
+            )),
+        }
+    }
+}
+
+/// extract keywords from the filename
+fn sample_keywords(path: &str, keywords: &mut Vec<String>) -> HashSet<String> {
+    let mut keyword_set = HashSet::new();
+    for k in keywords.drain(..) {
+        keyword_set.insert(k);
+    }
+
+    let path2 = Path::new(path);
+    if let Some(os_filename) = path2.file_stem() {
+        if let Some(str_filename) = os_filename.to_str() {
+            let tokens = str_filename.split(|c| c == ' ' || c == '_' || c == '-' || c == '.');
+            for token in tokens {
+                keyword_set.insert(token.to_lowercase().to_string());
+            }
+        }
+    }
+    keyword_set
+}
+
+/// Load a sample into a ruffbox buffer. Files that are longer than the
+/// stream threshold are registered to be streamed from disk instead.
+/// The duration (which determines the default sustain) is capped at
+/// max_duration (or the default, if none is given).
+#[allow(clippy::too_many_arguments)]
 pub fn load_sample<const BUFSIZE: usize, const NCHAN: usize>(
     function_map: &sync::Arc<Mutex<FunctionMap>>,
     ruffbox: &sync::Arc<RuffboxControls<BUFSIZE, NCHAN>>,
@@ -96,29 +177,39 @@ pub fn load_sample<const BUFSIZE: usize, const NCHAN: usize>(
     keywords: &mut Vec<String>,
     path: String,
     downmix_stereo: bool,
+    max_duration: Option<usize>,
 ) {
-    if let Some((mut duration, samplerate, channels, mut sample_buffer)) = if path
-        .as_str()
-        .to_lowercase()
-        .as_str()
-        .trim()
-        .ends_with(".flac")
-    {
-        load_audio_file::load_flac(&path, ruffbox.samplerate)
-    } else if path
-        .as_str()
-        .to_lowercase()
-        .as_str()
-        .trim()
-        .ends_with(".wav")
+    // check whether this should be streamed before decoding
+    // the whole file ...
+    if let Some(file_duration) = load_audio_file::file_duration(&path) {
+        let threshold = sample_set.lock().stream_threshold;
+        if file_duration > threshold {
+            let keyword_set = sample_keywords(&path, keywords);
+            messages::info(format!(
+                "sample path: {} dur: {} exceeds {}, will be streamed",
+                path, file_duration, threshold
+            ));
+            sample_set.lock().insert_stream(
+                set,
+                SampleInfo {
+                    key: keyword_set,
+                    bufnum: 0, // will be replaced by stream id
+                    duration: file_duration,
+                    path,
+                    downmix_stereo,
+                    max_duration,
+                },
+            );
+            return;
+        }
+    }
+
+    if let Some((mut duration, samplerate, channels, mut sample_buffer)) =
+        load_audio_file::load_audio_file(&path, ruffbox.samplerate)
     {
-        load_audio_file::load_wav(&path, ruffbox.samplerate)
-    } else {
-        None
-    } {
-        // max duration ten seconds
-        if duration > 10000 {
-            duration = 10000;
+        let max_dur = max_duration.unwrap_or(DEFAULT_MAX_SAMPLE_DURATION);
+        if duration > max_dur {
+            duration = max_dur;
         }
 
         // downmix
@@ -146,22 +237,9 @@ pub fn load_sample<const BUFSIZE: usize, const NCHAN: usize>(
             ruffbox.load_mono_sample(&mut sample_buffer, true, samplerate)
         };
 
-        let mut keyword_set = HashSet::new();
-        for k in keywords.drain(..) {
-            keyword_set.insert(k);
-        }
+        let keyword_set = sample_keywords(&path, keywords);
 
-        let path2 = Path::new(&path);
-        if let Some(os_filename) = path2.file_stem() {
-            if let Some(str_filename) = os_filename.to_str() {
-                let tokens = str_filename.split(|c| c == ' ' || c == '_' || c == '-' || c == '.');
-                for token in tokens {
-                    keyword_set.insert(token.to_lowercase().to_string());
-                }
-            }
-        }
-
-        println!(
+        messages::info(format!(
             "sample path: {} channels: {} dur: {} orig sr: {} ruf sr: {} resampled: {}",
             path,
             channels,
@@ -169,29 +247,33 @@ pub fn load_sample<const BUFSIZE: usize, const NCHAN: usize>(
             samplerate,
             ruffbox.samplerate,
             samplerate != ruffbox.samplerate
-        );
+        ));
 
-        sample_set
-            .lock()
-            .insert(set.clone(), keyword_set, bufnum, duration);
-        function_map
-            .lock()
-            .fmap
-            .insert(set, eval::events::sound::sound);
+        sample_set.lock().insert(
+            set.clone(),
+            SampleInfo {
+                key: keyword_set,
+                bufnum,
+                duration,
+                path: path.clone(),
+                downmix_stereo,
+                max_duration,
+            },
+        );
+        function_map.lock().register(
+            FunctionCategory::Sample,
+            &set,
+            &format!("'keyword... {} {}", SOUND_KEYWORDS, SAMPLE_KEYWORDS),
+            eval::events::sound::sound,
+        );
     } else {
-        println!("can't load sample {}", path);
+        messages::warning(format!("can't load sample {}", path));
     }
 }
 
-pub fn load_sample_set<const BUFSIZE: usize, const NCHAN: usize>(
-    function_map: &sync::Arc<Mutex<FunctionMap>>,
-    ruffbox: &sync::Arc<RuffboxControls<BUFSIZE, NCHAN>>,
-    sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
-    samples_path: &Path,
-    downmix_stereo: bool,
-) {
-    // determine set name or use default
-    let set_name = if let Some(os_filename) = samples_path.file_stem() {
+/// determine set name from folder or use default
+fn sample_set_name(samples_path: &Path) -> String {
+    if let Some(os_filename) = samples_path.file_stem() {
         if let Some(str_filename) = os_filename.to_str() {
             if str_filename.chars().next().unwrap().is_numeric() {
                 let mut owned_string: String = "_".to_owned();
@@ -205,31 +287,58 @@ pub fn load_sample_set<const BUFSIZE: usize, const NCHAN: usize>(
         }
     } else {
         "default".to_string()
-    };
+    }
+}
+
+/// only flac and wav files are considered samples
+fn is_sample_file(path: &Path) -> bool {
+    if path.is_file() {
+        if let Some(ext) = path.extension() {
+            if let Ok(ext_str) = ext.to_os_string().into_string() {
+                let ext_str_lc = ext_str.as_str().to_lowercase();
+                return ext_str_lc == "flac" || ext_str_lc == "wav";
+            }
+        }
+    }
+    false
+}
+
+pub fn load_sample_set<const BUFSIZE: usize, const NCHAN: usize>(
+    function_map: &sync::Arc<Mutex<FunctionMap>>,
+    ruffbox: &sync::Arc<RuffboxControls<BUFSIZE, NCHAN>>,
+    sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    samples_path: &Path,
+    downmix_stereo: bool,
+    max_duration: Option<usize>,
+) {
+    let set_name = sample_set_name(samples_path);
 
     if let Ok(entries) = fs::read_dir(samples_path) {
+        messages::info(format!("loading sample set {} ...", set_name));
+        let mut num_files = 0;
         for entry in entries.flatten() {
             let path = entry.path();
             // only consider files here ...
-            if path.is_file() {
-                if let Some(ext) = path.extension() {
-                    if let Ok(ext_str) = ext.to_os_string().into_string() {
-                        let ext_str_lc = ext_str.as_str().to_lowercase();
-                        if ext_str_lc == "flac" || ext_str_lc == "wav" {
-                            load_sample(
-                                function_map,
-                                ruffbox,
-                                sample_set,
-                                set_name.clone(),
-                                &mut Vec::new(),
-                                path.to_str().unwrap().to_string(),
-                                downmix_stereo,
-                            );
-                        }
-                    }
-                }
+            if is_sample_file(&path) {
+                load_sample(
+                    function_map,
+                    ruffbox,
+                    sample_set,
+                    set_name.clone(),
+                    &mut Vec::new(),
+                    path.to_str().unwrap().to_string(),
+                    downmix_stereo,
+                    max_duration,
+                );
+                num_files += 1;
             }
         }
+        messages::info(format!(
+            "loaded sample set {} ({} files)",
+            set_name, num_files
+        ));
+    } else {
+        messages::warning(format!("can't read sample folder {:?}", samples_path));
     }
 }
 
@@ -239,9 +348,17 @@ pub fn load_sample_set_string<const BUFSIZE: usize, const NCHAN: usize>(
     sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
     samples_path: String,
     downmix_stereo: bool,
+    max_duration: Option<usize>,
 ) {
     let path = Path::new(&samples_path);
-    load_sample_set(function_map, ruffbox, sample_set, path, downmix_stereo);
+    load_sample_set(
+        function_map,
+        ruffbox,
+        sample_set,
+        path,
+        downmix_stereo,
+        max_duration,
+    );
 }
 
 pub fn load_sample_sets<const BUFSIZE: usize, const NCHAN: usize>(
@@ -250,9 +367,17 @@ pub fn load_sample_sets<const BUFSIZE: usize, const NCHAN: usize>(
     sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
     folder_path: String,
     downmix_stereo: bool,
+    max_duration: Option<usize>,
 ) {
     let root_path = Path::new(&folder_path);
-    load_sample_sets_path(function_map, ruffbox, sample_set, root_path, downmix_stereo);
+    load_sample_sets_path(
+        function_map,
+        ruffbox,
+        sample_set,
+        root_path,
+        downmix_stereo,
+        max_duration,
+    );
 }
 
 pub fn load_sample_sets_path<const BUFSIZE: usize, const NCHAN: usize>(
@@ -261,14 +386,184 @@ pub fn load_sample_sets_path<const BUFSIZE: usize, const NCHAN: usize>(
     sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
     root_path: &Path,
     downmix_stereo: bool,
+    max_duration: Option<usize>,
 ) {
     if let Ok(entries) = fs::read_dir(root_path) {
         for entry in entries.flatten() {
             let path = entry.path();
             if path.is_dir() {
-                load_sample_set(function_map, ruffbox, sample_set, &path, downmix_stereo);
+                load_sample_set(
+                    function_map,
+                    ruffbox,
+                    sample_set,
+                    &path,
+                    downmix_stereo,
+                    max_duration,
+                );
+            }
+        }
+    }
+}
+
+/// Remove a sample set. Running generators that reference other sets
+/// aren't affected. The set's ruffbox buffers aren't re-used (ruffbox
+/// can't free them), so generators that still play the set keep
+/// playing the old samples instead of whatever gets loaded next.
+pub fn unload_sample_set(sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>, set: &str) {
+    // the function map entry is kept, as the set name might shadow
+    // a built-in sound event, and sample events with an empty set
+    // won't evaluate anyway ...
+    let maybe_infos = sample_set.lock().remove_set(set);
+    if let Some(infos) = maybe_infos {
+        messages::info(format!(
+            "unloaded sample set {} ({} samples)",
+            set,
+            infos.len()
+        ));
+    } else {
+        messages::warning(format!("can't unload unknown sample set {}", set));
+    }
+}
+
+/// Reload all the files a sample set was loaded from,
+/// i.e. to pick up changes made on disk. The samples are
+/// loaded into new buffers, the old ones stay reserved.
+pub fn reload_sample_set<const BUFSIZE: usize, const NCHAN: usize>(
+    function_map: &sync::Arc<Mutex<FunctionMap>>,
+    ruffbox: &sync::Arc<RuffboxControls<BUFSIZE, NCHAN>>,
+    sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    set: String,
+) {
+    let maybe_infos = sample_set.lock().remove_set(&set);
+    if let Some(mut infos) = maybe_infos {
+        for info in infos.drain(..) {
+            if Path::new(&info.path).exists() {
+                let mut keywords = info.key.into_iter().collect();
+                load_sample(
+                    function_map,
+                    ruffbox,
+                    sample_set,
+                    set.clone(),
+                    &mut keywords,
+                    info.path,
+                    info.downmix_stereo,
+                    info.max_duration,
+                );
+            } else {
+                messages::warning(format!("sample {} vanished, won't reload", info.path));
             }
         }
+    } else {
+        messages::warning(format!("can't reload unknown sample set {}", set));
+    }
+}
+
+// set name, modification time and size ...
+type SampleFileState = (String, SystemTime, u64);
+
+fn scan_sample_files(root_path: &Path) -> HashMap<PathBuf, SampleFileState> {
+    let mut files = HashMap::new();
+    if let Ok(entries) = fs::read_dir(root_path) {
+        for entry in entries.flatten() {
+            let set_path = entry.path();
+            if !set_path.is_dir() {
+                continue;
+            }
+            let set_name = sample_set_name(&set_path);
+            if let Ok(set_entries) = fs::read_dir(&set_path) {
+                for set_entry in set_entries.flatten() {
+                    let path = set_entry.path();
+                    if !is_sample_file(&path) {
+                        continue;
+                    }
+                    if let Ok(meta) = set_entry.metadata() {
+                        if let Ok(modified) = meta.modified() {
+                            files.insert(path, (set_name.clone(), modified, meta.len()));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    files
+}
+
+/// Watch the samples folder and keep the loaded sample sets in sync with it.
+/// Added files are loaded, changed files are re-loaded and removed files are
+/// removed from their set. Sets that aren't touched stay as they are.
+/// This doesn't return, so it should be called from a separate thread.
+pub fn watch_sample_sets_path<const BUFSIZE: usize, const NCHAN: usize>(
+    function_map: &sync::Arc<Mutex<FunctionMap>>,
+    ruffbox: &sync::Arc<RuffboxControls<BUFSIZE, NCHAN>>,
+    sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    root_path: &Path,
+    downmix_stereo: bool,
+) {
+    messages::info(format!("watching sample folder {:?}", root_path));
+    let mut known = scan_sample_files(root_path);
+    let mut last_scan = known.clone();
+
+    loop {
+        thread::sleep(Duration::from_millis(1000));
+        let current = scan_sample_files(root_path);
+
+        // REMOVED FILES
+        let removed: Vec<PathBuf> = known
+            .keys()
+            .filter(|p| !current.contains_key(*p))
+            .cloned()
+            .collect();
+
+        for path in removed.iter() {
+            if let Some((set, _, _)) = known.remove(path) {
+                let path_str = path.to_str().unwrap().to_string();
+                let removed = sample_set.lock().remove_sample(&set, &path_str);
+                if removed.is_some() {
+                    messages::info(format!("sample {} removed from set {}", path_str, set));
+                }
+            }
+        }
+
+        // ADDED OR CHANGED FILES
+        for (path, state) in current.iter() {
+            // only act once a file didn't change between two scans,
+            // otherwise we might load a file that's still being written
+            if last_scan.get(path) != Some(state) || known.get(path) == Some(state) {
+                continue;
+            }
+
+            let path_str = path.to_str().unwrap().to_string();
+            let (set, _, _) = state;
+
+            // new files are loaded like the rest of their set
+            let mut keywords = Vec::new();
+            let (mut downmix, mut max_duration) = sample_set
+                .lock()
+                .set_options(set)
+                .unwrap_or((downmix_stereo, None));
+            let removed = sample_set.lock().remove_sample(set, &path_str);
+            if let Some(old) = removed {
+                messages::info(format!("sample {} changed, reload", path_str));
+                keywords = old.key.into_iter().collect();
+                downmix = old.downmix_stereo;
+                max_duration = old.max_duration;
+            }
+
+            load_sample(
+                function_map,
+                ruffbox,
+                sample_set,
+                set.clone(),
+                &mut keywords,
+                path_str,
+                downmix,
+                max_duration,
+            );
+
+            known.insert(path.clone(), state.clone());
+        }
+
+        last_scan = current;
     }
 }
 
@@ -277,87 +572,96 @@ pub fn load_part(parts_store: &sync::Arc<Mutex<PartsStore>>, name: String, part:
     ps.insert(name, part);
 }
 
+/// place recordings in the recordings folder, if it exists
+fn recording_path(base_dir: &str, id: String) -> String {
+    let recordings_path = Path::new(base_dir).join("recordings");
+    if recordings_path.exists() {
+        recordings_path
+            .join(id)
+            .into_os_string()
+            .into_string()
+            .unwrap()
+    } else {
+        id
+    }
+}
+
+/// link a generator to another generator or context
+pub fn link<const BUFSIZE: usize, const NCHAN: usize>(
+    session: &sync::Arc<Mutex<Session<BUFSIZE, NCHAN>>>,
+    link: GeneratorLink,
+) {
+    let links = sync::Arc::clone(&session.lock().links);
+    if let Err(e) = add_link(&links, link) {
+        messages::warning(e);
+    }
+}
+
+/// remove all links from a generator
+pub fn unlink<const BUFSIZE: usize, const NCHAN: usize>(
+    session: &sync::Arc<Mutex<Session<BUFSIZE, NCHAN>>>,
+    name: &str,
+) {
+    let links = sync::Arc::clone(&session.lock().links);
+    remove_links(&links, name);
+}
+
 /// start a recording of the output
 pub fn start_recording<const BUFSIZE: usize, const NCHAN: usize>(
     session: &sync::Arc<Mutex<Session<BUFSIZE, NCHAN>>>,
     prefix: Option<String>,
     base_dir: String,
-    rec_input: bool,
+    options: real_time_streaming::RecordingOptions,
 ) {
     let maybe_rec_ctrl = session.lock().rec_control.take();
     if let Some(mut rec_ctrl) = maybe_rec_ctrl {
         //println!("rec state {} {}", rec_ctrl.is_recording_output.load(Ordering::SeqCst) ,rec_ctrl.is_recording_input.load(Ordering::SeqCst));
 
+        // the writer adds the extension (and suffixes for stems or parts)
+        let id = if let Some(p) = prefix {
+            format!("{}_{}", p, Local::now().format("%Y%m%d_%H%M_%S"))
+        } else {
+            format!("megra_recording_{}", Local::now().format("%Y%m%d_%H%M_%S"))
+        };
+
         // OUTPUT RECORDING
         if rec_ctrl.is_recording_output.load(Ordering::SeqCst) {
-            println!("there's already a recording in progress, please stop first !");
+            messages::warning(
+                "there's already a recording in progress, please stop first !".to_string(),
+            );
         } else {
             let maybe_catch = rec_ctrl.catch_out.take();
             //println!("catch none ? {}", maybe_catch.is_none());
             if let Some(catch_out) = maybe_catch {
-                // place in recordings folder
-
-                let id = if let Some(p) = prefix.clone() {
-                    format!("{}_{}_output.wav", p, Local::now().format("%Y%m%d_%H%M_%S"))
-                } else {
-                    format!(
-                        "megra_recording_{}_output.wav",
-                        Local::now().format("%Y%m%d_%H%M_%S")
-                    )
-                };
-
-                let recordings_path = Path::new(&base_dir).join("recordings");
-
-                let file_path = if recordings_path.exists() {
-                    let path = recordings_path.join(id).into_os_string().into_string();
-                    path.unwrap()
-                } else {
-                    id
-                };
+                let file_path = recording_path(&base_dir, format!("{}_output", id));
 
                 rec_ctrl.catch_out_handle = Some(real_time_streaming::start_writer_thread(
                     catch_out,
                     rec_ctrl.samplerate,
                     file_path,
+                    options.clone(),
                 ));
 
                 rec_ctrl.is_recording_output.store(true, Ordering::SeqCst);
             } else {
-                println!("can't get catch");
+                messages::warning("can't get catch".to_string());
             }
         }
         // INPUT RECORDING
         // record input if desired ...
-        if rec_input && !rec_ctrl.is_recording_input.load(Ordering::SeqCst) {
+        if options.rec_input && !rec_ctrl.is_recording_input.load(Ordering::SeqCst) {
             let maybe_catch = rec_ctrl.catch_in.take();
             if let Some(catch_in) = maybe_catch {
-                // place in recordings folder
-                if let Some(proj_dirs) = ProjectDirs::from("de", "parkellipsen", "megra") {
-                    let id = if let Some(p) = prefix {
-                        format!("{}_{}_input.wav", p, Local::now().format("%Y%m%d_%H%M_%S"))
-                    } else {
-                        format!(
-                            "megra_recording_{}_input.wav",
-                            Local::now().format("%Y%m%d_%H%M_%S")
-                        )
-                    };
-
-                    let recordings_path = proj_dirs.config_dir().join("recordings");
-
-                    let file_path = if recordings_path.exists() {
-                        let path = recordings_path.join(id).into_os_string().into_string();
-                        path.unwrap()
-                    } else {
-                        id
-                    };
-
-                    rec_ctrl.catch_in_handle = Some(real_time_streaming::start_writer_thread(
-                        catch_in,
-                        rec_ctrl.samplerate,
-                        file_path,
-                    ));
-                    rec_ctrl.is_recording_input.store(true, Ordering::SeqCst);
-                }
+                // same place as the output recording
+                let file_path = recording_path(&base_dir, format!("{}_input", id));
+
+                rec_ctrl.catch_in_handle = Some(real_time_streaming::start_writer_thread(
+                    catch_in,
+                    rec_ctrl.samplerate,
+                    file_path,
+                    options,
+                ));
+                rec_ctrl.is_recording_input.store(true, Ordering::SeqCst);
             }
         }
 
@@ -379,7 +683,7 @@ pub fn stop_recording<const BUFSIZE: usize, const NCHAN: usize>(
                 rec_ctrl.catch_out = Some(real_time_streaming::stop_writer_thread(catch_handle));
             }
         } else {
-            println!("can't stop output recording that isn't running !");
+            messages::warning("can't stop output recording that isn't running !".to_string());
         }
         if rec_ctrl.is_recording_input.load(Ordering::SeqCst) {
             let maybe_catch_handle = rec_ctrl.catch_in_handle.take();
@@ -388,13 +692,44 @@ pub fn stop_recording<const BUFSIZE: usize, const NCHAN: usize>(
                 rec_ctrl.catch_in = Some(real_time_streaming::stop_writer_thread(catch_handle));
             }
         } else {
-            println!("can't stop input recording that isn't running !");
+            messages::warning("can't stop input recording that isn't running !".to_string());
         }
 
         session.lock().rec_control = Some(rec_ctrl);
     }
 }
 
+/// save the last seconds of output from the retro buffer
+pub fn save_last<const BUFSIZE: usize, const NCHAN: usize>(
+    session: &sync::Arc<Mutex<Session<BUFSIZE, NCHAN>>>,
+    seconds: f32,
+    prefix: Option<String>,
+    base_dir: String,
+) {
+    let maybe_retro = session
+        .lock()
+        .rec_control
+        .as_ref()
+        .and_then(|rec_ctrl| rec_ctrl.retro_buffer.clone());
+
+    if let Some(retro) = maybe_retro {
+        let id = if let Some(p) = prefix {
+            format!("{}_{}", p, Local::now().format("%Y%m%d_%H%M_%S"))
+        } else {
+            format!("megra_retro_{}", Local::now().format("%Y%m%d_%H%M_%S"))
+        };
+        let file_path = recording_path(&base_dir, id);
+        real_time_streaming::save_retro(
+            &retro,
+            seconds,
+            file_path,
+            real_time_streaming::RecordingOptions::default(),
+        );
+    } else {
+        messages::warning("no retro buffer, start with --retro-buffer <seconds> !".to_string());
+    }
+}
+
 /// execute a pre-defined part step by step
 pub fn step_part<const BUFSIZE: usize, const NCHAN: usize>(
     ruffbox: &sync::Arc<RuffboxControls<BUFSIZE, NCHAN>>,
@@ -411,8 +746,9 @@ pub fn step_part<const BUFSIZE: usize, const NCHAN: usize>(
         let mut ps = parts_store.lock();
         if let Some(Part::Combined(gens, _)) = ps.get_mut(&part_name) {
             for gen in gens.iter_mut() {
-                gen.current_transition(global_parameters);
-                let mut current_events = gen.current_events(global_parameters);
+                let now = ruffbox.get_now();
+                gen.current_transition(global_parameters, now);
+                let mut current_events = gen.current_events(global_parameters, now);
                 for ev in current_events.drain(..) {
                     match ev {
                         InterpretableEvent::Control(c) => control_events.push(c),
@@ -456,6 +792,56 @@ pub fn set_default_duration(global_parameters: &sync::Arc<GlobalParameters>, n:
     ); // init on first attempt
 }
 
+pub fn set_global_scale(global_parameters: &sync::Arc<GlobalParameters>, scale: Scale) {
+    global_parameters.insert(
+        BuiltinGlobalParameters::GlobalScale,
+        ConfigParameter::Scale(sync::Arc::new(scale)),
+    );
+}
+
+pub fn set_global_tuning(global_parameters: &sync::Arc<GlobalParameters>, tuning: Tuning) {
+    global_parameters.insert(
+        BuiltinGlobalParameters::GlobalTuning,
+        ConfigParameter::Tuning(sync::Arc::new(tuning)),
+    );
+}
+
+/// load a tuning from scala files
+pub fn load_tuning(
+    global_parameters: &sync::Arc<GlobalParameters>,
+    scl_path: &str,
+    kbm_path: Option<&str>,
+    reference: Option<f32>,
+) {
+    let mut tuning = match fs::read_to_string(scl_path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| Tuning::from_scl(&content))
+    {
+        Ok(t) => t,
+        Err(e) => {
+            messages::warning(format!("can't load tuning {}: {}", scl_path, e));
+            return;
+        }
+    };
+
+    if let Some(kbm) = kbm_path {
+        if let Err(e) = fs::read_to_string(kbm)
+            .map_err(|e| e.to_string())
+            .and_then(|content| tuning.set_kbm(&content))
+        {
+            messages::warning(format!("can't load keyboard mapping {}: {}", kbm, e));
+            return;
+        }
+    }
+
+    if let Some(r) = reference {
+        tuning.mapping.ref_freq = r;
+    }
+
+    messages::info(format!("loaded tuning {}", scl_path));
+    set_global_tuning(global_parameters, tuning);
+}
+
 pub fn set_global_lifemodel_resources(global_parameters: &sync::Arc<GlobalParameters>, val: f32) {
     global_parameters.insert(
         BuiltinGlobalParameters::LifemodelGlobalResources,
@@ -463,18 +849,32 @@ pub fn set_global_lifemodel_resources(global_parameters: &sync::Arc<GlobalParame
     ); // init on first attempt
 }
 
+pub fn set_global_variable(
+    global_parameters: &sync::Arc<GlobalParameters>,
+    name: String,
+    val: f32,
+) {
+    global_parameters.insert(
+        BuiltinGlobalParameters::UserDefined(name),
+        ConfigParameter::Numeric(val),
+    );
+}
+
 pub fn set_global_ruffbox_parameters<const BUFSIZE: usize, const NCHAN: usize>(
     ruffbox: &sync::Arc<RuffboxControls<BUFSIZE, NCHAN>>,
     params: &mut HashMap<SynthParameterLabel, ParameterValue>,
 ) {
     for (k, v) in params.iter_mut() {
-        ruffbox.set_master_parameter(*k, resolve_parameter(*k, v))
+        ruffbox.set_master_parameter(
+            *k,
+            resolve_parameter(*k, v, &EvalContext::at(ruffbox.get_now())),
+        )
     }
 }
 
 pub fn export_dot_static(filename: &str, generator: &Generator) {
     let dot_string = pfa::to_dot::<char>(&generator.root_generator.generator);
-    println!("export to {}", filename);
+    messages::info(format!("export to {}", filename));
     fs::write(filename, dot_string).expect("Unable to write file");
 }
 
@@ -499,7 +899,7 @@ pub fn export_dot_part(
             filename_tagged = filename_tagged[..filename_tagged.len() - 1].to_string();
             filename_tagged.push_str(".dot");
             let dot_string = pfa::to_dot::<char>(&gen.root_generator.generator);
-            println!("export to {}", filename_tagged);
+            messages::info(format!("export to {}", filename_tagged));
             fs::write(filename_tagged, dot_string).expect("Unable to write file");
         }
     }
@@ -535,7 +935,7 @@ pub fn export_dot_running<const BUFSIZE: usize, const NCHAN: usize>(
         filename_tagged = filename_tagged[..filename_tagged.len() - 1].to_string();
         filename_tagged.push_str(".dot");
         let dot_string = pfa::to_dot::<char>(&gen.root_generator.generator);
-        println!("export to {}", filename_tagged);
+        messages::info(format!("export to {}", filename_tagged));
         fs::write(filename_tagged, dot_string).expect("Unable to write file");
     }
 }
@@ -570,6 +970,17 @@ pub fn once<const BUFSIZE: usize, const NCHAN: usize>(
             continue;
         }
 
+        // streams are played outside of ruffbox
+        if s.name == "stream" {
+            let maybe_sc = session.lock().stream_control.clone();
+            if let Some(sc) = maybe_sc {
+                let (id, params) =
+                    stream_player::stream_parameters(s, ruffbox.get_now(), output_mode);
+                sc.start_stream(id, params);
+            }
+            continue;
+        }
+
         s.build_envelope(); // build consistent envelope before evaluating
 
         let mut bufnum: usize = 0;
@@ -632,7 +1043,7 @@ pub fn once<const BUFSIZE: usize, const NCHAN: usize>(
             }
             ruffbox.trigger(inst);
         } else {
-            println!("can't prepare this instance !");
+            messages::warning("can't prepare this instance !".to_string());
         }
     }
 }
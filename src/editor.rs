@@ -1,21 +1,38 @@
 // editor modules
+mod completion;
+mod evaluation;
+mod generator_panel;
+mod keymap;
 mod livecode_text_edit;
+mod meters;
+mod modal;
+mod multi_cursor;
+mod paredit;
+mod search;
+mod sketchbook;
 mod syntax_highlighting;
+mod theme;
 
 use parking_lot::Mutex;
 use ruffbox_synth::ruffbox::RuffboxControls;
-use std::collections::HashMap;
-use std::sync;
+use std::collections::{BTreeSet, HashMap};
+use std::{fs, path, sync, thread};
 
 mod megra_editor;
+use completion::Vocabulary;
+use generator_panel::GeneratorAction;
+use keymap::load_keymap;
 use megra_editor::{EditorFont, MegraEditor};
+use theme::{load_theme, write_builtin_themes};
 
 use crate::builtin_types::*;
 use crate::interpreter;
+use crate::messages;
 use crate::parser;
-use crate::parser::FunctionMap;
+use crate::parser::{EvalError, FunctionMap};
+use crate::real_time_streaming::{MeterReading, MeterTap};
 use crate::sample_set::SampleAndWavematrixSet;
-use crate::session::{OutputMode, Session};
+use crate::session::{GeneratorStatus, OutputMode, Session};
 
 #[allow(clippy::too_many_arguments)]
 pub fn run_editor<const BUFSIZE: usize, const NCHAN: usize>(
@@ -31,6 +48,8 @@ pub fn run_editor<const BUFSIZE: usize, const NCHAN: usize>(
     mode: OutputMode,
     font: Option<&str>,
     font_size: f32,
+    theme: Option<&str>,
+    meter_tap: Option<MeterTap<BUFSIZE, NCHAN>>,
 ) {
     let session2 = sync::Arc::clone(session);
     let function_map2 = sync::Arc::clone(function_map);
@@ -41,7 +60,7 @@ pub fn run_editor<const BUFSIZE: usize, const NCHAN: usize>(
     let parts_store2 = sync::Arc::clone(parts_store);
     let base_dir_2 = base_dir.clone();
 
-    let callback_ref: sync::Arc<Mutex<dyn FnMut(&String)>> =
+    let callback_ref: sync::Arc<Mutex<dyn FnMut(&String) -> Result<(), EvalError>>> =
         sync::Arc::new(Mutex::new(move |text: &String| {
             let pfa_in = parser::eval_from_str(
                 text,
@@ -64,13 +83,68 @@ pub fn run_editor<const BUFSIZE: usize, const NCHAN: usize>(
                         mode,
                         base_dir_2.to_string(),
                     );
+                    Ok(())
                 }
                 Err(e) => {
-                    println!("could not parse this! {} {}", text, e)
+                    messages::error(format!("could not evaluate this! {}", e));
+                    Err(e)
                 }
             }
         }));
 
+    // sample sets are registered as functions when they're loaded,
+    // so the function map covers them
+    let function_map3 = sync::Arc::clone(function_map);
+    let session3 = sync::Arc::clone(session);
+    let vocabulary_ref: sync::Arc<Mutex<dyn FnMut() -> Vocabulary>> =
+        sync::Arc::new(Mutex::new(move || {
+            let mut generators = BTreeSet::new();
+            for id_tags in session3.lock().schedulers.keys() {
+                generators.extend(id_tags.iter().cloned());
+            }
+            Vocabulary::new(&function_map3.lock(), generators)
+        }));
+
+    let session4 = sync::Arc::clone(session);
+    let generator_source_ref: sync::Arc<Mutex<dyn FnMut() -> Vec<GeneratorStatus>>> =
+        sync::Arc::new(Mutex::new(move || Session::generator_status(&session4)));
+
+    let session5 = sync::Arc::clone(session);
+    let generator_control_ref: sync::Arc<Mutex<dyn FnMut(GeneratorAction)>> =
+        sync::Arc::new(Mutex::new(move |action: GeneratorAction| match action {
+            GeneratorAction::Mute(id_tags, mute) => {
+                Session::mute_generator(&session5, &id_tags, mute)
+            }
+            GeneratorAction::Solo(id_tags, solo) => {
+                Session::solo_generator(&session5, &id_tags, solo)
+            }
+            GeneratorAction::Stop(id_tags) => {
+                // stopping waits for the scheduler thread, so don't block the editor
+                let session6 = sync::Arc::clone(&session5);
+                thread::spawn(move || {
+                    Session::stop_generator(&session6, &id_tags);
+                });
+            }
+        }));
+
+    // the levels come from a tap in the audio callback
+    let meter_source_ref =
+        meter_tap.map(|mut tap| -> sync::Arc<Mutex<dyn FnMut() -> MeterReading>> {
+            sync::Arc::new(Mutex::new(move || tap.read()))
+        });
+
+    // the builtin themes go to the themes folder, so there's
+    // something to start from when making your own
+    let themes_path = path::Path::new(&base_dir).join("themes");
+    if !themes_path.exists() {
+        println!("create megra themes directory {:?}", themes_path);
+        if fs::create_dir_all(&themes_path).is_ok() {
+            write_builtin_themes(&themes_path);
+        }
+    }
+    let theme_colors = load_theme(path::Path::new(&base_dir), theme.unwrap_or("dark"));
+    let keymap = load_keymap(path::Path::new(&base_dir));
+
     let ifont = match font {
         Some("mononoki") => EditorFont::Mononoki,
         Some("ComicMono") => EditorFont::ComicMono,
@@ -92,7 +166,15 @@ pub fn run_editor<const BUFSIZE: usize, const NCHAN: usize>(
             let mut inner_app = MegraEditor::new(cc, base_dir, cs);
             inner_app.set_font_size(fs);
             inner_app.set_font(ifont);
+            inner_app.set_theme(theme_colors);
+            inner_app.set_keymap(keymap);
             inner_app.set_callback(callback_ref);
+            inner_app.set_vocabulary_source(vocabulary_ref);
+            inner_app.set_generator_source(generator_source_ref);
+            inner_app.set_generator_control(generator_control_ref);
+            if let Some(source) = meter_source_ref {
+                inner_app.set_meter_source(source);
+            }
             Box::new(inner_app)
         }),
     );
@@ -0,0 +1,254 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+
+use crate::parser::{self, FunctionCategory, FunctionInfo, FunctionMap};
+
+// don't flood the popup
+const MAX_CANDIDATES: usize = 12;
+
+/// Everything the editor knows about, taken from the function
+/// registry and the running session. Used for highlighting
+/// and completion.
+#[derive(Clone, Default)]
+pub struct Vocabulary {
+    pub functions: BTreeMap<String, Option<FunctionInfo>>,
+    pub generators: BTreeSet<String>,
+    fingerprint: u64,
+}
+
+// the highlighter cache is keyed by this, so only hash the fingerprint
+impl Hash for Vocabulary {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.fingerprint.hash(state);
+    }
+}
+
+impl Vocabulary {
+    pub fn new(function_map: &FunctionMap, generators: BTreeSet<String>) -> Self {
+        let mut functions = BTreeMap::new();
+        // functions that were inserted without a signature
+        // are completed but not highlighted
+        for name in function_map.fmap.keys() {
+            functions.insert(name.clone(), function_map.info.get(name).cloned());
+        }
+
+        let mut hasher = DefaultHasher::new();
+        for (name, info) in functions.iter() {
+            name.hash(&mut hasher);
+            info.as_ref().map(|i| i.category).hash(&mut hasher);
+        }
+        generators.hash(&mut hasher);
+
+        Vocabulary {
+            functions,
+            generators,
+            fingerprint: hasher.finish(),
+        }
+    }
+
+    pub fn category(&self, name: &str) -> Option<FunctionCategory> {
+        self.functions
+            .get(name)
+            .and_then(|i| i.as_ref().map(|i| i.category))
+    }
+
+    pub fn signature(&self, name: &str) -> Option<&str> {
+        self.functions
+            .get(name)
+            .and_then(|i| i.as_ref().map(|i| i.signature.as_str()))
+    }
+
+    /// Find completions for the word before the cursor.
+    /// Keywords are taken from the enclosing function if it's known,
+    /// symbols are completed with the tags of running generators.
+    pub fn candidates(&self, text: &str, cursor: usize) -> Vec<Candidate> {
+        let (_, word) = word_before(text, cursor);
+        let mut candidates = Vec::new();
+
+        if let Some(prefix) = word.strip_prefix(':') {
+            let mut keywords = BTreeSet::new();
+            if let Some(info) = enclosing_function(text, cursor)
+                .and_then(|f| self.functions.get(&f).cloned().flatten())
+            {
+                for kw in info.keywords() {
+                    if kw.starts_with(prefix) {
+                        keywords.insert(kw.to_string());
+                    }
+                }
+                for kw in keywords {
+                    candidates.push(Candidate {
+                        label: format!(":{}", kw),
+                        detail: info.signature.clone(),
+                    });
+                }
+            } else {
+                for info in self.functions.values().flatten() {
+                    for kw in info.keywords() {
+                        if kw.starts_with(prefix) {
+                            keywords.insert(kw.to_string());
+                        }
+                    }
+                }
+                for kw in keywords {
+                    candidates.push(Candidate {
+                        label: format!(":{}", kw),
+                        detail: String::new(),
+                    });
+                }
+            }
+        } else if let Some(prefix) = word.strip_prefix('\'') {
+            for tag in self.generators.iter().filter(|t| t.starts_with(prefix)) {
+                candidates.push(Candidate {
+                    label: format!("'{}", tag),
+                    detail: "running generator".to_string(),
+                });
+            }
+        } else if !word.is_empty() {
+            for (name, info) in self.functions.iter() {
+                if name.starts_with(word) {
+                    candidates.push(Candidate {
+                        label: name.clone(),
+                        detail: match info {
+                            Some(i) if i.category == FunctionCategory::Sample => {
+                                format!("sample set {}", i.signature)
+                            }
+                            Some(i) => i.signature.clone(),
+                            None => String::new(),
+                        },
+                    });
+                }
+            }
+        }
+
+        // nothing left to complete
+        if candidates.len() == 1 && candidates[0].label == word {
+            candidates.clear();
+        }
+
+        candidates.truncate(MAX_CANDIDATES);
+        candidates
+    }
+}
+
+/// A completion, along with a hint (mostly the signature).
+#[derive(Clone, PartialEq, Debug)]
+pub struct Candidate {
+    pub label: String,
+    pub detail: String,
+}
+
+/// chars that can be part of something completable
+pub fn is_completion_char(c: char) -> bool {
+    parser::valid_function_name_char(c) || c == ':' || c == '\''
+}
+
+/// the start (char index) and the word before the cursor
+pub fn word_before(text: &str, cursor: usize) -> (usize, &str) {
+    let end = byte_index(text, cursor);
+    let start = text[..end]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_completion_char(*c))
+        .last()
+        .map_or(end, |(i, _)| i);
+    (text[..start].chars().count(), &text[start..end])
+}
+
+/// the name of the innermost function call the cursor is in
+pub fn enclosing_function(text: &str, cursor: usize) -> Option<String> {
+    let end = byte_index(text, cursor);
+    let mut depth = 0;
+    for (i, c) in text[..end].char_indices().rev() {
+        match c {
+            ')' => depth += 1,
+            '(' if depth > 0 => depth -= 1,
+            '(' => {
+                let name: String = text[i + 1..]
+                    .chars()
+                    .take_while(|c| parser::valid_function_name_char(*c))
+                    .collect();
+                return if name.is_empty() { None } else { Some(name) };
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn byte_index(text: &str, char_index: usize) -> usize {
+    text.char_indices()
+        .nth(char_index)
+        .map_or(text.len(), |(i, _)| i)
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn vocabulary() -> Vocabulary {
+        let mut functions = FunctionMap::new();
+        functions.register(
+            FunctionCategory::Constructor,
+            "cyc",
+            "'name \"pattern\" :dur :rep :events",
+            |_, _, _, _, _| None,
+        );
+        functions.register(FunctionCategory::Command, "clear", "", |_, _, _, _, _| None);
+        functions.register(
+            FunctionCategory::Sample,
+            "bd",
+            "'keyword... :rate",
+            |_, _, _, _, _| None,
+        );
+        let mut generators = BTreeSet::new();
+        generators.insert("drums".to_string());
+        Vocabulary::new(&functions, generators)
+    }
+
+    #[test]
+    fn test_word_and_enclosing_function() {
+        let text = "(sx 'ga #t (cyc 'beat \"bd ~\" :du";
+        let cursor = text.chars().count();
+        assert_eq!(word_before(text, cursor), (cursor - 3, ":du"));
+        assert_eq!(enclosing_function(text, cursor), Some("cyc".to_string()));
+
+        let text = "(sx 'ga #t (cyc 'beat \"bd ~\") ";
+        let cursor = text.chars().count();
+        assert_eq!(word_before(text, cursor), (cursor, ""));
+        assert_eq!(enclosing_function(text, cursor), Some("sx".to_string()));
+    }
+
+    #[test]
+    fn test_candidates() {
+        let voc = vocabulary();
+
+        let text = "(c";
+        let cands = voc.candidates(text, 2);
+        let labels: Vec<&str> = cands.iter().map(|c| c.label.as_str()).collect();
+        assert_eq!(labels, vec!["clear", "cyc"]);
+        assert_eq!(cands[1].detail, "(cyc 'name \"pattern\" :dur :rep :events)");
+
+        let text = "(cyc 'a \"bd\" :d";
+        let labels: Vec<String> = voc
+            .candidates(text, text.chars().count())
+            .into_iter()
+            .map(|c| c.label)
+            .collect();
+        assert_eq!(labels, vec![":dur"]);
+
+        let text = "(sx 'd";
+        let labels: Vec<String> = voc
+            .candidates(text, text.chars().count())
+            .into_iter()
+            .map(|c| c.label)
+            .collect();
+        assert_eq!(labels, vec!["'drums"]);
+
+        assert_eq!(voc.category("bd"), Some(FunctionCategory::Sample));
+        // complete words don't need completion
+        assert!(voc.candidates("(clear", 6).is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+// what to evaluate besides the top-level form around the cursor:
+// everything here works on char indices, like the text edit
+
+use crate::parser::split_forms;
+
+/// What gets evaluated, one form after another.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Evaluation {
+    /// the top-level form around the cursor
+    Form,
+    /// the forms in the selection, or the form around the cursor
+    Selection,
+    Buffer,
+    /// the region around the cursor, or the one with this name
+    Region(Option<String>),
+    /// the forms that aren't in this (earlier) version of the text
+    Changed(String),
+}
+
+/// What happens when a sketch is saved with the save command.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum EvalOnSave {
+    #[default]
+    Off,
+    Buffer,
+    /// only the forms changed since the last save
+    Changed,
+}
+
+impl EvalOnSave {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EvalOnSave::Off => "off",
+            EvalOnSave::Buffer => "buffer",
+            EvalOnSave::Changed => "changed",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [EvalOnSave::Off, EvalOnSave::Buffer, EvalOnSave::Changed]
+            .into_iter()
+            .find(|e| e.name() == name)
+    }
+}
+
+/// A part of a sketch starting with a `;; region: name` comment,
+/// ending with `;; endregion`, the next region or the end of the text.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Region {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+// the marker comment on this line, if any
+fn marker(line: &str) -> Option<Option<&str>> {
+    let comment = line
+        .trim()
+        .strip_prefix(';')?
+        .trim_start_matches(';')
+        .trim();
+    if comment == "endregion" {
+        Some(None)
+    } else {
+        comment
+            .strip_prefix("region:")
+            .map(|name| Some(name.trim()))
+    }
+}
+
+/// The regions in the text, in order.
+pub fn regions(text: &str) -> Vec<Region> {
+    let mut regions = Vec::new();
+    let mut current: Option<Region> = None;
+    let mut line_start = 0;
+
+    for line in text.split('\n') {
+        if let Some(name) = marker(line) {
+            if let Some(mut region) = current.take() {
+                region.end = line_start;
+                regions.push(region);
+            }
+            current = name.map(|name| Region {
+                name: name.to_string(),
+                start: line_start,
+                end: line_start,
+            });
+        }
+        line_start += line.chars().count() + 1;
+    }
+    if let Some(mut region) = current {
+        region.end = text.chars().count();
+        regions.push(region);
+    }
+    regions
+}
+
+/// The region around `index`.
+pub fn region_at(text: &str, index: usize) -> Option<Region> {
+    regions(text)
+        .into_iter()
+        .find(|r| r.start <= index && index <= r.end)
+}
+
+/// The top-level forms within `start..end`.
+pub fn forms_in(text: &str, start: usize, end: usize) -> Vec<(usize, usize)> {
+    let part: String = text.chars().skip(start).take(end - start).collect();
+    split_forms(&part)
+        .into_iter()
+        .map(|(from, to)| {
+            (
+                start + part[..from].chars().count(),
+                start + part[..to].chars().count(),
+            )
+        })
+        .collect()
+}
+
+/// The top-level forms of `text` that aren't in `before`,
+/// ignoring whitespace around them.
+pub fn changed_forms(before: &str, text: &str) -> Vec<(usize, usize)> {
+    let old: Vec<&str> = split_forms(before)
+        .into_iter()
+        .map(|(from, to)| before[from..to].trim())
+        .collect();
+    let all = forms_in(text, 0, text.chars().count());
+    let forms: Vec<String> = all
+        .iter()
+        .map(|(from, to)| text.chars().skip(*from).take(to - from).collect())
+        .collect();
+    all.into_iter()
+        .zip(forms.iter())
+        .filter(|(_, form)| !old.contains(&form.trim()))
+        .map(|(range, _)| range)
+        .collect()
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_regions() {
+        let text = "(tempo 120)\n;; region: drums\n(sx 'bd #t)\n;;; region: bass\n(sx 'b #t)\n;; endregion\n(sx 'ü #t)";
+        assert_eq!(
+            regions(text),
+            vec![
+                Region {
+                    name: "drums".to_string(),
+                    start: 12,
+                    end: 41
+                },
+                Region {
+                    name: "bass".to_string(),
+                    start: 41,
+                    end: 69
+                }
+            ]
+        );
+        assert_eq!(region_at(text, 50).unwrap().name, "bass");
+        assert_eq!(region_at(text, 5), None);
+        assert_eq!(forms_in(text, 12, 41), vec![(29, 40)]);
+        // a region may run to the end
+        assert_eq!(regions(";; region: all\n(a)")[0].end, 18);
+    }
+
+    #[test]
+    fn test_changed_forms() {
+        let before = "(sx 'a #t (cyc 'a \"bd\"))\n\n(sx 'b #t (cyc 'b \"sn\"))";
+        let text = "(sx 'ä #t (cyc 'a \"bd\"))\n\n  (sx 'b #t (cyc 'b \"sn\"))\n(tempo 130)";
+        assert_eq!(changed_forms(before, text), vec![(0, 24), (53, 64)]);
+        assert!(changed_forms(text, text).is_empty());
+
+        assert_eq!(EvalOnSave::from_name("changed"), Some(EvalOnSave::Changed));
+        assert_eq!(EvalOnSave::from_name("sometimes"), None);
+    }
+}
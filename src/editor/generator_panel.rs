@@ -0,0 +1,112 @@
+use std::collections::BTreeSet;
+
+use egui::{Color32, FontId, RichText};
+
+use crate::session::GeneratorStatus;
+
+/// What the user wants to do with a generator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GeneratorAction {
+    Mute(BTreeSet<String>, bool),
+    Solo(BTreeSet<String>, bool),
+    Stop(BTreeSet<String>),
+}
+
+/// tags the way they'd be written in the code
+pub fn format_tags(id_tags: &BTreeSet<String>) -> String {
+    id_tags
+        .iter()
+        .map(|t| format!("'{}", t))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// List the generators, grouped by context, with mute, solo and stop
+/// buttons. Returns whatever was clicked.
+pub fn generator_panel(
+    ui: &mut egui::Ui,
+    status: &[GeneratorStatus],
+    font_size: f32,
+    accent: Color32,
+) -> Option<GeneratorAction> {
+    let mut action = None;
+    let font = FontId::monospace(font_size * 0.8);
+    let dim = Color32::from_gray(140);
+
+    if status.is_empty() {
+        ui.label(RichText::new("nothing running").font(font).color(dim));
+        return None;
+    }
+
+    let mut context = None;
+    for (i, gen) in status.iter().enumerate() {
+        // status is sorted by context, so a new context starts a new group
+        if i == 0 || gen.context != context {
+            context = gen.context.clone();
+            if i > 0 {
+                ui.separator();
+            }
+            let heading = match &context {
+                Some(name) => format!("context '{}", name),
+                None => "no context".to_string(),
+            };
+            ui.label(RichText::new(heading).font(font.clone()).strong());
+        }
+
+        ui.horizontal(|ui| {
+            let color = if !gen.running || gen.muted {
+                dim
+            } else {
+                accent
+            };
+            ui.label(
+                RichText::new(format_tags(&gen.id_tags))
+                    .font(font.clone())
+                    .color(color),
+            );
+
+            if !gen.running {
+                ui.label(RichText::new("stopped").font(font.clone()).color(dim));
+                return;
+            }
+
+            if ui.selectable_label(gen.muted, "mute").clicked() {
+                action = Some(GeneratorAction::Mute(gen.id_tags.clone(), !gen.muted));
+            }
+            if ui.selectable_label(gen.soloed, "solo").clicked() {
+                action = Some(GeneratorAction::Solo(gen.id_tags.clone(), !gen.soloed));
+            }
+            if ui.button("stop").clicked() {
+                action = Some(GeneratorAction::Stop(gen.id_tags.clone()));
+            }
+        });
+
+        if gen.running {
+            let state = gen.state.as_deref().unwrap_or("-");
+            let symbol = gen.last_symbol.map_or("-".to_string(), |s| s.to_string());
+            ui.label(
+                RichText::new(format!("  state {}  last {}", state, symbol))
+                    .font(font.clone())
+                    .color(dim),
+            );
+        }
+    }
+
+    action
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_format_tags() {
+        let mut id_tags = BTreeSet::new();
+        id_tags.insert("troll".to_string());
+        id_tags.insert("ga".to_string());
+        assert_eq!(format_tags(&id_tags), "'ga 'troll");
+        assert_eq!(format_tags(&BTreeSet::new()), "");
+    }
+}
@@ -0,0 +1,499 @@
+use std::{fs, path::Path};
+
+use egui::{Key, Modifiers};
+use enum_map::EnumMap;
+
+use crate::editor::evaluation::EvalOnSave;
+use crate::messages;
+
+pub const KEYMAP_FILE: &str = "editor.keymap";
+
+/// The editor commands that can be bound to keys.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, enum_map::Enum)]
+pub enum EditorCommand {
+    Evaluate,
+    EvaluateSelection,
+    EvaluateBuffer,
+    EvaluateRegion,
+    Save,
+    ToggleComment,
+    Format,
+    ToggleSelection,
+    CancelSelection,
+    CutSelection,
+    KillLine,
+    KillLineBefore,
+    ForwardChar,
+    SelectAll,
+    Undo,
+    Redo,
+    AddCursor,
+    Search,
+    SlurpForward,
+    BarfForward,
+    Wrap,
+    Splice,
+    Raise,
+    SelectEnclosing,
+}
+
+// the names used in keymap files
+const COMMAND_NAMES: [(&str, EditorCommand); 24] = [
+    ("evaluate", EditorCommand::Evaluate),
+    ("evaluate-selection", EditorCommand::EvaluateSelection),
+    ("evaluate-buffer", EditorCommand::EvaluateBuffer),
+    ("evaluate-region", EditorCommand::EvaluateRegion),
+    ("save", EditorCommand::Save),
+    ("toggle-comment", EditorCommand::ToggleComment),
+    ("format", EditorCommand::Format),
+    ("toggle-selection", EditorCommand::ToggleSelection),
+    ("cancel-selection", EditorCommand::CancelSelection),
+    ("cut-selection", EditorCommand::CutSelection),
+    ("kill-line", EditorCommand::KillLine),
+    ("kill-line-before", EditorCommand::KillLineBefore),
+    ("forward-char", EditorCommand::ForwardChar),
+    ("select-all", EditorCommand::SelectAll),
+    ("undo", EditorCommand::Undo),
+    ("redo", EditorCommand::Redo),
+    ("add-cursor", EditorCommand::AddCursor),
+    ("search", EditorCommand::Search),
+    ("slurp-forward", EditorCommand::SlurpForward),
+    ("barf-forward", EditorCommand::BarfForward),
+    ("wrap", EditorCommand::Wrap),
+    ("splice", EditorCommand::Splice),
+    ("raise", EditorCommand::Raise),
+    ("select-enclosing", EditorCommand::SelectEnclosing),
+];
+
+const KEY_NAMES: [(&str, Key); 51] = [
+    ("a", Key::A),
+    ("b", Key::B),
+    ("c", Key::C),
+    ("d", Key::D),
+    ("e", Key::E),
+    ("f", Key::F),
+    ("g", Key::G),
+    ("h", Key::H),
+    ("i", Key::I),
+    ("j", Key::J),
+    ("k", Key::K),
+    ("l", Key::L),
+    ("m", Key::M),
+    ("n", Key::N),
+    ("o", Key::O),
+    ("p", Key::P),
+    ("q", Key::Q),
+    ("r", Key::R),
+    ("s", Key::S),
+    ("t", Key::T),
+    ("u", Key::U),
+    ("v", Key::V),
+    ("w", Key::W),
+    ("x", Key::X),
+    ("y", Key::Y),
+    ("z", Key::Z),
+    ("0", Key::Num0),
+    ("1", Key::Num1),
+    ("2", Key::Num2),
+    ("3", Key::Num3),
+    ("4", Key::Num4),
+    ("5", Key::Num5),
+    ("6", Key::Num6),
+    ("7", Key::Num7),
+    ("8", Key::Num8),
+    ("9", Key::Num9),
+    ("enter", Key::Enter),
+    ("space", Key::Space),
+    ("tab", Key::Tab),
+    ("escape", Key::Escape),
+    ("backspace", Key::Backspace),
+    ("delete", Key::Delete),
+    ("insert", Key::Insert),
+    ("home", Key::Home),
+    ("end", Key::End),
+    ("pageup", Key::PageUp),
+    ("pagedown", Key::PageDown),
+    ("left", Key::ArrowLeft),
+    ("right", Key::ArrowRight),
+    ("up", Key::ArrowUp),
+    ("down", Key::ArrowDown),
+];
+
+/// A key plus modifiers, like `ctrl+shift+z`.
+/// `ctrl` is the control key everywhere, `cmd` is command
+/// on mac and control elsewhere.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyCombo {
+    pub key: Key,
+    pub ctrl: bool,
+    pub command: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+impl KeyCombo {
+    fn cmd(key: Key) -> Self {
+        KeyCombo {
+            key,
+            ctrl: false,
+            command: true,
+            alt: false,
+            shift: false,
+        }
+    }
+
+    fn ctrl(key: Key) -> Self {
+        KeyCombo {
+            key,
+            ctrl: true,
+            command: false,
+            alt: false,
+            shift: false,
+        }
+    }
+
+    fn plain(key: Key) -> Self {
+        KeyCombo {
+            key,
+            ctrl: false,
+            command: false,
+            alt: false,
+            shift: false,
+        }
+    }
+
+    fn alt(key: Key) -> Self {
+        KeyCombo {
+            key,
+            ctrl: false,
+            command: false,
+            alt: true,
+            shift: false,
+        }
+    }
+
+    fn shift(self) -> Self {
+        KeyCombo {
+            shift: true,
+            ..self
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts: Vec<&str> = s.trim().split('+').collect();
+        let key_name = parts.pop()?.to_lowercase();
+        let (_, key) = KEY_NAMES.iter().find(|(name, _)| *name == key_name)?;
+
+        let mut combo = KeyCombo::plain(*key);
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" => combo.ctrl = true,
+                "cmd" => combo.command = true,
+                "alt" => combo.alt = true,
+                "shift" => combo.shift = true,
+                _ => return None,
+            }
+        }
+        Some(combo)
+    }
+
+    pub fn matches(&self, key: Key, modifiers: &Modifiers) -> bool {
+        self.key == key
+            && self.alt == modifiers.alt
+            && self.shift == modifiers.shift
+            && (!self.ctrl || modifiers.ctrl)
+            && (!self.command || modifiers.command)
+            // on linux and windows, ctrl and cmd are the same thing
+            && (self.ctrl || self.command) == (modifiers.ctrl || modifiers.command)
+    }
+}
+
+impl std::fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (on, name) in [
+            (self.ctrl, "ctrl+"),
+            (self.command, "cmd+"),
+            (self.alt, "alt+"),
+            (self.shift, "shift+"),
+        ] {
+            if on {
+                write!(f, "{}", name)?;
+            }
+        }
+        let name = KEY_NAMES
+            .iter()
+            .find(|(_, key)| *key == self.key)
+            .map_or("?", |(name, _)| name);
+        write!(f, "{}", name)
+    }
+}
+
+/// Which keys trigger which editor command, whether the vim-style
+/// normal/insert modes are on, whether brackets are protected
+/// and what's evaluated when saving.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Keymap {
+    pub bindings: EnumMap<EditorCommand, Vec<KeyCombo>>,
+    pub modal: bool,
+    pub paredit: bool,
+    pub eval_on_save: EvalOnSave,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap {
+            bindings: enum_map::enum_map![
+                EditorCommand::Evaluate => vec![KeyCombo::cmd(Key::Enter)],
+                EditorCommand::EvaluateSelection => vec![KeyCombo::cmd(Key::Enter).shift()],
+                EditorCommand::EvaluateBuffer => vec![KeyCombo {
+                    alt: true,
+                    ..KeyCombo::cmd(Key::Enter)
+                }],
+                EditorCommand::EvaluateRegion => vec![KeyCombo::alt(Key::Enter)],
+                EditorCommand::Save => vec![KeyCombo::cmd(Key::S).shift()],
+                EditorCommand::ToggleComment => vec![KeyCombo::cmd(Key::T)],
+                EditorCommand::Format => vec![KeyCombo::plain(Key::Tab)],
+                EditorCommand::ToggleSelection => vec![KeyCombo::cmd(Key::Space)],
+                EditorCommand::CancelSelection => vec![KeyCombo::ctrl(Key::G)],
+                EditorCommand::CutSelection => vec![KeyCombo::ctrl(Key::W)],
+                EditorCommand::KillLine => vec![KeyCombo::ctrl(Key::K)],
+                EditorCommand::KillLineBefore => vec![KeyCombo::ctrl(Key::U)],
+                EditorCommand::ForwardChar => vec![KeyCombo::cmd(Key::F)],
+                EditorCommand::SelectAll => vec![KeyCombo::cmd(Key::A)],
+                EditorCommand::Undo => vec![KeyCombo::cmd(Key::Z)],
+                EditorCommand::Redo => vec![KeyCombo::cmd(Key::Z).shift(), KeyCombo::cmd(Key::Y)],
+                EditorCommand::AddCursor => vec![KeyCombo::cmd(Key::D)],
+                EditorCommand::Search => vec![KeyCombo::cmd(Key::S)],
+                EditorCommand::SlurpForward => vec![KeyCombo::alt(Key::ArrowRight).shift()],
+                EditorCommand::BarfForward => vec![KeyCombo::alt(Key::ArrowLeft).shift()],
+                EditorCommand::Wrap => vec![KeyCombo::alt(Key::Num9)],
+                EditorCommand::Splice => vec![KeyCombo::alt(Key::S)],
+                EditorCommand::Raise => vec![KeyCombo::alt(Key::R)],
+                EditorCommand::SelectEnclosing => vec![KeyCombo::alt(Key::ArrowUp).shift()],
+            ],
+            modal: false,
+            paredit: true,
+            eval_on_save: EvalOnSave::Off,
+        }
+    }
+}
+
+impl Keymap {
+    /// The command bound to this key, if any.
+    pub fn command_for(&self, key: Key, modifiers: &Modifiers) -> Option<EditorCommand> {
+        self.bindings
+            .iter()
+            .find(|(_, combos)| combos.iter().any(|c| c.matches(key, modifiers)))
+            .map(|(command, _)| command)
+    }
+
+    /// Whether the keys for `command` were pressed in this frame.
+    pub fn pressed(&self, input: &egui::InputState, command: EditorCommand) -> bool {
+        self.bindings[command]
+            .iter()
+            .any(|c| input.key_pressed(c.key) && c.matches(c.key, &input.modifiers))
+    }
+
+    /// Read a keymap file. Each line binds a command to one or more keys,
+    /// like `evaluate = ctrl+enter alt+enter`, or to `none`. `modal = on`
+    /// turns on normal and insert modes, `paredit = off` lets brackets be
+    /// deleted one by one, `eval-on-save = buffer` (or `changed`) evaluates
+    /// the sketch when saving. Lines starting with `;;` are comments,
+    /// whatever isn't mentioned keeps its default binding.
+    pub fn parse(text: &str) -> (Self, Vec<String>) {
+        let mut keymap = Keymap::default();
+        let mut problems = Vec::new();
+
+        for (num, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(";;") {
+                continue;
+            }
+
+            let (key, value) = if let Some((key, value)) = line.split_once('=') {
+                (key.trim(), value.trim())
+            } else {
+                problems.push(format!("line {}: expected 'command = keys'", num + 1));
+                continue;
+            };
+
+            if key == "modal" || key == "paredit" {
+                let on = match value {
+                    "on" | "true" => true,
+                    "off" | "false" => false,
+                    _ => {
+                        problems.push(format!("line {}: {} is either on or off", num + 1, key));
+                        continue;
+                    }
+                };
+                if key == "modal" {
+                    keymap.modal = on;
+                } else {
+                    keymap.paredit = on;
+                }
+                continue;
+            }
+
+            if key == "eval-on-save" {
+                if let Some(eval_on_save) = EvalOnSave::from_name(value) {
+                    keymap.eval_on_save = eval_on_save;
+                } else {
+                    problems.push(format!(
+                        "line {}: eval-on-save is off, buffer or changed",
+                        num + 1
+                    ));
+                }
+                continue;
+            }
+
+            let command = if let Some((_, command)) = COMMAND_NAMES.iter().find(|(n, _)| *n == key)
+            {
+                *command
+            } else {
+                problems.push(format!("line {}: unknown command '{}'", num + 1, key));
+                continue;
+            };
+
+            let mut combos = Vec::new();
+            for combo in value.split_whitespace().filter(|c| *c != "none") {
+                if let Some(combo) = KeyCombo::parse(combo) {
+                    combos.push(combo);
+                } else {
+                    problems.push(format!("line {}: can't read key '{}'", num + 1, combo));
+                }
+            }
+            keymap.bindings[command] = combos;
+        }
+
+        (keymap, problems)
+    }
+
+    /// the keymap as a keymap file, to start your own from
+    pub fn to_file_content(&self) -> String {
+        let mut content = ";; mégra editor keymap, modifiers are ctrl, cmd (command on mac, ctrl elsewhere), alt and shift\n".to_string();
+        for (name, on) in [("modal", self.modal), ("paredit", self.paredit)] {
+            content.push_str(&format!("{} = {}\n", name, if on { "on" } else { "off" }));
+        }
+        content.push_str(&format!("eval-on-save = {}\n", self.eval_on_save.name()));
+        for (name, command) in COMMAND_NAMES.iter() {
+            let combos: Vec<String> = self.bindings[*command]
+                .iter()
+                .map(|c| c.to_string())
+                .collect();
+            let keys = if combos.is_empty() {
+                "none".to_string()
+            } else {
+                combos.join(" ")
+            };
+            content.push_str(&format!("{} = {}\n", name, keys));
+        }
+        content
+    }
+}
+
+/// Load the keymap from `<base_dir>/editor.keymap`, writing
+/// the default one first if there's none yet.
+pub fn load_keymap(base_dir: &Path) -> Keymap {
+    let path = base_dir.join(KEYMAP_FILE);
+
+    if !path.exists() {
+        if let Err(e) = fs::write(&path, Keymap::default().to_file_content()) {
+            messages::warning(format!("couldn't write keymap: {}", e));
+        }
+        return Keymap::default();
+    }
+
+    match fs::read_to_string(&path) {
+        Ok(text) => {
+            let (keymap, problems) = Keymap::parse(&text);
+            for p in problems {
+                messages::warning(format!("keymap: {}", p));
+            }
+            keymap
+        }
+        Err(e) => {
+            messages::warning(format!("can't read keymap {}: {}", path.display(), e));
+            Keymap::default()
+        }
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_key_combos() {
+        let combo = KeyCombo::parse("ctrl+shift+z").unwrap();
+        assert_eq!(combo, KeyCombo::ctrl(Key::Z).shift());
+        assert_eq!(combo.to_string(), "ctrl+shift+z");
+        assert_eq!(KeyCombo::parse("down").unwrap().key, Key::ArrowDown);
+        assert!(KeyCombo::parse("hyper+z").is_none());
+        assert!(KeyCombo::parse("ctrl+").is_none());
+
+        let ctrl = Modifiers {
+            ctrl: true,
+            command: !cfg!(target_os = "macos"),
+            ..Default::default()
+        };
+        assert!(KeyCombo::ctrl(Key::G).matches(Key::G, &ctrl));
+        assert!(!KeyCombo::ctrl(Key::G).matches(Key::G, &Modifiers::default()));
+        assert!(!KeyCombo::plain(Key::Tab).matches(Key::Tab, &ctrl));
+        assert!(!KeyCombo::ctrl(Key::Z).matches(
+            Key::Z,
+            &Modifiers {
+                shift: true,
+                ..Default::default()
+            }
+        ));
+    }
+
+    #[test]
+    fn test_parse_keymap() {
+        let text = ";; mine\nmodal = on\nevaluate = alt+enter ctrl+e\ntoggle-selection = none\nfly = ctrl+f\nformat = ctrl+nope\n";
+        let (keymap, problems) = Keymap::parse(text);
+        assert!(keymap.modal);
+        assert_eq!(
+            keymap.bindings[EditorCommand::Evaluate],
+            vec![
+                KeyCombo {
+                    alt: true,
+                    ..KeyCombo::plain(Key::Enter)
+                },
+                KeyCombo::ctrl(Key::E)
+            ]
+        );
+        assert!(keymap.bindings[EditorCommand::ToggleSelection].is_empty());
+        // untouched
+        assert_eq!(
+            keymap.bindings[EditorCommand::Undo],
+            Keymap::default().bindings[EditorCommand::Undo]
+        );
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with("line 5"));
+
+        let alt = Modifiers {
+            alt: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            keymap.command_for(Key::Enter, &alt),
+            Some(EditorCommand::Evaluate)
+        );
+        assert_eq!(keymap.command_for(Key::Space, &alt), None);
+    }
+
+    #[test]
+    fn test_keymap_file_roundtrip() {
+        let mut keymap = Keymap::default();
+        keymap.modal = true;
+        keymap.paredit = false;
+        keymap.eval_on_save = EvalOnSave::Changed;
+        keymap.bindings[EditorCommand::Format].clear();
+        let (parsed, problems) = Keymap::parse(&keymap.to_file_content());
+        assert!(problems.is_empty());
+        assert_eq!(parsed, keymap);
+    }
+}
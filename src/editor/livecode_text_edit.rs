@@ -8,6 +8,17 @@ use egui::widgets::text_edit::{CCursorRange, CursorRange};
 
 use parking_lot::Mutex;
 
+use crate::editor::completion::{enclosing_function, is_completion_char, word_before, Vocabulary};
+use crate::editor::evaluation::{self, Evaluation};
+use crate::editor::keymap::{EditorCommand, Keymap};
+use crate::editor::modal::{
+    self, InsertAt, Mode, Motion, NormalCommand, Operator, Parsed, Target, TextObject,
+};
+use crate::editor::multi_cursor::{self, CursorEdit};
+use crate::editor::paredit::{self, Deletion};
+use crate::messages;
+use crate::parser::EvalError;
+
 /// The text edit state stored between frames.
 #[derive(Clone, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
@@ -20,10 +31,12 @@ pub struct LivecodeTextEditState {
     ccursor_range: Option<CCursorRange>,
 
     #[serde(skip)]
-    pub flash_cursor_range: Option<CursorRange>, // flashed on ctrl-enter
+    pub flashes: Vec<(CCursorRange, bool)>, // evaluated forms, and whether they failed
     #[serde(skip)]
     pub flash_alpha: u8, // soft fade out
     #[serde(skip)]
+    pub error_ranges: Vec<CCursorRange>, // underlined until the text changes
+    #[serde(skip)]
     pub selection_toggle: bool, // toggle selection emacs-style
 
     #[serde(skip)]
@@ -32,6 +45,26 @@ pub struct LivecodeTextEditState {
     #[serde(skip)]
     pub closing_paren_range: Option<CursorRange>, // mark parenthesis
 
+    #[serde(skip)]
+    pub completion_open: bool, // show completion popup while typing a word
+    #[serde(skip)]
+    pub completion_selected: usize,
+    #[serde(skip)]
+    pub signature_hint: bool, // show signature after a function name
+
+    #[serde(skip)]
+    pub extra_cursors: Vec<CCursorRange>, // edit at several places at once
+
+    #[serde(skip)]
+    pub redo_stack: Vec<(CCursorRange, String)>, // whatever was undone
+
+    #[serde(skip)]
+    pub mode: Mode, // only used if the keymap is modal
+    #[serde(skip)]
+    pub pending_keys: String, // normal mode keys that don't form a command yet
+    #[serde(skip)]
+    pub register: String, // deleted or yanked in normal mode
+
     /// Wrapped in Arc for cheaper clones.
     #[cfg_attr(feature = "serde", serde(skip))]
     pub undoer: Arc<Mutex<Undoer>>,
@@ -115,7 +148,14 @@ pub struct LivecodeTextEdit<'t> {
     desired_height_rows: usize,
     lock_focus: bool,
     cursor_at_end: bool,
-    eval_callback: Option<Arc<Mutex<dyn FnMut(&String)>>>,
+    eval_callback: Option<Arc<Mutex<dyn FnMut(&String) -> Result<(), EvalError>>>>,
+    vocabulary: Option<&'t Vocabulary>,
+    flash_color: Color32,
+    error_color: Color32,
+    highlights: &'t [CCursorRange],
+    jump_to: Option<CCursorRange>,
+    evaluate: Option<Evaluation>,
+    keymap: Option<&'t Keymap>,
 }
 
 impl<'t> WidgetWithState for LivecodeTextEdit<'t> {
@@ -137,6 +177,13 @@ impl<'t> LivecodeTextEdit<'t> {
             lock_focus: false,
             cursor_at_end: true,
             eval_callback: None,
+            vocabulary: None,
+            flash_color: Color32::from_rgb(220, 80, 20),
+            error_color: Color32::from_rgb(230, 10, 40),
+            highlights: &[],
+            jump_to: None,
+            evaluate: None,
+            keymap: None,
         }
     }
 
@@ -148,11 +195,60 @@ impl<'t> LivecodeTextEdit<'t> {
         self.font(FontId::monospace(15.0)).lock_focus(true)
     }
 
-    pub fn eval_callback(mut self, callback: &Arc<Mutex<dyn FnMut(&String)>>) -> Self {
+    /// A source for the unique [`Id`], e.g. `.id_source("second_text_edit_field")` or `.id_source(loop_index)`.
+    /// Each sketch needs its own, so cursors and undo history don't get mixed up.
+    pub fn id_source(mut self, id_source: impl std::hash::Hash) -> Self {
+        self.id_source = Some(Id::new(id_source));
+        self
+    }
+
+    pub fn eval_callback(
+        mut self,
+        callback: &Arc<Mutex<dyn FnMut(&String) -> Result<(), EvalError>>>,
+    ) -> Self {
         self.eval_callback = Some(Arc::clone(callback));
         self
     }
 
+    /// Complete function names, keywords and generator names
+    /// from this vocabulary.
+    pub fn vocabulary(mut self, vocabulary: &'t Vocabulary) -> Self {
+        self.vocabulary = Some(vocabulary);
+        self
+    }
+
+    /// Flash evaluated code in this color, or in `error`
+    /// if the evaluation failed. Errors are underlined in `error`, too.
+    pub fn flash_colors(mut self, flash: Color32, error: Color32) -> Self {
+        self.flash_color = flash;
+        self.error_color = error;
+        self
+    }
+
+    /// Mark these ranges, i.e. search matches.
+    pub fn highlights(mut self, highlights: &'t [CCursorRange]) -> Self {
+        self.highlights = highlights;
+        self
+    }
+
+    /// Select this range and scroll to it.
+    pub fn jump_to(mut self, range: Option<CCursorRange>) -> Self {
+        self.jump_to = range;
+        self
+    }
+
+    /// Evaluate this right away, i.e. when picked from a menu.
+    pub fn evaluate(mut self, evaluation: Option<Evaluation>) -> Self {
+        self.evaluate = evaluation;
+        self
+    }
+
+    /// Use these key bindings instead of the default ones.
+    pub fn keymap(mut self, keymap: &'t Keymap) -> Self {
+        self.keymap = Some(keymap);
+        self
+    }
+
     pub fn font(mut self, font: FontId) -> Self {
         self.font_selection = FontSelection::FontId(font);
         self
@@ -253,8 +349,18 @@ impl<'t> LivecodeTextEdit<'t> {
             lock_focus,
             cursor_at_end,
             eval_callback,
+            vocabulary,
+            flash_color,
+            error_color,
+            highlights,
+            jump_to,
+            evaluate,
+            keymap,
         } = self;
 
+        let default_keymap = Keymap::default();
+        let keymap = keymap.unwrap_or(&default_keymap);
+
         let text_color = text_color
             .or(ui.visuals().override_text_color)
             // .unwrap_or_else(|| ui.style().interact(&response).text_color()); // too bright
@@ -262,6 +368,7 @@ impl<'t> LivecodeTextEdit<'t> {
 
         let prev_text = text.as_str().to_owned();
         let font_id = font_selection.resolve(ui.style());
+        let popup_font_id = font_id.clone();
         let row_height = ui.fonts().row_height(&font_id);
         const MIN_WIDTH: f32 = 24.0; // Never make a `LivecodeTextEdit` more narrow than this.
         let available_width = ui.available_width().at_least(MIN_WIDTH);
@@ -305,6 +412,20 @@ impl<'t> LivecodeTextEdit<'t> {
         });
         let mut state = LivecodeTextEditState::load(ui.ctx(), id).unwrap_or_default();
 
+        if let Some(range) = jump_to {
+            state.extra_cursors.clear();
+            state.set_cursor_range(Some(CursorRange {
+                primary: galley.from_ccursor(range.primary),
+                secondary: galley.from_ccursor(range.secondary),
+            }));
+        }
+
+        if let Some(evaluation) = evaluate {
+            let cursor_range = state.cursor_range(&galley).unwrap_or_default();
+            let forms = evaluation_ranges(text.as_str(), &evaluation, &cursor_range);
+            evaluate_forms(text.as_str(), &forms, &mut state, eval_callback.as_ref());
+        }
+
         // On touch screens (e.g. mobile in egui_web), should
         // dragging select text, or scroll the enclosing `ScrollArea` (if any)?
         // Since currently copying selected text in not supported on `egui_web`,
@@ -357,7 +478,15 @@ impl<'t> LivecodeTextEdit<'t> {
             } else if allow_drag_to_select {
                 if response.hovered() && ui.input().pointer.any_pressed() {
                     ui.memory().request_focus(id);
-                    if ui.input().modifiers.shift {
+                    state.completion_open = false;
+                    state.signature_hint = false;
+                    if ui.input().modifiers.alt {
+                        // alt-click adds a cursor
+                        if let Some(cursor_range) = state.cursor_range(&galley) {
+                            state.extra_cursors.push(cursor_range.as_ccursor_range());
+                        }
+                        state.set_cursor_range(Some(CursorRange::one(cursor_at_pointer)));
+                    } else if ui.input().modifiers.shift {
                         if let Some(mut cursor_range) = state.cursor_range(&galley) {
                             cursor_range.primary = cursor_at_pointer;
                             state.set_cursor_range(Some(cursor_range));
@@ -365,6 +494,7 @@ impl<'t> LivecodeTextEdit<'t> {
                             state.set_cursor_range(Some(CursorRange::one(cursor_at_pointer)));
                         }
                     } else {
+                        state.extra_cursors.clear();
                         state.set_cursor_range(Some(CursorRange::one(cursor_at_pointer)));
                     }
                 } else if ui.input().pointer.any_down() && response.is_pointer_button_down_on() {
@@ -401,6 +531,8 @@ impl<'t> LivecodeTextEdit<'t> {
                 wrap_width,
                 default_cursor_range,
                 eval_callback,
+                vocabulary,
+                keymap,
             );
 
             if changed {
@@ -423,6 +555,20 @@ impl<'t> LivecodeTextEdit<'t> {
         if ui.is_rect_visible(rect) {
             painter.galley(text_draw_pos, galley.clone());
 
+            for highlight in highlights.iter() {
+                paint_cursor_selection(
+                    ui,
+                    &painter,
+                    text_draw_pos,
+                    &galley,
+                    &CursorRange {
+                        primary: galley.from_ccursor(highlight.primary),
+                        secondary: galley.from_ccursor(highlight.secondary),
+                    },
+                    Some(with_alpha(flash_color, 60)),
+                );
+            }
+
             if ui.memory().has_focus(id) {
                 if let Some(cursor_range) = state.cursor_range(&galley) {
                     // We paint the cursor on top of the text, in case
@@ -436,23 +582,51 @@ impl<'t> LivecodeTextEdit<'t> {
                         None,
                     );
 
-                    if let Some(cursorp) = state.flash_cursor_range {
-                        if state.flash_alpha > 40 {
+                    for extra in state.extra_cursors.iter() {
+                        let extra = CursorRange {
+                            primary: galley.from_ccursor(extra.primary),
+                            secondary: galley.from_ccursor(extra.secondary),
+                        };
+                        paint_cursor_selection(ui, &painter, text_draw_pos, &galley, &extra, None);
+                        paint_cursor_end(
+                            ui,
+                            row_height,
+                            &painter,
+                            text_draw_pos,
+                            &galley,
+                            &extra.primary,
+                        );
+                    }
+
+                    if state.flash_alpha > 40 {
+                        for (range, failed) in state.flashes.iter() {
+                            let color = if *failed { error_color } else { flash_color };
                             paint_cursor_selection(
                                 ui,
                                 &painter,
                                 text_draw_pos,
                                 &galley,
-                                &cursorp,
-                                Some(Color32::from_rgba_unmultiplied(
-                                    220,
-                                    80,
-                                    20,
-                                    state.flash_alpha,
-                                )),
+                                &CursorRange {
+                                    primary: galley.from_ccursor(range.primary),
+                                    secondary: galley.from_ccursor(range.secondary),
+                                },
+                                Some(with_alpha(color, state.flash_alpha)),
                             );
-                            state.flash_alpha -= 40;
                         }
+                        state.flash_alpha -= 40;
+                    }
+
+                    for error_range in state.error_ranges.iter() {
+                        paint_underline(
+                            &painter,
+                            text_draw_pos,
+                            &galley,
+                            &CursorRange {
+                                primary: galley.from_ccursor(error_range.primary),
+                                secondary: galley.from_ccursor(error_range.secondary),
+                            },
+                            error_color,
+                        );
                     }
 
                     if let Some(opening_cursor) = state.opening_paren_range {
@@ -485,16 +659,53 @@ impl<'t> LivecodeTextEdit<'t> {
                         &cursor_range.primary,
                     );
 
+                    // a block cursor in normal mode
+                    if keymap.modal && state.mode == Mode::Normal {
+                        let next = galley.cursor_right_one_character(&cursor_range.primary);
+                        let color = with_alpha(ui.visuals().selection.stroke.color, 120);
+                        paint_cursor_selection(
+                            ui,
+                            &painter,
+                            text_draw_pos,
+                            &galley,
+                            &CursorRange::two(cursor_range.primary, next),
+                            Some(color),
+                        );
+                    }
+
                     if response.changed() || selection_changed {
                         ui.scroll_to_rect(cursor_pos, None); // keep cursor in view
                     }
 
+                    if let Some(voc) = vocabulary {
+                        show_completion(
+                            ui,
+                            id,
+                            voc,
+                            &mut state,
+                            text.as_str(),
+                            cursor_range.primary.ccursor.index,
+                            cursor_pos.left_bottom(),
+                            &popup_font_id,
+                        );
+                    }
+
                     if text.is_mutable() {
                         // egui_web uses `text_cursor_pos` when showing IME,
                         // so only set it when text is editable and visible!
                         ui.ctx().output().text_cursor_pos = Some(cursor_pos.left_top());
                     }
                 }
+            } else if let Some(cursor_range) = state.cursor_range(&galley) {
+                // keep showing the selection, i.e. while searching
+                paint_cursor_selection(ui, &painter, text_draw_pos, &galley, &cursor_range, None);
+            }
+
+            if let Some(range) = jump_to {
+                let target = galley
+                    .pos_from_cursor(&galley.from_ccursor(range.primary))
+                    .translate(text_draw_pos.to_vec2());
+                ui.scroll_to_rect(target, Some(Align::Center));
             }
         }
 
@@ -537,7 +748,9 @@ fn livecode_events(
     layouter: &mut dyn FnMut(&Ui, &str, f32) -> Arc<Galley>,
     wrap_width: f32,
     default_cursor_range: CursorRange,
-    eval_callback: Option<Arc<Mutex<dyn FnMut(&String)>>>,
+    eval_callback: Option<Arc<Mutex<dyn FnMut(&String) -> Result<(), EvalError>>>>,
+    vocabulary: Option<&Vocabulary>,
+    keymap: &Keymap,
 ) -> (bool, CursorRange) {
     let mut cursor_range = state.cursor_range(&*galley).unwrap_or(default_cursor_range);
 
@@ -556,7 +769,270 @@ fn livecode_events(
 
     let events = ui.input().events.clone(); // avoid dead-lock by cloning. TODO: optimize
     for event in &events {
+        let mut command = match event {
+            Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+            } => keymap.command_for(*key, modifiers),
+            _ => None,
+        };
+
+        // in normal mode, typing runs commands instead of inserting text
+        if keymap.modal {
+            let handled = match (state.mode, event) {
+                (
+                    Mode::Insert,
+                    Event::Key {
+                        key: Key::Escape,
+                        pressed: true,
+                        ..
+                    },
+                ) => {
+                    state.mode = Mode::Normal;
+                    state.completion_open = false;
+                    state.signature_hint = false;
+                    state.extra_cursors.clear();
+                    cursor_range.secondary = cursor_range.primary;
+                    true
+                }
+                (Mode::Insert, _) => false,
+                (Mode::Normal, Event::Text(keys)) => {
+                    state.pending_keys.push_str(keys);
+                    match modal::parse_normal(&state.pending_keys, !cursor_range.is_empty()) {
+                        Parsed::Pending => {}
+                        Parsed::Invalid => state.pending_keys.clear(),
+                        Parsed::Command(normal_command) => {
+                            state.pending_keys.clear();
+                            if let Some(new_ccursor_range) = on_normal_command(
+                                ui,
+                                normal_command,
+                                &mut cursor_range,
+                                text,
+                                galley,
+                                state,
+                            ) {
+                                any_change = true;
+                                state.error_ranges.clear();
+                                if normal_command != NormalCommand::Undo {
+                                    state.redo_stack.clear();
+                                }
+                                *galley = layouter(ui, text.as_str(), wrap_width);
+                                cursor_range = CursorRange {
+                                    primary: galley.from_ccursor(new_ccursor_range.primary),
+                                    secondary: galley.from_ccursor(new_ccursor_range.secondary),
+                                };
+                            }
+                        }
+                    }
+                    true
+                }
+                (
+                    Mode::Normal,
+                    Event::Key {
+                        key: Key::R,
+                        pressed: true,
+                        modifiers,
+                    },
+                ) if modifiers.ctrl && command.is_none() => {
+                    command = Some(EditorCommand::Redo);
+                    false
+                }
+                (
+                    Mode::Normal,
+                    Event::Key {
+                        key: Key::Escape,
+                        pressed: true,
+                        ..
+                    },
+                ) => {
+                    state.pending_keys.clear();
+                    state.selection_toggle = false;
+                    cursor_range.secondary = cursor_range.primary;
+                    true
+                }
+                // anything that would type or delete, unless it's bound to a command
+                (
+                    Mode::Normal,
+                    Event::Key {
+                        key: Key::Backspace | Key::Delete | Key::Enter | Key::Tab,
+                        pressed: true,
+                        ..
+                    },
+                ) => command.is_none(),
+                (
+                    Mode::Normal,
+                    Event::Paste(_)
+                    | Event::Cut
+                    | Event::CompositionStart
+                    | Event::CompositionUpdate(_)
+                    | Event::CompositionEnd(_),
+                ) => true,
+                _ => false,
+            };
+            if handled {
+                continue;
+            }
+        }
+
+        // with several cursors, typing and deleting happens at all of them,
+        // anything else that moves the cursor or edits goes back to a single one
+        if !state.extra_cursors.is_empty() {
+            let mut cursors = vec![cursor_range.as_ccursor_range()];
+            cursors.extend(state.extra_cursors.iter().cloned());
+            let edit = match event {
+                Event::Text(text_to_insert)
+                    if !text_to_insert.is_empty()
+                        && text_to_insert != "\n"
+                        && text_to_insert != "\r" =>
+                {
+                    Some(CursorEdit::Insert(text_to_insert))
+                }
+                Event::Key {
+                    key: Key::Backspace,
+                    pressed: true,
+                    modifiers,
+                } if modifiers.is_none() => Some(CursorEdit::DeleteBack),
+                Event::Key {
+                    key: Key::Delete,
+                    pressed: true,
+                    modifiers,
+                } if modifiers.is_none() => Some(CursorEdit::DeleteForward),
+                _ => None,
+            };
+
+            let moved = match event {
+                Event::Key {
+                    key: key @ (Key::ArrowLeft | Key::ArrowRight),
+                    pressed: true,
+                    modifiers,
+                } if modifiers.is_none() => Some(multi_cursor::move_cursors(
+                    text.as_str(),
+                    &cursors,
+                    *key == Key::ArrowLeft,
+                )),
+                _ => None,
+            };
+
+            let handled = edit.is_some() || moved.is_some();
+            if let Some(edit) = edit {
+                cursors = multi_cursor::edit_at_cursors(text, &cursors, edit);
+                any_change = true;
+                state.error_ranges.clear();
+                state.redo_stack.clear();
+                *galley = layouter(ui, text.as_str(), wrap_width);
+            } else if let Some(moved) = moved {
+                cursors = moved;
+            }
+
+            if handled {
+                let mut cursors = multi_cursor::dedup_cursors(cursors);
+                let primary = cursors.remove(0);
+                state.extra_cursors = cursors;
+                state.completion_open = false;
+                cursor_range = CursorRange {
+                    primary: galley.from_ccursor(primary.primary),
+                    secondary: galley.from_ccursor(primary.secondary),
+                };
+                continue;
+            }
+
+            match event {
+                _ if command == Some(EditorCommand::AddCursor) => {}
+                Event::Key {
+                    key:
+                        Key::Backspace
+                        | Key::Delete
+                        | Key::ArrowLeft
+                        | Key::ArrowRight
+                        | Key::ArrowUp
+                        | Key::ArrowDown
+                        | Key::Home
+                        | Key::End
+                        | Key::PageUp
+                        | Key::PageDown
+                        | Key::Escape
+                        | Key::Enter
+                        | Key::Tab,
+                    pressed: true,
+                    ..
+                }
+                | Event::Paste(_)
+                | Event::Cut => state.extra_cursors.clear(),
+                Event::Key {
+                    pressed: true,
+                    modifiers,
+                    ..
+                } if modifiers.ctrl || modifiers.command => state.extra_cursors.clear(),
+                _ => {}
+            }
+        }
+
+        // undo and redo keep what's left to redo
+        let mut keep_redo = false;
+
+        // while the completion popup is open, it takes the
+        // navigation keys and tab/enter to accept a completion
+        let mut completed = None;
+        if let (
+            true,
+            Some(voc),
+            Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+            },
+        ) = (state.completion_open, vocabulary, event)
+        {
+            let cursor = cursor_range.primary.ccursor;
+            let candidates = voc.candidates(text.as_str(), cursor.index);
+            if !candidates.is_empty() && modifiers.is_none() {
+                match key {
+                    Key::ArrowDown => {
+                        state.completion_selected =
+                            (state.completion_selected + 1) % candidates.len();
+                        continue;
+                    }
+                    Key::ArrowUp => {
+                        state.completion_selected =
+                            (state.completion_selected + candidates.len() - 1) % candidates.len();
+                        continue;
+                    }
+                    Key::Escape => {
+                        state.completion_open = false;
+                        continue;
+                    }
+                    Key::Tab | Key::Enter => {
+                        let selected =
+                            &candidates[state.completion_selected.min(candidates.len() - 1)];
+                        let (start, _) = word_before(text.as_str(), cursor.index);
+                        let mut ccursor =
+                            delete_selected_ccursor_range(text, [CCursor::new(start), cursor]);
+                        insert_text(&mut ccursor, text, &selected.label);
+                        state.completion_open = false;
+                        state.signature_hint = true;
+                        completed = Some(CCursorRange::one(ccursor));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // any other key closes the popup, typing re-opens it
+        if completed.is_none() {
+            if let Event::Key {
+                key, pressed: true, ..
+            } = event
+            {
+                if *key != Key::Backspace {
+                    state.completion_open = false;
+                    state.signature_hint = false;
+                }
+            }
+        }
+
         let did_mutate_text = match event {
+            _ if completed.is_some() => completed,
             Event::Copy => {
                 // clear selection
                 state.selection_toggle = false;
@@ -588,31 +1064,17 @@ fn livecode_events(
                     None
                 }
             }
-            Event::Key {
-                key: Key::W,
-                pressed: true,
-                modifiers,
-            } => {
-                if modifiers.ctrl {
-                    // clear selection
-                    state.selection_toggle = false;
-                    state.clear_paren_selection();
-                    copy_if_not_password(ui, selected_str(text, &cursor_range).to_owned());
-                    Some(CCursorRange::one(delete_selected(text, &cursor_range)))
-                } else {
-                    None
-                }
+            _ if command == Some(EditorCommand::CutSelection) => {
+                // clear selection
+                state.selection_toggle = false;
+                state.clear_paren_selection();
+                copy_if_not_password(ui, selected_str(text, &cursor_range).to_owned());
+                Some(CCursorRange::one(delete_selected(text, &cursor_range)))
             }
-            Event::Key {
-                key: Key::G,
-                pressed: true,
-                modifiers,
-            } => {
-                if modifiers.ctrl {
-                    // clear selection
-                    state.selection_toggle = false;
-                    cursor_range.secondary = cursor_range.primary;
-                }
+            _ if command == Some(EditorCommand::CancelSelection) => {
+                // clear selection
+                state.selection_toggle = false;
+                cursor_range.secondary = cursor_range.primary;
                 None
             }
             Event::Text(text_to_insert) => {
@@ -620,9 +1082,26 @@ fn livecode_events(
                 state.selection_toggle = false;
                 state.clear_paren_selection();
 
+                state.completion_open = text_to_insert.chars().all(is_completion_char);
+                state.completion_selected = 0;
+                state.signature_hint = text_to_insert == " ";
+
+                // closing what's closed already just moves on
+                let skip_close = if keymap.paredit && cursor_range.is_empty() {
+                    paredit::typed_close(
+                        text.as_str(),
+                        cursor_range.primary.ccursor.index,
+                        text_to_insert,
+                    )
+                } else {
+                    None
+                };
+
                 // Newlines are handled by `Key::Enter`.
                 if !text_to_insert.is_empty() && text_to_insert != "\n" && text_to_insert != "\r" {
-                    if text_to_insert == "(" {
+                    if let Some(index) = skip_close {
+                        Some(CCursorRange::one(CCursor::new(index)))
+                    } else if text_to_insert == "(" {
                         // enclose selection in parenthesis and
                         // jump to opening ...
                         let selection = selected_str(text, &cursor_range).to_string();
@@ -672,11 +1151,7 @@ fn livecode_events(
                     None
                 }
             }
-            Event::Key {
-                key: Key::Tab,
-                pressed: true,
-                ..
-            } => {
+            _ if command == Some(EditorCommand::Format) => {
                 if let Some(sexp_cursors) = find_toplevel_sexp(text.as_str(), &cursor_range) {
                     let old_cursor = cursor_range.as_ccursor_range();
                     let cup = CursorRange {
@@ -693,89 +1168,110 @@ fn livecode_events(
                     None
                 }
             }
-            Event::Key {
-                key: Key::F,
-                pressed: true,
-                modifiers,
-            } if modifiers.command => {
+            _ if command == Some(EditorCommand::ForwardChar) => {
                 cursor_range.primary = galley.cursor_right_one_character(&cursor_range.primary);
                 Some(CCursorRange::one(cursor_range.primary.ccursor))
             }
+            _ if matches!(
+                command,
+                Some(
+                    EditorCommand::Evaluate
+                        | EditorCommand::EvaluateSelection
+                        | EditorCommand::EvaluateBuffer
+                        | EditorCommand::EvaluateRegion
+                )
+            ) =>
+            {
+                let evaluation = match command {
+                    Some(EditorCommand::EvaluateSelection) => Evaluation::Selection,
+                    Some(EditorCommand::EvaluateBuffer) => Evaluation::Buffer,
+                    Some(EditorCommand::EvaluateRegion) => Evaluation::Region(None),
+                    _ => Evaluation::Form,
+                };
+                let forms = evaluation_ranges(text.as_str(), &evaluation, &cursor_range);
+
+                // clear selection
+                state.selection_toggle = false;
+                state.clear_paren_selection();
+
+                evaluate_forms(text.as_str(), &forms, state, eval_callback.as_ref());
+                break; // one evaluation per frame
+            }
             Event::Key {
                 key: Key::Enter,
                 pressed: true,
-                modifiers,
+                ..
             } => {
                 // clear selection
                 state.selection_toggle = false;
                 state.clear_paren_selection();
 
-                if modifiers.command {
-                    if let Some(sexp_cursors) = find_toplevel_sexp(text.as_str(), &cursor_range) {
-                        let cup = CursorRange {
-                            primary: galley.from_ccursor(sexp_cursors.primary),
-                            secondary: galley.from_ccursor(sexp_cursors.secondary),
-                        };
+                // let's check if we're in an s-expression
+                // auto-indent in that case
+                if let Some(sexp_cursors) = find_toplevel_sexp(text.as_str(), &cursor_range) {
+                    let mut ccursorp = cursor_range.as_ccursor_range();
+                    // only need indentation, so let's get the text
+                    // from the beginning of the current s-expression
+                    // to the current cursor pos
+                    let cup = CursorRange {
+                        primary: galley.from_ccursor(sexp_cursors.primary),
+                        secondary: galley.from_ccursor(ccursorp.primary),
+                    };
 
-                        // flash selected sexp ...
-                        let sel = selected_str(text, &cup);
-                        state.flash_cursor_range = Some(cup);
-                        state.flash_alpha = 240; // set flash alpha ()
-                        if let Some(cb) = eval_callback {
-                            let mut cb_loc = cb.lock();
-                            cb_loc(&sel.to_string());
-                        } else {
-                            println!("no callback!");
+                    // get indentation level
+                    let indent_level = sexp_indent_level(selected_str(text, &cup));
+                    // insert line break and indentation ...
+                    insert_text(&mut ccursorp.secondary, text, "\n");
+                    if indent_level > 0 {
+                        for _ in 0..indent_level {
+                            insert_text(&mut ccursorp.secondary, text, "  ");
                         }
                     }
-                    break; // need to break here because of callback move ...
+                    Some(CCursorRange::one(ccursorp.secondary))
                 } else {
-                    // let's check if we're in an s-expression
-                    // auto-indent in that case
-                    if let Some(sexp_cursors) = find_toplevel_sexp(text.as_str(), &cursor_range) {
-                        let mut ccursorp = cursor_range.as_ccursor_range();
-                        // only need indentation, so let's get the text
-                        // from the beginning of the current s-expression
-                        // to the current cursor pos
-                        let cup = CursorRange {
-                            primary: galley.from_ccursor(sexp_cursors.primary),
-                            secondary: galley.from_ccursor(ccursorp.primary),
-                        };
-
-                        // get indentation level
-                        let indent_level = sexp_indent_level(selected_str(text, &cup));
-                        // insert line break and indentation ...
-                        insert_text(&mut ccursorp.secondary, text, "\n");
-                        if indent_level > 0 {
-                            for _ in 0..indent_level {
-                                insert_text(&mut ccursorp.secondary, text, "  ");
-                            }
-                        }
-                        Some(CCursorRange::one(ccursorp.secondary))
-                    } else {
-                        let mut ccursor = delete_selected(text, &cursor_range);
-                        insert_text(&mut ccursor, text, "\n");
-                        Some(CCursorRange::one(ccursor))
-                    }
+                    let mut ccursor = delete_selected(text, &cursor_range);
+                    insert_text(&mut ccursor, text, "\n");
+                    Some(CCursorRange::one(ccursor))
                 }
             }
-            Event::Key {
-                key: Key::Z,
-                pressed: true,
-                modifiers,
-            } if modifiers.command && !modifiers.shift => {
-                // TODO: redo
-                if let Some((undo_ccursor_range, undo_txt)) = state
-                    .undoer
-                    .lock()
-                    .undo(&(cursor_range.as_ccursor_range(), text.as_str().to_owned()))
-                {
-                    text.replace(undo_txt);
-                    Some(*undo_ccursor_range)
+            _ if command == Some(EditorCommand::Undo) => {
+                keep_redo = true;
+                undo(state, &cursor_range, text)
+            }
+            _ if command == Some(EditorCommand::Redo) => {
+                keep_redo = true;
+                if let Some((redo_ccursor_range, redo_txt)) = state.redo_stack.pop() {
+                    text.replace(&redo_txt);
+                    Some(redo_ccursor_range)
                 } else {
                     None
                 }
             }
+            _ if command == Some(EditorCommand::AddCursor) => {
+                if cursor_range.is_empty() {
+                    // select the word under the cursor first
+                    let word = select_word_at(text.as_str(), cursor_range.primary.ccursor);
+                    cursor_range = CursorRange {
+                        primary: galley.from_ccursor(word.primary),
+                        secondary: galley.from_ccursor(word.secondary),
+                    };
+                } else {
+                    // then add a cursor at the next occurrence
+                    let needle = selected_str(text, &cursor_range).to_string();
+                    let mut cursors = vec![cursor_range.as_ccursor_range()];
+                    cursors.extend(state.extra_cursors.iter().cloned());
+                    if let Some(next) =
+                        multi_cursor::next_occurrence(text.as_str(), &needle, &cursors)
+                    {
+                        state.extra_cursors.push(cursor_range.as_ccursor_range());
+                        cursor_range = CursorRange {
+                            primary: galley.from_ccursor(next.secondary),
+                            secondary: galley.from_ccursor(next.primary),
+                        };
+                    }
+                }
+                None
+            }
             Event::Key {
                 key: Key::Escape,
                 pressed: true,
@@ -788,23 +1284,112 @@ fn livecode_events(
                 //ui.memory().surrender_focus(id);
                 break;
             }
+            _ if command == Some(EditorCommand::SlurpForward) => structural_edit(
+                text,
+                paredit::slurp_forward(text.as_str(), cursor_range.primary.ccursor.index),
+            ),
+            _ if command == Some(EditorCommand::BarfForward) => structural_edit(
+                text,
+                paredit::barf_forward(text.as_str(), cursor_range.primary.ccursor.index),
+            ),
+            _ if command == Some(EditorCommand::Wrap) => {
+                let [min, max] = cursor_range.sorted_cursors();
+                structural_edit(
+                    text,
+                    paredit::wrap(text.as_str(), min.ccursor.index, max.ccursor.index),
+                )
+            }
+            _ if command == Some(EditorCommand::Splice) => structural_edit(
+                text,
+                paredit::splice(text.as_str(), cursor_range.primary.ccursor.index),
+            ),
+            _ if command == Some(EditorCommand::Raise) => structural_edit(
+                text,
+                paredit::raise(text.as_str(), cursor_range.primary.ccursor.index),
+            ),
+            _ if command == Some(EditorCommand::SelectEnclosing) => {
+                let [min, max] = cursor_range.sorted_cursors();
+                if let Some((start, end)) =
+                    paredit::expand_selection(text.as_str(), min.ccursor.index, max.ccursor.index)
+                {
+                    cursor_range = CursorRange {
+                        primary: galley.from_ccursor(CCursor::new(end)),
+                        secondary: galley.from_ccursor(CCursor::new(start)),
+                    };
+                }
+                None
+            }
+            _ if command == Some(EditorCommand::KillLine) && keymap.paredit => {
+                // kill to the end of the line, but leave the brackets alone
+                let index = cursor_range.primary.ccursor.index;
+                let line_end = find_end_of_row(text.as_str(), &cursor_range.primary.ccursor)
+                    .map_or(text.as_str().chars().count(), |(end, _)| end.index);
+                let end = if line_end == index {
+                    // on an empty line, join with the next one
+                    (index + 1).min(text.as_str().chars().count())
+                } else {
+                    paredit::kill_end(text.as_str(), index, line_end)
+                };
+                Some(CCursorRange::one(delete_selected_ccursor_range(
+                    text,
+                    [CCursor::new(index), CCursor::new(end)],
+                )))
+            }
             Event::Key {
-                key: Key::Space,
+                key: key @ (Key::Backspace | Key::Delete),
                 pressed: true,
                 modifiers,
-            } => {
-                if modifiers.command {
-                    state.selection_toggle = !state.selection_toggle;
+            } if keymap.paredit && modifiers.is_none() && cursor_range.is_empty() => {
+                let index = cursor_range.primary.ccursor.index;
+                let deletion = if *key == Key::Backspace {
+                    paredit::delete_backward(text.as_str(), index)
                 } else {
-                    state.clear_paren_selection();
+                    paredit::delete_forward(text.as_str(), index)
+                };
+                match deletion {
+                    Deletion::Range(start, end) => {
+                        Some(CCursorRange::one(delete_selected_ccursor_range(
+                            text,
+                            [CCursor::new(start), CCursor::new(end)],
+                        )))
+                    }
+                    Deletion::Skip(index) => Some(CCursorRange::one(CCursor::new(index))),
+                    Deletion::Normal => on_key_press(
+                        &mut cursor_range,
+                        text,
+                        galley,
+                        *key,
+                        modifiers,
+                        command,
+                        state,
+                    ),
                 }
+            }
+            _ if command == Some(EditorCommand::ToggleSelection) => {
+                state.selection_toggle = !state.selection_toggle;
+                None
+            }
+            Event::Key {
+                key: Key::Space,
+                pressed: true,
+                ..
+            } => {
+                state.clear_paren_selection();
                 None
             }
             Event::Key {
                 key,
                 pressed: true,
                 modifiers,
-            } => on_key_press(&mut cursor_range, text, galley, *key, modifiers, state),
+            } => on_key_press(
+                &mut cursor_range,
+                text,
+                galley,
+                *key,
+                modifiers,
+                command,
+                state,
+            ),
 
             Event::CompositionStart => {
                 state.has_ime = true;
@@ -843,6 +1428,10 @@ fn livecode_events(
 
         if let Some(new_ccursor_range) = did_mutate_text {
             any_change = true;
+            state.error_ranges.clear();
+            if !keep_redo {
+                state.redo_stack.clear();
+            }
 
             // Layout again to avoid frame delay, and to keep `text` and `galley` in sync.
             *galley = layouter(ui, text.as_str(), wrap_width);
@@ -867,6 +1456,60 @@ fn livecode_events(
 
 // ----------------------------------------------------------------------------
 
+/// Show the completion popup below the cursor, or, right after a
+/// function name, the signature of that function.
+#[allow(clippy::too_many_arguments)]
+fn show_completion(
+    ui: &Ui,
+    id: Id,
+    vocabulary: &Vocabulary,
+    state: &mut LivecodeTextEditState,
+    text: &str,
+    cursor: usize,
+    pos: Pos2,
+    font_id: &FontId,
+) {
+    let (candidates, detail) = if state.completion_open {
+        let candidates = vocabulary.candidates(text, cursor);
+        if candidates.is_empty() {
+            return;
+        }
+        state.completion_selected = state.completion_selected.min(candidates.len() - 1);
+        let detail = candidates[state.completion_selected].detail.clone();
+        (candidates, detail)
+    } else if state.signature_hint {
+        match enclosing_function(text, cursor).and_then(|f| vocabulary.signature(&f)) {
+            Some(sig) => (Vec::new(), sig.to_string()),
+            None => return,
+        }
+    } else {
+        return;
+    };
+
+    Area::new(id.with("completion"))
+        .order(Order::Foreground)
+        .fixed_pos(pos)
+        .show(ui.ctx(), |ui| {
+            Frame::popup(ui.style()).show(ui, |ui| {
+                for (i, cand) in candidates.iter().enumerate() {
+                    let mut label = RichText::new(&cand.label).font(font_id.clone());
+                    if i == state.completion_selected {
+                        label = label.background_color(ui.visuals().selection.bg_fill);
+                    }
+                    ui.add(Label::new(label).wrap(false));
+                }
+                if !detail.is_empty() {
+                    if !candidates.is_empty() {
+                        ui.separator();
+                    }
+                    ui.add(
+                        Label::new(RichText::new(detail).font(font_id.clone()).weak()).wrap(false),
+                    );
+                }
+            });
+        });
+}
+
 fn paint_cursor_selection(
     ui: &mut Ui,
     painter: &Painter,
@@ -915,6 +1558,45 @@ fn paint_cursor_selection(
     }
 }
 
+/// the flash fades out, so it needs its own alpha
+fn with_alpha(color: Color32, alpha: u8) -> Color32 {
+    Color32::from_rgba_unmultiplied(color.r(), color.g(), color.b(), alpha)
+}
+
+/// Underline a range, i.e. to mark an error.
+fn paint_underline(
+    painter: &Painter,
+    pos: Pos2,
+    galley: &Galley,
+    cursor_range: &CursorRange,
+    color: Color32,
+) {
+    let [min, max] = cursor_range.sorted_cursors();
+    let min = min.rcursor;
+    let max = max.rcursor;
+
+    for ri in min.row..=max.row {
+        let row = &galley.rows[ri];
+        let left = if ri == min.row {
+            row.x_offset(min.column)
+        } else {
+            row.rect.left()
+        };
+        let right = if ri == max.row {
+            row.x_offset(max.column)
+        } else {
+            row.rect.right()
+        };
+        painter.line_segment(
+            [
+                pos + vec2(left, row.max_y() - 1.0),
+                pos + vec2(right, row.max_y() - 1.0),
+            ],
+            Stroke::new(2.0, color),
+        );
+    }
+}
+
 fn paint_cursor_end(
     ui: &mut Ui,
     row_height: f32,
@@ -1050,6 +1732,7 @@ fn on_key_press(
     galley: &Galley,
     key: Key,
     modifiers: &Modifiers,
+    command: Option<EditorCommand>,
     state: &mut LivecodeTextEditState,
 ) -> Option<CCursorRange> {
     state.clear_paren_selection();
@@ -1119,14 +1802,13 @@ fn on_key_press(
             Some(CCursorRange::one(ccursor))
         }
 
-        Key::A if modifiers.command => {
+        _ if command == Some(EditorCommand::SelectAll) => {
             // select all
             *cursor_range = CursorRange::two(Cursor::default(), galley.end());
             None
         }
 
-        Key::T if modifiers.command => {
-            // select all
+        _ if command == Some(EditorCommand::ToggleComment) => {
             let old_cursor = cursor_range.as_ccursor_range().primary;
             let on = toggle_sexp(text, galley, &cursor_range.primary.ccursor);
             if let Some(sexp_cursors) = find_toplevel_sexp(text.as_str(), cursor_range) {
@@ -1153,12 +1835,12 @@ fn on_key_press(
             }
         }
 
-        Key::K if modifiers.ctrl => {
+        _ if command == Some(EditorCommand::KillLine) => {
             let ccursor = delete_paragraph_after_cursor(text, galley, cursor_range);
             Some(CCursorRange::one(ccursor))
         }
 
-        Key::U if modifiers.ctrl => {
+        _ if command == Some(EditorCommand::KillLineBefore) => {
             let ccursor = delete_paragraph_before_cursor(text, galley, cursor_range);
             Some(CCursorRange::one(ccursor))
         }
@@ -1250,6 +1932,225 @@ fn on_key_press(
     }
 }
 
+/// Replace the text with the result of a structural edit, if there is one.
+fn structural_edit(
+    text: &mut dyn TextBuffer,
+    edit: Option<(String, usize)>,
+) -> Option<CCursorRange> {
+    let (edited, index) = edit?;
+    text.replace(&edited);
+    Some(CCursorRange::one(CCursor::new(index)))
+}
+
+/// Go back to the last undo point, keeping the current state for redo.
+fn undo(
+    state: &mut LivecodeTextEditState,
+    cursor_range: &CursorRange,
+    text: &mut dyn TextBuffer,
+) -> Option<CCursorRange> {
+    let (undo_ccursor_range, undo_txt) = state
+        .undoer
+        .lock()
+        .undo(&(cursor_range.as_ccursor_range(), text.as_str().to_owned()))
+        .cloned()?;
+    state
+        .redo_stack
+        .push((cursor_range.as_ccursor_range(), text.as_str().to_owned()));
+    text.replace(&undo_txt);
+    Some(undo_ccursor_range)
+}
+
+/// Carry out a normal mode command.
+/// Returns `Some(new_cursor)` if we did mutate `text`.
+fn on_normal_command(
+    ui: &Ui,
+    command: NormalCommand,
+    cursor_range: &mut CursorRange,
+    text: &mut dyn TextBuffer,
+    galley: &Galley,
+    state: &mut LivecodeTextEditState,
+) -> Option<CCursorRange> {
+    state.clear_paren_selection();
+    let index = cursor_range.primary.ccursor.index;
+
+    match command {
+        NormalCommand::Move(motion) => {
+            cursor_range.primary = motion_target(galley, &cursor_range.primary, motion);
+            if !state.selection_toggle {
+                cursor_range.secondary = cursor_range.primary;
+            }
+            None
+        }
+        NormalCommand::Operate(operator, target) => {
+            let (start, mut end) = target_range(galley, cursor_range, target)?;
+            // changing a line keeps the line break
+            if operator == Operator::Change
+                && target == Target::Line
+                && text.as_str().chars().nth(end.saturating_sub(1)) == Some('\n')
+            {
+                end -= 1;
+            }
+            let range = [CCursor::new(start), CCursor::new(end)];
+            state.register = selected_str(
+                text,
+                &CursorRange::two(galley.from_ccursor(range[0]), galley.from_ccursor(range[1])),
+            )
+            .to_string();
+            ui.ctx().output().copied_text = state.register.clone();
+            state.selection_toggle = false;
+
+            match operator {
+                Operator::Yank => {
+                    *cursor_range = CursorRange::one(galley.from_ccursor(range[0]));
+                    None
+                }
+                Operator::Delete => Some(CCursorRange::one(delete_selected_ccursor_range(
+                    text, range,
+                ))),
+                Operator::Change => {
+                    state.mode = Mode::Insert;
+                    Some(CCursorRange::one(delete_selected_ccursor_range(
+                        text, range,
+                    )))
+                }
+            }
+        }
+        NormalCommand::Insert(at) => {
+            state.mode = Mode::Insert;
+            state.selection_toggle = false;
+            let (line_start, mut line_end) = modal::line_range(text.as_str(), index);
+            if text.as_str().chars().nth(line_end.saturating_sub(1)) == Some('\n') {
+                line_end -= 1;
+            }
+            let new_index = match at {
+                InsertAt::Cursor => index,
+                InsertAt::AfterCursor => (index + 1).min(line_end),
+                InsertAt::LineStart => line_start,
+                InsertAt::LineEnd => line_end,
+                InsertAt::LineBelow => {
+                    let mut ccursor = CCursor::new(line_end);
+                    insert_text(&mut ccursor, text, "\n");
+                    return Some(CCursorRange::one(ccursor));
+                }
+                InsertAt::LineAbove => {
+                    let mut ccursor = CCursor::new(line_start);
+                    insert_text(&mut ccursor, text, "\n");
+                    return Some(CCursorRange::one(CCursor::new(line_start)));
+                }
+            };
+            *cursor_range = CursorRange::one(galley.from_ccursor(CCursor::new(new_index)));
+            None
+        }
+        NormalCommand::DeleteChar => match text.as_str().chars().nth(index) {
+            Some(c) if c != '\n' => {
+                state.register = c.to_string();
+                Some(CCursorRange::one(delete_next_char(
+                    text,
+                    CCursor::new(index),
+                )))
+            }
+            _ => None,
+        },
+        NormalCommand::Paste { before } => {
+            if state.register.is_empty() {
+                return None;
+            }
+            let position = modal::paste_position(text.as_str(), index, &state.register, before);
+            let mut ccursor = CCursor::new(position);
+            insert_text(&mut ccursor, text, &state.register);
+            Some(CCursorRange::one(ccursor))
+        }
+        NormalCommand::Undo => undo(state, cursor_range, text),
+        NormalCommand::ToggleSelection => {
+            state.selection_toggle = !state.selection_toggle;
+            if !state.selection_toggle {
+                cursor_range.secondary = cursor_range.primary;
+            }
+            None
+        }
+    }
+}
+
+fn motion_target(galley: &Galley, cursor: &Cursor, motion: Motion) -> Cursor {
+    let text = galley.text();
+    let ccursor = cursor.ccursor;
+    match motion {
+        Motion::Left => galley.cursor_left_one_character(cursor),
+        Motion::Right => galley.cursor_right_one_character(cursor),
+        Motion::Up => galley.cursor_up_one_row(cursor),
+        Motion::Down => galley.cursor_down_one_row(cursor),
+        Motion::WordForward => galley.from_ccursor(ccursor_next_word(text, ccursor)),
+        Motion::WordBack => galley.from_ccursor(ccursor_previous_word(text, ccursor)),
+        Motion::LineStart => galley.cursor_begin_of_row(cursor),
+        Motion::LineEnd => galley.cursor_end_of_row(cursor),
+        Motion::Top => Cursor::default(),
+        Motion::Bottom => galley.end(),
+        Motion::Open => {
+            find_opening_paren(text, &ccursor).map_or(*cursor, |c| galley.from_ccursor(c))
+        }
+        Motion::Close => {
+            // when on a closing paren already, go to the next one out
+            let from = if text.chars().nth(ccursor.index) == Some(')') {
+                CCursor::new(ccursor.index + 1)
+            } else {
+                ccursor
+            };
+            find_closing_paren(text, &from)
+                .map_or(*cursor, |c| galley.from_ccursor(CCursor::new(c.index - 1)))
+        }
+        Motion::NextForm | Motion::PreviousForm => {
+            modal::toplevel_form_start(text, ccursor.index, motion == Motion::NextForm)
+                .map_or(*cursor, |i| galley.from_ccursor(CCursor::new(i)))
+        }
+    }
+}
+
+/// The char range an operator works on.
+fn target_range(
+    galley: &Galley,
+    cursor_range: &CursorRange,
+    target: Target,
+) -> Option<(usize, usize)> {
+    let text = galley.text();
+    let ccursor = cursor_range.primary.ccursor;
+    match target {
+        Target::Selection => {
+            let [min, max] = cursor_range.sorted_cursors();
+            Some((min.ccursor.index, max.ccursor.index))
+        }
+        Target::Line => Some(modal::line_range(text, ccursor.index)),
+        Target::Motion(motion) => {
+            let to = motion_target(galley, &cursor_range.primary, motion)
+                .ccursor
+                .index;
+            Some((ccursor.index.min(to), ccursor.index.max(to)))
+        }
+        Target::Object(TextObject::Word, around) => {
+            let (start, mut end) = multi_cursor::bounds(&select_word_at(text, ccursor));
+            if around {
+                end += text.chars().skip(end).take_while(|c| *c == ' ').count();
+            }
+            Some((start, end))
+        }
+        Target::Object(TextObject::Sexp, around) => {
+            let (open, close) = find_current_sexp(text, &ccursor)?;
+            if around {
+                Some((open.index, close.index))
+            } else {
+                Some((open.index + 1, close.index - 1))
+            }
+        }
+        Target::Object(TextObject::Form, around) => {
+            let (start, end) = multi_cursor::bounds(&find_toplevel_sexp(text, cursor_range)?);
+            if around {
+                Some((start, end))
+            } else {
+                Some((start + 1, end - 1))
+            }
+        }
+    }
+}
+
 fn move_single_cursor(cursor: &mut Cursor, galley: &Galley, key: Key, modifiers: &Modifiers) {
     match key {
         Key::ArrowLeft => {
@@ -1398,6 +2299,104 @@ fn is_word_char(c: char) -> bool {
 }
 
 // LIVECODE TEXT EDIT HELPERS
+/// The forms to evaluate, in order (char indices).
+fn evaluation_ranges(
+    text: &str,
+    evaluation: &Evaluation,
+    cursorp: &CursorRange,
+) -> Vec<(usize, usize)> {
+    let [min, max] = cursorp.sorted_cursors();
+    match evaluation {
+        Evaluation::Selection if min.ccursor.index != max.ccursor.index => {
+            evaluation::forms_in(text, min.ccursor.index, max.ccursor.index)
+        }
+        Evaluation::Form | Evaluation::Selection => find_toplevel_sexp(text, cursorp)
+            .map(|sexp| {
+                let (a, b) = (sexp.primary.index, sexp.secondary.index);
+                vec![(a.min(b), a.max(b))]
+            })
+            .unwrap_or_default(),
+        Evaluation::Buffer => evaluation::forms_in(text, 0, text.chars().count()),
+        Evaluation::Region(name) => {
+            let region = match name {
+                Some(name) => evaluation::regions(text)
+                    .into_iter()
+                    .find(|r| r.name == *name),
+                None => evaluation::region_at(text, min.ccursor.index),
+            };
+            if let Some(region) = region {
+                evaluation::forms_in(text, region.start, region.end)
+            } else {
+                messages::warning(format!(
+                    "no region {}",
+                    name.as_deref().unwrap_or("around the cursor")
+                ));
+                Vec::new()
+            }
+        }
+        Evaluation::Changed(before) => evaluation::changed_forms(before, text),
+    }
+}
+
+/// Evaluate the forms one after another, so later ones can use what
+/// earlier ones defined. Each one flashes, in the error color if it failed.
+fn evaluate_forms(
+    text: &str,
+    forms: &[(usize, usize)],
+    state: &mut LivecodeTextEditState,
+    eval_callback: Option<&Arc<Mutex<dyn FnMut(&String) -> Result<(), EvalError>>>>,
+) {
+    if forms.is_empty() {
+        return;
+    }
+
+    let cb = if let Some(cb) = eval_callback {
+        cb
+    } else {
+        println!("no callback!");
+        return;
+    };
+
+    state.flashes.clear();
+    state.error_ranges.clear();
+    state.flash_alpha = 240; // set flash alpha ()
+
+    let mut cb_loc = cb.lock();
+    for (start, end) in forms.iter() {
+        let form: String = text.chars().skip(*start).take(end - start).collect();
+        let failed = match cb_loc(&form) {
+            Ok(()) => false,
+            Err(e) => {
+                // the span is given in bytes within the form
+                if let Some((from, to)) = e.span {
+                    state.error_ranges.push(CCursorRange::two(
+                        CCursor::new(start + form[..from].chars().count()),
+                        CCursor::new(start + form[..to].chars().count()),
+                    ));
+                }
+                true
+            }
+        };
+        state.flashes.push((
+            CCursorRange::two(CCursor::new(*start), CCursor::new(*end)),
+            failed,
+        ));
+    }
+
+    if forms.len() > 1 {
+        let failed = state.flashes.iter().filter(|(_, failed)| *failed).count();
+        if failed > 0 {
+            messages::warning(format!(
+                "evaluated {} forms, {} failed",
+                forms.len(),
+                failed
+            ));
+        } else {
+            messages::info(format!("evaluated {} forms", forms.len()));
+        }
+    }
+}
+
 /// find toplevel s-expression from current cursor position ...
 fn find_toplevel_sexp(text: &str, cursorp: &CursorRange) -> Option<CCursorRange> {
     let [min, _] = cursorp.sorted_cursors();
@@ -1419,10 +2418,12 @@ fn find_toplevel_sexp(text: &str, cursorp: &CursorRange) -> Option<CCursorRange>
             pos += 1;
         }
     }
-    if let Some(prev_char) = text.chars().nth(pos - 1) {
-        if prev_char == ')' {
-            rev_pos = text.chars().count() - (pos - 1);
-            pos -= 1;
+    if pos > 0 {
+        if let Some(prev_char) = text.chars().nth(pos - 1) {
+            if prev_char == ')' {
+                rev_pos = text.chars().count() - (pos - 1);
+                pos -= 1;
+            }
         }
     }
 
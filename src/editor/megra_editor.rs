@@ -4,15 +4,75 @@ use parking_lot::Mutex;
 use std::{fs, path, sync::*};
 
 use egui::style::Margin;
+use egui::widgets::text_edit::CCursorRange;
 use egui::FontId;
-use epaint::text::{FontData, FontDefinitions, FontFamily};
+use epaint::text::{cursor::CCursor, FontData, FontDefinitions, FontFamily};
 // custom text edit window
+use crate::editor::completion::Vocabulary;
+use crate::editor::evaluation::{self, EvalOnSave, Evaluation};
+use crate::editor::generator_panel::{generator_panel, GeneratorAction};
+use crate::editor::keymap::{EditorCommand, Keymap};
 use crate::editor::livecode_text_edit::LivecodeTextEdit;
+use crate::editor::meters::{meter_panel, Meters};
+use crate::editor::search::*;
+use crate::editor::sketchbook::*;
 use crate::editor::syntax_highlighting::*;
+use crate::editor::theme::{ThemeColors, TokenType};
+use crate::messages::{self, Message, Severity};
+use crate::parser::EvalError;
+use crate::real_time_streaming::MeterReading;
+use crate::session::GeneratorStatus;
+
+// how often (in seconds) function names and generators are refreshed
+const VOCABULARY_REFRESH_INTERVAL: f64 = 1.0;
+// lines kept in the log pane
+const MAX_LOG_LINES: usize = 500;
+// how often (in seconds) the generator panel is refreshed
+const GENERATOR_REFRESH_INTERVAL: f64 = 0.1;
+// how often (in seconds) the level meters are refreshed
+const METER_REFRESH_INTERVAL: f64 = 0.05;
+
+/// An open sketch. Sketches without a path are never saved.
+struct Sketch {
+    path: String,
+    content: String,
+    // what's on disk, to know what needs saving
+    saved: String,
+    // what was last evaluated on save, to know what changed
+    evaluated: String,
+}
+
+impl Sketch {
+    fn name(&self) -> String {
+        if self.path.is_empty() {
+            "scratch".to_string()
+        } else {
+            sketch_name(&self.path)
+        }
+    }
+
+    fn is_modified(&self) -> bool {
+        !self.path.is_empty() && self.content != self.saved
+    }
+}
+
+// things that need a name or a confirmation
+enum SketchDialog {
+    Rename(String),
+    Open(String),
+    Delete,
+}
 
-#[derive(PartialEq)]
-enum SketchNumber {
-    Num(usize),
+enum SketchAction {
+    Select(usize),
+    Close(usize),
+    New,
+    Duplicate,
+    Rename(String),
+    Delete,
+    Open(String),
+    ShowDialog(SketchDialog),
+    CloseDialog,
 }
 
 pub enum EditorFont {
@@ -24,32 +84,90 @@ pub enum EditorFont {
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct MegraEditor {
-    content: String,
     #[serde(skip)]
-    callback: Option<Arc<Mutex<dyn FnMut(&String)>>>,
+    callback: Option<Arc<Mutex<dyn FnMut(&String) -> Result<(), EvalError>>>>,
+    #[serde(skip)]
+    sketches: Vec<Sketch>,
+    #[serde(skip)]
+    current: usize,
+    #[serde(skip)]
+    sketchbook: path::PathBuf,
     #[serde(skip)]
     sketch_list: Vec<String>,
     #[serde(skip)]
-    current_sketch: String,
+    sketch_dialog: Option<SketchDialog>,
+    #[serde(skip)]
+    sketch_dialog_focus: bool,
     #[serde(skip)]
-    sketch_number: usize,
+    search: Option<SearchBar>,
+    #[serde(skip)]
+    keymap: Keymap,
     #[serde(skip)]
     font: Option<EditorFont>,
     #[serde(skip)]
     font_size: f32,
+    #[serde(skip)]
+    vocabulary_source: Option<Arc<Mutex<dyn FnMut() -> Vocabulary>>>,
+    #[serde(skip)]
+    vocabulary: Vocabulary,
+    #[serde(skip)]
+    vocabulary_time: f64,
+    #[serde(skip)]
+    log: Vec<Message>,
+    #[serde(skip)]
+    generator_source: Option<Arc<Mutex<dyn FnMut() -> Vec<GeneratorStatus>>>>,
+    #[serde(skip)]
+    generator_control: Option<Arc<Mutex<dyn FnMut(GeneratorAction)>>>,
+    #[serde(skip)]
+    generators: Vec<GeneratorStatus>,
+    #[serde(skip)]
+    generators_time: f64,
+    #[serde(skip)]
+    meter_source: Option<Arc<Mutex<dyn FnMut() -> MeterReading>>>,
+    #[serde(skip)]
+    meters: Meters,
+    #[serde(skip)]
+    meters_time: f64,
+    #[serde(skip)]
+    theme: ThemeColors,
+    #[serde(skip)]
+    theme_applied: bool,
 }
 
 impl Default for MegraEditor {
     fn default() -> Self {
         Self {
-            content: "(sx 'ga #t (infer 'troll :events 'a (saw 400) :rules (rule 'a 'a 100 400)))"
-                .to_owned(),
             callback: None,
+            sketches: vec![Sketch {
+                path: String::new(),
+                content:
+                    "(sx 'ga #t (infer 'troll :events 'a (saw 400) :rules (rule 'a 'a 100 400)))"
+                        .to_owned(),
+                saved: String::new(),
+                evaluated: String::new(),
+            }],
+            current: 0,
+            sketchbook: path::PathBuf::new(),
             sketch_list: Vec::new(),
-            current_sketch: "".to_string(),
-            sketch_number: 0,
+            sketch_dialog: None,
+            sketch_dialog_focus: false,
+            search: None,
+            keymap: Keymap::default(),
             font: None,
             font_size: 15.0,
+            vocabulary_source: None,
+            vocabulary: Vocabulary::default(),
+            vocabulary_time: f64::NEG_INFINITY,
+            log: Vec::new(),
+            generator_source: None,
+            generator_control: None,
+            generators: Vec::new(),
+            generators_time: f64::NEG_INFINITY,
+            meter_source: None,
+            meters: Meters::default(),
+            meters_time: f64::NEG_INFINITY,
+            theme: ThemeColors::dark(),
+            theme_applied: false,
         }
     }
 }
@@ -63,10 +181,167 @@ impl MegraEditor {
         self.font_size = *font_size;
     }
 
-    pub fn set_callback(&mut self, callback: Arc<Mutex<dyn FnMut(&String)>>) {
+    pub fn set_theme(&mut self, theme: ThemeColors) {
+        self.theme = theme;
+        self.theme_applied = false;
+    }
+
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    pub fn set_callback(
+        &mut self,
+        callback: Arc<Mutex<dyn FnMut(&String) -> Result<(), EvalError>>>,
+    ) {
         self.callback = Some(callback);
     }
 
+    /// Set where function names, sample sets and running
+    /// generators for highlighting and completion come from.
+    pub fn set_vocabulary_source(&mut self, source: Arc<Mutex<dyn FnMut() -> Vocabulary>>) {
+        self.vocabulary_source = Some(source);
+    }
+
+    /// Set where the generator panel gets the running generators from.
+    pub fn set_generator_source(
+        &mut self,
+        source: Arc<Mutex<dyn FnMut() -> Vec<GeneratorStatus>>>,
+    ) {
+        self.generator_source = Some(source);
+    }
+
+    /// Set what happens when a generator is muted, soloed or stopped.
+    pub fn set_generator_control(&mut self, control: Arc<Mutex<dyn FnMut(GeneratorAction)>>) {
+        self.generator_control = Some(control);
+    }
+
+    /// Set where the level meters get the output levels from.
+    pub fn set_meter_source(&mut self, source: Arc<Mutex<dyn FnMut() -> MeterReading>>) {
+        self.meter_source = Some(source);
+    }
+
+    fn refresh_sketch_list(&mut self) {
+        self.sketch_list = list_sketches(&self.sketchbook);
+    }
+
+    /// Start a sketch marked with the date. It'll be saved with the next autosave.
+    fn new_sketch(&mut self) {
+        // several new sketches within a second shouldn't end up in the same file
+        let path = unused_path(&self.sketchbook, &timestamped_stem(), |p| {
+            self.sketches.iter().any(|s| path::Path::new(&s.path) == p)
+        });
+        self.sketches.push(Sketch {
+            path: path.to_str().unwrap().to_string(),
+            content: format!(
+                ";; Created {}",
+                Local::now().format("%A, %F, %H:%M:%S ... good luck!")
+            ),
+            saved: String::new(),
+            evaluated: String::new(),
+        });
+        self.current = self.sketches.len() - 1;
+    }
+
+    /// Open a file in a new tab, or switch to it if it's open already.
+    fn open_sketch(&mut self, file: &str) {
+        if let Some(i) = self.sketches.iter().position(|s| s.path == file) {
+            self.current = i;
+            return;
+        }
+        match read_sketch(path::Path::new(file)) {
+            Ok(content) => {
+                self.sketches.push(Sketch {
+                    path: file.to_string(),
+                    saved: content.clone(),
+                    evaluated: String::new(),
+                    content,
+                });
+                self.current = self.sketches.len() - 1;
+            }
+            Err(e) => messages::warning(format!("couldn't read sketch {}: {}", file, e)),
+        }
+    }
+
+    fn save_sketch(&mut self, i: usize) {
+        let sketch = &mut self.sketches[i];
+        if !sketch.is_modified() {
+            return;
+        }
+        match fs::write(&sketch.path, sketch.content.as_bytes()) {
+            Ok(_) => sketch.saved = sketch.content.clone(),
+            Err(e) => messages::warning(format!("couldn't save sketch {}: {}", sketch.path, e)),
+        }
+    }
+
+    /// Save the current sketch right away. Depending on the
+    /// keymap, it's evaluated as well.
+    fn save_current_sketch(&mut self) -> Option<Evaluation> {
+        self.save_sketch(self.current);
+        let sketch = &mut self.sketches[self.current];
+        let evaluation = match self.keymap.eval_on_save {
+            EvalOnSave::Off => None,
+            EvalOnSave::Buffer => Some(Evaluation::Buffer),
+            EvalOnSave::Changed => Some(Evaluation::Changed(sketch.evaluated.clone())),
+        };
+        if evaluation.is_some() {
+            sketch.evaluated = sketch.content.clone();
+        }
+        evaluation
+    }
+
+    fn apply_sketch_action(&mut self, action: SketchAction) {
+        match action {
+            SketchAction::Select(i) => self.current = i,
+            SketchAction::Close(i) => {
+                self.save_sketch(i);
+                self.sketches.remove(i);
+                if self.current >= i && self.current > 0 {
+                    self.current -= 1;
+                }
+            }
+            SketchAction::New => self.new_sketch(),
+            SketchAction::Duplicate => {
+                self.save_sketch(self.current);
+                let file = self.sketches[self.current].path.clone();
+                match duplicate_sketch(path::Path::new(&file)) {
+                    Ok(copy) => self.open_sketch(copy.to_str().unwrap()),
+                    Err(e) => messages::warning(format!("couldn't duplicate {}: {}", file, e)),
+                }
+            }
+            SketchAction::Rename(name) => {
+                self.save_sketch(self.current);
+                let sketch = &mut self.sketches[self.current];
+                match rename_sketch(path::Path::new(&sketch.path), &name) {
+                    Ok(new_path) => sketch.path = new_path.to_str().unwrap().to_string(),
+                    Err(e) => messages::warning(format!("couldn't rename {}: {}", sketch.path, e)),
+                }
+            }
+            SketchAction::Delete => {
+                let sketch = self.sketches.remove(self.current);
+                // sketches that were never saved aren't on disk
+                if path::Path::new(&sketch.path).exists() {
+                    if let Err(e) = fs::remove_file(&sketch.path) {
+                        messages::warning(format!("couldn't delete {}: {}", sketch.path, e));
+                    }
+                }
+                self.current = self.current.saturating_sub(1);
+                if self.sketches.is_empty() {
+                    self.new_sketch();
+                }
+            }
+            SketchAction::Open(file) => self.open_sketch(&file),
+            SketchAction::ShowDialog(dialog) => {
+                self.sketch_dialog = Some(dialog);
+                self.sketch_dialog_focus = true;
+                return;
+            }
+            SketchAction::CloseDialog => {}
+        }
+        self.sketch_dialog = None;
+        self.refresh_sketch_list();
+    }
+
     pub fn new(
         cc: &eframe::CreationContext<'_>,
         base_dir: String,
@@ -133,57 +408,19 @@ impl MegraEditor {
         cc.egui_ctx.set_fonts(fonts);
 
         // create sketch and load sketch file list ...
-
-        let base_dir_buf = std::path::PathBuf::from(base_dir);
-        let sketchbook_path = base_dir_buf.join("sketchbook");
-        if sketchbook_path.exists() {
+        ed.sketchbook = path::PathBuf::from(base_dir).join("sketchbook");
+        if ed.sketchbook.exists() {
+            ed.refresh_sketch_list();
             if *create_sketch {
-                // prepare sketch marked with date
-                let id = format!("sketch_{}.megra3", Local::now().format("%Y%m%d_%H%M_%S"));
-                let file_path = sketchbook_path.join(id);
-                ed.current_sketch = file_path.to_str().unwrap().to_string();
-                // push current sketch so it'll be the one visible
-                ed.sketch_list.push(ed.current_sketch.clone());
-
-                ed.content = format!(
-                    ";; Created {}",
-                    Local::now().format("%A, %F, %H:%M:%S ... good luck!")
-                );
+                ed.sketches.clear();
+                ed.new_sketch();
+            } else if let Some(first) = ed.sketch_list.first().cloned() {
+                ed.sketches.clear();
+                ed.open_sketch(&first);
             }
-
-            if let Ok(entries) = fs::read_dir(sketchbook_path) {
-                let mut disk_sketches = Vec::new();
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    // only consider files here ...
-                    if path.is_file() {
-                        if let Some(ext) = path.extension() {
-                            if ext == "megra3" {
-                                disk_sketches.push(path.to_str().unwrap().to_string());
-                            }
-                        }
-                    }
-                }
-
-                disk_sketches.sort();
-                // sort sketch list so it's easier to find the sketches
-                ed.sketch_list.append(&mut disk_sketches);
-            }
-
-            if !*create_sketch && !ed.sketch_list.is_empty() {
-                ed.current_sketch = ed.sketch_list[0].clone();
-                let p = path::Path::new(&ed.current_sketch);
-                match fs::read_to_string(p) {
-                    Ok(mut s) => {
-                        if !s.ends_with('\n') {
-                            s.push('\n');
-                        }
-                        ed.content = s
-                    }
-                    Err(e) => {
-                        println!("couldn't read sketch {}", e);
-                    }
-                }
+            // keep the scratch sketch if nothing could be opened
+            if ed.sketches.is_empty() {
+                ed.sketches = Self::default().sketches;
             }
         }
 
@@ -195,14 +432,134 @@ impl eframe::App for MegraEditor {
     /// Called each time the UI needs repainting, which may be many times per second.
     /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
     fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
+        // new functions (i.e. sample sets) and generators come and go,
+        // so check every now and then
+        let now = ctx.input().time;
+        if now - self.vocabulary_time > VOCABULARY_REFRESH_INTERVAL {
+            if let Some(source) = self.vocabulary_source.as_ref() {
+                let mut source_loc = source.lock();
+                self.vocabulary = source_loc();
+            }
+            self.vocabulary_time = now;
+        }
+
+        if now - self.generators_time > GENERATOR_REFRESH_INTERVAL {
+            if let Some(source) = self.generator_source.as_ref() {
+                let mut source_loc = source.lock();
+                self.generators = source_loc();
+            }
+            self.generators_time = now;
+        }
+        if let Some(source) = self.meter_source.as_ref() {
+            if now - self.meters_time > METER_REFRESH_INTERVAL {
+                let mut source_loc = source.lock();
+                let elapsed = (now - self.meters_time).min(1.0);
+                self.meters.update(&source_loc(), elapsed);
+                self.meters_time = now;
+            }
+        }
+
+        // states change without any input, so keep the panels going
+        ctx.request_repaint_after(std::time::Duration::from_secs_f64(
+            if self.meter_source.is_some() {
+                METER_REFRESH_INTERVAL
+            } else {
+                GENERATOR_REFRESH_INTERVAL
+            },
+        ));
+
+        // widgets (menus, scroll bars ...) should match the theme
+        if !self.theme_applied {
+            ctx.set_visuals(if self.theme.is_light() {
+                egui::Visuals::light()
+            } else {
+                egui::Visuals::dark()
+            });
+            self.theme_applied = true;
+        }
+
         // some frame options ...
         let mut frame = egui::Frame::none();
-        frame.fill = egui::Color32::BLACK;
+        frame.fill = self.theme.background;
         frame.inner_margin = Margin::symmetric(3.0, 3.0);
+
+        // collect evaluation errors, sample loading progress etc.
+        self.log.append(&mut messages::take());
+        if self.log.len() > MAX_LOG_LINES {
+            self.log.drain(..self.log.len() - MAX_LOG_LINES);
+        }
+
+        egui::TopBottomPanel::bottom("log_pane")
+            .frame(frame)
+            .resizable(true)
+            .default_height(100.0)
+            .show(ctx, |ui| {
+                ScrollArea::vertical()
+                    .stick_to_bottom(true)
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for msg in self.log.iter() {
+                            let color = match msg.severity {
+                                Severity::Info => self.theme.tokens[TokenType::Comment],
+                                Severity::Warning => egui::Color32::from_rgb(230, 170, 20),
+                                Severity::Error => self.theme.error,
+                            };
+                            ui.add(
+                                egui::Label::new(
+                                    egui::RichText::new(format!(
+                                        "{} {}",
+                                        msg.time.format("%H:%M:%S"),
+                                        msg.text
+                                    ))
+                                    .font(FontId::monospace(self.font_size * 0.8))
+                                    .color(color),
+                                )
+                                .wrap(true),
+                            );
+                        }
+                    });
+            });
+
+        egui::SidePanel::right("generator_panel")
+            .frame(frame)
+            .resizable(true)
+            .default_width(240.0)
+            .show(ctx, |ui| {
+                if self.meter_source.is_some() {
+                    meter_panel(
+                        ui,
+                        &mut self.meters,
+                        self.font_size,
+                        self.theme.flash,
+                        self.theme.error,
+                    );
+                    ui.separator();
+                }
+                ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        if let Some(action) =
+                            generator_panel(ui, &self.generators, self.font_size, self.theme.flash)
+                        {
+                            if let Some(control) = self.generator_control.as_ref() {
+                                let mut control_loc = control.lock();
+                                control_loc(action);
+                            }
+                            // show the result right away
+                            self.generators_time = f64::NEG_INFINITY;
+                        }
+                    });
+            });
+
         egui::CentralPanel::default().frame(frame).show(ctx, |ui| {
-            let mut sketch_number = SketchNumber::Num(self.sketch_number);
+            let mut action = None;
 
-            ui.horizontal(|ui| {
+            let mut evaluate = None;
+            if self.keymap.pressed(&ctx.input(), EditorCommand::Save) {
+                evaluate = self.save_current_sketch();
+            }
+
+            ui.horizontal_wrapped(|ui| {
                 ui.add(
                     egui::Label::new(
                         egui::RichText::new("Mégra Editor").font(FontId::monospace(self.font_size)),
@@ -210,51 +567,224 @@ impl eframe::App for MegraEditor {
                     .wrap(false),
                 );
 
-                let id = ui.make_persistent_id("file_chooser_box");
-                egui::ComboBox::from_id_source(id)
-                    .selected_text(&self.sketch_list[self.sketch_number])
-                    .show_ui(ui, |ui| {
-                        for i in 0..self.sketch_list.len() {
-                            ui.selectable_value(
-                                &mut sketch_number,
-                                SketchNumber::Num(i),
-                                &self.sketch_list[i],
-                            );
+                // one tab per open sketch, unsaved ones are marked
+                for (i, sketch) in self.sketches.iter().enumerate() {
+                    let mut label = sketch.name();
+                    if sketch.is_modified() {
+                        label.push('*');
+                    }
+                    if ui.selectable_label(i == self.current, label).clicked() {
+                        action = Some(SketchAction::Select(i));
+                    }
+                    if self.sketches.len() > 1 && ui.small_button("x").clicked() {
+                        action = Some(SketchAction::Close(i));
+                    }
+                }
+
+                let saved = !self.sketches[self.current].path.is_empty();
+                ui.menu_button("sketch", |ui| {
+                    if ui.button("new").clicked() {
+                        action = Some(SketchAction::New);
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(saved, egui::Button::new("duplicate"))
+                        .clicked()
+                    {
+                        action = Some(SketchAction::Duplicate);
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(saved, egui::Button::new("rename ..."))
+                        .clicked()
+                    {
+                        action = Some(SketchAction::ShowDialog(SketchDialog::Rename(
+                            self.sketches[self.current].name(),
+                        )));
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(saved, egui::Button::new("delete ..."))
+                        .clicked()
+                    {
+                        action = Some(SketchAction::ShowDialog(SketchDialog::Delete));
+                        ui.close_menu();
+                    }
+                    if ui.button("open file ...").clicked() {
+                        let dir = format!(
+                            "{}{}",
+                            self.sketchbook.to_str().unwrap_or(""),
+                            path::MAIN_SEPARATOR
+                        );
+                        action = Some(SketchAction::ShowDialog(SketchDialog::Open(dir)));
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                        for file in self.sketch_list.iter() {
+                            if ui.button(sketch_name(file)).clicked() {
+                                action = Some(SketchAction::Open(file.clone()));
+                                ui.close_menu();
+                            }
                         }
                     });
-            });
-
-            let SketchNumber::Num(sk_num) = sketch_number;
+                });
 
-            //let mut sketch_switched = false;
-            if sk_num != self.sketch_number {
-                println!("switch sketch from {} to {}", self.sketch_number, sk_num);
-                self.sketch_number = sk_num;
+                // whole sketches, or the regions marked in them
+                ui.menu_button("eval", |ui| {
+                    if ui.button("buffer").clicked() {
+                        evaluate = Some(Evaluation::Buffer);
+                        ui.close_menu();
+                    }
+                    for region in evaluation::regions(&self.sketches[self.current].content) {
+                        if ui.button(format!("region {}", region.name)).clicked() {
+                            evaluate = Some(Evaluation::Region(Some(region.name)));
+                            ui.close_menu();
+                        }
+                    }
+                    ui.separator();
+                    ui.label("on save");
+                    for eval_on_save in [EvalOnSave::Off, EvalOnSave::Buffer, EvalOnSave::Changed] {
+                        ui.radio_value(
+                            &mut self.keymap.eval_on_save,
+                            eval_on_save,
+                            eval_on_save.name(),
+                        );
+                    }
+                });
+            });
 
-                // store content explicitly when changing ...
-                if !self.current_sketch.is_empty() {
-                    let p = path::Path::new(&self.current_sketch);
-                    match fs::write(p, self.content.as_bytes()) {
-                        Ok(_) => {}
-                        Err(e) => {
-                            println!("couldn't save sketch {}", e);
+            if let Some(dialog) = self.sketch_dialog.as_mut() {
+                ui.horizontal(|ui| {
+                    let (label, input) = match dialog {
+                        SketchDialog::Rename(name) => ("rename to", Some(name)),
+                        SketchDialog::Open(file) => ("open file", Some(file)),
+                        SketchDialog::Delete => ("delete this sketch?", None),
+                    };
+                    ui.label(label);
+
+                    let mut confirmed = false;
+                    if let Some(input) = input {
+                        let response = ui.text_edit_singleline(input);
+                        if self.sketch_dialog_focus {
+                            response.request_focus();
+                            self.sketch_dialog_focus = false;
                         }
+                        confirmed =
+                            response.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
                     }
+
+                    if ui.button("ok").clicked() || confirmed {
+                        action = Some(match dialog {
+                            SketchDialog::Rename(name) => SketchAction::Rename(name.clone()),
+                            SketchDialog::Open(file) => SketchAction::Open(file.clone()),
+                            SketchDialog::Delete => SketchAction::Delete,
+                        });
+                    }
+                    if ui.button("cancel").clicked() {
+                        action = Some(SketchAction::CloseDialog);
+                    }
+                });
+            }
+
+            if let Some(action) = action {
+                self.apply_sketch_action(action);
+            }
+
+            // ctrl+s opens the search bar, or jumps to the next match
+            let search_key = self.keymap.pressed(&ctx.input(), EditorCommand::Search);
+            let mut search_action = None;
+            if search_key {
+                if self.search.is_some() {
+                    search_action = Some(SearchAction::Next);
+                }
+                self.search.get_or_insert_with(SearchBar::default).focus = true;
+            }
+
+            let mut highlights = Vec::new();
+            let mut jump = None;
+            if let Some(bar) = self.search.as_mut() {
+                let sketch = &mut self.sketches[self.current];
+                let mut matches = find_matches(&sketch.content, &bar.query, bar.regex);
+                if let Some(action) = search_bar(ui, bar, &matches, self.theme.error) {
+                    search_action = Some(action);
                 }
 
-                self.current_sketch = self.sketch_list[sk_num].clone();
-                let p = path::Path::new(&self.current_sketch);
-                match fs::read_to_string(p) {
-                    Ok(mut s) => {
-                        if !s.ends_with('\n') {
-                            s.push('\n');
+                // where to look for the next match
+                let from = match search_action {
+                    Some(SearchAction::Changed) => Some(bar.current.map_or(0, |c| c.0)),
+                    Some(SearchAction::Next) => Some(bar.current.map_or(0, |c| c.1)),
+                    Some(SearchAction::Replace) => {
+                        // only replace a match that has been jumped to,
+                        // so nothing is replaced by surprise
+                        let current = matches.as_ref().ok().and_then(|m| {
+                            m.iter()
+                                .find(|m| Some((m.char_start, m.char_end)) == bar.current)
+                                .copied()
+                        });
+                        if let Some(m) = current {
+                            match replace_match(
+                                &sketch.content,
+                                &bar.query,
+                                &bar.replacement,
+                                bar.regex,
+                                &m,
+                            ) {
+                                Ok((replaced, len)) => {
+                                    sketch.content = replaced;
+                                    Some(m.char_start + len)
+                                }
+                                Err(e) => {
+                                    messages::warning(format!("can't replace: {}", e));
+                                    None
+                                }
+                            }
+                        } else {
+                            Some(0)
                         }
-                        self.content = s
                     }
-                    Err(e) => {
-                        println!("couldn't read sketch {}", e);
+                    Some(SearchAction::ReplaceAll) => {
+                        match replace_all(&sketch.content, &bar.query, &bar.replacement, bar.regex)
+                        {
+                            Ok((replaced, count)) => {
+                                sketch.content = replaced;
+                                messages::info(format!("replaced {} matches", count));
+                            }
+                            Err(e) => messages::warning(format!("can't replace: {}", e)),
+                        }
+                        bar.current = None;
+                        None
                     }
+                    Some(SearchAction::Close) | None => None,
+                };
+
+                if matches!(
+                    search_action,
+                    Some(SearchAction::Replace | SearchAction::ReplaceAll)
+                ) {
+                    matches = find_matches(&sketch.content, &bar.query, bar.regex);
                 }
+
+                let matches = matches.unwrap_or_default();
+                if let Some(from) = from {
+                    bar.current = next_match(&matches, from).map(|m| {
+                        jump = Some(CCursorRange::two(
+                            CCursor::new(m.char_start),
+                            CCursor::new(m.char_end),
+                        ));
+                        (m.char_start, m.char_end)
+                    });
+                }
+                highlights = matches
+                    .iter()
+                    .map(|m| {
+                        CCursorRange::two(CCursor::new(m.char_start), CCursor::new(m.char_end))
+                    })
+                    .collect();
+            }
+            if matches!(search_action, Some(SearchAction::Close)) {
+                self.search = None;
+                highlights.clear();
             }
 
             ui.separator();
@@ -262,28 +792,43 @@ impl eframe::App for MegraEditor {
             ScrollArea::vertical()
                 .always_show_scroll(true)
                 .show(ui, |ui| {
-                    let num_lines = self.content.lines().count() + 1;
+                    let sketch = &mut self.sketches[self.current];
+                    let num_lines = sketch.content.lines().count() + 1;
 
-                    let theme = CodeTheme::dark(self.font_size);
+                    let theme = CodeTheme::new(self.font_size, &self.theme);
+                    let vocabulary = &self.vocabulary;
                     let mut layouter = |ui: &egui::Ui, string: &str, _wrap_width: f32| {
-                        let layout_job = highlight(ui.ctx(), &theme, string);
+                        let layout_job = highlight(ui.ctx(), &theme, vocabulary, string);
                         ui.fonts().layout_job(layout_job)
                     };
 
                     let tx = if let Some(cb) = self.callback.as_ref() {
-                        LivecodeTextEdit::multiline(&mut self.content)
+                        LivecodeTextEdit::multiline(&mut sketch.content)
+                            .id_source(sketch.path.as_str())
                             .desired_rows(30)
                             //.reset_cursor(sketch_switched)
                             .code_editor()
                             .desired_width(800.0)
                             .eval_callback(cb)
+                            .vocabulary(vocabulary)
+                            .flash_colors(self.theme.flash, self.theme.error)
+                            .highlights(&highlights)
+                            .jump_to(jump)
+                            .evaluate(evaluate)
+                            .keymap(&self.keymap)
                             .layouter(&mut layouter)
                     } else {
-                        LivecodeTextEdit::multiline(&mut self.content)
+                        LivecodeTextEdit::multiline(&mut sketch.content)
+                            .id_source(sketch.path.as_str())
                             .desired_rows(30)
                             .code_editor()
                             //.reset_cursor(!sketch_switched)
                             .desired_width(800.0)
+                            .vocabulary(vocabulary)
+                            .flash_colors(self.theme.flash, self.theme.error)
+                            .highlights(&highlights)
+                            .jump_to(jump)
+                            .keymap(&self.keymap)
                             .layouter(&mut layouter)
                     };
 
@@ -309,14 +854,8 @@ impl eframe::App for MegraEditor {
     }
 
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        if !self.current_sketch.is_empty() {
-            let p = path::Path::new(&self.current_sketch);
-            match fs::write(p, self.content.as_bytes()) {
-                Ok(_) => {}
-                Err(e) => {
-                    println!("couldn't save sketch {}", e);
-                }
-            }
+        for i in 0..self.sketches.len() {
+            self.save_sketch(i);
         }
 
         eframe::set_value(storage, eframe::APP_KEY, self);
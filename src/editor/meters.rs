@@ -0,0 +1,225 @@
+use egui::{pos2, vec2, Color32, FontId, Rect, RichText, Sense, Shape, Stroke};
+
+use crate::real_time_streaming::MeterReading;
+
+// the range the meters show, in dB
+const MIN_DB: f32 = -60.0;
+// how fast the peak marker falls back, in dB per second
+const PEAK_FALL: f32 = 20.0;
+// a channel this quiet while others aren't might have been lost
+const SILENCE_DB: f32 = -90.0;
+// seconds until such a channel is marked
+const SILENCE_WARNING: f64 = 2.0;
+
+pub fn to_db(amplitude: f32) -> f32 {
+    if amplitude > 0.0 {
+        20.0 * amplitude.log10()
+    } else {
+        f32::NEG_INFINITY
+    }
+}
+
+/// What the meters show. The peak markers fall back slowly,
+/// clipping stays marked until the meters are clicked.
+#[derive(Default)]
+pub struct Meters {
+    pub peak_db: Vec<f32>,
+    pub rms_db: Vec<f32>,
+    pub clipped: Vec<bool>,
+    // how long each channel has been silent while others weren't
+    silent_for: Vec<f64>,
+    pub scope: Vec<Vec<f32>>,
+}
+
+impl Meters {
+    /// Take a new reading, `elapsed` seconds after the last one.
+    pub fn update(&mut self, reading: &MeterReading, elapsed: f64) {
+        let channels = reading.peak.len();
+        if self.peak_db.len() != channels {
+            *self = Meters {
+                peak_db: vec![f32::NEG_INFINITY; channels],
+                rms_db: vec![f32::NEG_INFINITY; channels],
+                clipped: vec![false; channels],
+                silent_for: vec![0.0; channels],
+                scope: Vec::new(),
+            };
+        }
+        self.scope = reading.scope.clone();
+
+        // nothing came in, keep showing what was there
+        if reading.frames == 0 {
+            return;
+        }
+
+        let any_signal = reading.peak.iter().any(|p| to_db(*p) > SILENCE_DB);
+        for ch in 0..channels {
+            let peak = to_db(reading.peak[ch]);
+            self.peak_db[ch] = peak.max(self.peak_db[ch] - PEAK_FALL * elapsed as f32);
+            self.rms_db[ch] = to_db(reading.rms[ch]);
+            if reading.peak[ch] >= 1.0 {
+                self.clipped[ch] = true;
+            }
+            if any_signal && peak <= SILENCE_DB {
+                self.silent_for[ch] += elapsed;
+            } else {
+                self.silent_for[ch] = 0.0;
+            }
+        }
+    }
+
+    /// Whether the channel has been silent for a while
+    /// though there's something on the others.
+    pub fn is_silent(&self, ch: usize) -> bool {
+        self.silent_for[ch] > SILENCE_WARNING
+    }
+
+    pub fn clear_clipped(&mut self) {
+        self.clipped.iter_mut().for_each(|c| *c = false);
+    }
+}
+
+/// One meter per channel (RMS as a bar, peak as a line, clipping in
+/// `error` on top) and an oscilloscope of all channels. Channels that
+/// went silent get their number in `error`.
+pub fn meter_panel(
+    ui: &mut egui::Ui,
+    meters: &mut Meters,
+    font_size: f32,
+    accent: Color32,
+    error: Color32,
+) {
+    let font = FontId::monospace(font_size * 0.8);
+    let dim = Color32::from_gray(140);
+
+    if meters.peak_db.is_empty() {
+        ui.label(RichText::new("no output yet").font(font).color(dim));
+        return;
+    }
+
+    let height = font_size * 6.0;
+    let background = ui.visuals().extreme_bg_color;
+    let peak_color = ui.visuals().text_color();
+    let mut clear = false;
+
+    ui.horizontal(|ui| {
+        for ch in 0..meters.peak_db.len() {
+            ui.vertical(|ui| {
+                let (rect, response) =
+                    ui.allocate_exact_size(vec2(font_size * 0.8, height), Sense::click());
+                let response = response.on_hover_text(format!(
+                    "peak {:.1} dB, rms {:.1} dB",
+                    meters.peak_db[ch], meters.rms_db[ch]
+                ));
+                clear |= response.clicked();
+
+                let level = |db: f32| {
+                    rect.bottom() - rect.height() * ((db - MIN_DB) / -MIN_DB).clamp(0.0, 1.0)
+                };
+                let painter = ui.painter_at(rect);
+                painter.rect_filled(rect, 0.0, background);
+                painter.rect_filled(
+                    Rect::from_min_max(
+                        pos2(rect.left(), level(meters.rms_db[ch])),
+                        rect.right_bottom(),
+                    ),
+                    0.0,
+                    accent,
+                );
+                let peak = level(meters.peak_db[ch]);
+                painter.line_segment(
+                    [pos2(rect.left(), peak), pos2(rect.right(), peak)],
+                    Stroke::new(2.0, peak_color),
+                );
+                if meters.clipped[ch] {
+                    painter.rect_filled(
+                        Rect::from_min_size(rect.min, vec2(rect.width(), rect.height() / 10.0)),
+                        0.0,
+                        error,
+                    );
+                }
+
+                let color = if meters.is_silent(ch) { error } else { dim };
+                ui.label(
+                    RichText::new(format!("{}", ch + 1))
+                        .font(font.clone())
+                        .color(color),
+                );
+            });
+        }
+
+        let (rect, _) = ui.allocate_exact_size(
+            vec2(ui.available_width().max(font_size * 4.0), height),
+            Sense::hover(),
+        );
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, background);
+        painter.line_segment(
+            [rect.left_center(), rect.right_center()],
+            Stroke::new(1.0, dim),
+        );
+        for samples in meters.scope.iter().filter(|s| s.len() > 1) {
+            let step = rect.width() / (samples.len() - 1) as f32;
+            let points = samples
+                .iter()
+                .enumerate()
+                .map(|(i, s)| {
+                    pos2(
+                        rect.left() + i as f32 * step,
+                        rect.center().y - s.clamp(-1.0, 1.0) * rect.height() / 2.0,
+                    )
+                })
+                .collect();
+            painter.add(Shape::line(points, Stroke::new(1.0, accent)));
+        }
+    });
+
+    if clear {
+        meters.clear_clipped();
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn reading(peak: Vec<f32>, frames: usize) -> MeterReading {
+        MeterReading {
+            rms: peak.iter().map(|p| p / 2.0).collect(),
+            peak,
+            frames,
+            scope: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_meters() {
+        let mut meters = Meters::default();
+        meters.update(&reading(vec![1.0, 0.5, 0.0, 0.1], 512), 0.1);
+        assert_eq!(meters.peak_db[0], 0.0);
+        assert!(meters.clipped[0] && !meters.clipped[1]);
+
+        // the peak falls back slowly, the clip marker stays
+        meters.update(&reading(vec![0.1, 0.5, 0.0, 0.1], 512), 0.1);
+        assert_eq!(meters.peak_db[0], -2.0);
+        assert!(meters.clipped[0]);
+        meters.clear_clipped();
+        assert!(!meters.clipped[0]);
+
+        // a channel that stays silent while the others play
+        for _ in 0..30 {
+            meters.update(&reading(vec![0.1, 0.5, 0.0, 0.1], 512), 0.1);
+        }
+        assert!(meters.is_silent(2));
+        assert!(!meters.is_silent(1));
+        // silence everywhere is just silence
+        meters.update(&reading(vec![0.0; 4], 512), 0.1);
+        assert!(!meters.is_silent(2));
+
+        // readings without any frames don't change the levels
+        let before = meters.rms_db.clone();
+        meters.update(&reading(vec![0.0; 4], 0), 0.1);
+        assert_eq!(meters.rms_db, before);
+    }
+}
@@ -0,0 +1,294 @@
+// vim-style normal mode: the keys typed are collected until they
+// form a command, which is then carried out by the text edit
+
+/// Normal mode moves around and edits with commands,
+/// insert mode types as usual.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Mode {
+    #[default]
+    Normal,
+    Insert,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordForward,
+    WordBack,
+    LineStart,
+    LineEnd,
+    Top,
+    Bottom,
+    /// the opening paren of the enclosing s-expression
+    Open,
+    /// the closing paren of the enclosing s-expression
+    Close,
+    NextForm,
+    PreviousForm,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextObject {
+    Word,
+    /// the enclosing s-expression
+    Sexp,
+    /// the enclosing top-level s-expression
+    Form,
+}
+
+/// What an operator works on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Target {
+    Motion(Motion),
+    /// a text object, with or without what's around it
+    /// (whitespace for words, parens for s-expressions)
+    Object(TextObject, bool),
+    Line,
+    Selection,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+/// Where to start typing when switching to insert mode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InsertAt {
+    Cursor,
+    AfterCursor,
+    LineStart,
+    LineEnd,
+    LineBelow,
+    LineAbove,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NormalCommand {
+    Move(Motion),
+    Operate(Operator, Target),
+    Insert(InsertAt),
+    DeleteChar,
+    Paste { before: bool },
+    Undo,
+    ToggleSelection,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Parsed {
+    Pending,
+    Invalid,
+    Command(NormalCommand),
+}
+
+fn motion(c: char) -> Option<Motion> {
+    match c {
+        'h' => Some(Motion::Left),
+        'l' | ' ' => Some(Motion::Right),
+        'k' => Some(Motion::Up),
+        'j' => Some(Motion::Down),
+        'w' => Some(Motion::WordForward),
+        'b' => Some(Motion::WordBack),
+        '0' => Some(Motion::LineStart),
+        '$' => Some(Motion::LineEnd),
+        'G' => Some(Motion::Bottom),
+        '(' => Some(Motion::Open),
+        ')' => Some(Motion::Close),
+        '}' => Some(Motion::NextForm),
+        '{' => Some(Motion::PreviousForm),
+        _ => None,
+    }
+}
+
+fn text_object(c: char) -> Option<TextObject> {
+    match c {
+        'w' => Some(TextObject::Word),
+        '(' | ')' | 'b' => Some(TextObject::Sexp),
+        'f' => Some(TextObject::Form),
+        _ => None,
+    }
+}
+
+/// Parse the keys typed in normal mode so far. With a selection,
+/// the operators work on the selection right away.
+pub fn parse_normal(keys: &str, selection: bool) -> Parsed {
+    let chars: Vec<char> = keys.chars().collect();
+    let command = match chars.as_slice() {
+        [] => return Parsed::Pending,
+        ['g'] => return Parsed::Pending,
+        ['g', 'g'] => NormalCommand::Move(Motion::Top),
+        ['i'] => NormalCommand::Insert(InsertAt::Cursor),
+        ['a'] => NormalCommand::Insert(InsertAt::AfterCursor),
+        ['I'] => NormalCommand::Insert(InsertAt::LineStart),
+        ['A'] => NormalCommand::Insert(InsertAt::LineEnd),
+        ['o'] => NormalCommand::Insert(InsertAt::LineBelow),
+        ['O'] => NormalCommand::Insert(InsertAt::LineAbove),
+        ['x'] if selection => NormalCommand::Operate(Operator::Delete, Target::Selection),
+        ['x'] => NormalCommand::DeleteChar,
+        ['p'] => NormalCommand::Paste { before: false },
+        ['P'] => NormalCommand::Paste { before: true },
+        ['u'] => NormalCommand::Undo,
+        ['v'] => NormalCommand::ToggleSelection,
+        ['D'] => NormalCommand::Operate(Operator::Delete, Target::Motion(Motion::LineEnd)),
+        ['C'] => NormalCommand::Operate(Operator::Change, Target::Motion(Motion::LineEnd)),
+        [c] if motion(*c).is_some() => NormalCommand::Move(motion(*c).unwrap()),
+        [op, rest @ ..] if matches!(*op, 'd' | 'c' | 'y') => {
+            let operator = match *op {
+                'd' => Operator::Delete,
+                'c' => Operator::Change,
+                _ => Operator::Yank,
+            };
+            let target = match rest {
+                _ if selection => Target::Selection,
+                [] | ['i' | 'a'] | ['g'] => return Parsed::Pending,
+                [c] if c == op => Target::Line,
+                ['g', 'g'] => Target::Motion(Motion::Top),
+                [around @ ('i' | 'a'), c] => {
+                    if let Some(object) = text_object(*c) {
+                        Target::Object(object, *around == 'a')
+                    } else {
+                        return Parsed::Invalid;
+                    }
+                }
+                [c] if motion(*c).is_some() => Target::Motion(motion(*c).unwrap()),
+                _ => return Parsed::Invalid,
+            };
+            NormalCommand::Operate(operator, target)
+        }
+        _ => return Parsed::Invalid,
+    };
+    Parsed::Command(command)
+}
+
+/// The line around `index`, including its line break (char indices).
+pub fn line_range(text: &str, index: usize) -> (usize, usize) {
+    let mut start = 0;
+    for (i, c) in text.chars().enumerate() {
+        if c == '\n' {
+            if i >= index {
+                return (start, i + 1);
+            }
+            start = i + 1;
+        }
+    }
+    (start, text.chars().count())
+}
+
+/// The start of the next (or previous) top-level s-expression,
+/// that is, an opening paren at the beginning of a line.
+pub fn toplevel_form_start(text: &str, index: usize, forward: bool) -> Option<usize> {
+    let mut starts = Vec::new();
+    let mut line_start = true;
+    for (i, c) in text.chars().enumerate() {
+        if c == '(' && line_start {
+            starts.push(i);
+        }
+        line_start = c == '\n';
+    }
+
+    if forward {
+        starts.into_iter().find(|s| *s > index)
+    } else {
+        starts.into_iter().rev().find(|s| *s < index)
+    }
+}
+
+/// Where to paste. Whole lines go below (or above) the current line,
+/// anything else after (or at) the cursor.
+pub fn paste_position(text: &str, index: usize, register: &str, before: bool) -> usize {
+    if register.ends_with('\n') {
+        let (start, end) = line_range(text, index);
+        if before {
+            start
+        } else {
+            end
+        }
+    } else if before {
+        index
+    } else {
+        // stay on the same line
+        match text.chars().nth(index) {
+            Some('\n') | None => index,
+            Some(_) => index + 1,
+        }
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_parse_normal() {
+        assert_eq!(
+            parse_normal("j", false),
+            Parsed::Command(NormalCommand::Move(Motion::Down))
+        );
+        assert_eq!(parse_normal("d", false), Parsed::Pending);
+        assert_eq!(parse_normal("di", false), Parsed::Pending);
+        assert_eq!(
+            parse_normal("di(", false),
+            Parsed::Command(NormalCommand::Operate(
+                Operator::Delete,
+                Target::Object(TextObject::Sexp, false)
+            ))
+        );
+        assert_eq!(
+            parse_normal("caf", false),
+            Parsed::Command(NormalCommand::Operate(
+                Operator::Change,
+                Target::Object(TextObject::Form, true)
+            ))
+        );
+        assert_eq!(
+            parse_normal("yy", false),
+            Parsed::Command(NormalCommand::Operate(Operator::Yank, Target::Line))
+        );
+        assert_eq!(
+            parse_normal("dw", false),
+            Parsed::Command(NormalCommand::Operate(
+                Operator::Delete,
+                Target::Motion(Motion::WordForward)
+            ))
+        );
+        assert_eq!(
+            parse_normal("gg", false),
+            Parsed::Command(NormalCommand::Move(Motion::Top))
+        );
+        // operators work on the selection right away
+        assert_eq!(
+            parse_normal("y", true),
+            Parsed::Command(NormalCommand::Operate(Operator::Yank, Target::Selection))
+        );
+        assert_eq!(parse_normal("dy", false), Parsed::Invalid);
+        assert_eq!(parse_normal("diz", false), Parsed::Invalid);
+        assert_eq!(parse_normal("Z", false), Parsed::Invalid);
+    }
+
+    #[test]
+    fn test_text_helpers() {
+        let text = "(sx 'a #t\n  (cyc 'b \"bd\"))\n\n(sx 'c #t\n  (cyc 'd \"sn\"))";
+        assert_eq!(line_range(text, 3), (0, 10));
+        assert_eq!(line_range(text, 9), (0, 10));
+        assert_eq!(line_range(text, 10), (10, 27));
+        // the last line has no line break
+        assert_eq!(line_range(text, 40), (38, 54));
+
+        assert_eq!(toplevel_form_start(text, 3, true), Some(28));
+        assert_eq!(toplevel_form_start(text, 28, true), None);
+        assert_eq!(toplevel_form_start(text, 30, false), Some(28));
+        assert_eq!(toplevel_form_start(text, 28, false), Some(0));
+
+        assert_eq!(paste_position(text, 3, "bd", false), 4);
+        assert_eq!(paste_position(text, 9, "bd", false), 9);
+        assert_eq!(paste_position(text, 3, "(sx)\n", false), 10);
+        assert_eq!(paste_position(text, 3, "(sx)\n", true), 0);
+    }
+}
@@ -0,0 +1,183 @@
+use egui::widgets::text_edit::CCursorRange;
+use egui::TextBuffer;
+use epaint::text::cursor::CCursor;
+
+/// What to do at each cursor.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CursorEdit<'a> {
+    Insert(&'a str),
+    DeleteBack,
+    DeleteForward,
+}
+
+/// start and end (char index) of a cursor range
+pub fn bounds(range: &CCursorRange) -> (usize, usize) {
+    let (a, b) = (range.primary.index, range.secondary.index);
+    (a.min(b), a.max(b))
+}
+
+/// Apply an edit at all cursors, replacing selections.
+/// Returns the new cursors, in the same order.
+pub fn edit_at_cursors(
+    text: &mut dyn TextBuffer,
+    cursors: &[CCursorRange],
+    edit: CursorEdit,
+) -> Vec<CCursorRange> {
+    // edit from the back, so the positions in front stay valid,
+    // and move the ones behind by whatever was inserted or deleted
+    let mut order: Vec<usize> = (0..cursors.len()).collect();
+    order.sort_by_key(|i| std::cmp::Reverse(bounds(&cursors[*i]).0));
+
+    let mut result = vec![0; cursors.len()];
+    for (n, i) in order.iter().enumerate() {
+        let before = text.as_str().chars().count();
+        let (start, end) = bounds(&cursors[*i]);
+        let mut index = start;
+        if start != end {
+            text.delete_char_range(start..end);
+            if let CursorEdit::Insert(s) = edit {
+                index += text.insert_text(s, index);
+            }
+        } else {
+            match edit {
+                CursorEdit::Insert(s) => index += text.insert_text(s, index),
+                CursorEdit::DeleteBack if start > 0 => {
+                    text.delete_char_range(start - 1..start);
+                    index -= 1;
+                }
+                CursorEdit::DeleteForward if start < before => {
+                    text.delete_char_range(start..start + 1);
+                }
+                _ => {}
+            }
+        }
+
+        let after = text.as_str().chars().count();
+        for j in order[..n].iter() {
+            result[*j] = (result[*j] + after).saturating_sub(before);
+        }
+        result[*i] = index;
+    }
+
+    result
+        .into_iter()
+        .map(|index| CCursorRange::one(CCursor::new(index)))
+        .collect()
+}
+
+/// Move all cursors one char to the left or right. Selections collapse.
+pub fn move_cursors(text: &str, cursors: &[CCursorRange], left: bool) -> Vec<CCursorRange> {
+    let len = text.chars().count();
+    cursors
+        .iter()
+        .map(|c| {
+            let (start, end) = bounds(c);
+            let index = match (start == end, left) {
+                (true, true) => start.saturating_sub(1),
+                (true, false) => (end + 1).min(len),
+                (false, true) => start,
+                (false, false) => end,
+            };
+            CCursorRange::one(CCursor::new(index))
+        })
+        .collect()
+}
+
+/// Cursors that ended up in the same place are merged, the first one wins.
+pub fn dedup_cursors(cursors: Vec<CCursorRange>) -> Vec<CCursorRange> {
+    let mut unique: Vec<CCursorRange> = Vec::new();
+    for c in cursors {
+        if !unique.iter().any(|u| bounds(u) == bounds(&c)) {
+            unique.push(c);
+        }
+    }
+    unique
+}
+
+/// The next occurrence of `needle` behind the last cursor that doesn't
+/// have a cursor yet, starting over at the top if there's none.
+pub fn next_occurrence(text: &str, needle: &str, cursors: &[CCursorRange]) -> Option<CCursorRange> {
+    if needle.is_empty() {
+        return None;
+    }
+    let from = cursors.iter().map(|c| bounds(c).1).max().unwrap_or(0);
+    let needle_len = needle.chars().count();
+
+    let mut free = Vec::new();
+    let mut chars = 0;
+    let mut last_byte = 0;
+    for (byte, _) in text.match_indices(needle) {
+        chars += text[last_byte..byte].chars().count();
+        last_byte = byte;
+        let range = (chars, chars + needle_len);
+        if !cursors.iter().any(|c| bounds(c) == range) {
+            free.push(range);
+        }
+    }
+
+    free.iter()
+        .find(|(start, _)| *start >= from)
+        .or_else(|| free.first())
+        .map(|(start, end)| CCursorRange::two(CCursor::new(*start), CCursor::new(*end)))
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn at(index: usize) -> CCursorRange {
+        CCursorRange::one(CCursor::new(index))
+    }
+
+    fn indices(cursors: &[CCursorRange]) -> Vec<usize> {
+        cursors.iter().map(|c| c.primary.index).collect()
+    }
+
+    #[test]
+    fn test_edit_at_cursors() {
+        let mut text = "(saw 400) (sqr 400)".to_string();
+
+        // replace both 400s, second cursor first to check the ordering
+        let cursors = vec![
+            CCursorRange::two(CCursor::new(15), CCursor::new(18)),
+            CCursorRange::two(CCursor::new(5), CCursor::new(8)),
+        ];
+        let cursors = edit_at_cursors(&mut text, &cursors, CursorEdit::Insert("1000"));
+        assert_eq!(text, "(saw 1000) (sqr 1000)");
+        assert_eq!(indices(&cursors), vec![20, 9]);
+
+        let cursors = edit_at_cursors(&mut text, &cursors, CursorEdit::DeleteBack);
+        assert_eq!(text, "(saw 100) (sqr 100)");
+        assert_eq!(indices(&cursors), vec![18, 8]);
+
+        let cursors = move_cursors(&text, &cursors, true);
+        let cursors = edit_at_cursors(&mut text, &cursors, CursorEdit::DeleteForward);
+        assert_eq!(text, "(saw 10) (sqr 10)");
+        assert_eq!(indices(&cursors), vec![16, 7]);
+
+        // nothing to delete at the very start
+        let cursors = edit_at_cursors(&mut text, &[at(0), at(1)], CursorEdit::DeleteBack);
+        assert_eq!(text, "saw 10) (sqr 10)");
+        assert_eq!(indices(&dedup_cursors(cursors)), vec![0]);
+    }
+
+    #[test]
+    fn test_next_occurrence() {
+        let text = "(saw 400) (sqr 400) (sine 400)";
+        let first = CCursorRange::two(CCursor::new(5), CCursor::new(8));
+        let second = next_occurrence(text, "400", &[first]).unwrap();
+        assert_eq!(bounds(&second), (15, 18));
+        let third = next_occurrence(text, "400", &[first, second]).unwrap();
+        assert_eq!(bounds(&third), (26, 29));
+        // all taken
+        assert!(next_occurrence(text, "400", &[first, second, third]).is_none());
+        // start over at the top
+        let wrapped = next_occurrence(text, "400", &[third]).unwrap();
+        assert_eq!(bounds(&wrapped), (5, 8));
+        // chars, not bytes
+        let found = next_occurrence("(é 400) (é 400)", "400", &[]).unwrap();
+        assert_eq!(bounds(&found), (3, 6));
+    }
+}
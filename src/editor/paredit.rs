@@ -0,0 +1,442 @@
+// structural editing: the text is scanned for brackets and strings
+// (skipping comments), and the edits below keep them balanced
+
+const OPENING: [char; 3] = ['(', '[', '{'];
+const CLOSING: [char; 3] = [')', ']', '}'];
+
+/// The brackets and strings in a piece of code.
+#[derive(Default, Debug)]
+pub struct Structure {
+    /// matched brackets, as char indices of the opening and the closing one
+    pub pairs: Vec<(usize, usize)>,
+    /// strings, as char indices of the opening and the closing quote
+    pub strings: Vec<(usize, usize)>,
+}
+
+impl Structure {
+    pub fn scan(text: &str) -> Self {
+        let mut structure = Structure::default();
+        let mut stack: Vec<(usize, char)> = Vec::new();
+        let mut string_start = None;
+        let mut in_comment = false;
+        let mut escaped = false;
+
+        for (i, c) in text.chars().enumerate() {
+            if in_comment {
+                in_comment = c != '\n';
+            } else if let Some(start) = string_start {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == '"' {
+                    structure.strings.push((start, i));
+                    string_start = None;
+                }
+            } else if c == '"' {
+                string_start = Some(i);
+            } else if c == ';' {
+                in_comment = true;
+            } else if OPENING.contains(&c) {
+                stack.push((i, c));
+            } else if let Some(pos) = CLOSING.iter().position(|b| *b == c) {
+                // mismatched brackets are left alone
+                if let Some((open, o)) = stack.last() {
+                    if *o == OPENING[pos] {
+                        structure.pairs.push((*open, i));
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        structure
+    }
+
+    /// The innermost list around the range `start..end`.
+    pub fn enclosing(&self, start: usize, end: usize) -> Option<(usize, usize)> {
+        self.pairs
+            .iter()
+            .filter(|(open, close)| *open < start && end <= *close)
+            .min_by_key(|(open, close)| close - open)
+            .copied()
+    }
+
+    fn string_around(&self, index: usize) -> Option<(usize, usize)> {
+        self.strings
+            .iter()
+            .find(|(open, close)| *open < index && index <= *close)
+            .copied()
+    }
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || OPENING.contains(&c) || CLOSING.contains(&c) || c == '"' || c == ';'
+}
+
+/// The end of the element (list, string or atom) starting at `start`.
+fn element_end(chars: &[char], structure: &Structure, start: usize) -> Option<usize> {
+    let c = *chars.get(start)?;
+    if OPENING.contains(&c) {
+        let (_, close) = structure.pairs.iter().find(|(open, _)| *open == start)?;
+        Some(close + 1)
+    } else if c == '"' {
+        let (_, close) = structure.strings.iter().find(|(open, _)| *open == start)?;
+        Some(close + 1)
+    } else if is_delimiter(c) {
+        None
+    } else {
+        Some(
+            start
+                + chars[start..]
+                    .iter()
+                    .take_while(|c| !is_delimiter(**c))
+                    .count(),
+        )
+    }
+}
+
+/// The start of the element ending at `end`.
+fn element_start(chars: &[char], structure: &Structure, end: usize) -> Option<usize> {
+    let c = *chars.get(end.checked_sub(1)?)?;
+    if CLOSING.contains(&c) {
+        let (open, _) = structure
+            .pairs
+            .iter()
+            .find(|(_, close)| *close == end - 1)?;
+        Some(*open)
+    } else if c == '"' {
+        let (open, _) = structure
+            .strings
+            .iter()
+            .find(|(_, close)| *close == end - 1)?;
+        Some(*open)
+    } else if is_delimiter(c) {
+        None
+    } else {
+        Some(
+            end - chars[..end]
+                .iter()
+                .rev()
+                .take_while(|c| !is_delimiter(**c))
+                .count(),
+        )
+    }
+}
+
+fn element_at_chars(chars: &[char], structure: &Structure, index: usize) -> Option<(usize, usize)> {
+    if let Some((open, close)) = structure.string_around(index) {
+        return Some((open, close + 1));
+    }
+    match chars.get(index) {
+        Some(c) if !is_delimiter(*c) => {
+            let start = index
+                - chars[..index]
+                    .iter()
+                    .rev()
+                    .take_while(|c| !is_delimiter(**c))
+                    .count();
+            Some((start, element_end(chars, structure, index)?))
+        }
+        Some(c) if OPENING.contains(c) || *c == '"' => {
+            Some((index, element_end(chars, structure, index)?))
+        }
+        // right behind an element
+        _ => Some((element_start(chars, structure, index)?, index)),
+    }
+}
+
+/// The element (list, string or atom) the cursor is on, or right behind.
+pub fn element_at(text: &str, index: usize) -> Option<(usize, usize)> {
+    let chars: Vec<char> = text.chars().collect();
+    element_at_chars(&chars, &Structure::scan(text), index)
+}
+
+/// Pull the element after the enclosing list into it: `(a b) c` -> `(a b c)`.
+pub fn slurp_forward(text: &str, index: usize) -> Option<(String, usize)> {
+    let chars: Vec<char> = text.chars().collect();
+    let structure = Structure::scan(text);
+    let (_, close) = structure.enclosing(index, index)?;
+
+    let start = close
+        + 1
+        + chars[close + 1..]
+            .iter()
+            .take_while(|c| c.is_whitespace())
+            .count();
+    let end = element_end(&chars, &structure, start)?;
+
+    let mut slurped: String = chars[..close].iter().collect();
+    slurped.extend(&chars[close + 1..end]);
+    slurped.push(chars[close]);
+    slurped.extend(&chars[end..]);
+    Some((slurped, index))
+}
+
+/// Push the last element of the enclosing list out: `(a b c)` -> `(a b) c`.
+pub fn barf_forward(text: &str, index: usize) -> Option<(String, usize)> {
+    let chars: Vec<char> = text.chars().collect();
+    let structure = Structure::scan(text);
+    let (open, close) = structure.enclosing(index, index)?;
+
+    let trim_back = |mut pos: usize| {
+        while pos > open + 1 && chars[pos - 1].is_whitespace() {
+            pos -= 1;
+        }
+        pos
+    };
+    let start = element_start(&chars, &structure, trim_back(close))?;
+    // the head stays
+    if start <= open + 1 {
+        return None;
+    }
+    let new_close = trim_back(start);
+
+    let mut barfed: String = chars[..new_close].iter().collect();
+    barfed.push(chars[close]);
+    barfed.extend(&chars[new_close..close]);
+    barfed.extend(&chars[close + 1..]);
+    let cursor = if index > new_close { index + 1 } else { index };
+    Some((barfed, cursor))
+}
+
+/// Wrap the range in a new list, or, if it's empty, the element at the cursor.
+/// The cursor goes right behind the opening paren.
+pub fn wrap(text: &str, start: usize, end: usize) -> Option<(String, usize)> {
+    let (start, end) = if start == end {
+        element_at(text, start)?
+    } else {
+        (start, end)
+    };
+    let chars: Vec<char> = text.chars().collect();
+    let mut wrapped: String = chars[..start].iter().collect();
+    wrapped.push('(');
+    wrapped.extend(&chars[start..end]);
+    wrapped.push(')');
+    wrapped.extend(&chars[end..]);
+    Some((wrapped, start + 1))
+}
+
+/// Remove the brackets of the enclosing list: `(a (b c))` -> `(a b c)`.
+pub fn splice(text: &str, index: usize) -> Option<(String, usize)> {
+    let (open, close) = Structure::scan(text).enclosing(index, index)?;
+    let spliced = text
+        .chars()
+        .enumerate()
+        .filter(|(i, _)| *i != open && *i != close)
+        .map(|(_, c)| c)
+        .collect();
+    Some((spliced, index - 1))
+}
+
+/// Replace the enclosing list with the element at the cursor: `(a (b c))` -> `(a c)`.
+pub fn raise(text: &str, index: usize) -> Option<(String, usize)> {
+    let chars: Vec<char> = text.chars().collect();
+    let structure = Structure::scan(text);
+    let (start, end) = element_at_chars(&chars, &structure, index)?;
+    let (open, close) = structure.enclosing(start, end)?;
+
+    let mut raised: String = chars[..open].iter().collect();
+    raised.extend(&chars[start..end]);
+    raised.extend(&chars[close + 1..]);
+    Some((raised, open + index - start))
+}
+
+/// The next bigger structure around `start..end`: the element,
+/// the contents of the list, the list, the contents of the outer list ...
+pub fn expand_selection(text: &str, start: usize, end: usize) -> Option<(usize, usize)> {
+    let chars: Vec<char> = text.chars().collect();
+    let structure = Structure::scan(text);
+
+    let mut candidates = Vec::new();
+    if start == end {
+        candidates.extend(element_at_chars(&chars, &structure, start));
+    }
+    for (open, close) in structure.pairs.iter() {
+        candidates.push((open + 1, *close));
+        candidates.push((*open, close + 1));
+    }
+    for (open, close) in structure.strings.iter() {
+        candidates.push((*open, close + 1));
+    }
+
+    candidates
+        .into_iter()
+        .filter(|(s, e)| *s <= start && end <= *e && (*s, *e) != (start, end))
+        .min_by_key(|(s, e)| e - s)
+}
+
+/// What deleting a character should do.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Deletion {
+    /// an empty pair, delete both
+    Range(usize, usize),
+    /// a bracket or quote that still has content, just move over it
+    Skip(usize),
+    Normal,
+}
+
+fn deletion_at(structure: &Structure, pos: usize, forward: bool) -> Deletion {
+    let skip = if forward { pos + 1 } else { pos };
+    let pair = structure
+        .pairs
+        .iter()
+        .chain(structure.strings.iter())
+        .find(|(open, close)| *open == pos || *close == pos);
+
+    match pair {
+        Some((open, close)) if close - open == 1 => Deletion::Range(*open, close + 1),
+        Some(_) => Deletion::Skip(skip),
+        None => Deletion::Normal,
+    }
+}
+
+/// Backspace at `index`.
+pub fn delete_backward(text: &str, index: usize) -> Deletion {
+    if index == 0 {
+        return Deletion::Normal;
+    }
+    deletion_at(&Structure::scan(text), index - 1, false)
+}
+
+/// Delete at `index`.
+pub fn delete_forward(text: &str, index: usize) -> Deletion {
+    if index >= text.chars().count() {
+        return Deletion::Normal;
+    }
+    deletion_at(&Structure::scan(text), index, true)
+}
+
+/// Typing a closing bracket or quote moves over the closing one that's
+/// already there, or out of the enclosing list. Returns the new cursor.
+pub fn typed_close(text: &str, index: usize, typed: &str) -> Option<usize> {
+    let structure = Structure::scan(text);
+    let at = text.chars().nth(index);
+    match typed {
+        "\"" => structure
+            .strings
+            .iter()
+            .find(|(_, close)| *close == index)
+            .map(|_| index + 1),
+        ")" | "]" | "}" => {
+            if at == typed.chars().next() {
+                return Some(index + 1);
+            }
+            let (_, close) = structure.enclosing(index, index)?;
+            if text.chars().nth(close) == typed.chars().next() {
+                Some(close + 1)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// How far to kill from `from` towards `to` without taking
+/// the closing bracket of the enclosing list with it.
+pub fn kill_end(text: &str, from: usize, to: usize) -> usize {
+    let structure = Structure::scan(text);
+    match structure.enclosing(from, from) {
+        Some((_, close)) if close < to => close,
+        _ => {
+            // don't leave half a list behind either
+            let mut end = to;
+            for (open, close) in structure.pairs.iter() {
+                if *open >= from && *open < end && *close >= end {
+                    end = *open;
+                }
+            }
+            end
+        }
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_scan() {
+        let structure = Structure::scan("(cyc 'b \"bd (\") ;; )\n[a]");
+        assert_eq!(structure.pairs, vec![(0, 14), (21, 23)]);
+        assert_eq!(structure.strings, vec![(8, 13)]);
+        assert_eq!(structure.enclosing(3, 3), Some((0, 14)));
+        assert_eq!(structure.enclosing(0, 0), None);
+        assert_eq!(element_at("(cyc 'b \"bd (\")", 10), Some((8, 14)));
+        assert_eq!(element_at("(cyc 'b \"bd (\")", 2), Some((1, 4)));
+        assert_eq!(element_at("(cyc 'b \"bd (\")", 15), Some((0, 15)));
+    }
+
+    #[test]
+    fn test_slurp_barf() {
+        let text = "(sx 'a #t (cyc 'b \"bd\") (cyc 'c \"sn\"))";
+        let (barfed, cursor) = barf_forward(text, 12).unwrap();
+        assert_eq!(barfed, "(sx 'a #t (cyc 'b) \"bd\" (cyc 'c \"sn\"))");
+        assert_eq!(cursor, 12);
+        let (slurped, _) = slurp_forward(&barfed, 12).unwrap();
+        assert_eq!(slurped, text);
+        // nothing left to slurp
+        assert!(slurp_forward(text, 2).is_none());
+        // the head stays
+        assert!(barf_forward("(sx)", 1).is_none());
+    }
+
+    #[test]
+    fn test_wrap_splice_raise() {
+        let text = "(cyc 'b \"bd\")";
+        assert_eq!(
+            wrap(text, 2, 2).unwrap(),
+            ("((cyc) 'b \"bd\")".to_string(), 2)
+        );
+        assert_eq!(
+            wrap(text, 5, 7).unwrap(),
+            ("(cyc ('b) \"bd\")".to_string(), 6)
+        );
+
+        let text = "(sx 'a #t (cyc 'b))";
+        assert_eq!(
+            splice(text, 12).unwrap(),
+            ("(sx 'a #t cyc 'b)".to_string(), 11)
+        );
+        assert_eq!(raise(text, 12).unwrap(), ("(sx 'a #t cyc)".to_string(), 11));
+    }
+
+    #[test]
+    fn test_expand_selection() {
+        let text = "(sx 'a (cyc 'b))";
+        assert_eq!(expand_selection(text, 9, 9), Some((8, 11)));
+        assert_eq!(expand_selection(text, 8, 11), Some((8, 14)));
+        assert_eq!(expand_selection(text, 8, 14), Some((7, 15)));
+        assert_eq!(expand_selection(text, 7, 15), Some((1, 15)));
+        assert_eq!(expand_selection(text, 0, 16), None);
+    }
+
+    #[test]
+    fn test_protected_deletes() {
+        let text = "(cyc 'b \"\") ()";
+        assert_eq!(delete_backward(text, 11), Deletion::Skip(10));
+        assert_eq!(delete_backward(text, 1), Deletion::Skip(0));
+        assert_eq!(delete_backward(text, 10), Deletion::Range(8, 10));
+        assert_eq!(delete_backward(text, 14), Deletion::Range(12, 14));
+        assert_eq!(delete_backward(text, 5), Deletion::Normal);
+        assert_eq!(delete_forward(text, 0), Deletion::Skip(1));
+        assert_eq!(delete_forward(text, 12), Deletion::Range(12, 14));
+        assert_eq!(delete_forward(text, 14), Deletion::Normal);
+    }
+
+    #[test]
+    fn test_typed_close_and_kill() {
+        let text = "(cyc 'b \"bd\")";
+        assert_eq!(typed_close(text, 12, ")"), Some(13));
+        assert_eq!(typed_close(text, 4, ")"), Some(13));
+        assert_eq!(typed_close(text, 4, "]"), None);
+        assert_eq!(typed_close(text, 11, "\""), Some(12));
+        assert_eq!(typed_close(text, 8, "\""), None);
+
+        let text = "(sx 'a #t (cyc 'b)) (foo\n bar)";
+        assert_eq!(kill_end(text, 5, 19), 18);
+        assert_eq!(kill_end(text, 19, 24), 20);
+    }
+}
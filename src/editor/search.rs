@@ -0,0 +1,229 @@
+use egui::{Color32, Key};
+use regex::{NoExpand, Regex};
+
+/// A match, in bytes (for replacing) and chars (for the cursors).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Match {
+    pub start: usize,
+    pub end: usize,
+    pub char_start: usize,
+    pub char_end: usize,
+}
+
+fn build_regex(query: &str, is_regex: bool) -> Result<Regex, String> {
+    let pattern = if is_regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+    Regex::new(&pattern).map_err(|e| e.to_string())
+}
+
+/// All non-empty matches of the query.
+pub fn find_matches(text: &str, query: &str, is_regex: bool) -> Result<Vec<Match>, String> {
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let re = build_regex(query, is_regex)?;
+
+    let mut matches = Vec::new();
+    let mut chars = 0;
+    let mut last_byte = 0;
+    for m in re.find_iter(text).filter(|m| !m.as_str().is_empty()) {
+        chars += text[last_byte..m.start()].chars().count();
+        last_byte = m.start();
+        matches.push(Match {
+            start: m.start(),
+            end: m.end(),
+            char_start: chars,
+            char_end: chars + m.as_str().chars().count(),
+        });
+    }
+    Ok(matches)
+}
+
+/// The first match starting at `from` (in chars) or later,
+/// starting over at the top if there's none.
+pub fn next_match(matches: &[Match], from: usize) -> Option<Match> {
+    matches
+        .iter()
+        .find(|m| m.char_start >= from)
+        .or_else(|| matches.first())
+        .copied()
+}
+
+/// Replace a single match. In regex mode, the replacement can refer to
+/// groups, like `$1`. Returns the new text and the length of the
+/// inserted replacement in chars.
+pub fn replace_match(
+    text: &str,
+    query: &str,
+    replacement: &str,
+    is_regex: bool,
+    m: &Match,
+) -> Result<(String, usize), String> {
+    let re = build_regex(query, is_regex)?;
+    let mut inserted = String::new();
+    if is_regex {
+        let caps = re
+            .captures_iter(text)
+            .find(|c| c.get(0).map_or(false, |c| c.start() == m.start))
+            .ok_or_else(|| "match is gone".to_string())?;
+        caps.expand(replacement, &mut inserted);
+    } else {
+        inserted.push_str(replacement);
+    }
+    let len = inserted.chars().count();
+    Ok((
+        format!("{}{}{}", &text[..m.start], inserted, &text[m.end..]),
+        len,
+    ))
+}
+
+/// Replace all matches, returns the new text and the number of replacements.
+pub fn replace_all(
+    text: &str,
+    query: &str,
+    replacement: &str,
+    is_regex: bool,
+) -> Result<(String, usize), String> {
+    let count = find_matches(text, query, is_regex)?.len();
+    if count == 0 {
+        return Ok((text.to_string(), 0));
+    }
+    let re = build_regex(query, is_regex)?;
+    let replaced = if is_regex {
+        re.replace_all(text, replacement)
+    } else {
+        re.replace_all(text, NoExpand(replacement))
+    };
+    Ok((replaced.to_string(), count))
+}
+
+/// The state of the search bar.
+#[derive(Default)]
+pub struct SearchBar {
+    pub query: String,
+    pub replacement: String,
+    pub regex: bool,
+    /// the match that was jumped to last (chars)
+    pub current: Option<(usize, usize)>,
+    pub focus: bool,
+}
+
+pub enum SearchAction {
+    Changed,
+    Next,
+    Replace,
+    ReplaceAll,
+    Close,
+}
+
+/// Show the search bar, returns whatever the user did.
+pub fn search_bar(
+    ui: &mut egui::Ui,
+    bar: &mut SearchBar,
+    matches: &Result<Vec<Match>, String>,
+    error_color: Color32,
+) -> Option<SearchAction> {
+    let mut action = None;
+
+    ui.horizontal(|ui| {
+        ui.label("search");
+        let response = ui.text_edit_singleline(&mut bar.query);
+        if bar.focus {
+            response.request_focus();
+            bar.focus = false;
+        }
+        if response.changed() {
+            action = Some(SearchAction::Changed);
+        }
+        if response.lost_focus() {
+            if ui.input().key_pressed(Key::Enter) {
+                // keep going with enter
+                action = Some(SearchAction::Next);
+                bar.focus = true;
+            } else if ui.input().key_pressed(Key::Escape) {
+                action = Some(SearchAction::Close);
+            }
+        }
+
+        if ui.checkbox(&mut bar.regex, "regex").changed() {
+            action = Some(SearchAction::Changed);
+        }
+
+        ui.label("replace with");
+        ui.text_edit_singleline(&mut bar.replacement);
+
+        if ui.button("next").clicked() {
+            action = Some(SearchAction::Next);
+        }
+        if ui.button("replace").clicked() {
+            action = Some(SearchAction::Replace);
+        }
+        if ui.button("replace all").clicked() {
+            action = Some(SearchAction::ReplaceAll);
+        }
+        if ui.small_button("x").clicked() {
+            action = Some(SearchAction::Close);
+        }
+
+        match matches {
+            Ok(m) if bar.query.is_empty() || !m.is_empty() => {
+                ui.label(format!("{} matches", m.len()));
+            }
+            Ok(_) => {
+                ui.colored_label(error_color, "no matches");
+            }
+            Err(_) => {
+                ui.colored_label(error_color, "invalid regex");
+            }
+        }
+    });
+
+    action
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_find_and_next() {
+        let text = "(é 400) (sqr 400.5)";
+        let matches = find_matches(text, "400", false).unwrap();
+        assert_eq!(matches.len(), 2);
+        assert_eq!((matches[0].char_start, matches[0].char_end), (3, 6));
+        assert_eq!((matches[0].start, matches[0].end), (4, 7));
+        assert_eq!(next_match(&matches, 4).unwrap(), matches[1]);
+        // start over at the top
+        assert_eq!(next_match(&matches, 20).unwrap(), matches[0]);
+
+        // a literal dot
+        assert_eq!(find_matches(text, "400.5", false).unwrap().len(), 1);
+        assert_eq!(find_matches(text, "400.", true).unwrap().len(), 2);
+        assert!(find_matches(text, "(", true).is_err());
+        // empty matches are useless
+        assert!(find_matches(text, "x*", true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_replace() {
+        let text = "(saw 400) (sqr 400)";
+        let matches = find_matches(text, r"(\w+) 400", true).unwrap();
+        let (replaced, len) =
+            replace_match(text, r"(\w+) 400", "$1 200", true, &matches[1]).unwrap();
+        assert_eq!(replaced, "(saw 400) (sqr 200)");
+        assert_eq!(len, 7);
+
+        let (replaced, count) = replace_all(text, "400", "$1", false).unwrap();
+        assert_eq!(replaced, "(saw $1) (sqr $1)");
+        assert_eq!(count, 2);
+
+        let (replaced, count) = replace_all(text, r"(\d+)", "${1}0", true).unwrap();
+        assert_eq!(replaced, "(saw 4000) (sqr 4000)");
+        assert_eq!(count, 2);
+    }
+}
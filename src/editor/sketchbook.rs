@@ -0,0 +1,141 @@
+use chrono::Local;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub const SKETCH_EXTENSION: &str = "megra3";
+
+/// a fresh sketch name (without extension), marked with the date
+pub fn timestamped_stem() -> String {
+    format!("sketch_{}", Local::now().format("%Y%m%d_%H%M_%S"))
+}
+
+/// All sketches in the sketchbook folder, sorted by name.
+pub fn list_sketches(sketchbook: &Path) -> Vec<String> {
+    let mut sketches = Vec::new();
+    if let Ok(entries) = fs::read_dir(sketchbook) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            // only consider files here ...
+            if path.is_file() && path.extension().map_or(false, |e| e == SKETCH_EXTENSION) {
+                sketches.push(path.to_str().unwrap().to_string());
+            }
+        }
+    }
+    // sort sketch list so it's easier to find the sketches
+    sketches.sort();
+    sketches
+}
+
+pub fn read_sketch(path: &Path) -> io::Result<String> {
+    let mut s = fs::read_to_string(path)?;
+    if !s.ends_with('\n') {
+        s.push('\n');
+    }
+    Ok(s)
+}
+
+/// The name of a sketch, as shown in the tabs.
+pub fn sketch_name(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map_or_else(|| path.to_string(), |s| s.to_string_lossy().to_string())
+}
+
+/// A path in `dir` that doesn't exist yet, starting with `stem`.
+/// Paths for which `taken` holds are skipped as well, i.e. those
+/// of sketches that haven't been saved yet.
+pub fn unused_path(dir: &Path, stem: &str, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    let mut path = dir.join(format!("{}.{}", stem, SKETCH_EXTENSION));
+    let mut i = 1;
+    while path.exists() || taken(&path) {
+        path = dir.join(format!("{}_{}.{}", stem, i, SKETCH_EXTENSION));
+        i += 1;
+    }
+    path
+}
+
+/// Rename a sketch within its folder. Existing sketches aren't overwritten.
+pub fn rename_sketch(path: &Path, new_name: &str) -> io::Result<PathBuf> {
+    let new_name = new_name.trim();
+    if new_name.is_empty() || new_name.contains(['/', '\\']) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("can't name a sketch '{}'", new_name),
+        ));
+    }
+
+    let file_name = if Path::new(new_name).extension().is_some() {
+        new_name.to_string()
+    } else {
+        format!("{}.{}", new_name, SKETCH_EXTENSION)
+    };
+
+    let new_path = path.with_file_name(file_name);
+    if new_path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists already", new_path.display()),
+        ));
+    }
+
+    fs::rename(path, &new_path)?;
+    Ok(new_path)
+}
+
+/// Copy a sketch to `<name>_copy` next to it.
+pub fn duplicate_sketch(path: &Path) -> io::Result<PathBuf> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let stem = path
+        .file_stem()
+        .map_or_else(|| "sketch".to_string(), |s| s.to_string_lossy().to_string());
+    let new_path = unused_path(dir, &format!("{}_copy", stem), |_| false);
+    fs::copy(path, &new_path)?;
+    Ok(new_path)
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_sketch_file_operations() {
+        let dir = std::env::temp_dir().join(format!("megra_sketchbook_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("beat.megra3");
+        fs::write(&path, "(sx 'ga #t (cyc 'beat \"bd ~\"))").unwrap();
+
+        let copy = duplicate_sketch(&path).unwrap();
+        assert_eq!(copy, dir.join("beat_copy.megra3"));
+        let copy2 = duplicate_sketch(&path).unwrap();
+        assert_eq!(copy2, dir.join("beat_copy_1.megra3"));
+        // a trailing newline is added when reading
+        assert_eq!(
+            read_sketch(&copy).unwrap(),
+            "(sx 'ga #t (cyc 'beat \"bd ~\"))\n"
+        );
+
+        let renamed = rename_sketch(&copy2, "drums").unwrap();
+        assert_eq!(renamed, dir.join("drums.megra3"));
+        assert!(!copy2.exists());
+        // don't overwrite
+        assert!(rename_sketch(&copy, "drums").is_err());
+        assert!(rename_sketch(&copy, "../drums").is_err());
+
+        let names: Vec<String> = list_sketches(&dir).iter().map(|p| sketch_name(p)).collect();
+        assert_eq!(names, vec!["beat", "beat_copy", "drums"]);
+
+        // sketches that only exist in the editor so far
+        let unsaved = dir.join("beat_1.megra3");
+        assert_eq!(
+            unused_path(&dir, "beat", |p| p == unsaved.as_path()),
+            dir.join("beat_2.megra3")
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,12 +1,22 @@
-use crate::parser;
+use crate::editor::completion::Vocabulary;
+use crate::editor::theme::{ThemeColors, TokenType};
+use crate::parser::{self, FunctionCategory};
 use egui::text::LayoutJob;
 use egui::FontId;
 
 /// Memoized Code highlighting
-pub fn highlight(ctx: &egui::Context, theme: &CodeTheme, code: &str) -> LayoutJob {
-    impl egui::util::cache::ComputerMut<(&CodeTheme, &str), LayoutJob> for Highlighter {
-        fn compute(&mut self, (theme, code): (&CodeTheme, &str)) -> LayoutJob {
-            self.highlight(theme, code)
+pub fn highlight(
+    ctx: &egui::Context,
+    theme: &CodeTheme,
+    vocabulary: &Vocabulary,
+    code: &str,
+) -> LayoutJob {
+    impl egui::util::cache::ComputerMut<(&CodeTheme, &Vocabulary, &str), LayoutJob> for Highlighter {
+        fn compute(
+            &mut self,
+            (theme, vocabulary, code): (&CodeTheme, &Vocabulary, &str),
+        ) -> LayoutJob {
+            self.highlight(theme, vocabulary, code)
         }
     }
 
@@ -14,27 +24,11 @@ pub fn highlight(ctx: &egui::Context, theme: &CodeTheme, code: &str) -> LayoutJo
 
     let mut memory = ctx.memory();
     let highlight_cache = memory.caches.cache::<HighlightCache<'_>>();
-    highlight_cache.get((theme, code))
+    highlight_cache.get((theme, vocabulary, code))
 }
 
 // ----------------------------------------------------------------------------
 
-#[derive(Clone, Copy, PartialEq, Debug)]
-#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
-#[derive(enum_map::Enum)]
-enum TokenType {
-    Comment,
-    Normal,
-    Linebreak,
-    Keyword,
-    Boolean,
-    StringLiteral,
-    Function,
-    Command,
-    GenMod,
-    Whitespace,
-}
-
 #[derive(Clone, Hash, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 #[cfg_attr(feature = "serde", serde(default))]
@@ -44,27 +38,17 @@ pub struct CodeTheme {
 
 impl Default for CodeTheme {
     fn default() -> Self {
-        Self::dark(15.0)
+        Self::new(15.0, &ThemeColors::dark())
     }
 }
 
 impl CodeTheme {
-    pub fn dark(font_size: f32) -> Self {
+    pub fn new(font_size: f32, colors: &ThemeColors) -> Self {
         let text_style = FontId::monospace(font_size);
-        use egui::{Color32, TextFormat};
         Self {
-            formats: enum_map::enum_map![
-                        TokenType::Comment => TextFormat::simple(text_style.clone(), Color32::from_gray(120)),
-                TokenType::Normal => TextFormat::simple(text_style.clone(), Color32::from_gray(200)),
-                TokenType::Boolean => TextFormat::simple(text_style.clone(), Color32::from_rgb(0, 200, 100)),
-                        TokenType::Keyword => TextFormat::simple(text_style.clone(), Color32::from_rgb(200, 20, 200)),
-                        TokenType::StringLiteral => TextFormat::simple(text_style.clone(), egui::Color32::from_rgb(200, 200, 10)),
-                    TokenType::Function => TextFormat::simple(text_style.clone(), Color32::from_rgb(220, 20, 100)),
-            TokenType::Command => TextFormat::simple(text_style.clone(), Color32::from_rgb(100, 220, 110)),
-            TokenType::GenMod => TextFormat::simple(text_style.clone(), Color32::from_rgb(190, 190, 140)),
-                        TokenType::Whitespace => TextFormat::simple(text_style.clone(), Color32::TRANSPARENT),
-                TokenType::Linebreak => TextFormat::simple(text_style.clone(), Color32::TRANSPARENT),
-                    ],
+            formats: enum_map::EnumMap::from_fn(|tt| {
+                egui::TextFormat::simple(text_style.clone(), colors.tokens[tt])
+            }),
         }
     }
 }
@@ -74,7 +58,7 @@ struct Highlighter {}
 
 impl Highlighter {
     #[allow(clippy::unused_self, clippy::unnecessary_wraps)]
-    fn highlight(&self, theme: &CodeTheme, mut text: &str) -> LayoutJob {
+    fn highlight(&self, theme: &CodeTheme, vocabulary: &Vocabulary, mut text: &str) -> LayoutJob {
         let mut job = LayoutJob::default();
 
         while !text.is_empty() {
@@ -110,14 +94,18 @@ impl Highlighter {
                     .find(|c: char| !parser::valid_function_name_char(c))
                     .map_or_else(|| text.len(), |i| i + 1);
                 let word = &text[..end];
-                let tt = if is_function(word) {
-                    TokenType::Function
-                } else if is_command(word) {
-                    TokenType::Command
-                } else if is_genmod(word) {
-                    TokenType::GenMod
-                } else {
-                    TokenType::Normal
+                let tt = match vocabulary.category(word) {
+                    Some(
+                        FunctionCategory::Session
+                        | FunctionCategory::Constructor
+                        | FunctionCategory::Processor
+                        | FunctionCategory::Composition,
+                    ) => TokenType::Function,
+                    Some(FunctionCategory::Command | FunctionCategory::Arithmetic) => {
+                        TokenType::Command
+                    }
+                    Some(FunctionCategory::Modifier) => TokenType::GenMod,
+                    _ => TokenType::Normal,
                 };
                 job.append(word, 0.0, theme.formats[tt].clone());
                 text = &text[end..];
@@ -143,80 +131,3 @@ impl Highlighter {
         job
     }
 }
-fn is_function(word: &str) -> bool {
-    matches!(
-        word,
-        "apple"
-            | "friendship"
-            | "learn"
-            | "pear"
-            | "nuc"
-            | "fully"
-            | "flower"
-            | "sx"
-            | "cyc"
-            | "xspread"
-            | "xdup"
-            | "life"
-            | "lin"
-            | "loop"
-            | "facts"
-            | "ls"
-            | "list"
-            | "every"
-            | "infer"
-            | "once"
-            | "cmp"
-            | "chop"
-            | "inh"
-            | "exh"
-            | "inexh"
-            | "stages"
-    )
-}
-
-fn is_command(word: &str) -> bool {
-    matches!(
-        word,
-        "tmod"
-            | "midi-callback"
-            | "export-dot"
-            | "step-part"
-            | "latency"
-            | "global-resources"
-            | "delay"
-            | "reverb"
-            | "default-duration"
-            | "bpm"
-            | "defpart"
-            | "clear"
-            | "rec"
-            | "stop-rec"
-            | "add"
-            | "sub"
-            | "mul"
-            | "div"
-            | "mod"
-            | "pow"
-    )
-}
-
-fn is_genmod(word: &str) -> bool {
-    matches!(
-        word,
-        "haste"
-            | "shrink"
-            | "grow"
-            | "grown"
-            | "keep"
-            | "rep"
-            | "rnd"
-            | "relax"
-            | "blur"
-            | "sharpen"
-            | "skip"
-            | "rewind"
-            | "reverse"
-            | "solidify"
-    )
-}
@@ -0,0 +1,289 @@
+use std::{fs, path::Path};
+
+use egui::Color32;
+
+use crate::messages;
+
+// the token types the highlighter knows about
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[derive(enum_map::Enum)]
+pub enum TokenType {
+    Comment,
+    Normal,
+    Linebreak,
+    Keyword,
+    Boolean,
+    StringLiteral,
+    Function,
+    Command,
+    GenMod,
+    Whitespace,
+}
+
+// the names used in theme files, whitespace is always transparent
+const TOKEN_NAMES: [(&str, TokenType); 8] = [
+    ("normal", TokenType::Normal),
+    ("comment", TokenType::Comment),
+    ("keyword", TokenType::Keyword),
+    ("boolean", TokenType::Boolean),
+    ("string", TokenType::StringLiteral),
+    ("function", TokenType::Function),
+    ("command", TokenType::Command),
+    ("genmod", TokenType::GenMod),
+];
+
+/// The editor colors, per token type plus the background
+/// and the colors to flash evaluated (or failed) code in.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ThemeColors {
+    pub tokens: enum_map::EnumMap<TokenType, Color32>,
+    pub background: Color32,
+    pub flash: Color32,
+    pub error: Color32,
+}
+
+impl Default for ThemeColors {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl ThemeColors {
+    pub fn dark() -> Self {
+        Self {
+            tokens: enum_map::enum_map![
+                TokenType::Comment => Color32::from_gray(120),
+                TokenType::Normal => Color32::from_gray(200),
+                TokenType::Boolean => Color32::from_rgb(0, 200, 100),
+                TokenType::Keyword => Color32::from_rgb(200, 20, 200),
+                TokenType::StringLiteral => Color32::from_rgb(200, 200, 10),
+                TokenType::Function => Color32::from_rgb(220, 20, 100),
+                TokenType::Command => Color32::from_rgb(100, 220, 110),
+                TokenType::GenMod => Color32::from_rgb(190, 190, 140),
+                TokenType::Whitespace => Color32::TRANSPARENT,
+                TokenType::Linebreak => Color32::TRANSPARENT,
+            ],
+            background: Color32::BLACK,
+            flash: Color32::from_rgb(220, 80, 20),
+            error: Color32::from_rgb(230, 10, 40),
+        }
+    }
+
+    /// for projectors and daylight
+    pub fn light() -> Self {
+        Self {
+            tokens: enum_map::enum_map![
+                TokenType::Comment => Color32::from_gray(120),
+                TokenType::Normal => Color32::from_gray(20),
+                TokenType::Boolean => Color32::from_rgb(0, 120, 50),
+                TokenType::Keyword => Color32::from_rgb(140, 0, 140),
+                TokenType::StringLiteral => Color32::from_rgb(140, 100, 0),
+                TokenType::Function => Color32::from_rgb(190, 0, 70),
+                TokenType::Command => Color32::from_rgb(20, 120, 40),
+                TokenType::GenMod => Color32::from_rgb(90, 80, 30),
+                TokenType::Whitespace => Color32::TRANSPARENT,
+                TokenType::Linebreak => Color32::TRANSPARENT,
+            ],
+            background: Color32::from_rgb(250, 250, 245),
+            flash: Color32::from_rgb(240, 130, 30),
+            error: Color32::from_rgb(210, 0, 30),
+        }
+    }
+
+    /// full saturation on black, for washed-out screens
+    pub fn high_contrast() -> Self {
+        Self {
+            tokens: enum_map::enum_map![
+                TokenType::Comment => Color32::from_gray(170),
+                TokenType::Normal => Color32::WHITE,
+                TokenType::Boolean => Color32::from_rgb(0, 255, 120),
+                TokenType::Keyword => Color32::from_rgb(255, 80, 255),
+                TokenType::StringLiteral => Color32::from_rgb(255, 255, 0),
+                TokenType::Function => Color32::from_rgb(255, 60, 120),
+                TokenType::Command => Color32::from_rgb(80, 255, 80),
+                TokenType::GenMod => Color32::from_rgb(120, 200, 255),
+                TokenType::Whitespace => Color32::TRANSPARENT,
+                TokenType::Linebreak => Color32::TRANSPARENT,
+            ],
+            background: Color32::BLACK,
+            flash: Color32::from_rgb(255, 140, 0),
+            error: Color32::from_rgb(255, 0, 0),
+        }
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high-contrast" => Some(Self::high_contrast()),
+            _ => None,
+        }
+    }
+
+    /// light backgrounds need dark widgets and vice versa
+    pub fn is_light(&self) -> bool {
+        let bg = self.background;
+        (bg.r() as u32 + bg.g() as u32 + bg.b() as u32) / 3 > 127
+    }
+
+    /// Read a theme file. Each line sets one color, like `keyword = #c814c8`,
+    /// lines starting with `;;` are comments. Whatever isn't set is taken
+    /// from `base`, lines that can't be read are reported and skipped.
+    pub fn parse(text: &str, base: ThemeColors) -> (Self, Vec<String>) {
+        let mut colors = base;
+        let mut problems = Vec::new();
+
+        for (num, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(";;") {
+                continue;
+            }
+
+            let (key, value) = if let Some((key, value)) = line.split_once('=') {
+                (key.trim(), value.trim())
+            } else {
+                problems.push(format!("line {}: expected 'name = #rrggbb'", num + 1));
+                continue;
+            };
+
+            let color = if let Some(color) = parse_color(value) {
+                color
+            } else {
+                problems.push(format!("line {}: can't read color '{}'", num + 1, value));
+                continue;
+            };
+
+            match key {
+                "background" => colors.background = color,
+                "flash" => colors.flash = color,
+                "error" => colors.error = color,
+                _ => {
+                    if let Some((_, tt)) = TOKEN_NAMES.iter().find(|(name, _)| *name == key) {
+                        colors.tokens[*tt] = color;
+                    } else {
+                        problems.push(format!("line {}: unknown color '{}'", num + 1, key));
+                    }
+                }
+            }
+        }
+
+        (colors, problems)
+    }
+
+    /// the theme as a theme file, to start your own from
+    pub fn to_file_content(&self) -> String {
+        let mut content = ";; mégra editor theme, colors are #rrggbb or #rrggbbaa\n".to_string();
+        content.push_str(&format!("background = {}\n", format_color(self.background)));
+        content.push_str(&format!("flash = {}\n", format_color(self.flash)));
+        content.push_str(&format!("error = {}\n", format_color(self.error)));
+        for (name, tt) in TOKEN_NAMES.iter() {
+            content.push_str(&format!("{} = {}\n", name, format_color(self.tokens[*tt])));
+        }
+        content
+    }
+}
+
+/// Load a theme by name, either from `<base_dir>/themes/<name>.theme`
+/// or one of the builtin ones. A theme file with a builtin name
+/// only needs to contain the colors it changes.
+pub fn load_theme(base_dir: &Path, name: &str) -> ThemeColors {
+    let base = ThemeColors::builtin(name);
+    let path = base_dir.join("themes").join(format!("{}.theme", name));
+
+    match fs::read_to_string(&path) {
+        Ok(text) => {
+            let (colors, problems) = ThemeColors::parse(&text, base.unwrap_or_default());
+            for p in problems {
+                messages::warning(format!("theme {}: {}", name, p));
+            }
+            colors
+        }
+        Err(_) => base.unwrap_or_else(|| {
+            messages::warning(format!(
+                "can't find theme {} (looked for {}), using dark theme",
+                name,
+                path.display()
+            ));
+            ThemeColors::dark()
+        }),
+    }
+}
+
+/// Write the builtin themes to the themes folder, as starting points.
+/// Files that exist already are kept.
+pub fn write_builtin_themes(themes_path: &Path) {
+    for name in ["dark", "light", "high-contrast"] {
+        let path = themes_path.join(format!("{}.theme", name));
+        if path.exists() {
+            continue;
+        }
+        let content = ThemeColors::builtin(name).unwrap().to_file_content();
+        if let Err(e) = fs::write(&path, content) {
+            messages::warning(format!("couldn't write theme {}: {}", name, e));
+        }
+    }
+}
+
+fn parse_color(s: &str) -> Option<Color32> {
+    let hex = s.strip_prefix('#')?;
+    if !hex.is_ascii() || (hex.len() != 6 && hex.len() != 8) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    let (r, g, b) = (channel(0)?, channel(2)?, channel(4)?);
+    if hex.len() == 8 {
+        Some(Color32::from_rgba_unmultiplied(r, g, b, channel(6)?))
+    } else {
+        Some(Color32::from_rgb(r, g, b))
+    }
+}
+
+fn format_color(c: Color32) -> String {
+    if c.a() == 255 {
+        format!("#{:02x}{:02x}{:02x}", c.r(), c.g(), c.b())
+    } else {
+        let [r, g, b, a] = c.to_srgba_unmultiplied();
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_parse_theme() {
+        let text =
+            ";; my theme\nbackground = #ffffff\n\nkeyword = #102030\nfoo = #000000\nflash = red\n";
+        let (colors, problems) = ThemeColors::parse(text, ThemeColors::dark());
+        assert_eq!(colors.background, Color32::WHITE);
+        assert_eq!(
+            colors.tokens[TokenType::Keyword],
+            Color32::from_rgb(16, 32, 48)
+        );
+        // not mentioned, so taken from the base
+        assert_eq!(colors.flash, ThemeColors::dark().flash);
+        assert_eq!(colors.tokens[TokenType::Comment], Color32::from_gray(120));
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with("line 5"));
+        assert!(colors.is_light());
+    }
+
+    #[test]
+    fn test_theme_file_roundtrip() {
+        for name in ["dark", "light", "high-contrast"] {
+            let theme = ThemeColors::builtin(name).unwrap();
+            // parse on top of something else to make sure everything is written
+            let (parsed, problems) =
+                ThemeColors::parse(&theme.to_file_content(), ThemeColors::high_contrast());
+            assert!(problems.is_empty());
+            assert_eq!(parsed, theme);
+        }
+        assert!(ThemeColors::builtin("nope").is_none());
+        assert!(!ThemeColors::dark().is_light());
+        assert!(ThemeColors::light().is_light());
+    }
+}
@@ -5,6 +5,7 @@ use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::*;
 
 use crate::builtin_types::Command;
+use crate::parameter::modifier::EvalContext;
 use crate::parameter::{resolve_parameter, shake_parameter, ParameterValue};
 use crate::session::SyncContext;
 use crate::synth_parameter_value_arithmetic::*;
@@ -228,11 +229,15 @@ impl Event {
         }
     }
 
-    pub fn evaluate_parameters(&mut self) -> HashMap<SynthParameterLabel, SynthParameterValue> {
+    /// Evaluate the parameters in the given context (logical time and scale).
+    pub fn evaluate_parameters(
+        &mut self,
+        ctx: &EvalContext,
+    ) -> HashMap<SynthParameterLabel, SynthParameterValue> {
         let mut map = HashMap::new();
 
         for (k, v) in self.params.iter_mut() {
-            map.insert(*k, resolve_parameter(*k, v));
+            map.insert(*k, resolve_parameter(*k, v, ctx));
         }
 
         map
@@ -246,10 +251,12 @@ impl Event {
         }
     }
 
-    pub fn get_static(&mut self) -> StaticEvent {
+    /// A static copy of the event, as it sounds in the given
+    /// context (logical time and scale).
+    pub fn get_static(&mut self, ctx: &EvalContext) -> StaticEvent {
         StaticEvent {
             name: self.name.clone(),
-            params: self.evaluate_parameters(),
+            params: self.evaluate_parameters(ctx),
             tags: self.tags.clone(),
             op: self.op,
         }
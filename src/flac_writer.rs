@@ -0,0 +1,347 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+
+// a common choice, and what most encoders use by default
+const BLOCKSIZE: usize = 4096;
+
+// the rice parameter is coded with 4 bits, 15 is the escape code
+const MAX_RICE_PARAM: u32 = 14;
+
+/// A simple FLAC encoder, as there's no encoder among the dependencies.
+/// It only uses the fixed predictors (with a single rice partition),
+/// which doesn't compress quite as well as the reference encoder, but
+/// is fast and good enough to record a session.
+pub struct FlacWriter {
+    out: BufWriter<File>,
+    channels: usize,
+    bits: u32,
+    samplerate: u32,
+    block: Vec<Vec<i32>>,
+    frame_number: u64,
+    total_samples: u64,
+    bytes_written: u64,
+    min_frame_size: u32,
+    max_frame_size: u32,
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    nbits: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter {
+            bytes: Vec::new(),
+            acc: 0,
+            nbits: 0,
+        }
+    }
+
+    // write the lowest `bits` bits of val, up to 32 at a time
+    fn write(&mut self, val: u64, bits: u32) {
+        if bits == 0 {
+            return;
+        }
+        self.acc = (self.acc << bits) | (val & ((1 << bits) - 1));
+        self.nbits += bits;
+        while self.nbits >= 8 {
+            self.nbits -= 8;
+            self.bytes.push((self.acc >> self.nbits) as u8);
+        }
+    }
+
+    fn write_signed(&mut self, val: i32, bits: u32) {
+        self.write(val as u32 as u64, bits);
+    }
+
+    fn write_unary(&mut self, mut zeros: u64) {
+        while zeros >= 32 {
+            self.write(0, 32);
+            zeros -= 32;
+        }
+        self.write(1, zeros as u32 + 1);
+    }
+
+    fn align(&mut self) {
+        if self.nbits > 0 {
+            self.write(0, 8 - self.nbits);
+        }
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc: u8 = 0;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+// the frame number is coded like an utf-8 character
+fn write_utf8_number(w: &mut BitWriter, n: u64) {
+    if n < 0x80 {
+        w.write(n, 8);
+    } else {
+        let (len, lead) = if n < 0x800 {
+            (2, 0xC0)
+        } else if n < 0x10000 {
+            (3, 0xE0)
+        } else if n < 0x200000 {
+            (4, 0xF0)
+        } else if n < 0x4000000 {
+            (5, 0xF8)
+        } else {
+            (6, 0xFC)
+        };
+        w.write(lead | (n >> (6 * (len - 1))), 8);
+        for i in (0..len - 1).rev() {
+            w.write(0x80 | ((n >> (6 * i)) & 0x3F), 8);
+        }
+    }
+}
+
+fn fixed_residual(samples: &[i32], order: usize) -> Vec<i32> {
+    (order..samples.len())
+        .map(|i| {
+            let s = |o: usize| samples[i - o] as i64;
+            (match order {
+                0 => s(0),
+                1 => s(0) - s(1),
+                2 => s(0) - 2 * s(1) + s(2),
+                3 => s(0) - 3 * s(1) + 3 * s(2) - s(3),
+                _ => s(0) - 4 * s(1) + 6 * s(2) - 4 * s(3) + s(4),
+            }) as i32
+        })
+        .collect()
+}
+
+fn zigzag(r: i32) -> u64 {
+    ((r << 1) ^ (r >> 31)) as u32 as u64
+}
+
+// find the cheapest rice parameter, returns parameter and size in bits
+fn rice_parameter(residual: &[i32]) -> (u32, u64) {
+    let mut best = (0, u64::MAX);
+    for k in 0..=MAX_RICE_PARAM {
+        let bits: u64 = residual
+            .iter()
+            .map(|r| (zigzag(*r) >> k) + 1 + k as u64)
+            .sum();
+        if bits < best.1 {
+            best = (k, bits);
+        }
+    }
+    best
+}
+
+fn write_subframe(w: &mut BitWriter, samples: &[i32], bits: u32) {
+    // pick the fixed predictor with the smallest residual
+    let max_order = 4.min(samples.len() - 1);
+    let mut best: Option<(usize, Vec<i32>, u32, u64)> = None;
+    for order in 0..=max_order {
+        let residual = fixed_residual(samples, order);
+        let (k, res_bits) = rice_parameter(&residual);
+        let size = res_bits + order as u64 * bits as u64;
+        if best.as_ref().map(|b| size < b.3).unwrap_or(true) {
+            best = Some((order, residual, k, size));
+        }
+    }
+
+    let (order, residual, k, size) = best.unwrap();
+
+    if size >= samples.len() as u64 * bits as u64 {
+        // VERBATIM
+        w.write(0b0000010, 8);
+        for s in samples {
+            w.write_signed(*s, bits);
+        }
+    } else {
+        // FIXED
+        w.write((0b001000 | order as u64) << 1, 8);
+        for s in samples.iter().take(order) {
+            w.write_signed(*s, bits);
+        }
+        // rice coding, partition order 0
+        w.write(0, 2);
+        w.write(0, 4);
+        w.write(k as u64, 4);
+        for r in residual {
+            let u = zigzag(r);
+            w.write_unary(u >> k);
+            w.write(u, k);
+        }
+    }
+}
+
+impl FlacWriter {
+    /// create a new file, 16 or 24 bits
+    pub fn create(path: &str, channels: usize, samplerate: u32, bits: u32) -> io::Result<Self> {
+        let mut writer = FlacWriter {
+            out: BufWriter::new(File::create(path)?),
+            channels,
+            bits,
+            samplerate,
+            block: vec![Vec::with_capacity(BLOCKSIZE); channels],
+            frame_number: 0,
+            total_samples: 0,
+            bytes_written: 0,
+            min_frame_size: 0,
+            max_frame_size: 0,
+        };
+        writer.out.write_all(b"fLaC")?;
+        // last metadata block, STREAMINFO, 34 bytes
+        writer.out.write_all(&[0x80, 0, 0, 34])?;
+        writer.write_streaminfo()?;
+        writer.bytes_written = 42;
+        Ok(writer)
+    }
+
+    fn write_streaminfo(&mut self) -> io::Result<()> {
+        let mut w = BitWriter::new();
+        w.write(BLOCKSIZE as u64, 16);
+        w.write(BLOCKSIZE as u64, 16);
+        w.write(self.min_frame_size as u64, 24);
+        w.write(self.max_frame_size as u64, 24);
+        w.write(self.samplerate as u64, 20);
+        w.write(self.channels as u64 - 1, 3);
+        w.write(self.bits as u64 - 1, 5);
+        w.write(self.total_samples >> 32, 4);
+        w.write(self.total_samples & 0xFFFFFFFF, 32);
+        // no md5 signature
+        for _ in 0..4 {
+            w.write(0, 32);
+        }
+        self.out.write_all(&w.bytes)
+    }
+
+    /// write one (interleaved) frame of float samples
+    pub fn write_frame(&mut self, frame: &[f32]) -> io::Result<()> {
+        let max_val = ((1 << (self.bits - 1)) - 1) as f32;
+        for (ch, block) in self.block.iter_mut().enumerate() {
+            let s = frame.get(ch).copied().unwrap_or(0.0).clamp(-1.0, 1.0);
+            block.push((s * max_val) as i32);
+        }
+        if self.block[0].len() >= BLOCKSIZE {
+            self.write_block()?;
+        }
+        Ok(())
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        let len = self.block[0].len();
+        if len == 0 {
+            return Ok(());
+        }
+
+        let mut w = BitWriter::new();
+        // FRAME HEADER
+        w.write(0b11111111111110, 14);
+        w.write(0, 1); // reserved
+        w.write(0, 1); // fixed blocksize
+        w.write(0b0111, 4); // blocksize at end of header, 16 bit
+        w.write(0b0000, 4); // samplerate from streaminfo
+        w.write(self.channels as u64 - 1, 4); // independent channels
+        w.write(if self.bits == 16 { 0b100 } else { 0b110 }, 3);
+        w.write(0, 1); // reserved
+        write_utf8_number(&mut w, self.frame_number);
+        w.write(len as u64 - 1, 16);
+        let crc = crc8(&w.bytes);
+        w.write(crc as u64, 8);
+
+        // SUBFRAMES
+        for block in self.block.iter() {
+            write_subframe(&mut w, block, self.bits);
+        }
+
+        // FRAME FOOTER
+        w.align();
+        let crc = crc16(&w.bytes);
+        w.write(crc as u64, 16);
+
+        self.out.write_all(&w.bytes)?;
+
+        let frame_size = w.bytes.len() as u32;
+        if self.min_frame_size == 0 || frame_size < self.min_frame_size {
+            self.min_frame_size = frame_size;
+        }
+        if frame_size > self.max_frame_size {
+            self.max_frame_size = frame_size;
+        }
+
+        self.bytes_written += frame_size as u64;
+        self.total_samples += len as u64;
+        self.frame_number += 1;
+        for block in self.block.iter_mut() {
+            block.clear();
+        }
+        Ok(())
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// write the remaining samples and update the header
+    pub fn finalize(mut self) -> io::Result<()> {
+        self.write_block()?;
+        self.out.seek(SeekFrom::Start(8))?;
+        self.write_streaminfo()?;
+        self.out.flush()
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_flac_roundtrip() {
+        let path = "megra_flac_test.flac";
+        let len = BLOCKSIZE * 2 + 100; // make sure there's a short last block
+        let mut writer = FlacWriter::create(path, 2, 44100, 24).unwrap();
+        let mut expected = Vec::new();
+        for i in 0..len {
+            let l = (i as f32 * 0.01).sin() * 0.5;
+            let r = if i % 7 == 0 { 0.9 } else { -0.3 };
+            writer.write_frame(&[l, r]).unwrap();
+            expected.push((l * 8388607.0) as i32);
+            expected.push((r * 8388607.0) as i32);
+        }
+        writer.finalize().unwrap();
+
+        let mut reader = claxon::FlacReader::open(path).unwrap();
+        assert_eq!(reader.streaminfo().samples, Some(len as u64));
+        assert_eq!(reader.streaminfo().channels, 2);
+        assert_eq!(reader.streaminfo().bits_per_sample, 24);
+        let decoded: Vec<i32> = reader.samples().map(|s| s.unwrap()).collect();
+        assert_eq!(decoded, expected);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
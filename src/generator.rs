@@ -3,10 +3,11 @@ use crate::{
     event::{EventOperation, InterpretableEvent, StaticEvent},
     generator_processor::GeneratorProcessor,
     markov_sequence_generator::MarkovSequenceGenerator,
+    parameter::modifier::EvalContext,
 };
 use ruffbox_synth::building_blocks::{SynthParameterLabel, SynthParameterValue};
 use std::boxed::Box;
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::*;
 
 // little helper struct for fixed time operations
@@ -46,12 +47,22 @@ pub struct Generator {
 impl Generator {
     pub fn transfer_state(&mut self, other: &Generator) {
         self.root_generator.transfer_state(&other.root_generator);
-        // this will only work if the generators remain in the same order,
-        // but it'll still be helpful I think ..
-        for (idx, gp) in self.processors.iter_mut().enumerate() {
-            if let Some(g) = other.processors.get(idx) {
+        // match processors by identity, so they can be re-ordered or
+        // inserted without scrambling the state. If several processors
+        // share an identity, they're matched in order of appearance.
+        let mut seen = HashMap::<String, usize>::new();
+        for gp in self.processors.iter_mut() {
+            let id = gp.get_id().to_string();
+            let nth = seen.entry(id.clone()).or_insert(0);
+            if let Some(g) = other
+                .processors
+                .iter()
+                .filter(|g| g.get_id() == id)
+                .nth(*nth)
+            {
                 gp.set_state(g.get_state());
             }
+            *nth += 1;
         }
     }
 
@@ -59,11 +70,24 @@ impl Generator {
         self.root_generator.reached_end_state()
     }
 
+    /// the context the events are evaluated in, the (outermost)
+    /// scale processor sets the scale
+    fn eval_context(&self, time: f64) -> EvalContext {
+        EvalContext {
+            time,
+            scale: self.processors.iter().rev().find_map(|p| p.scale()),
+        }
+    }
+
+    /// The events of the current state, evaluated at the given
+    /// logical time (in seconds).
     pub fn current_events(
         &mut self,
         global_parameters: &Arc<GlobalParameters>,
+        time: f64,
     ) -> Vec<InterpretableEvent> {
-        let mut events = self.root_generator.current_events();
+        let ctx = self.eval_context(time);
+        let mut events = self.root_generator.current_events(&ctx);
 
         for ev in events.iter_mut() {
             if let InterpretableEvent::Sound(s) = ev {
@@ -78,7 +102,7 @@ impl Generator {
         tmp_procs.append(&mut self.processors);
 
         for proc in tmp_procs.iter_mut() {
-            proc.process_events(&mut events, global_parameters);
+            proc.process_events(&mut events, global_parameters, &ctx);
 
             proc.process_generator(self, global_parameters);
         }
@@ -93,10 +117,15 @@ impl Generator {
         events
     }
 
-    pub fn current_transition(&mut self, global_parameters: &Arc<GlobalParameters>) -> StaticEvent {
-        let mut trans = self.root_generator.current_transition();
+    pub fn current_transition(
+        &mut self,
+        global_parameters: &Arc<GlobalParameters>,
+        time: f64,
+    ) -> StaticEvent {
+        let ctx = self.eval_context(time);
+        let mut trans = self.root_generator.current_transition(&ctx);
         for proc in self.processors.iter_mut() {
-            proc.process_transition(&mut trans, global_parameters);
+            proc.process_transition(&mut trans, global_parameters, &ctx);
         }
         if let Some(tmod) = self.time_mods.pop() {
             //println!("apply time mod");
@@ -110,3 +139,102 @@ mod modifier_functions;
 pub use modifier_functions::*;
 
 pub mod modifier_functions_raw;
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::event::{Event, SourceEvent};
+    use crate::generator_processor::{
+        EveryProcessor, GeneratorProcessorState, PearProcessor, ScaleProcessor,
+    };
+    use crate::music_theory::Scale;
+    use crate::parameter::modifier::scale_modifier::quantize_parameter;
+    use crate::parameter::{DynVal, ParameterValue};
+    use crate::parser::eval::constructors::arp::step_generator;
+
+    fn every_with(id: &str, step_count: usize) -> Box<dyn GeneratorProcessor + Send> {
+        let mut proc = EveryProcessor::new();
+        proc.set_id(id.to_string());
+        proc.step_count = step_count;
+        Box::new(proc)
+    }
+
+    fn step_count(proc: &(dyn GeneratorProcessor + Send)) -> usize {
+        if let GeneratorProcessorState::Count(c) = proc.get_state() {
+            c
+        } else {
+            0
+        }
+    }
+
+    #[test]
+    fn test_transfer_state_by_id() {
+        let mut old = step_generator(
+            "gen".to_string(),
+            vec![Vec::new()],
+            DynVal::with_value(100.0),
+            false,
+        );
+        old.processors.push(every_with("a", 5));
+        old.processors.push(every_with("b", 7));
+
+        // re-ordered, with a new processor in front and one that didn't exist before
+        let mut new = old.clone();
+        new.processors.clear();
+        new.processors.push(Box::new(PearProcessor::new()));
+        new.processors.push(every_with("b", 1));
+        new.processors.push(every_with("a", 1));
+        new.processors.push(every_with("c", 1));
+
+        new.transfer_state(&old);
+
+        assert_eq!(step_count(new.processors[1].as_ref()), 7);
+        assert_eq!(step_count(new.processors[2].as_ref()), 5);
+        assert_eq!(step_count(new.processors[3].as_ref()), 1);
+    }
+
+    #[test]
+    fn test_generator_scale() {
+        let globals = Arc::new(GlobalParameters::new());
+        let pitch = |quant: bool| {
+            let mut ev = Event::with_name("saw".to_string());
+            let mut freq = ParameterValue::Scalar(DynVal::with_value(356.0));
+            if quant {
+                quantize_parameter(&mut freq, &globals);
+            }
+            ev.params.insert(SynthParameterLabel::PitchFrequency, freq);
+            SourceEvent::Sound(ev)
+        };
+        let mut gen = step_generator(
+            "gen".to_string(),
+            vec![vec![pitch(true), pitch(false)]],
+            DynVal::with_value(100.0),
+            false,
+        );
+        gen.processors.push(Box::new(ScaleProcessor::new(
+            Scale::from_name("minor-pentatonic", "a").unwrap(),
+        )));
+
+        gen.current_transition(&globals, 0.0);
+        let freqs: Vec<f32> = gen
+            .current_events(&globals, 0.0)
+            .iter()
+            .filter_map(|ev| match ev {
+                InterpretableEvent::Sound(s) => {
+                    match s.params[&SynthParameterLabel::PitchFrequency] {
+                        SynthParameterValue::ScalarF32(f) => Some(f),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+            .collect();
+        // e4 in the generator's scale (it'd be f4 in the global c major),
+        // the pitch without :quant stays where it is
+        assert_eq!(freqs.len(), 2);
+        assert!((freqs[0] - 329.63).abs() < 0.01);
+        assert_eq!(freqs[1], 356.0);
+    }
+}
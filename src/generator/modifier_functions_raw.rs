@@ -2,7 +2,7 @@ use crate::{
     event::{Event, EventOperation, SourceEvent},
     generator::TimeMod,
     markov_sequence_generator::MarkovSequenceGenerator,
-    parameter::{DynVal, ParameterValue},
+    parameter::{modifier::EvalContext, DynVal, ParameterValue},
     pfa_growth::*,
     pfa_reverse::*,
 };
@@ -190,9 +190,10 @@ pub fn shake_raw(
 }
 
 pub fn skip_raw(gen: &mut MarkovSequenceGenerator, times: usize) {
+    // skipped events aren't played, so their time doesn't matter
     for _ in 0..times {
-        gen.current_events();
-        gen.current_transition();
+        gen.current_events(&EvalContext::default());
+        gen.current_transition(&EvalContext::default());
     }
 }
 
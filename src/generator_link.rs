@@ -0,0 +1,395 @@
+use parking_lot::Mutex;
+use std::collections::{BTreeSet, HashMap};
+use std::sync;
+
+use crate::event::StaticEvent;
+use crate::session::SyncContext;
+
+/// What makes a link fire.
+#[derive(Clone, PartialEq)]
+pub enum LinkTrigger {
+    Tag(String),   // an event with this tag (or name) is played
+    State(String), // the source generator enters this pfa state
+}
+
+/// What a link does once it fires.
+#[derive(Clone)]
+pub enum LinkAction {
+    Advance(String),    // step the named generator
+    Start(SyncContext), // start a context
+}
+
+impl LinkAction {
+    fn target_name(&self) -> &str {
+        match self {
+            LinkAction::Advance(name) => name,
+            LinkAction::Start(ctx) => &ctx.name,
+        }
+    }
+}
+
+/// A reactive link between a running generator and another
+/// generator or context.
+#[derive(Clone)]
+pub struct GeneratorLink {
+    pub source: String,
+    pub trigger: LinkTrigger,
+    pub actions: Vec<LinkAction>,
+    // advanced generators don't move on their own
+    pub follow: bool,
+}
+
+impl GeneratorLink {
+    pub fn is_source(&self, id_tags: &BTreeSet<String>) -> bool {
+        id_tags.contains(&self.source)
+    }
+
+    /// check whether a generator is only moved by this link
+    pub fn is_followed_by(&self, id_tags: &BTreeSet<String>) -> bool {
+        self.follow
+            && self.actions.iter().any(|a| match a {
+                LinkAction::Advance(name) => id_tags.contains(name),
+                _ => false,
+            })
+    }
+
+    /// check whether the link fires for the current state and
+    /// the events that are played
+    pub fn fires(&self, state: Option<char>, events: &[&StaticEvent]) -> bool {
+        match &self.trigger {
+            LinkTrigger::Tag(tag) => events
+                .iter()
+                .any(|ev| ev.name == *tag || ev.tags.contains(tag)),
+            LinkTrigger::State(label) => {
+                matches!(state, Some(cur) if cur.encode_utf8(&mut [0; 4]) == label)
+            }
+        }
+    }
+
+    fn advanced(&self) -> impl Iterator<Item = &String> {
+        self.actions.iter().filter_map(|a| match a {
+            LinkAction::Advance(name) => Some(name),
+            _ => None,
+        })
+    }
+
+    // two links are the same if they connect the same things the same way,
+    // in which case re-evaluating replaces the old one
+    fn same_as(&self, other: &GeneratorLink) -> bool {
+        self.source == other.source
+            && self.trigger == other.trigger
+            && self.actions.len() == other.actions.len()
+            && self
+                .actions
+                .iter()
+                .zip(other.actions.iter())
+                .all(|(a, b)| a.target_name() == b.target_name())
+    }
+}
+
+// how many advances are kept for a generator that doesn't pick them up,
+// i.e. because it isn't running
+const MAX_PENDING_ADVANCES: usize = 16;
+
+/// The links, and the advances that are waiting for the
+/// linked generators to pick them up.
+#[derive(Default)]
+pub struct Links {
+    pub links: Vec<GeneratorLink>,
+    // generator name (or tag) and the numbered times to play at,
+    // numbered so that every generator with that name picks them up once
+    pending: HashMap<String, Vec<(u64, f64)>>,
+    num_advances: u64,
+}
+
+pub type LinkStore = sync::Arc<Mutex<Links>>;
+
+/// Add a link, unless it would close a loop of generators
+/// advancing each other.
+pub fn add_link(links: &LinkStore, link: GeneratorLink) -> Result<(), String> {
+    let mut links = links.lock();
+    links.links.retain(|l| !l.same_as(&link));
+
+    // follow the advances from the link's targets, if they
+    // lead back to the source, there's a cycle
+    let mut visited = BTreeSet::new();
+    let mut to_visit: Vec<String> = link.advanced().cloned().collect();
+    while let Some(name) = to_visit.pop() {
+        if name == link.source {
+            return Err(format!(
+                "can't link {}, generators would advance each other in a loop",
+                link.source
+            ));
+        }
+        if visited.insert(name.clone()) {
+            for l in links.links.iter().filter(|l| l.source == name) {
+                to_visit.extend(l.advanced().cloned());
+            }
+        }
+    }
+
+    links.links.push(link);
+    Ok(())
+}
+
+/// remove all links from a source generator
+pub fn remove_links(links: &LinkStore, source: &str) {
+    links.lock().links.retain(|l| l.source != source);
+}
+
+/// Fire the links of a source generator. Advances are queued for the
+/// linked generators, which pick them up in their own scheduler loop,
+/// the contexts to start are returned.
+pub fn fire_links(
+    links: &LinkStore,
+    id_tags: &BTreeSet<String>,
+    state: Option<char>,
+    events: &[&StaticEvent],
+    time: f64,
+) -> Vec<SyncContext> {
+    let mut links = links.lock();
+    let mut advance = Vec::new();
+    let mut start = Vec::new();
+    for link in links
+        .links
+        .iter()
+        .filter(|l| l.is_source(id_tags) && l.fires(state, events))
+    {
+        for action in link.actions.iter() {
+            match action {
+                LinkAction::Advance(name) => advance.push(name.clone()),
+                LinkAction::Start(ctx) => start.push(ctx.clone()),
+            }
+        }
+    }
+    for name in advance {
+        links.num_advances += 1;
+        let num = links.num_advances;
+        let pending = links.pending.entry(name).or_default();
+        if pending.len() == MAX_PENDING_ADVANCES {
+            pending.remove(0);
+        }
+        pending.push((num, time));
+    }
+    start
+}
+
+/// The times a generator has been advanced at since it last looked.
+/// The advances stay around for the other generators with the same
+/// name or tag, the generator keeps track of the last one it has seen.
+pub fn take_advances(links: &LinkStore, id_tags: &BTreeSet<String>, seen: &mut u64) -> Vec<f64> {
+    let links = links.lock();
+    let mut advances: Vec<(u64, f64)> = id_tags
+        .iter()
+        .filter_map(|tag| links.pending.get(tag))
+        .flatten()
+        .filter(|(num, _)| *num > *seen)
+        .copied()
+        .collect();
+    advances.sort_by_key(|(num, _)| *num);
+    if let Some((num, _)) = advances.last() {
+        *seen = *num;
+    }
+    advances.into_iter().map(|(_, time)| time).collect()
+}
+
+/// whether a generator is only moved by a link
+pub fn is_follower(links: &LinkStore, id_tags: &BTreeSet<String>) -> bool {
+    links.lock().links.iter().any(|l| l.is_followed_by(id_tags))
+}
+
+/// remove all links and pending advances
+/// (the advances keep counting, so what generators have seen stays valid)
+pub fn clear_links(links: &LinkStore) {
+    let mut links = links.lock();
+    links.links.clear();
+    links.pending.clear();
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::parameter::DynVal;
+    use crate::parser::eval::constructors::arp::step_generator;
+
+    fn advance(source: &str, trigger: LinkTrigger, target: &str) -> GeneratorLink {
+        GeneratorLink {
+            source: source.to_string(),
+            trigger,
+            actions: vec![LinkAction::Advance(target.to_string())],
+            follow: true,
+        }
+    }
+
+    fn tags(t: &[&str]) -> BTreeSet<String> {
+        t.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_link_store() {
+        let links: LinkStore = sync::Arc::new(Mutex::new(Links::default()));
+        add_link(
+            &links,
+            advance("drums", LinkTrigger::Tag("bd".to_string()), "bass"),
+        )
+        .unwrap();
+        add_link(
+            &links,
+            advance("drums", LinkTrigger::Tag("bd".to_string()), "bass"),
+        )
+        .unwrap();
+        add_link(
+            &links,
+            advance("drums", LinkTrigger::State("x".to_string()), "lead"),
+        )
+        .unwrap();
+        assert_eq!(links.lock().links.len(), 2);
+
+        assert!(links.lock().links[0].is_followed_by(&tags(&["bass"])));
+        assert!(is_follower(&links, &tags(&["bass"])));
+        assert!(!is_follower(&links, &tags(&["drums"])));
+
+        let link = &links.lock().links[1];
+        assert!(link.is_source(&tags(&["drums"])));
+        assert!(link.fires(Some('x'), &[]));
+        assert!(!link.fires(Some('y'), &[]));
+        // the whole label has to match
+        assert!(
+            !advance("drums", LinkTrigger::State("xy".to_string()), "lead").fires(Some('x'), &[])
+        );
+
+        remove_links(&links, "drums");
+        assert!(links.lock().links.is_empty());
+    }
+
+    #[test]
+    fn test_link_cycles() {
+        let links: LinkStore = sync::Arc::new(Mutex::new(Links::default()));
+        let on_bd = || LinkTrigger::Tag("bd".to_string());
+        add_link(&links, advance("drums", on_bd(), "bass")).unwrap();
+        add_link(&links, advance("bass", on_bd(), "lead")).unwrap();
+        assert!(add_link(&links, advance("lead", on_bd(), "drums")).is_err());
+        assert!(add_link(&links, advance("lead", on_bd(), "lead")).is_err());
+        // not a cycle, just two links to the same generator
+        add_link(&links, advance("drums", on_bd(), "lead")).unwrap();
+        assert_eq!(links.lock().links.len(), 3);
+    }
+
+    #[test]
+    fn test_fire_advance() {
+        let links: LinkStore = sync::Arc::new(Mutex::new(Links::default()));
+        add_link(
+            &links,
+            advance("drums", LinkTrigger::State("x".to_string()), "bass"),
+        )
+        .unwrap();
+
+        // the advance waits for the linked generator ...
+        let mut seen = 0;
+        assert!(fire_links(&links, &tags(&["drums"]), Some('x'), &[], 1.5).is_empty());
+        fire_links(&links, &tags(&["drums"]), Some('y'), &[], 2.0);
+        fire_links(&links, &tags(&["drums"]), Some('x'), &[], 2.5);
+        assert_eq!(
+            take_advances(&links, &tags(&["bass", "ctx"]), &mut seen),
+            vec![1.5, 2.5]
+        );
+        // ... which picks it up only once
+        assert!(take_advances(&links, &tags(&["bass"]), &mut seen).is_empty());
+
+        // generators that never pick them up don't pile them up
+        let mut seen = 0;
+        for i in 0..100 {
+            fire_links(&links, &tags(&["drums"]), Some('x'), &[], i as f64);
+        }
+        assert_eq!(
+            take_advances(&links, &tags(&["bass"]), &mut seen).len(),
+            MAX_PENDING_ADVANCES
+        );
+    }
+
+    #[test]
+    fn test_fire_advance_shared_tag() {
+        let links: LinkStore = sync::Arc::new(Mutex::new(Links::default()));
+        // advance a whole context
+        add_link(
+            &links,
+            advance("drums", LinkTrigger::State("x".to_string()), "ctx"),
+        )
+        .unwrap();
+        fire_links(&links, &tags(&["drums"]), Some('x'), &[], 1.0);
+
+        // every generator in the context gets the advance, once
+        let mut seen_bass = 0;
+        let mut seen_lead = 0;
+        assert_eq!(
+            take_advances(&links, &tags(&["bass", "ctx"]), &mut seen_bass),
+            vec![1.0]
+        );
+        assert_eq!(
+            take_advances(&links, &tags(&["lead", "ctx"]), &mut seen_lead),
+            vec![1.0]
+        );
+        assert!(take_advances(&links, &tags(&["bass", "ctx"]), &mut seen_bass).is_empty());
+
+        fire_links(&links, &tags(&["drums"]), Some('x'), &[], 2.0);
+        assert_eq!(
+            take_advances(&links, &tags(&["lead", "ctx"]), &mut seen_lead),
+            vec![2.0]
+        );
+
+        // the bass hasn't seen the second one yet, but after clearing,
+        // only the advances fired from then on count
+        clear_links(&links);
+        assert!(take_advances(&links, &tags(&["bass", "ctx"]), &mut seen_bass).is_empty());
+        add_link(
+            &links,
+            advance("drums", LinkTrigger::State("x".to_string()), "ctx"),
+        )
+        .unwrap();
+        fire_links(&links, &tags(&["drums"]), Some('x'), &[], 3.0);
+        assert_eq!(
+            take_advances(&links, &tags(&["bass", "ctx"]), &mut seen_bass),
+            vec![3.0]
+        );
+    }
+
+    #[test]
+    fn test_fire_start() {
+        let links: LinkStore = sync::Arc::new(Mutex::new(Links::default()));
+        let ctx = SyncContext {
+            name: "answer".to_string(),
+            generators: vec![step_generator(
+                "answer".to_string(),
+                vec![Vec::new()],
+                DynVal::with_value(100.0),
+                false,
+            )],
+            part_proxies: Vec::new(),
+            sync_to: None,
+            active: true,
+            shift: 0,
+            block_tags: BTreeSet::new(),
+            solo_tags: BTreeSet::new(),
+        };
+        add_link(
+            &links,
+            GeneratorLink {
+                source: "drums".to_string(),
+                trigger: LinkTrigger::State("x".to_string()),
+                actions: vec![LinkAction::Start(ctx)],
+                follow: true,
+            },
+        )
+        .unwrap();
+
+        assert!(fire_links(&links, &tags(&["drums"]), Some('y'), &[], 0.0).is_empty());
+        let started = fire_links(&links, &tags(&["drums"]), Some('x'), &[], 0.0);
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].name, "answer");
+        assert!(started[0].generators[0].id_tags.contains("answer"));
+        // starting doesn't advance anything
+        assert!(take_advances(&links, &tags(&["answer"]), &mut 0).is_empty());
+    }
+}
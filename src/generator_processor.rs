@@ -9,12 +9,14 @@ use crate::{
     event::{Event, InterpretableEvent, StaticEvent},
     generator::GenModFun,
     generator::Generator,
-    parameter::DynVal,
+    music_theory::Scale,
+    parameter::{modifier::EvalContext, DynVal},
 };
 
 pub enum GeneratorProcessorState {
     Count(usize),
     WrappedGenerator(Generator),
+    Lifemodel(usize, f32), // step count, local resources
     None,
 }
 
@@ -22,11 +24,13 @@ pub enum GeneratorProcessorState {
 /// a subset of the methods available ...
 pub trait GeneratorProcessor: GeneratorProcessorClone {
     /// implement this if you want to modify the previous
-    /// processor's event stream
+    /// processor's event stream (the context holds the logical
+    /// time of the events and the generator's scale)
     fn process_events(
         &mut self,
         _events: &mut Vec<InterpretableEvent>,
         _global_parameters: &Arc<GlobalParameters>,
+        _ctx: &EvalContext,
     ) {
         /* pass by default */
     }
@@ -45,10 +49,17 @@ pub trait GeneratorProcessor: GeneratorProcessorClone {
         &mut self,
         _transition: &mut StaticEvent,
         _global_parameters: &Arc<GlobalParameters>,
+        _ctx: &EvalContext,
     ) {
         /* pass by default */
     }
 
+    /// implement this if the processor sets the scale
+    /// the generator's pitches are resolved against
+    fn scale(&self) -> Option<Arc<Scale>> {
+        None
+    }
+
     /// implement this if the processor has a state, such as a step
     /// counter
     fn set_state(&mut self, _: GeneratorProcessorState) {
@@ -62,6 +73,18 @@ pub trait GeneratorProcessor: GeneratorProcessorClone {
         GeneratorProcessorState::None
     }
 
+    /// implement this if the processor has a state, so that the state
+    /// can find the matching processor after re-evaluation, no matter
+    /// where it ends up in the processor list
+    fn set_id(&mut self, _: String) {
+        /* stateless processors don't need an identity */
+    }
+
+    /// the identity set above
+    fn get_id(&self) -> &str {
+        ""
+    }
+
     /// if the processor holds something that can be visualized
     /// such as a markov chain ...
     fn visualize_if_possible(&mut self, _vis_client: &sync::Arc<VisualizerClient>) {
@@ -115,3 +138,9 @@ pub use lifemodel_processor::*;
 
 mod generator_wrapper_processor;
 pub use generator_wrapper_processor::*;
+
+mod scale_processor;
+pub use scale_processor::*;
+
+mod when_processor;
+pub use when_processor::*;
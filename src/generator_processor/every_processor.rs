@@ -5,19 +5,62 @@ use crate::{
     event::{InterpretableEvent, StaticEvent},
     generator::Generator,
     generator_processor::*,
+    parameter::{modifier::EvalContext, DynVal},
 };
 
+/// When an every-context applies: every n steps (shifted by an offset),
+/// or, if there is one, following a pattern of steps.
+#[derive(Clone)]
+pub struct EveryCondition {
+    pub n: DynVal,
+    pub offset: usize,
+    pub pattern: Vec<bool>,
+}
+
+impl EveryCondition {
+    pub fn with_n(n: DynVal) -> Self {
+        EveryCondition {
+            n,
+            offset: 0,
+            pattern: Vec::new(),
+        }
+    }
+
+    pub fn with_pattern(pattern: Vec<bool>) -> Self {
+        EveryCondition {
+            n: DynVal::with_value(1.0),
+            offset: 0,
+            pattern,
+        }
+    }
+
+    /// check the step count (which starts at 1) against the
+    /// pattern or the current interval
+    pub fn applies(&self, n: usize, step_count: usize) -> bool {
+        if !self.pattern.is_empty() {
+            let len = self.pattern.len();
+            let idx = (step_count + len - 1 + len - self.offset % len) % len;
+            self.pattern[idx]
+        } else {
+            let n = n.max(1);
+            (step_count + n - self.offset % n) % n == 0
+        }
+    }
+}
+
 /// Apple-ys events to the throughcoming ones
 #[derive(Clone)]
 pub struct EveryProcessor {
+    pub id: String,
     pub step_count: usize,
-    pub things_to_be_applied: Vec<(DynVal, EventsAndFilters, GenModFunsAndArgs)>,
-    pub last_static: Vec<(usize, StaticEventsAndFilters)>, // only needed for events, not filters
+    pub things_to_be_applied: Vec<(EveryCondition, EventsAndFilters, GenModFunsAndArgs)>,
+    pub last_static: Vec<(usize, usize, StaticEventsAndFilters)>, // only needed for events, not filters
 }
 
 impl EveryProcessor {
     pub fn new() -> Self {
         EveryProcessor {
+            id: String::new(),
             step_count: 1,
             things_to_be_applied: Vec::new(),
             last_static: Vec::new(),
@@ -36,18 +79,31 @@ impl GeneratorProcessor for EveryProcessor {
         GeneratorProcessorState::Count(self.step_count)
     }
 
+    fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
+
+    fn get_id(&self) -> &str {
+        &self.id
+    }
+
     // this one
-    fn process_events(&mut self, events: &mut Vec<InterpretableEvent>, _: &Arc<GlobalParameters>) {
+    fn process_events(
+        &mut self,
+        events: &mut Vec<InterpretableEvent>,
+        _: &Arc<GlobalParameters>,
+        ctx: &EvalContext,
+    ) {
         self.last_static.clear();
-        for (step, filtered_events, _) in self.things_to_be_applied.iter_mut() {
+        for (idx, (cond, filtered_events, _)) in self.things_to_be_applied.iter_mut().enumerate() {
             // genmodfuns not needed here ...
-            let cur_step: usize = (step.evaluate_numerical() as usize) % 101; // make sure prob is always between 0 and 100
-            if self.step_count % cur_step == 0 {
+            let cur_step: usize = (cond.n.evaluate_numerical_in(ctx) as usize) % 101; // make sure prob is always between 0 and 100
+            if cond.applies(cur_step, self.step_count) {
                 let mut stat_evs = HashMap::new();
                 for (filter, (mode, evs)) in filtered_events.iter_mut() {
                     let mut evs_static = Vec::new();
                     for ev in evs.iter_mut() {
-                        let ev_static = ev.get_static();
+                        let ev_static = ev.get_static(ctx);
                         for in_ev in events.iter_mut() {
                             match in_ev {
                                 InterpretableEvent::Sound(s) => {
@@ -62,16 +118,16 @@ impl GeneratorProcessor for EveryProcessor {
                     }
                     stat_evs.insert(filter.to_vec(), evs_static);
                 }
-                self.last_static.push((cur_step, stat_evs));
+                self.last_static.push((idx, cur_step, stat_evs));
             }
         }
     }
 
     fn process_generator(&mut self, gen: &mut Generator, _: &Arc<GlobalParameters>) {
-        for (step, _, gen_mods) in self.things_to_be_applied.iter_mut() {
+        for (cond, _, gen_mods) in self.things_to_be_applied.iter_mut() {
             // genmodfuns not needed here ...
-            let cur_step: usize = (step.static_val as usize) % 101;
-            if self.step_count % cur_step == 0 {
+            let cur_step: usize = (cond.n.static_val as usize) % 101;
+            if cond.applies(cur_step, self.step_count) {
                 for (gen_mod_fun, pos_args, named_args) in gen_mods.iter() {
                     gen_mod_fun(gen, pos_args, named_args)
                 }
@@ -81,9 +137,17 @@ impl GeneratorProcessor for EveryProcessor {
         self.step_count += 1;
     }
 
-    fn process_transition(&mut self, trans: &mut StaticEvent, _: &Arc<GlobalParameters>) {
-        for (cur_step, filtered_events) in self.last_static.iter() {
-            if self.step_count % cur_step == 0 {
+    fn process_transition(
+        &mut self,
+        trans: &mut StaticEvent,
+        _: &Arc<GlobalParameters>,
+        _: &EvalContext,
+    ) {
+        for (idx, cur_step, filtered_events) in self.last_static.iter() {
+            if self.things_to_be_applied[*idx]
+                .0
+                .applies(*cur_step, self.step_count)
+            {
                 for (filter, evs) in filtered_events.iter() {
                     for ev in evs.iter() {
                         trans.apply(ev, filter, true); // not sure
@@ -101,3 +165,29 @@ impl GeneratorProcessor for EveryProcessor {
         // pass
     }
 }
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_every_condition() {
+        let mut cond = EveryCondition::with_n(DynVal::with_value(4.0));
+        let steps: Vec<usize> = (1..13).filter(|s| cond.applies(4, *s)).collect();
+        assert_eq!(steps, vec![4, 8, 12]);
+
+        cond.offset = 1;
+        let steps: Vec<usize> = (1..13).filter(|s| cond.applies(4, *s)).collect();
+        assert_eq!(steps, vec![1, 5, 9]);
+
+        let mut cond = EveryCondition::with_pattern(vec![true, false, false, true]);
+        let steps: Vec<usize> = (1..9).filter(|s| cond.applies(1, *s)).collect();
+        assert_eq!(steps, vec![1, 4, 5, 8]);
+
+        cond.offset = 1;
+        let steps: Vec<usize> = (1..9).filter(|s| cond.applies(1, *s)).collect();
+        assert_eq!(steps, vec![1, 2, 5, 6]);
+    }
+}
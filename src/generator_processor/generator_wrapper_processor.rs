@@ -5,11 +5,13 @@ use crate::{
     event::{InterpretableEvent, StaticEvent},
     generator::Generator,
     generator_processor::*,
+    parameter::modifier::EvalContext,
 };
 
 /// Apple-ys events to the throughcoming ones
 #[derive(Clone)]
 pub struct GeneratorWrapperProcessor {
+    id: String,
     wrapped_generator: Generator,
     current_events: Vec<InterpretableEvent>,
     filter: Vec<String>,
@@ -17,7 +19,17 @@ pub struct GeneratorWrapperProcessor {
 
 impl GeneratorWrapperProcessor {
     pub fn with_generator(gen: Generator) -> Self {
+        // the wrapped generator's name makes a good identity
+        let id = format!(
+            "wrap-{}",
+            gen.id_tags
+                .iter()
+                .cloned()
+                .collect::<Vec<String>>()
+                .join("-")
+        );
         GeneratorWrapperProcessor {
+            id,
             wrapped_generator: gen,
             current_events: Vec::new(),
             filter: vec!["".to_string()],
@@ -37,11 +49,20 @@ impl GeneratorProcessor for GeneratorWrapperProcessor {
         GeneratorProcessorState::WrappedGenerator(self.wrapped_generator.clone())
     }
 
+    fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
+
+    fn get_id(&self) -> &str {
+        &self.id
+    }
+
     // another pure event-stream processor
     fn process_events(
         &mut self,
         events: &mut Vec<InterpretableEvent>,
         _glob: &Arc<GlobalParameters>,
+        _: &EvalContext,
     ) {
         for ev in self.current_events.iter_mut() {
             if let InterpretableEvent::Sound(sev) = ev {
@@ -60,12 +81,18 @@ impl GeneratorProcessor for GeneratorWrapperProcessor {
         }
     }
 
-    fn process_transition(&mut self, trans: &mut StaticEvent, glob: &Arc<GlobalParameters>) {
-        self.wrapped_generator.current_transition(glob);
+    fn process_transition(
+        &mut self,
+        trans: &mut StaticEvent,
+        glob: &Arc<GlobalParameters>,
+        ctx: &EvalContext,
+    ) {
+        // the wrapped generator has its own scale, if any
+        self.wrapped_generator.current_transition(glob, ctx.time);
 
         // already get current events here so we have the same execution
         // order and still can properly process the first transition
-        self.current_events = self.wrapped_generator.current_events(glob);
+        self.current_events = self.wrapped_generator.current_events(glob, ctx.time);
 
         for ev in self.current_events.iter_mut() {
             if let InterpretableEvent::Sound(sev) = ev {
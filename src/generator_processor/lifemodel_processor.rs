@@ -33,6 +33,7 @@ impl LifemodelDefaults {
 /// Apple-ys events to the throughcoming ones
 #[derive(Clone)]
 pub struct LifemodelProcessor {
+    pub id: String,
     pub step_count: usize,
     pub growth_cycle: usize,
     pub growth_method: String,
@@ -57,6 +58,7 @@ pub struct LifemodelProcessor {
 impl LifemodelProcessor {
     pub fn new() -> Self {
         LifemodelProcessor {
+            id: String::new(),
             step_count: 0,
             growth_cycle: LifemodelDefaults::GROWTH_CYCLE,
             growth_method: LifemodelDefaults::GROWTH_METHOD.to_string(),
@@ -81,7 +83,24 @@ impl LifemodelProcessor {
 }
 
 impl GeneratorProcessor for LifemodelProcessor {
-    // I'm a bit surprises this one's stateless ...
+    fn set_state(&mut self, other: GeneratorProcessorState) {
+        if let GeneratorProcessorState::Lifemodel(c, r) = other {
+            self.step_count = c;
+            self.local_resources = r;
+        }
+    }
+
+    fn get_state(&self) -> GeneratorProcessorState {
+        GeneratorProcessorState::Lifemodel(self.step_count, self.local_resources)
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
+
+    fn get_id(&self) -> &str {
+        &self.id
+    }
 
     // this one only processes the generators ...
     fn process_generator(
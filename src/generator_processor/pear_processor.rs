@@ -6,7 +6,7 @@ use crate::{
     builtin_types::GlobalParameters,
     event::{InterpretableEvent, StaticEvent},
     generator_processor::*,
-    parameter::DynVal,
+    parameter::{modifier::EvalContext, DynVal},
 };
 
 /// Apple-ys events to the throughcoming ones
@@ -28,19 +28,24 @@ impl PearProcessor {
 // zip mode etc seem to be outdated ... going for any mode for now
 impl GeneratorProcessor for PearProcessor {
     // this one only processes the event stream ...
-    fn process_events(&mut self, events: &mut Vec<InterpretableEvent>, _: &Arc<GlobalParameters>) {
+    fn process_events(
+        &mut self,
+        events: &mut Vec<InterpretableEvent>,
+        _: &Arc<GlobalParameters>,
+        ctx: &EvalContext,
+    ) {
         self.last_static.clear();
         let mut rng = rand::thread_rng();
         // the four nested loops are intimidating but keep in mind that the
         // event count is usually very small ...
         for (prob, filtered_events) in self.events_to_be_applied.iter_mut() {
             let mut stat_evs = HashMap::new();
-            let cur_prob: usize = (prob.evaluate_numerical() as usize) % 101; // make sure prob is always between 0 and 100
-                                                                              //println!("cur p {}", cur_prob);
+            let cur_prob: usize = (prob.evaluate_numerical_in(ctx) as usize) % 101; // make sure prob is always between 0 and 100
+                                                                                    //println!("cur p {}", cur_prob);
             for (filter, (mode, evs)) in filtered_events.iter_mut() {
                 let mut evs_static = Vec::new();
                 for ev in evs.iter_mut() {
-                    let ev_static = ev.get_static();
+                    let ev_static = ev.get_static(ctx);
                     for in_ev in events.iter_mut() {
                         match in_ev {
                             InterpretableEvent::Sound(s) => {
@@ -61,7 +66,12 @@ impl GeneratorProcessor for PearProcessor {
         }
     }
     // .. including transition events
-    fn process_transition(&mut self, trans: &mut StaticEvent, _: &Arc<GlobalParameters>) {
+    fn process_transition(
+        &mut self,
+        trans: &mut StaticEvent,
+        _: &Arc<GlobalParameters>,
+        _: &EvalContext,
+    ) {
         let mut rng = rand::thread_rng();
         for (prob, filtered_events) in self.last_static.iter_mut() {
             for (filter, evs) in filtered_events.iter_mut() {
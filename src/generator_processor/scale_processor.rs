@@ -0,0 +1,24 @@
+use std::sync::*;
+
+use crate::{generator_processor::*, music_theory::Scale};
+
+/// Sets the scale a generator's `:quant` pitches and scale degrees
+/// are resolved against, instead of the global scale.
+#[derive(Clone)]
+pub struct ScaleProcessor {
+    pub scale: Arc<Scale>,
+}
+
+impl ScaleProcessor {
+    pub fn new(scale: Scale) -> Self {
+        ScaleProcessor {
+            scale: Arc::new(scale),
+        }
+    }
+}
+
+impl GeneratorProcessor for ScaleProcessor {
+    fn scale(&self) -> Option<Arc<Scale>> {
+        Some(Arc::clone(&self.scale))
+    }
+}
@@ -0,0 +1,228 @@
+use std::sync::*;
+
+use crate::{
+    builtin_types::{BuiltinGlobalParameters, ConfigParameter, GlobalParameters},
+    event::{InterpretableEvent, StaticEvent},
+    generator::Generator,
+    generator_processor::*,
+    parameter::modifier::{clock::Clock, EvalContext},
+};
+
+/// how a global value is compared
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Comparison {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "gt" => Some(Comparison::Greater),
+            "ge" | "gte" => Some(Comparison::GreaterOrEqual),
+            "lt" => Some(Comparison::Less),
+            "le" | "lte" => Some(Comparison::LessOrEqual),
+            "eq" => Some(Comparison::Equal),
+            "ne" | "neq" => Some(Comparison::NotEqual),
+            _ => None,
+        }
+    }
+
+    pub fn compare(&self, a: f32, b: f32) -> bool {
+        match self {
+            Comparison::Greater => a > b,
+            Comparison::GreaterOrEqual => a >= b,
+            Comparison::Less => a < b,
+            Comparison::LessOrEqual => a <= b,
+            Comparison::Equal => (a - b).abs() < f32::EPSILON,
+            Comparison::NotEqual => (a - b).abs() >= f32::EPSILON,
+        }
+    }
+}
+
+/// A condition for the when processor. Step counts start at 1.
+#[derive(Clone, PartialEq, Debug)]
+pub enum WhenCondition {
+    After(usize),          // after the first n steps
+    Between(usize, usize), // after step a, up to and including step b
+    First(usize),          // only the first n steps
+    BarPhase(f32, f32),    // position within a bar of four beats, from 0 to 1
+    Global(String, Comparison, f32),
+}
+
+impl WhenCondition {
+    /// Whether the condition holds at the given step and logical time (in seconds).
+    pub fn holds(
+        &self,
+        step_count: usize,
+        global_parameters: &Arc<GlobalParameters>,
+        time: f64,
+    ) -> bool {
+        match self {
+            WhenCondition::After(n) => step_count > *n,
+            WhenCondition::Between(a, b) => step_count > *a && step_count <= *b,
+            WhenCondition::First(n) => step_count <= *n,
+            WhenCondition::BarPhase(from, to) => {
+                let phase =
+                    Clock::Beats(Arc::clone(global_parameters)).position(0.0, 4.0, time) / 4.0;
+                phase >= *from && phase < *to
+            }
+            WhenCondition::Global(name, cmp, val) => {
+                if let Some(r) =
+                    global_parameters.get(&BuiltinGlobalParameters::UserDefined(name.clone()))
+                {
+                    if let ConfigParameter::Numeric(g) = r.value() {
+                        return cmp.compare(*g, *val);
+                    }
+                }
+                // unset globals never hold
+                false
+            }
+        }
+    }
+}
+
+/// Applies events and generator modifiers if all conditions
+/// of a context hold.
+#[derive(Clone)]
+pub struct WhenProcessor {
+    pub id: String,
+    pub step_count: usize,
+    pub things_to_be_applied: Vec<(Vec<WhenCondition>, EventsAndFilters, GenModFunsAndArgs)>,
+    pub active: Vec<bool>, // evaluated once per step, so events, generator and transition agree
+    pub last_static: Vec<StaticEventsAndFilters>,
+}
+
+impl WhenProcessor {
+    pub fn new() -> Self {
+        WhenProcessor {
+            id: String::new(),
+            step_count: 1,
+            things_to_be_applied: Vec::new(),
+            active: Vec::new(),
+            last_static: Vec::new(),
+        }
+    }
+}
+
+impl GeneratorProcessor for WhenProcessor {
+    fn set_state(&mut self, other: GeneratorProcessorState) {
+        if let GeneratorProcessorState::Count(c) = other {
+            self.step_count = c;
+        }
+    }
+
+    fn get_state(&self) -> GeneratorProcessorState {
+        GeneratorProcessorState::Count(self.step_count)
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
+
+    fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    fn process_events(
+        &mut self,
+        events: &mut Vec<InterpretableEvent>,
+        global_parameters: &Arc<GlobalParameters>,
+        ctx: &EvalContext,
+    ) {
+        self.last_static.clear();
+        self.active.clear();
+        for (conditions, filtered_events, _) in self.things_to_be_applied.iter_mut() {
+            let active = conditions
+                .iter()
+                .all(|c| c.holds(self.step_count, global_parameters, ctx.time));
+            self.active.push(active);
+            if active {
+                let mut stat_evs = HashMap::new();
+                for (filter, (mode, evs)) in filtered_events.iter_mut() {
+                    let mut evs_static = Vec::new();
+                    for ev in evs.iter_mut() {
+                        let ev_static = ev.get_static(ctx);
+                        for in_ev in events.iter_mut() {
+                            if let InterpretableEvent::Sound(s) = in_ev {
+                                s.apply(&ev_static, filter, *mode);
+                            }
+                        }
+                        evs_static.push(ev_static);
+                    }
+                    stat_evs.insert(filter.to_vec(), evs_static);
+                }
+                self.last_static.push(stat_evs);
+            }
+        }
+    }
+
+    fn process_generator(&mut self, gen: &mut Generator, _: &Arc<GlobalParameters>) {
+        for ((_, _, gen_mods), active) in self.things_to_be_applied.iter().zip(self.active.iter()) {
+            if *active {
+                for (gen_mod_fun, pos_args, named_args) in gen_mods.iter() {
+                    gen_mod_fun(gen, pos_args, named_args)
+                }
+            }
+        }
+        self.step_count += 1;
+    }
+
+    fn process_transition(
+        &mut self,
+        trans: &mut StaticEvent,
+        _: &Arc<GlobalParameters>,
+        _: &EvalContext,
+    ) {
+        for filtered_events in self.last_static.iter() {
+            for (filter, evs) in filtered_events.iter() {
+                for ev in evs.iter() {
+                    trans.apply(ev, filter, true);
+                }
+            }
+        }
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_when_conditions() {
+        let globals = Arc::new(GlobalParameters::new());
+
+        assert!(!WhenCondition::After(64).holds(64, &globals, 0.0));
+        assert!(WhenCondition::After(64).holds(65, &globals, 0.0));
+        assert!(WhenCondition::First(8).holds(8, &globals, 0.0));
+        assert!(!WhenCondition::First(8).holds(9, &globals, 0.0));
+        assert!(!WhenCondition::Between(32, 64).holds(32, &globals, 0.0));
+        assert!(WhenCondition::Between(32, 64).holds(64, &globals, 0.0));
+
+        let dense = WhenCondition::Global("density".to_string(), Comparison::Greater, 0.5);
+        assert!(!dense.holds(1, &globals, 0.0));
+        globals.insert(
+            BuiltinGlobalParameters::UserDefined("density".to_string()),
+            ConfigParameter::Numeric(0.7),
+        );
+        assert!(dense.holds(1, &globals, 0.0));
+
+        // the full bar always matches
+        assert!(WhenCondition::BarPhase(0.0, 1.0).holds(1, &globals, 0.0));
+
+        // the phase follows the logical time, a bar is two seconds at 500ms per beat
+        globals.insert(
+            BuiltinGlobalParameters::DefaultDuration,
+            ConfigParameter::Numeric(500.0),
+        );
+        let second_half = WhenCondition::BarPhase(0.5, 1.0);
+        assert!(!second_half.holds(1, &globals, 10.5));
+        assert!(second_half.holds(1, &globals, 11.0));
+    }
+}
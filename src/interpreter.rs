@@ -26,6 +26,9 @@ pub fn interpret_command<const BUFSIZE: usize, const NCHAN: usize>(
 ) {
     match c {
         Command::Clear => {
+            if let Some(sc) = &session.lock().stream_control {
+                sc.stop_all();
+            }
             let session2 = sync::Arc::clone(session);
             let parts_store2 = sync::Arc::clone(parts_store);
             thread::spawn(move || {
@@ -41,13 +44,22 @@ pub fn interpret_command<const BUFSIZE: usize, const NCHAN: usize>(
                 println!("visualizer already connected !");
             }
         }
-        Command::StartRecording(prefix, rec_input) => {
-            commands::start_recording(session, prefix, base_dir, rec_input);
+        Command::StartRecording(prefix, options) => {
+            commands::start_recording(session, prefix, base_dir, options);
         }
         Command::StopRecording => {
             commands::stop_recording(session);
         }
-        Command::LoadSample(set, mut keywords, path, downmix_stereo) => {
+        Command::Link(l) => {
+            commands::link(session, l);
+        }
+        Command::Unlink(name) => {
+            commands::unlink(session, &name);
+        }
+        Command::SaveLast(seconds, prefix) => {
+            commands::save_last(session, seconds, prefix, base_dir);
+        }
+        Command::LoadSample(set, mut keywords, path, downmix_stereo, max_duration) => {
             let ruffbox2 = sync::Arc::clone(ruffbox);
             let fmap2 = sync::Arc::clone(function_map);
             let sample_set2 = sync::Arc::clone(sample_set);
@@ -60,12 +72,14 @@ pub fn interpret_command<const BUFSIZE: usize, const NCHAN: usize>(
                     &mut keywords,
                     path,
                     downmix_stereo,
+                    max_duration,
                 );
                 println!("a command (load sample)");
             });
         }
         Command::LoadSampleAsWavematrix(key, path, method, matrix_size, start) => {
             let sample_set2 = sync::Arc::clone(sample_set);
+            let samplerate = ruffbox.samplerate;
             thread::spawn(move || {
                 commands::load_sample_as_wavematrix(
                     &sample_set2,
@@ -74,20 +88,44 @@ pub fn interpret_command<const BUFSIZE: usize, const NCHAN: usize>(
                     &method,
                     matrix_size,
                     start,
+                    samplerate,
                 );
                 println!("a command (load wavematrix)");
             });
         }
-        Command::LoadSampleSets(path, downmix_stereo) => {
+        Command::CaptureWavematrix(key, source, method, matrix_size, start) => {
+            let live_buffer = session.lock().live_buffer.clone();
+            let sample_set2 = sync::Arc::clone(sample_set);
+            thread::spawn(move || {
+                commands::capture_wavematrix(
+                    &live_buffer,
+                    &sample_set2,
+                    key,
+                    source,
+                    &method,
+                    matrix_size,
+                    start,
+                );
+                println!("a command (capture wavematrix)");
+            });
+        }
+        Command::LoadSampleSets(path, downmix_stereo, max_duration) => {
             let ruffbox2 = sync::Arc::clone(ruffbox);
             let fmap2 = sync::Arc::clone(function_map);
             let sample_set2 = sync::Arc::clone(sample_set);
             thread::spawn(move || {
-                commands::load_sample_sets(&fmap2, &ruffbox2, &sample_set2, path, downmix_stereo);
+                commands::load_sample_sets(
+                    &fmap2,
+                    &ruffbox2,
+                    &sample_set2,
+                    path,
+                    downmix_stereo,
+                    max_duration,
+                );
                 println!("a command (load sample sets)");
             });
         }
-        Command::LoadSampleSet(path, downmix_stereo) => {
+        Command::LoadSampleSet(path, downmix_stereo, max_duration) => {
             let ruffbox2 = sync::Arc::clone(ruffbox);
             let fmap2 = sync::Arc::clone(function_map);
             let sample_set2 = sync::Arc::clone(sample_set);
@@ -98,16 +136,30 @@ pub fn interpret_command<const BUFSIZE: usize, const NCHAN: usize>(
                     &sample_set2,
                     path,
                     downmix_stereo,
+                    max_duration,
                 );
                 println!("a command (load sample sets)");
             });
         }
+        Command::UnloadSampleSet(set) => {
+            commands::unload_sample_set(sample_set, &set);
+        }
+        Command::ReloadSampleSet(set) => {
+            let ruffbox2 = sync::Arc::clone(ruffbox);
+            let fmap2 = sync::Arc::clone(function_map);
+            let sample_set2 = sync::Arc::clone(sample_set);
+            thread::spawn(move || {
+                commands::reload_sample_set(&fmap2, &ruffbox2, &sample_set2, set);
+                println!("a command (reload sample set)");
+            });
+        }
         Command::LoadPart((name, part)) => {
             commands::load_part(parts_store, name, part);
             println!("a command (load part)");
         }
         Command::FreezeBuffer(freezbuf, inbuf) => {
-            commands::freeze_buffer(ruffbox, freezbuf, inbuf);
+            let live_buffer = session.lock().live_buffer.clone();
+            commands::freeze_buffer(ruffbox, &live_buffer, freezbuf, inbuf);
             println!("freeze buffer");
         }
         Command::Tmod(p) => {
@@ -122,9 +174,21 @@ pub fn interpret_command<const BUFSIZE: usize, const NCHAN: usize>(
         Command::Bpm(b) => {
             commands::set_default_duration(global_parameters, b);
         }
+        Command::Scale(s) => {
+            commands::set_global_scale(global_parameters, s);
+        }
+        Command::Tuning(t) => {
+            commands::set_global_tuning(global_parameters, t);
+        }
+        Command::LoadTuning(scl, kbm, reference) => {
+            commands::load_tuning(global_parameters, &scl, kbm.as_deref(), reference);
+        }
         Command::GlobRes(v) => {
             commands::set_global_lifemodel_resources(global_parameters, v);
         }
+        Command::SetGlobal(name, v) => {
+            commands::set_global_variable(global_parameters, name, v);
+        }
         Command::GlobalRuffboxParams(mut m) => {
             commands::set_global_ruffbox_parameters(ruffbox, &mut m);
         }
@@ -191,6 +255,9 @@ pub fn interpret<const BUFSIZE: usize, const NCHAN: usize>(
         EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(_)) => {
             println!("a sound event");
         }
+        EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(_)) => {
+            println!("a list of sound events");
+        }
         EvaluatedExpr::BuiltIn(BuiltIn::ControlEvent(_)) => {
             println!("a control event");
         }
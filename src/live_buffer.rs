@@ -0,0 +1,113 @@
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+/// A copy of the live input buffers (and of what got frozen from them)
+/// on the megra side, so their content can be used for things that happen
+/// outside of ruffbox, like building wavematrices.
+/// The live part is written from the input callback without locking,
+/// so no input gets lost while somebody's reading it.
+pub struct LiveBufferMirror {
+    // samples are stored as their bit patterns, so they can be atomic
+    live: Vec<Vec<AtomicU32>>,
+    idx: AtomicUsize,
+    frozen: Mutex<Vec<Vec<f32>>>,
+}
+
+impl LiveBufferMirror {
+    pub fn new(num_buffers: usize, len: usize, num_freeze_buffers: usize) -> Self {
+        LiveBufferMirror {
+            live: (0..num_buffers)
+                .map(|_| (0..len).map(|_| AtomicU32::new(0)).collect())
+                .collect(),
+            idx: AtomicUsize::new(0),
+            frozen: Mutex::new(vec![Vec::new(); num_freeze_buffers]),
+        }
+    }
+
+    /// write one sample per channel (only ever call this from the input callback)
+    pub fn write_frame(&self, frame: &[f32]) {
+        let len = self.live.first().map(|b| b.len()).unwrap_or(0);
+        if len == 0 {
+            return;
+        }
+        let idx = self.idx.load(Ordering::Relaxed);
+        for (buf, s) in self.live.iter().zip(frame.iter()) {
+            buf[idx].store(s.to_bits(), Ordering::Relaxed);
+        }
+        self.idx.store((idx + 1) % len, Ordering::Release);
+    }
+
+    /// The content of a live buffer, oldest sample first.
+    /// As the input keeps running while this is copied, the first few
+    /// samples might already be newer than the rest.
+    pub fn contents(&self, buffer: usize) -> Option<Vec<f32>> {
+        let idx = self.idx.load(Ordering::Acquire);
+        self.live.get(buffer).map(|buf| {
+            buf[idx..]
+                .iter()
+                .chain(buf[..idx].iter())
+                .map(|s| f32::from_bits(s.load(Ordering::Relaxed)))
+                .collect()
+        })
+    }
+
+    /// keep the current content of a live buffer,
+    /// like ruffbox does when freezing it
+    pub fn freeze(&self, freeze_buffer: usize, buffer: usize) {
+        if let Some(content) = self.contents(buffer) {
+            if let Some(frozen) = self.frozen.lock().get_mut(freeze_buffer) {
+                *frozen = content;
+            }
+        }
+    }
+
+    /// the content of a freeze buffer, if anything has been frozen into it yet
+    pub fn frozen_contents(&self, freeze_buffer: usize) -> Option<Vec<f32>> {
+        self.frozen
+            .lock()
+            .get(freeze_buffer)
+            .filter(|content| !content.is_empty())
+            .cloned()
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_live_buffer_mirror() {
+        let mirror = LiveBufferMirror::new(2, 4, 1);
+        for i in 0..6 {
+            mirror.write_frame(&[i as f32, -(i as f32)]);
+        }
+
+        assert_eq!(mirror.contents(0).unwrap(), vec![2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(mirror.contents(1).unwrap(), vec![-2.0, -3.0, -4.0, -5.0]);
+        assert!(mirror.contents(2).is_none());
+    }
+
+    #[test]
+    fn test_live_buffer_mirror_freeze() {
+        let mirror = LiveBufferMirror::new(2, 4, 2);
+        for i in 0..4 {
+            mirror.write_frame(&[i as f32, -(i as f32)]);
+        }
+
+        assert!(mirror.frozen_contents(0).is_none());
+        mirror.freeze(0, 1);
+
+        // the frozen content stays, no matter what comes in later ...
+        for i in 4..8 {
+            mirror.write_frame(&[i as f32, -(i as f32)]);
+        }
+        assert_eq!(
+            mirror.frozen_contents(0).unwrap(),
+            vec![-0.0, -1.0, -2.0, -3.0]
+        );
+        assert!(mirror.frozen_contents(1).is_none());
+        assert!(mirror.frozen_contents(2).is_none());
+    }
+}
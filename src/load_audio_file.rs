@@ -63,3 +63,38 @@ pub fn load_wav(path: &str, _: f32) -> Option<(usize, f32, u32, Vec<f32>)> {
         None
     }
 }
+
+/// get the duration (in ms) of a flac or wav file from the header,
+/// without decoding the file
+pub fn file_duration(path: &str) -> Option<usize> {
+    let lc_path = path.to_lowercase();
+    if lc_path.trim().ends_with(".flac") {
+        if let Ok(reader) = claxon::FlacReader::open(path) {
+            let info = reader.streaminfo();
+            info.samples
+                .map(|samples| (1000.0 * (samples as f32 / info.sample_rate as f32)) as usize)
+        } else {
+            None
+        }
+    } else if lc_path.trim().ends_with(".wav") {
+        if let Ok(reader) = hound::WavReader::open(path) {
+            Some(((reader.duration() as f32 / reader.spec().sample_rate as f32) * 1000.0) as usize)
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+/// load a flac or wav file, depending on the extension
+pub fn load_audio_file(path: &str, samplerate: f32) -> Option<(usize, f32, u32, Vec<f32>)> {
+    let lc_path = path.to_lowercase();
+    if lc_path.trim().ends_with(".flac") {
+        load_flac(path, samplerate)
+    } else if lc_path.trim().ends_with(".wav") {
+        load_wav(path, samplerate)
+    } else {
+        None
+    }
+}
@@ -10,11 +10,15 @@ pub mod cyc_parser;
 pub mod editor;
 pub mod event;
 pub mod event_helpers;
+pub mod flac_writer;
 pub mod generator;
+pub mod generator_link;
 pub mod generator_processor;
 pub mod interpreter;
+pub mod live_buffer;
 pub mod load_audio_file;
 pub mod markov_sequence_generator;
+pub mod messages;
 pub mod midi_input;
 pub mod music_theory;
 pub mod parameter;
@@ -26,6 +30,7 @@ pub mod repl;
 pub mod sample_set;
 pub mod scheduler;
 pub mod session;
+pub mod stream_player;
 pub mod synth_parameter_value_arithmetic;
 
 #[rustfmt::skip]
@@ -78,6 +83,12 @@ const BLOCKSIZE: usize = 512;
 #[cfg(not(any(feature = "ringbuffer", feature = "low_latency")))]
 const BLOCKSIZE_FLOAT: f32 = 512.0;
 
+// how much of the output the editor's oscilloscope shows
+const SCOPE_FRAMES: usize = 1024;
+
+// ruffbox's freeze buffers (and the megra-side copies of them)
+const NUM_FREEZE_BUFFERS: usize = 10;
+
 struct RunOptions {
     mode: OutputMode,
     num_live_buffers: usize,
@@ -86,13 +97,17 @@ struct RunOptions {
     editor: bool,
     create_sketch: bool,
     load_samples: bool,
+    watch_samples: bool,
     sample_folder: Option<String>,
     base_folder: Option<String>,
     reverb_mode: ReverbMode,
     font: Option<String>,
     font_size: f32,
+    theme: Option<String>,
     midi_in: Option<usize>,
     downmix_stereo: bool,
+    stream_threshold: usize,
+    retro_buffer: f32,
 }
 
 fn main() -> Result<(), anyhow::Error> {
@@ -116,6 +131,11 @@ fn main() -> Result<(), anyhow::Error> {
 
     opts.optflag("h", "help", "Print this help");
     opts.optflag("n", "no-samples", "don't load default samples");
+    opts.optflag(
+        "",
+        "watch-samples",
+        "watch the sample folder and pick up added, changed and removed files",
+    );
     opts.optopt("o", "output-mode", "output mode (stereo, 8ch)", "stereo");
     opts.optopt(
         "",
@@ -170,6 +190,26 @@ fn main() -> Result<(), anyhow::Error> {
     );
 
     opts.optopt("", "font-size", "editor font size", "15.0");
+    opts.optopt(
+        "",
+        "theme",
+        "editor theme (dark, light, high-contrast or a file in the themes folder)",
+        "dark",
+    );
+
+    opts.optopt(
+        "",
+        "stream-threshold",
+        "samples longer than this (in seconds) will be streamed from disk",
+        "60",
+    );
+
+    opts.optopt(
+        "",
+        "retro-buffer",
+        "keep the last seconds of output, to save them with (save-last ...)",
+        "300",
+    );
 
     let matches = match opts.parse(argv) {
         Ok(m) => m,
@@ -187,6 +227,7 @@ fn main() -> Result<(), anyhow::Error> {
     let editor: bool = !matches.opt_present("r");
     let create_sketch: bool = !matches.opt_present("nosketch");
     let load_samples: bool = !matches.opt_present("n");
+    let watch_samples: bool = matches.opt_present("watch-samples");
     let downmix_stereo: bool = !matches.opt_present("use-stereo-samples");
 
     if matches.opt_present("h") {
@@ -277,6 +318,27 @@ fn main() -> Result<(), anyhow::Error> {
         15.0
     };
 
+    let stream_threshold: f32 = if let Some(s) = matches.opt_str("stream-threshold") {
+        if let Ok(f) = s.parse() {
+            f
+        } else {
+            60.0
+        }
+    } else {
+        60.0
+    };
+
+    // off by default
+    let retro_buffer: f32 = if let Some(s) = matches.opt_str("retro-buffer") {
+        if let Ok(f) = s.parse() {
+            f
+        } else {
+            0.0
+        }
+    } else {
+        0.0
+    };
+
     println!("using a live buffer time of: {}", live_buffer_time);
 
     #[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd"))]
@@ -352,13 +414,17 @@ fn main() -> Result<(), anyhow::Error> {
         editor,
         create_sketch,
         load_samples,
+        watch_samples,
         sample_folder: matches.opt_str("sample-folder"),
         base_folder: matches.opt_str("base"),
         reverb_mode,
         font: matches.opt_str("font"),
         font_size,
+        theme: matches.opt_str("theme"),
         midi_in,
         downmix_stereo,
+        stream_threshold: (stream_threshold * 1000.0) as usize,
+        retro_buffer,
     };
 
     match out_mode {
@@ -446,7 +512,7 @@ where
         &options.reverb_mode,
         sample_rate.into(),
         options.max_sample_buffers,
-        10,
+        NUM_FREEZE_BUFFERS,
     );
 
     // OUTPUT RECORDING
@@ -461,6 +527,38 @@ where
         0.25,
     );
 
+    // RETROACTIVE RECORDING
+    let (throw_retro, retro_buffer, retro_handle) = if options.retro_buffer > 0.0 {
+        let (throw_retro, catch_retro) = real_time_streaming::init_real_time_stream::<
+            BLOCKSIZE,
+            NCHAN,
+        >((BLOCKSIZE_FLOAT / sample_rate) as f64, 0.25);
+        let retro_buffer = sync::Arc::new(Mutex::new(real_time_streaming::RetroBuffer::new(
+            NCHAN,
+            (options.retro_buffer * sample_rate) as usize,
+            sample_rate as u32,
+        )));
+        let retro_handle =
+            real_time_streaming::start_retro_thread(catch_retro, sync::Arc::clone(&retro_buffer));
+        println!("using a retro buffer of {} seconds", options.retro_buffer);
+        (Some(throw_retro), Some(retro_buffer), Some(retro_handle))
+    } else {
+        (None, None, None)
+    };
+
+    // LEVEL METERS (in the editor)
+    let (throw_meter, meter_tap) = if options.editor {
+        let (throw_meter, catch_meter) = real_time_streaming::init_real_time_stream::<
+            BLOCKSIZE,
+            NCHAN,
+        >((BLOCKSIZE_FLOAT / sample_rate) as f64, 0.25);
+        let meter_tap =
+            real_time_streaming::MeterTap::new(catch_meter, sample_rate as u32, SCOPE_FRAMES);
+        (Some(throw_meter), Some(meter_tap))
+    } else {
+        (None, None)
+    };
+
     let is_recording_output = sync::Arc::new(AtomicBool::new(false));
     let is_recording_input = sync::Arc::new(AtomicBool::new(false));
 
@@ -472,8 +570,25 @@ where
         catch_in: Some(catch_in),
         catch_in_handle: None,
         samplerate: sample_rate as u32,
+        retro_buffer,
+        retro_handle,
     };
 
+    // STREAMING PLAYBACK OF LONG FILES
+    let mut raw_sample_set = SampleAndWavematrixSet::new();
+    raw_sample_set.stream_threshold = options.stream_threshold;
+    let sample_set = sync::Arc::new(Mutex::new(raw_sample_set));
+    let (stream_control, mut stream_mixer) =
+        stream_player::init_stream_player::<BLOCKSIZE, NCHAN>(&sample_set, sample_rate);
+
+    // megra-side copy of the live and freeze buffers, i.e. to capture wavematrices
+    let live_buffer = sync::Arc::new(live_buffer::LiveBufferMirror::new(
+        options.num_live_buffers,
+        (options.live_buffer_time * sample_rate) as usize,
+        NUM_FREEZE_BUFFERS,
+    ));
+    let live_buffer_in = sync::Arc::clone(&live_buffer);
+
     let playhead_out = sync::Arc::new(Mutex::new(playhead)); // the one for the audio thread (out stream)...
     let playhead_in = sync::Arc::clone(&playhead_out); // the one for the audio thread (in stream)...
 
@@ -500,6 +615,7 @@ where
                         ruff.write_sample_to_live_buffer(ch, *s);
                         stream_item.buffer[ch][f] = *s;
                     }
+                    live_buffer_in.write_frame(frame);
                     stream_item.size += 1; // increment once per frame
                 }
                 throw_in.throw_next(stream_item);
@@ -509,6 +625,7 @@ where
                     for (ch, s) in frame.iter().enumerate() {
                         ruff.write_sample_to_live_buffer(ch, *s);
                     }
+                    live_buffer_in.write_frame(frame);
                 }
             }
         },
@@ -572,6 +689,7 @@ where
                 for (ch, s) in frame.iter().enumerate() {
                     ruff.write_sample_to_live_buffer(ch, *s);
                 }
+                live_buffer_in.write_frame(frame);
             }
         },
         err_fn,
@@ -590,12 +708,21 @@ where
 
             // as the jack timing from cpal can't be trusted right now, the
             // ruffbox handles it's own logical time ...
-            let ruff_out = ruff.process(0.0, true);
+            let mut ruff_out = ruff.process(0.0, true);
+            stream_mixer.mix(&mut ruff_out);
 
             if is_recording_output.load(Ordering::SeqCst) {
                 throw_out.write_samples(&ruff_out, BLOCKSIZE);
             }
 
+            if let Some(throw_retro) = throw_retro.as_ref() {
+                throw_retro.write_samples(&ruff_out, BLOCKSIZE);
+            }
+
+            if let Some(throw_meter) = throw_meter.as_ref() {
+                throw_meter.write_samples(&ruff_out, BLOCKSIZE);
+            }
+
             // there might be a faster way to de-interleave here ...
             for (frame_count, frame) in data.chunks_mut(out_channels).enumerate() {
                 for ch in 0..out_channels {
@@ -647,12 +774,21 @@ where
                 let mut samples_actually_needed = current_blocksize - samples_available;
 
                 while samples_actually_needed > 0 {
-                    let ruff_out = ruff.process(0.0, true);
+                    let mut ruff_out = ruff.process(0.0, true);
+                    stream_mixer.mix(&mut ruff_out);
 
                     if is_recording_output.load(Ordering::SeqCst) {
                         throw_out.write_samples(&ruff_out, BLOCKSIZE);
                     }
 
+                    if let Some(throw_retro) = throw_retro.as_ref() {
+                        throw_retro.write_samples(&ruff_out, BLOCKSIZE);
+                    }
+
+                    if let Some(throw_meter) = throw_meter.as_ref() {
+                        throw_meter.write_samples(&ruff_out, BLOCKSIZE);
+                    }
+
                     //produced += BLOCKSIZE;
                     for ch in 0..out_channels {
                         let mut tmp_write_idx = write_idx;
@@ -710,10 +846,11 @@ where
     // global data
     let mut raw_session = Session::new();
     raw_session.rec_control = Some(rec_control);
+    raw_session.stream_control = Some(sync::Arc::new(stream_control));
+    raw_session.live_buffer = Some(live_buffer);
     let session = sync::Arc::new(Mutex::new(raw_session));
 
     let global_parameters = sync::Arc::new(GlobalParameters::with_capacity(1));
-    let sample_set = sync::Arc::new(Mutex::new(SampleAndWavematrixSet::new()));
     let parts_store = sync::Arc::new(Mutex::new(PartsStore::new()));
     // define the "standard library"
     let stdlib = sync::Arc::new(Mutex::new(define_standard_library()));
@@ -791,20 +928,33 @@ where
         std::fs::create_dir_all(recordings_path.to_str().unwrap())?;
     }
     // load the default sample set ...
-    if options.load_samples {
+    if options.load_samples || options.watch_samples {
         println!("load samples from path: {:?}", samples_path);
         let controls_arc2 = sync::Arc::clone(&controls_arc);
         let sample_set2 = sync::Arc::clone(&sample_set);
         let stdlib2 = sync::Arc::clone(&stdlib);
         thread::spawn(move || {
-            commands::load_sample_sets_path(
-                &stdlib2,
-                &controls_arc2,
-                &sample_set2,
-                &samples_path,
-                options.downmix_stereo,
-            );
-            println!("a command (load default sample sets)");
+            if options.load_samples {
+                commands::load_sample_sets_path(
+                    &stdlib2,
+                    &controls_arc2,
+                    &sample_set2,
+                    &samples_path,
+                    options.downmix_stereo,
+                    None,
+                );
+                println!("a command (load default sample sets)");
+            }
+            // keep the sets in sync with the folder from here on ...
+            if options.watch_samples {
+                commands::watch_sample_sets_path(
+                    &stdlib2,
+                    &controls_arc2,
+                    &sample_set2,
+                    &samples_path,
+                    options.downmix_stereo,
+                );
+            }
         });
     }
 
@@ -822,6 +972,8 @@ where
             options.mode,
             options.font.as_deref(),
             options.font_size,
+            options.theme.as_deref(),
+            meter_tap,
         );
         Ok(())
     } else {
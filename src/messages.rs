@@ -0,0 +1,74 @@
+use chrono::{DateTime, Local};
+use parking_lot::{const_mutex, Mutex};
+
+// older messages are dropped if nobody picks them up
+const MAX_MESSAGES: usize = 500;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// A message for the user, like sample loading progress or warnings.
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub time: DateTime<Local>,
+    pub severity: Severity,
+    pub text: String,
+}
+
+static MESSAGES: Mutex<Vec<Message>> = const_mutex(Vec::new());
+
+/// Print a message and keep it around for the editor.
+pub fn post(severity: Severity, text: String) {
+    println!("{}", text);
+    let mut messages = MESSAGES.lock();
+    if messages.len() >= MAX_MESSAGES {
+        messages.remove(0);
+    }
+    messages.push(Message {
+        time: Local::now(),
+        severity,
+        text,
+    });
+}
+
+pub fn info(text: String) {
+    post(Severity::Info, text);
+}
+
+pub fn warning(text: String) {
+    post(Severity::Warning, text);
+}
+
+pub fn error(text: String) {
+    post(Severity::Error, text);
+}
+
+/// take all messages posted since the last call
+pub fn take() -> Vec<Message> {
+    std::mem::take(&mut *MESSAGES.lock())
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_post_and_take() {
+        warning("can't load sample nope.wav".to_string());
+        info("loaded sample set bd (4 samples)".to_string());
+        // other tests might post, too, so only look for ours
+        let messages = take();
+        assert!(messages
+            .iter()
+            .any(|m| m.severity == Severity::Warning && m.text == "can't load sample nope.wav"));
+        assert!(messages
+            .iter()
+            .any(|m| m.severity == Severity::Info && m.text.starts_with("loaded sample set bd")));
+    }
+}
@@ -1,7 +1,265 @@
 use rust_music_theory::note::{Note, PitchClass};
 
-pub enum Tuning {
-    EqualTemperament,
+/// A tuning, in the spirit of Scala: the pitches of the degrees
+/// in cents (the last one being the period, usually the octave),
+/// and a keyboard mapping that assigns the degrees to (midi) keys.
+#[derive(Clone, Debug)]
+pub struct Tuning {
+    pub cents: Vec<f32>,
+    pub mapping: KeyboardMapping,
+}
+
+/// Like a Scala .kbm file, an empty map means linear mapping.
+#[derive(Clone, Debug)]
+pub struct KeyboardMapping {
+    pub middle_note: i32, // the key the first degree is mapped to
+    pub ref_note: i32,
+    pub ref_freq: f32,
+    pub octave_degree: usize,
+    pub map: Vec<Option<usize>>,
+}
+
+impl Default for KeyboardMapping {
+    fn default() -> Self {
+        KeyboardMapping {
+            middle_note: 60,
+            ref_note: 69,
+            ref_freq: 440.0,
+            octave_degree: 0,
+            map: Vec::new(),
+        }
+    }
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Tuning::equal(12)
+    }
+}
+
+fn ratio_to_cents(ratio: f32) -> f32 {
+    1200.0 * ratio.log2()
+}
+
+fn from_ratios(ratios: &[(f32, f32)]) -> Tuning {
+    Tuning {
+        cents: ratios.iter().map(|(a, b)| ratio_to_cents(a / b)).collect(),
+        mapping: KeyboardMapping::default(),
+    }
+}
+
+impl Tuning {
+    /// equal division of the octave
+    pub fn equal(steps: usize) -> Self {
+        let steps = steps.max(1);
+        Tuning {
+            cents: (1..=steps)
+                .map(|s| s as f32 * 1200.0 / steps as f32)
+                .collect(),
+            mapping: KeyboardMapping::default(),
+        }
+    }
+
+    /// 5-limit just intonation
+    pub fn just() -> Self {
+        from_ratios(&[
+            (16.0, 15.0),
+            (9.0, 8.0),
+            (6.0, 5.0),
+            (5.0, 4.0),
+            (4.0, 3.0),
+            (45.0, 32.0),
+            (3.0, 2.0),
+            (8.0, 5.0),
+            (5.0, 3.0),
+            (9.0, 5.0),
+            (15.0, 8.0),
+            (2.0, 1.0),
+        ])
+    }
+
+    pub fn pythagorean() -> Self {
+        from_ratios(&[
+            (256.0, 243.0),
+            (9.0, 8.0),
+            (32.0, 27.0),
+            (81.0, 64.0),
+            (4.0, 3.0),
+            (729.0, 512.0),
+            (3.0, 2.0),
+            (128.0, 81.0),
+            (27.0, 16.0),
+            (16.0, 9.0),
+            (243.0, 128.0),
+            (2.0, 1.0),
+        ])
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "equal" | "et" | "12tet" => Some(Tuning::equal(12)),
+            "just" => Some(Tuning::just()),
+            "pythagorean" => Some(Tuning::pythagorean()),
+            _ => None,
+        }
+    }
+
+    /// parse the contents of a Scala .scl file
+    pub fn from_scl(content: &str) -> Result<Self, String> {
+        // the description might be empty, so only skip comments here
+        let mut lines = content
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.starts_with('!'));
+
+        lines.next().ok_or("empty scl file")?; // description
+        let count: usize = lines
+            .next()
+            .and_then(|l| l.split_whitespace().next())
+            .and_then(|c| c.parse().ok())
+            .ok_or("can't read number of notes")?;
+
+        let mut cents = Vec::new();
+        for line in lines.filter(|l| !l.is_empty()).take(count) {
+            let val = line.split_whitespace().next().unwrap_or("");
+            let c = if val.contains('.') {
+                val.parse::<f32>().ok()
+            } else if let Some((a, b)) = val.split_once('/') {
+                match (a.parse::<f32>(), b.parse::<f32>()) {
+                    (Ok(a), Ok(b)) if a > 0.0 && b > 0.0 => Some(ratio_to_cents(a / b)),
+                    _ => None,
+                }
+            } else {
+                val.parse::<f32>()
+                    .ok()
+                    .filter(|r| *r > 0.0)
+                    .map(ratio_to_cents)
+            };
+            cents.push(c.ok_or(format!("can't read pitch {}", val))?);
+        }
+
+        if cents.is_empty() || cents.len() != count {
+            return Err(format!("expected {} pitches, found {}", count, cents.len()));
+        }
+
+        Ok(Tuning {
+            cents,
+            mapping: KeyboardMapping::default(),
+        })
+    }
+
+    /// parse the contents of a Scala .kbm file
+    pub fn set_kbm(&mut self, content: &str) -> Result<(), String> {
+        let vals: Vec<&str> = content
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.starts_with('!') && !l.is_empty())
+            .map(|l| l.split_whitespace().next().unwrap_or(""))
+            .collect();
+
+        if vals.len() < 7 {
+            return Err("incomplete kbm file".to_string());
+        }
+
+        let num = |i: usize| -> Result<i32, String> {
+            vals[i]
+                .parse::<i32>()
+                .map_err(|_| format!("can't read {}", vals[i]))
+        };
+
+        let size = num(0)? as usize;
+        // first and last note (1 and 2) define a range, which we ignore
+        let middle_note = num(3)?;
+        let ref_note = num(4)?;
+        let ref_freq = vals[5]
+            .parse::<f32>()
+            .map_err(|_| format!("can't read {}", vals[5]))?;
+        let octave_degree = num(6)?.max(0) as usize;
+
+        let mut map = Vec::new();
+        for i in 0..size {
+            // missing entries and 'x' are unmapped
+            map.push(vals.get(7 + i).and_then(|v| v.parse::<usize>().ok()));
+        }
+
+        self.mapping = KeyboardMapping {
+            middle_note,
+            ref_note,
+            ref_freq,
+            octave_degree,
+            map,
+        };
+        Ok(())
+    }
+
+    /// the number of keys that make one period
+    pub fn keys_per_period(&self) -> usize {
+        if self.mapping.map.is_empty() {
+            self.cents.len()
+        } else {
+            self.mapping.map.len()
+        }
+    }
+
+    fn degree_cents(&self, degree: i32) -> f32 {
+        let len = self.cents.len() as i32;
+        let period = self.cents[self.cents.len() - 1];
+        let idx = degree.rem_euclid(len);
+        let base = if idx == 0 {
+            0.0
+        } else {
+            self.cents[idx as usize - 1]
+        };
+        degree.div_euclid(len) as f32 * period + base
+    }
+
+    fn key_cents(&self, key: i32) -> Option<f32> {
+        let rel = key - self.mapping.middle_note;
+        if self.mapping.map.is_empty() {
+            Some(self.degree_cents(rel))
+        } else {
+            let size = self.mapping.map.len() as i32;
+            let octave_degree = if self.mapping.octave_degree == 0 {
+                self.cents.len()
+            } else {
+                self.mapping.octave_degree
+            } as i32;
+            self.mapping.map[rel.rem_euclid(size) as usize]
+                .map(|d| self.degree_cents(d as i32 + rel.div_euclid(size) * octave_degree))
+        }
+    }
+
+    // unmapped keys sound like the next mapped key below
+    fn key_cents_or_below(&self, key: i32) -> f32 {
+        for k in 0..=self.mapping.map.len() as i32 {
+            if let Some(c) = self.key_cents(key - k) {
+                return c;
+            }
+        }
+        0.0
+    }
+
+    /// the frequency of a (midi) key, fractional keys are
+    /// interpolated between the neighbours
+    pub fn key_freq(&self, key: f32) -> f32 {
+        let lo = key.floor();
+        let frac = key - lo;
+        let c_lo = self.key_cents_or_below(lo as i32);
+        let cents = if frac > 0.0 {
+            c_lo + (self.key_cents_or_below(lo as i32 + 1) - c_lo) * frac
+        } else {
+            c_lo
+        };
+        let ref_cents = self.key_cents_or_below(self.mapping.ref_note);
+        self.mapping.ref_freq * 2f32.powf((cents - ref_cents) / 1200.0)
+    }
+
+    /// the factor to transpose by a number of steps, based on the
+    /// average step size, as the steps might not be equal
+    pub fn step_factor(&self, steps: f32) -> f32 {
+        let period = self.cents[self.cents.len() - 1];
+        2f32.powf(period / self.keys_per_period() as f32 * steps / 1200.0)
+    }
 }
 
 pub fn from_string(string: &str) -> Note {
@@ -32,24 +290,354 @@ pub fn from_note_nr(nr: u8) -> Note {
     Note::new(pitch_class, octave)
 }
 
-pub fn from_freq(freq: f32, tuning: Tuning) -> Note {
-    match tuning {
-        Tuning::EqualTemperament => {
-            let a440 = to_note_nr(Note::new(PitchClass::from_str("A").unwrap(), 4));
-            from_note_nr(((12.0 * (freq / 440.0).log2()) as i16 + a440 as i16) as u8)
+pub fn from_freq(freq: f32, tuning: &Tuning) -> Note {
+    // find the nearest key
+    let mut nearest = (12, f32::MAX);
+    for key in 12..128 {
+        let dist = (freq / tuning.key_freq(key as f32)).log2().abs();
+        if dist < nearest.1 {
+            nearest = (key, dist);
         }
     }
+    from_note_nr(nearest.0 as u8 - 12)
 }
 
 pub fn to_note_nr(note: Note) -> u8 {
     note.pitch_class.into_u8() + 12 * note.octave
 }
 
-pub fn to_freq(note: Note, tuning: Tuning) -> f32 {
-    match tuning {
-        Tuning::EqualTemperament => {
-            let a440 = to_note_nr(Note::new(PitchClass::from_str("A").unwrap(), 4));
-            2f32.powf(((to_note_nr(note) as i16 - a440 as i16) as f32) / 12.0) * 440.0
+/// the midi key of a note (which counts octaves from -1)
+pub fn to_key(note: Note) -> i32 {
+    to_note_nr(note) as i32 + 12
+}
+
+pub fn to_freq(note: Note, tuning: &Tuning) -> f32 {
+    tuning.key_freq(to_key(note) as f32)
+}
+
+/// A scale, given as a root pitch class and the intervals of its
+/// degrees above the root, within one octave. The intervals are
+/// steps of the tuning, which are semitones in equal temperament.
+#[derive(Clone, Debug)]
+pub struct Scale {
+    pub root: u8,
+    pub intervals: Vec<f32>,
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Scale {
+            root: 0,
+            intervals: vec![0.0, 2.0, 4.0, 5.0, 7.0, 9.0, 11.0],
+        }
+    }
+}
+
+fn root_from_string(root: &str) -> u8 {
+    if let Some(p) = PitchClass::from_str(root) {
+        p.into_u8()
+    } else {
+        0
+    }
+}
+
+impl Scale {
+    pub fn from_name(name: &str, root: &str) -> Option<Self> {
+        let steps: &[f32] = match name {
+            "major" | "ionian" => &[2.0, 2.0, 1.0, 2.0, 2.0, 2.0, 1.0],
+            "minor" | "aeolian" => &[2.0, 1.0, 2.0, 2.0, 1.0, 2.0, 2.0],
+            "dorian" => &[2.0, 1.0, 2.0, 2.0, 2.0, 1.0, 2.0],
+            "phrygian" => &[1.0, 2.0, 2.0, 2.0, 1.0, 2.0, 2.0],
+            "lydian" => &[2.0, 2.0, 2.0, 1.0, 2.0, 2.0, 1.0],
+            "mixolydian" => &[2.0, 2.0, 1.0, 2.0, 2.0, 1.0, 2.0],
+            "locrian" => &[1.0, 2.0, 2.0, 1.0, 2.0, 2.0, 2.0],
+            "harmonic-minor" => &[2.0, 1.0, 2.0, 2.0, 1.0, 3.0, 1.0],
+            "melodic-minor" => &[2.0, 1.0, 2.0, 2.0, 2.0, 2.0, 1.0],
+            "pentatonic" | "major-pentatonic" => &[2.0, 2.0, 3.0, 2.0, 3.0],
+            "minor-pentatonic" => &[3.0, 2.0, 2.0, 3.0, 2.0],
+            "whole-tone" => &[2.0, 2.0, 2.0, 2.0, 2.0, 2.0],
+            "chromatic" => &[1.0; 12],
+            _ => return None,
+        };
+        Some(Scale::from_steps(steps, root))
+    }
+
+    /// user-defined scales, given as steps between the degrees,
+    /// i.e. (2 2 1 2 2 2 1) for major
+    pub fn from_steps(steps: &[f32], root: &str) -> Self {
+        let mut intervals = vec![0.0];
+        let mut acc = 0.0;
+        for s in steps.iter() {
+            acc += s;
+            // the octave is implied
+            if acc >= 12.0 {
+                break;
+            }
+            intervals.push(acc);
         }
+        Scale {
+            root: root_from_string(root),
+            intervals,
+        }
+    }
+
+    /// user-defined scales for tunings that don't have twelve keys
+    /// per octave, the last step leads to the period
+    pub fn from_period_steps(steps: &[f32], root: &str) -> Self {
+        let mut intervals = vec![0.0];
+        let mut acc = 0.0;
+        for s in steps.iter().take(steps.len().saturating_sub(1)) {
+            acc += s;
+            intervals.push(acc);
+        }
+        Scale {
+            root: root_from_string(root),
+            intervals,
+        }
+    }
+
+    // the key of a degree (starting at 0), the octave is relative to the fourth.
+    // The root is one of twelve pitch classes, so it's moved to the nearest
+    // key of the tuning's period, the intervals are kept as they are
+    fn key(&self, idx: i32, octave: i32, tuning: &Tuning) -> f32 {
+        let len = self.intervals.len() as i32;
+        let period = tuning.keys_per_period() as f32;
+        let root = (self.root as f32 * period / 12.0).round();
+        60.0 + root
+            + (octave + idx.div_euclid(len)) as f32 * period
+            + self.intervals[idx.rem_euclid(len) as usize]
+    }
+
+    /// snap a frequency to the nearest degree of the scale
+    pub fn quantize(&self, freq: f32, tuning: &Tuning) -> f32 {
+        if freq <= 0.0 {
+            return freq;
+        }
+        let len = self.intervals.len() as i32;
+        let mut nearest = (freq, f32::MAX);
+        // a few octaves up and down should be enough
+        for idx in (-6 * len)..(6 * len) {
+            let candidate = tuning.key_freq(self.key(idx, 0, tuning));
+            let dist = (freq / candidate).log2().abs();
+            if dist < nearest.1 {
+                nearest = (candidate, dist);
+            }
+        }
+        nearest.0
+    }
+
+    /// the frequency of a scale degree (starting at 1),
+    /// the octave is relative to the fourth
+    pub fn degree(&self, degree: i32, octave: i32, tuning: &Tuning) -> f32 {
+        tuning.key_freq(self.key(degree - 1, octave, tuning))
+    }
+
+    /// the keys of the chord on a scale degree (starting at 1),
+    /// stacked in thirds of the scale
+    pub fn chord_keys(&self, degree: i32, notes: usize, octave: i32, tuning: &Tuning) -> Vec<f32> {
+        (0..notes as i32)
+            .map(|n| self.key(degree - 1 + 2 * n, octave, tuning))
+            .collect()
+    }
+}
+
+/// The intervals of a chord quality, in semitones above the root.
+pub fn chord_intervals(quality: &str) -> Option<Vec<f32>> {
+    let intervals: &[f32] = match quality {
+        "maj" | "major" => &[0.0, 4.0, 7.0],
+        "min" | "minor" => &[0.0, 3.0, 7.0],
+        "dim" => &[0.0, 3.0, 6.0],
+        "aug" => &[0.0, 4.0, 8.0],
+        "sus2" => &[0.0, 2.0, 7.0],
+        "sus4" => &[0.0, 5.0, 7.0],
+        "6" | "maj6" => &[0.0, 4.0, 7.0, 9.0],
+        "min6" => &[0.0, 3.0, 7.0, 9.0],
+        "7" | "dom7" => &[0.0, 4.0, 7.0, 10.0],
+        "maj7" => &[0.0, 4.0, 7.0, 11.0],
+        "min7" => &[0.0, 3.0, 7.0, 10.0],
+        "minmaj7" => &[0.0, 3.0, 7.0, 11.0],
+        "dim7" => &[0.0, 3.0, 6.0, 9.0],
+        "min7b5" | "half-dim" => &[0.0, 3.0, 6.0, 10.0],
+        "add9" => &[0.0, 4.0, 7.0, 14.0],
+        "9" | "dom9" => &[0.0, 4.0, 7.0, 10.0, 14.0],
+        "maj9" => &[0.0, 4.0, 7.0, 11.0, 14.0],
+        "min9" => &[0.0, 3.0, 7.0, 10.0, 14.0],
+        _ => return None,
+    };
+    Some(intervals.to_vec())
+}
+
+/// Invert a chord (given as keys, lowest first), each inversion
+/// moves the lowest note up by an octave (or period) of the tuning.
+pub fn invert(keys: &[f32], inversion: usize, tuning: &Tuning) -> Vec<f32> {
+    let period = tuning.keys_per_period() as f32;
+    let mut inverted = keys.to_vec();
+    for _ in 0..inversion {
+        if inverted.is_empty() {
+            break;
+        }
+        let lowest = inverted.remove(0);
+        inverted.push(lowest + period);
+    }
+    inverted
+}
+
+/// The scale degree of a roman numeral (i to vii) and the quality of its chord,
+/// uppercase numerals are major, lowercase ones minor, a trailing 7
+/// adds the seventh, like 'V7 (dominant) or 'ii7 (minor seventh).
+pub fn roman_numeral(numeral: &str) -> Option<(i32, Vec<f32>)> {
+    let (base, seventh) = if let Some(b) = numeral.strip_suffix('7') {
+        (b, true)
+    } else {
+        (numeral, false)
+    };
+    let major = if base.chars().all(|c| c.is_ascii_uppercase()) {
+        true
+    } else if base.chars().all(|c| c.is_ascii_lowercase()) {
+        false
+    } else {
+        return None;
+    };
+    let degree = match base.to_lowercase().as_str() {
+        "i" => 1,
+        "ii" => 2,
+        "iii" => 3,
+        "iv" => 4,
+        "v" => 5,
+        "vi" => 6,
+        "vii" => 7,
+        _ => return None,
+    };
+    let quality = match (major, seventh) {
+        (true, false) => "maj",
+        (false, false) => "min",
+        (true, true) => "7",
+        (false, true) => "min7",
+    };
+    Some((degree, chord_intervals(quality)?))
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_scale_quantize() {
+        let tuning = Tuning::default();
+        let dorian = Scale::from_name("dorian", "d").unwrap();
+        assert_eq!(dorian.intervals, vec![0.0, 2.0, 3.0, 5.0, 7.0, 9.0, 10.0]);
+
+        // a4 is in d dorian ...
+        assert!((dorian.quantize(440.0, &tuning) - 440.0).abs() < 0.01);
+        // ... a#4 isn't, the nearest degree below is a4
+        assert!((dorian.quantize(460.0, &tuning) - 440.0).abs() < 0.01);
+        // a bit below c5 snaps to c5
+        assert!((dorian.quantize(520.0, &tuning) - 523.25).abs() < 0.01);
+
+        let custom = Scale::from_steps(&[2.0, 2.0, 1.0, 2.0, 2.0, 2.0, 1.0], "c");
+        assert_eq!(custom.intervals, Scale::default().intervals);
+
+        // a scale over 19-tet, the last step leads to the period
+        let custom = Scale::from_period_steps(&[3.0, 3.0, 2.0, 3.0, 3.0, 3.0, 2.0], "c");
+        assert_eq!(custom.intervals, vec![0.0, 3.0, 6.0, 8.0, 11.0, 14.0, 17.0]);
+    }
+
+    #[test]
+    fn test_scale_degree() {
+        let tuning = Tuning::default();
+        let major = Scale::from_name("major", "c").unwrap();
+        // e4
+        assert!((major.degree(3, 0, &tuning) - 329.63).abs() < 0.01);
+        // e5
+        assert!((major.degree(3, 1, &tuning) - 659.26).abs() < 0.01);
+        // the eighth degree is the octave, c5
+        assert!((major.degree(8, 0, &tuning) - 523.25).abs() < 0.01);
+        // b3
+        assert!((major.degree(0, 0, &tuning) - 246.94).abs() < 0.01);
+
+        // in 19-tet, d is three keys above c, and the steps can be fractional
+        let tet19 = Tuning::equal(19);
+        let custom = Scale::from_period_steps(&[3.0, 2.5, 13.5], "d");
+        assert_eq!(custom.key(0, 0, &tet19), 63.0);
+        assert_eq!(custom.key(2, 0, &tet19), 68.5);
+        assert_eq!(custom.key(3, 0, &tet19), 82.0);
+        assert!(
+            (custom.degree(3, 0, &tet19) - (tet19.key_freq(68.0) * tet19.key_freq(69.0)).sqrt())
+                .abs()
+                < 0.01
+        );
+    }
+
+    #[test]
+    fn test_chords() {
+        let c4 = to_key(from_string("c4")) as f32;
+        let min7: Vec<f32> = chord_intervals("min7")
+            .unwrap()
+            .iter()
+            .map(|i| c4 + i)
+            .collect();
+        assert_eq!(min7, vec![60.0, 63.0, 67.0, 70.0]);
+        let tuning = Tuning::default();
+        assert_eq!(invert(&min7, 1, &tuning), vec![63.0, 67.0, 70.0, 72.0]);
+        assert_eq!(invert(&min7, 4, &tuning), vec![72.0, 75.0, 79.0, 82.0]);
+        // in 19-tet, the octave is 19 keys up
+        assert_eq!(
+            invert(&min7, 1, &Tuning::equal(19)),
+            vec![63.0, 67.0, 70.0, 79.0]
+        );
+        assert!(chord_intervals("blah").is_none());
+
+        assert_eq!(roman_numeral("iv"), Some((4, vec![0.0, 3.0, 7.0])));
+        assert_eq!(roman_numeral("IV"), Some((4, vec![0.0, 4.0, 7.0])));
+        assert_eq!(roman_numeral("V7"), Some((5, vec![0.0, 4.0, 7.0, 10.0])));
+        assert_eq!(roman_numeral("v7"), Some((5, vec![0.0, 3.0, 7.0, 10.0])));
+        assert_eq!(roman_numeral("Iv"), None);
+        assert_eq!(roman_numeral("viii"), None);
+
+        // diatonic chords in a minor
+        let a_minor = Scale::from_name("minor", "a").unwrap();
+        // a c e
+        assert_eq!(a_minor.chord_keys(1, 3, 0, &tuning), vec![69.0, 72.0, 76.0]);
+        // e g b d
+        assert_eq!(
+            a_minor.chord_keys(5, 4, 0, &tuning),
+            vec![76.0, 79.0, 83.0, 86.0]
+        );
+    }
+
+    #[test]
+    fn test_tunings() {
+        let equal = Tuning::default();
+        assert!((equal.key_freq(69.0) - 440.0).abs() < 0.01);
+        assert!((to_freq(from_string("c4"), &equal) - 261.63).abs() < 0.01);
+        assert!((equal.step_factor(12.0) - 2.0).abs() < 0.0001);
+
+        let mut just = Tuning::just();
+        just.mapping.ref_freq = 432.0;
+        assert!((just.key_freq(69.0) - 432.0).abs() < 0.01);
+        // a is 5/3 above c
+        assert!((just.key_freq(60.0) - 432.0 * 3.0 / 5.0).abs() < 0.01);
+        // e is 5/4 above c
+        assert!((just.key_freq(64.0) - 432.0 * 3.0 / 5.0 * 5.0 / 4.0).abs() < 0.01);
+
+        // non-octave, bohlen-pierce like
+        let scl = "! bp.scl\n!\nsome tritave scale\n 3\n!\n 3/2\n 500.0\n 3\n";
+        let mut tritave = Tuning::from_scl(scl).unwrap();
+        assert_eq!(tritave.cents.len(), 3);
+        assert!((tritave.cents[1] - 500.0).abs() < 0.001);
+        assert!((tritave.key_freq(72.0) / tritave.key_freq(69.0) - 3.0).abs() < 0.001);
+
+        // map two degrees on a five-key pattern, middle note 60 is 100hz
+        let kbm = "! map.kbm\n5\n0\n127\n60\n60\n100.0\n3\n0\nx\n1\nx\nx\n";
+        tritave.set_kbm(kbm).unwrap();
+        assert!((tritave.key_freq(60.0) - 100.0).abs() < 0.01);
+        assert!((tritave.key_freq(62.0) - 150.0).abs() < 0.01);
+        // unmapped keys sound like the next mapped key below
+        assert!((tritave.key_freq(63.0) - 150.0).abs() < 0.01);
+        assert!((tritave.key_freq(65.0) - 300.0).abs() < 0.01);
+
+        assert!(Tuning::from_scl("broken\n 3\n 100.0\n").is_err());
     }
 }
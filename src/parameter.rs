@@ -1,9 +1,11 @@
 pub mod modifier;
+pub mod units;
 use modifier::*;
 
 use rand::Rng;
 use std::boxed::Box;
 use std::fmt::*;
+use std::hash::{Hash, Hasher};
 
 use ruffbox_synth::building_blocks::{EnvelopeSegmentInfo, EnvelopeSegmentType};
 use ruffbox_synth::building_blocks::{FilterType, SynthParameterLabel, SynthParameterValue, ValOp};
@@ -106,6 +108,61 @@ pub fn shake_parameter(v: &mut ParameterValue, factor: f32) {
     }
 }
 
+/// Feed the structure of a parameter into a hasher, i.e. to tell
+/// processors apart by their arguments. Modifiers can't be looked
+/// into, so only their presence and the current values count.
+pub fn hash_parameter<H: Hasher>(v: &ParameterValue, state: &mut H) {
+    std::mem::discriminant(v).hash(state);
+    match v {
+        ParameterValue::Scalar(val) => hash_dynval(val, state),
+        ParameterValue::Vector(vals) => {
+            vals.iter().for_each(|val| hash_dynval(val, state));
+        }
+        ParameterValue::Matrix(mat) => {
+            for row in mat.iter() {
+                row.len().hash(state);
+                row.iter().for_each(|val| hash_dynval(val, state));
+            }
+        }
+        ParameterValue::FilterType(t) => std::mem::discriminant(t).hash(state),
+        ParameterValue::Lfo(init, freq, phase, amp, add, op)
+        | ParameterValue::LFSaw(init, freq, phase, amp, add, op)
+        | ParameterValue::LFRSaw(init, freq, phase, amp, add, op)
+        | ParameterValue::LFTri(init, freq, phase, amp, add, op)
+        | ParameterValue::LFSquare(init, freq, phase, amp, add, op) => {
+            hash_dynval(init, state);
+            hash_parameter(freq, state);
+            hash_dynval(phase, state);
+            hash_parameter(amp, state);
+            hash_dynval(add, state);
+            std::mem::discriminant(op).hash(state);
+        }
+        ParameterValue::LinRamp(from, to, time, op)
+        | ParameterValue::LogRamp(from, to, time, op)
+        | ParameterValue::ExpRamp(from, to, time, op) => {
+            hash_dynval(from, state);
+            hash_dynval(to, state);
+            hash_dynval(time, state);
+            std::mem::discriminant(op).hash(state);
+        }
+        ParameterValue::EnvelopeSegmentType(t) => std::mem::discriminant(t).hash(state),
+        ParameterValue::MultiPointEnvelope(levels, times, types, loop_env, op) => {
+            levels.iter().for_each(|val| hash_dynval(val, state));
+            times.iter().for_each(|val| hash_dynval(val, state));
+            for t in types.iter() {
+                std::mem::discriminant(t).hash(state);
+            }
+            loop_env.hash(state);
+            std::mem::discriminant(op).hash(state);
+        }
+    }
+}
+
+pub fn hash_dynval<H: Hasher>(val: &DynVal, state: &mut H) {
+    val.static_val.to_bits().hash(state);
+    val.modifier.is_some().hash(state);
+}
+
 pub fn translate_stereo(val: SynthParameterValue) -> SynthParameterValue {
     match val {
         SynthParameterValue::ScalarF32(p) => SynthParameterValue::ScalarF32((p + 1.0) * 0.5),
@@ -198,20 +255,24 @@ pub fn translate_stereo(val: SynthParameterValue) -> SynthParameterValue {
     }
 }
 
-pub fn resolve_parameter(k: SynthParameterLabel, v: &mut ParameterValue) -> SynthParameterValue {
+pub fn resolve_parameter(
+    k: SynthParameterLabel,
+    v: &mut ParameterValue,
+    ctx: &EvalContext,
+) -> SynthParameterValue {
     match v {
         ParameterValue::FilterType(t) => SynthParameterValue::FilterType(*t),
         ParameterValue::Scalar(val) => {
             if k == SynthParameterLabel::SampleBufferNumber {
-                val.evaluate_val_usize()
+                val.evaluate_val_usize(ctx)
             } else {
-                val.evaluate_val_f32()
+                val.evaluate_val_f32(ctx)
             }
         }
         ParameterValue::Vector(vals) => {
             let mut static_vals: Vec<f32> = Vec::new();
             for val in vals.iter_mut() {
-                static_vals.push(val.evaluate_numerical());
+                static_vals.push(val.evaluate_numerical_in(ctx));
             }
             SynthParameterValue::VecF32(static_vals)
         }
@@ -226,7 +287,7 @@ pub fn resolve_parameter(k: SynthParameterLabel, v: &mut ParameterValue) -> Synt
                     cols = row.len();
                 }
                 for col in row.iter_mut() {
-                    static_vals[r].push(col.evaluate_numerical());
+                    static_vals[r].push(col.evaluate_numerical_in(ctx));
                 }
             }
 
@@ -239,82 +300,107 @@ pub fn resolve_parameter(k: SynthParameterLabel, v: &mut ParameterValue) -> Synt
             SynthParameterValue::MatrixF32((rows, cols), static_vals)
         }
         ParameterValue::Lfo(init, freq, eff_phase, amp, add, op) => SynthParameterValue::Lfo(
-            init.evaluate_numerical(),
-            Box::new(resolve_parameter(SynthParameterLabel::PitchFrequency, freq)),
-            eff_phase.evaluate_numerical(),
+            init.evaluate_numerical_in(ctx),
+            Box::new(resolve_parameter(
+                SynthParameterLabel::PitchFrequency,
+                freq,
+                ctx,
+            )),
+            eff_phase.evaluate_numerical_in(ctx),
             Box::new(resolve_parameter(
                 SynthParameterLabel::OscillatorAmplitude,
                 amp,
+                ctx,
             )),
-            add.evaluate_numerical(),
+            add.evaluate_numerical_in(ctx),
             *op,
         ),
         ParameterValue::LFSaw(init, freq, eff_phase, amp, add, op) => SynthParameterValue::LFSaw(
-            init.evaluate_numerical(),
-            Box::new(resolve_parameter(SynthParameterLabel::PitchFrequency, freq)),
-            eff_phase.evaluate_numerical(),
+            init.evaluate_numerical_in(ctx),
+            Box::new(resolve_parameter(
+                SynthParameterLabel::PitchFrequency,
+                freq,
+                ctx,
+            )),
+            eff_phase.evaluate_numerical_in(ctx),
             Box::new(resolve_parameter(
                 SynthParameterLabel::OscillatorAmplitude,
                 amp,
+                ctx,
             )),
-            add.evaluate_numerical(),
+            add.evaluate_numerical_in(ctx),
             *op,
         ),
         ParameterValue::LFRSaw(init, freq, eff_phase, amp, add, op) => SynthParameterValue::LFRSaw(
-            init.evaluate_numerical(),
-            Box::new(resolve_parameter(SynthParameterLabel::PitchFrequency, freq)),
-            eff_phase.evaluate_numerical(),
+            init.evaluate_numerical_in(ctx),
+            Box::new(resolve_parameter(
+                SynthParameterLabel::PitchFrequency,
+                freq,
+                ctx,
+            )),
+            eff_phase.evaluate_numerical_in(ctx),
             Box::new(resolve_parameter(
                 SynthParameterLabel::OscillatorAmplitude,
                 amp,
+                ctx,
             )),
-            add.evaluate_numerical(),
+            add.evaluate_numerical_in(ctx),
             *op,
         ),
         ParameterValue::LFTri(init, freq, eff_phase, amp, add, op) => SynthParameterValue::LFTri(
-            init.evaluate_numerical(),
-            Box::new(resolve_parameter(SynthParameterLabel::PitchFrequency, freq)),
-            eff_phase.evaluate_numerical(),
+            init.evaluate_numerical_in(ctx),
+            Box::new(resolve_parameter(
+                SynthParameterLabel::PitchFrequency,
+                freq,
+                ctx,
+            )),
+            eff_phase.evaluate_numerical_in(ctx),
             Box::new(resolve_parameter(
                 SynthParameterLabel::OscillatorAmplitude,
                 amp,
+                ctx,
             )),
-            add.evaluate_numerical(),
+            add.evaluate_numerical_in(ctx),
             *op,
         ),
         ParameterValue::LFSquare(init, freq, pw, amp, add, op) => SynthParameterValue::LFSquare(
-            init.evaluate_numerical(),
-            Box::new(resolve_parameter(SynthParameterLabel::PitchFrequency, freq)),
-            pw.evaluate_numerical(),
+            init.evaluate_numerical_in(ctx),
+            Box::new(resolve_parameter(
+                SynthParameterLabel::PitchFrequency,
+                freq,
+                ctx,
+            )),
+            pw.evaluate_numerical_in(ctx),
             Box::new(resolve_parameter(
                 SynthParameterLabel::OscillatorAmplitude,
                 amp,
+                ctx,
             )),
-            add.evaluate_numerical(),
+            add.evaluate_numerical_in(ctx),
             *op,
         ),
         ParameterValue::LinRamp(from, to, time, op) => SynthParameterValue::LinRamp(
-            from.evaluate_numerical(),
-            to.evaluate_numerical(),
-            time.evaluate_numerical(),
+            from.evaluate_numerical_in(ctx),
+            to.evaluate_numerical_in(ctx),
+            time.evaluate_numerical_in(ctx),
             *op,
         ),
         ParameterValue::LogRamp(from, to, time, op) => SynthParameterValue::LogRamp(
-            from.evaluate_numerical(),
-            to.evaluate_numerical(),
-            time.evaluate_numerical(),
+            from.evaluate_numerical_in(ctx),
+            to.evaluate_numerical_in(ctx),
+            time.evaluate_numerical_in(ctx),
             *op,
         ),
         ParameterValue::ExpRamp(from, to, time, op) => SynthParameterValue::ExpRamp(
-            from.evaluate_numerical(),
-            to.evaluate_numerical(),
-            time.evaluate_numerical(),
+            from.evaluate_numerical_in(ctx),
+            to.evaluate_numerical_in(ctx),
+            time.evaluate_numerical_in(ctx),
             *op,
         ),
         ParameterValue::EnvelopeSegmentType(e) => SynthParameterValue::EnvelopeSegmentType(*e),
         ParameterValue::MultiPointEnvelope(levels, times, types, loop_env, op) => {
             if levels.len() == 1 {
-                SynthParameterValue::ScalarF32(levels[0].evaluate_numerical())
+                SynthParameterValue::ScalarF32(levels[0].evaluate_numerical_in(ctx))
             } else if !levels.is_empty() {
                 let mut segments = Vec::new();
 
@@ -322,11 +408,11 @@ pub fn resolve_parameter(k: SynthParameterLabel, v: &mut ParameterValue) -> Synt
                 let mut times_evaluated = Vec::new();
 
                 for lvl in levels.iter_mut() {
-                    levels_evaluated.push(lvl.evaluate_numerical());
+                    levels_evaluated.push(lvl.evaluate_numerical_in(ctx));
                 }
 
                 for time in times.iter_mut() {
-                    times_evaluated.push(time.evaluate_numerical());
+                    times_evaluated.push(time.evaluate_numerical_in(ctx));
                 }
 
                 let mut time = if let Some(t) = times_evaluated.first() {
@@ -396,27 +482,34 @@ impl DynVal {
         }
     }
 
-    pub fn evaluate_val_f32(&mut self) -> SynthParameterValue {
+    pub fn evaluate_val_f32(&mut self, ctx: &EvalContext) -> SynthParameterValue {
         SynthParameterValue::ScalarF32(if let Some(m) = &mut self.modifier {
-            self.static_val = m.evaluate(self.val);
+            self.static_val = m.evaluate(self.val, ctx);
             self.static_val
         } else {
             self.val
         })
     }
 
-    pub fn evaluate_val_usize(&mut self) -> SynthParameterValue {
+    pub fn evaluate_val_usize(&mut self, ctx: &EvalContext) -> SynthParameterValue {
         SynthParameterValue::ScalarUsize(if let Some(m) = &mut self.modifier {
-            self.static_val = m.evaluate(self.val);
+            self.static_val = m.evaluate(self.val, ctx);
             self.static_val as usize
         } else {
             self.val as usize
         })
     }
 
+    /// Evaluate outside of the scheduler, where there's no logical
+    /// time, so time-based modifiers stay at time zero.
     pub fn evaluate_numerical(&mut self) -> f32 {
+        self.evaluate_numerical_in(&EvalContext::default())
+    }
+
+    /// Evaluate in the given context (logical time and scale).
+    pub fn evaluate_numerical_in(&mut self, ctx: &EvalContext) -> f32 {
         if let Some(m) = &mut self.modifier {
-            self.static_val = m.evaluate(self.val);
+            self.static_val = m.evaluate(self.val, ctx);
             self.static_val
         } else {
             self.val
@@ -1,10 +1,37 @@
+pub mod beats_modifier;
 pub mod bounce_modifier;
 pub mod brownian_modifier;
+pub mod choose_modifier;
+pub mod clock;
 pub mod envelope_modifier;
+pub mod lfo_step_modifier;
+pub mod noise_modifier;
 pub mod randrange_modifier;
+pub mod sample_and_hold_modifier;
+pub mod scale_modifier;
+pub mod sequence_modifier;
+pub mod tuning_modifier;
+
+use crate::music_theory::Scale;
+use std::sync::Arc;
+
+/// What a modifier gets to know about the moment it's evaluated in:
+/// the logical time (in seconds) the value is needed for, and the
+/// scale of the generator it belongs to, if that has one.
+#[derive(Clone, Default)]
+pub struct EvalContext {
+    pub time: f64,
+    pub scale: Option<Arc<Scale>>,
+}
+
+impl EvalContext {
+    pub fn at(time: f64) -> Self {
+        EvalContext { time, scale: None }
+    }
+}
 
 pub trait Modifier: ModifierClone {
-    fn evaluate(&mut self, input: f32) -> f32;
+    fn evaluate(&mut self, input: f32, ctx: &EvalContext) -> f32;
     fn shake(&mut self, factor: f32);
 }
 
@@ -0,0 +1,67 @@
+use crate::builtin_types::{BuiltinGlobalParameters, ConfigParameter, GlobalParameters};
+use crate::parameter::modifier::{EvalContext, Modifier};
+use crate::parameter::DynVal;
+use std::sync;
+
+/// the duration of a beat in milliseconds, which is the default duration
+pub fn beat_duration(global_parameters: &GlobalParameters) -> f32 {
+    if let Some(r) = global_parameters.get(&BuiltinGlobalParameters::DefaultDuration) {
+        if let ConfigParameter::Numeric(d) = r.value() {
+            return *d;
+        }
+    }
+    200.0
+}
+
+/// A number of beats, converted to milliseconds every time
+/// it's evaluated, so it follows tempo changes.
+#[derive(Clone)]
+pub struct BeatsModifier {
+    pub beats: DynVal,
+    pub global_parameters: sync::Arc<GlobalParameters>,
+}
+
+impl Modifier for BeatsModifier {
+    fn evaluate(&mut self, _: f32, ctx: &EvalContext) -> f32 {
+        self.beats.evaluate_numerical_in(ctx) * beat_duration(&self.global_parameters)
+    }
+
+    fn shake(&mut self, factor: f32) {
+        self.beats.shake(factor);
+    }
+}
+
+/// duration parameter (in milliseconds) from a number of beats
+pub fn beats_parameter(beats: DynVal, global_parameters: &sync::Arc<GlobalParameters>) -> DynVal {
+    let mut beats = beats;
+    let val = beats.evaluate_numerical() * beat_duration(global_parameters);
+    DynVal {
+        val,
+        static_val: val,
+        modifier: Some(Box::new(BeatsModifier {
+            beats,
+            global_parameters: sync::Arc::clone(global_parameters),
+        })),
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_beats_follow_tempo() {
+        let globals = sync::Arc::new(GlobalParameters::new());
+        let mut sixteenth = beats_parameter(DynVal::with_value(0.25), &globals);
+        assert_eq!(sixteenth.static_val, 50.0);
+
+        // 120 bpm
+        globals.insert(
+            BuiltinGlobalParameters::DefaultDuration,
+            ConfigParameter::Numeric(500.0),
+        );
+        assert_eq!(sixteenth.evaluate_numerical(), 125.0);
+    }
+}
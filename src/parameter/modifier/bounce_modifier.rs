@@ -1,4 +1,5 @@
-use crate::parameter::modifier::Modifier;
+use crate::parameter::modifier::clock::Clock;
+use crate::parameter::modifier::{EvalContext, Modifier};
 use crate::parameter::DynVal;
 
 #[derive(Clone)]
@@ -7,17 +8,22 @@ pub struct BounceModifier {
     pub max: DynVal,
     pub steps: DynVal,
     pub step_count: f32,
+    pub clock: Clock,
 }
 
 impl Modifier for BounceModifier {
-    fn evaluate(&mut self, _: f32) -> f32 {
-        let steps_raw: f32 = self.steps.evaluate_numerical();
+    fn evaluate(&mut self, _: f32, ctx: &EvalContext) -> f32 {
+        let steps_raw: f32 = self
+            .clock
+            .clamp_period(self.steps.evaluate_numerical_in(ctx));
         let dec_inc: f32 = 360.0 / steps_raw;
-        let min_raw: f32 = self.min.evaluate_numerical();
-        let max_raw: f32 = self.max.evaluate_numerical();
+        let min_raw: f32 = self.min.evaluate_numerical_in(ctx);
+        let max_raw: f32 = self.max.evaluate_numerical_in(ctx);
         let range_raw: f32 = max_raw - min_raw;
 
-        let degree: f32 = (dec_inc * (self.step_count % steps_raw)) % 360.0;
+        // with a time-based clock, the steps are milliseconds or beats
+        let degree: f32 =
+            (dec_inc * self.clock.position(self.step_count, steps_raw, ctx.time)) % 360.0;
         let abs_sin: f32 = degree.to_radians().sin().abs();
 
         let cur: f32 = min_raw + (abs_sin * range_raw);
@@ -1,4 +1,4 @@
-use crate::parameter::modifier::Modifier;
+use crate::parameter::modifier::{EvalContext, Modifier};
 use crate::parameter::DynVal;
 use rand::Rng;
 
@@ -12,14 +12,14 @@ pub struct BrownianModifier {
 }
 
 impl Modifier for BrownianModifier {
-    fn evaluate(&mut self, _: f32) -> f32 {
+    fn evaluate(&mut self, _: f32, ctx: &EvalContext) -> f32 {
         // why doesn't rust has a hashable float ?????
         let mut rng = rand::thread_rng();
         // heuristic ... from old megra ... not sure what i thought back then, let's see ...
         let rand = rng.gen_range(0..2000);
-        let step_size = self.step_size.evaluate_numerical();
-        let min = self.min.evaluate_numerical();
-        let max = self.max.evaluate_numerical();
+        let step_size = self.step_size.evaluate_numerical_in(ctx);
+        let min = self.min.evaluate_numerical_in(ctx);
+        let max = self.max.evaluate_numerical_in(ctx);
 
         if rand < 1000 {
             self.current -= step_size;
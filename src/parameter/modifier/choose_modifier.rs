@@ -0,0 +1,74 @@
+use crate::parameter::modifier::{EvalContext, Modifier};
+use crate::parameter::DynVal;
+use rand::Rng;
+
+/// Pick one of several values per evaluation, with optional weights.
+#[derive(Clone)]
+pub struct ChooseModifier {
+    pub values: Vec<DynVal>,
+    pub weights: Vec<f32>,
+}
+
+impl ChooseModifier {
+    /// missing weights default to 1
+    pub fn from_data(values: Vec<DynVal>, weights: &[f32]) -> Self {
+        let weights = (0..values.len())
+            .map(|i| weights.get(i).copied().unwrap_or(1.0).max(0.0))
+            .collect();
+        ChooseModifier { values, weights }
+    }
+}
+
+impl Modifier for ChooseModifier {
+    fn evaluate(&mut self, input: f32, ctx: &EvalContext) -> f32 {
+        if self.values.is_empty() {
+            return input;
+        }
+
+        let sum: f32 = self.weights.iter().sum();
+        let mut idx = self.values.len() - 1;
+        if sum > 0.0 {
+            let mut pick = rand::thread_rng().gen_range(0.0..sum);
+            for (i, w) in self.weights.iter().enumerate() {
+                if pick < *w {
+                    idx = i;
+                    break;
+                }
+                pick -= w;
+            }
+        } else {
+            idx = rand::thread_rng().gen_range(0..self.values.len());
+        }
+
+        self.values[idx].evaluate_numerical_in(ctx)
+    }
+
+    fn shake(&mut self, factor: f32) {
+        for v in self.values.iter_mut() {
+            v.shake(factor);
+        }
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_choose_weights() {
+        let values = vec![
+            DynVal::with_value(100.0),
+            DynVal::with_value(200.0),
+            DynVal::with_value(300.0),
+        ];
+
+        // zero weights are never picked
+        let mut choose = ChooseModifier::from_data(values, &[0.0, 1.0]);
+        assert_eq!(choose.weights, vec![0.0, 1.0, 1.0]);
+        for _ in 0..50 {
+            assert!(choose.evaluate(0.0, &EvalContext::default()) != 100.0);
+        }
+    }
+}
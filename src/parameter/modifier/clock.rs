@@ -0,0 +1,89 @@
+use crate::builtin_types::GlobalParameters;
+use crate::parameter::modifier::beats_modifier::beat_duration;
+use std::sync;
+
+/// What advances a modifier. `Steps` is the classic behaviour (one step
+/// per evaluation), `Time` counts milliseconds and `Beats` counts
+/// beats of the default duration.
+#[derive(Clone)]
+pub enum Clock {
+    Steps,
+    Time,
+    Beats(sync::Arc<GlobalParameters>),
+}
+
+impl Clock {
+    pub fn from_name(name: &str, global_parameters: &sync::Arc<GlobalParameters>) -> Self {
+        match name {
+            "time" | "ms" => Clock::Time,
+            "beats" | "beat" => Clock::Beats(sync::Arc::clone(global_parameters)),
+            _ => Clock::Steps,
+        }
+    }
+
+    /// Keep a period above the shortest one that makes sense for the clock
+    /// (a step, a millisecond or a thousandth of a beat), so modifiers
+    /// never divide by zero.
+    pub fn clamp_period(&self, period: f32) -> f32 {
+        match self {
+            Clock::Steps | Clock::Time => period.max(1.0),
+            Clock::Beats(_) => period.max(0.001),
+        }
+    }
+
+    /// The position within a period, given in the unit of the clock.
+    /// The step count is only used by the step clock, the (logical)
+    /// time in seconds only by the others.
+    pub fn position(&self, step_count: f32, period: f32, time: f64) -> f32 {
+        if period <= 0.0 {
+            return 0.0;
+        }
+        let pos = match self {
+            Clock::Steps => step_count as f64,
+            Clock::Time => time * 1000.0,
+            Clock::Beats(global_parameters) => {
+                time * 1000.0 / beat_duration(global_parameters) as f64
+            }
+        };
+        // keep the precision in f64 as long as possible,
+        // the absolute time grows large
+        pos.rem_euclid(period as f64) as f32
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::builtin_types::{BuiltinGlobalParameters, ConfigParameter};
+
+    #[test]
+    fn test_clock_position() {
+        let globals = sync::Arc::new(GlobalParameters::new());
+        globals.insert(
+            BuiltinGlobalParameters::DefaultDuration,
+            ConfigParameter::Numeric(500.0),
+        );
+
+        assert_eq!(Clock::Steps.position(5.0, 4.0, 10.25), 1.0);
+        // 10250 ms
+        assert_eq!(Clock::Time.position(5.0, 4000.0, 10.25), 2250.0);
+        // 20.5 beats
+        assert_eq!(
+            Clock::from_name("beats", &globals).position(5.0, 4.0, 10.25),
+            0.5
+        );
+    }
+
+    #[test]
+    fn test_clamp_period() {
+        let globals = sync::Arc::new(GlobalParameters::new());
+        assert_eq!(Clock::Steps.clamp_period(0.0), 1.0);
+        assert_eq!(Clock::Steps.clamp_period(16.0), 16.0);
+        assert_eq!(Clock::Time.clamp_period(-5.0), 1.0);
+        assert_eq!(Clock::from_name("beats", &globals).clamp_period(0.0), 0.001);
+        assert_eq!(Clock::from_name("beats", &globals).clamp_period(0.5), 0.5);
+        assert_eq!(Clock::Time.clamp_period(f32::NAN), 1.0);
+    }
+}
@@ -0,0 +1,181 @@
+use crate::parameter::modifier::clock::Clock;
+use crate::parameter::modifier::{EvalContext, Modifier};
+use crate::parameter::DynVal;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LfoShape {
+    Sine,
+    Tri,
+    Saw,
+    Rsaw,
+    Square,
+}
+
+impl LfoShape {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "tri" => LfoShape::Tri,
+            "saw" => LfoShape::Saw,
+            "rsaw" => LfoShape::Rsaw,
+            "sqr" | "square" => LfoShape::Square,
+            _ => LfoShape::Sine,
+        }
+    }
+
+    /// unipolar value for a phase between 0 and 1
+    fn value(&self, phase: f32) -> f32 {
+        match self {
+            LfoShape::Sine => 0.5 + 0.5 * (phase * 2.0 * std::f32::consts::PI).sin(),
+            LfoShape::Tri => 1.0 - (2.0 * phase - 1.0).abs(),
+            LfoShape::Saw => phase,
+            LfoShape::Rsaw => 1.0 - phase,
+            LfoShape::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// An oscillator that advances one step per evaluation,
+/// with a period given in steps (or milliseconds/beats,
+/// depending on the clock).
+#[derive(Clone)]
+pub struct LfoStepModifier {
+    pub period: DynVal,
+    pub shape: LfoShape,
+    pub min: DynVal,
+    pub max: DynVal,
+    pub phase: f32,
+    pub clock: Clock,
+    step_count: f32,
+}
+
+impl LfoStepModifier {
+    pub fn from_data(
+        period: DynVal,
+        shape: LfoShape,
+        min: DynVal,
+        max: DynVal,
+        phase: f32,
+    ) -> Self {
+        LfoStepModifier {
+            period,
+            shape,
+            min,
+            max,
+            phase,
+            clock: Clock::Steps,
+            step_count: 0.0,
+        }
+    }
+}
+
+impl Modifier for LfoStepModifier {
+    fn evaluate(&mut self, _: f32, ctx: &EvalContext) -> f32 {
+        let period = self
+            .clock
+            .clamp_period(self.period.evaluate_numerical_in(ctx));
+        let min = self.min.evaluate_numerical_in(ctx);
+        let max = self.max.evaluate_numerical_in(ctx);
+
+        let phase = (self.clock.position(self.step_count, period, ctx.time) / period + self.phase)
+            .rem_euclid(1.0);
+        self.step_count = (self.step_count + 1.0) % period;
+
+        min + self.shape.value(phase) * (max - min)
+    }
+
+    fn shake(&mut self, factor: f32) {
+        self.min.shake(factor);
+        self.max.shake(factor);
+        self.period.shake(factor);
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_lfo_step() {
+        let mut sine = LfoStepModifier::from_data(
+            DynVal::with_value(4.0),
+            LfoShape::Sine,
+            DynVal::with_value(0.0),
+            DynVal::with_value(2.0),
+            0.0,
+        );
+        let res: Vec<f32> = (0..5)
+            .map(|_| sine.evaluate(0.0, &EvalContext::default()))
+            .collect();
+        let expected = [1.0, 2.0, 1.0, 0.0, 1.0];
+        for (r, e) in res.iter().zip(expected.iter()) {
+            assert_approx_eq::assert_approx_eq!(*r, *e, 0.0001);
+        }
+
+        let mut saw = LfoStepModifier::from_data(
+            DynVal::with_value(4.0),
+            LfoShape::Saw,
+            DynVal::with_value(100.0),
+            DynVal::with_value(200.0),
+            0.0,
+        );
+        let res: Vec<f32> = (0..5)
+            .map(|_| saw.evaluate(0.0, &EvalContext::default()))
+            .collect();
+        assert_eq!(res, vec![100.0, 125.0, 150.0, 175.0, 100.0]);
+
+        // clocked by logical time, independent of the number of evaluations
+        let mut timed = LfoStepModifier::from_data(
+            DynVal::with_value(1000.0),
+            LfoShape::Saw,
+            DynVal::with_value(0.0),
+            DynVal::with_value(1.0),
+            0.0,
+        );
+        timed.clock = Clock::Time;
+        assert_approx_eq::assert_approx_eq!(
+            timed.evaluate(0.0, &EvalContext::at(100.25)),
+            0.25,
+            0.0001
+        );
+        assert_approx_eq::assert_approx_eq!(
+            timed.evaluate(0.0, &EvalContext::at(100.25)),
+            0.25,
+            0.0001
+        );
+        assert_approx_eq::assert_approx_eq!(
+            timed.evaluate(0.0, &EvalContext::at(100.5)),
+            0.5,
+            0.0001
+        );
+
+        // a zero period doesn't break any of the clocks
+        let globals = std::sync::Arc::new(crate::builtin_types::GlobalParameters::new());
+        for clock in [
+            Clock::Steps,
+            Clock::Time,
+            Clock::from_name("beats", &globals),
+        ] {
+            let mut zero = LfoStepModifier::from_data(
+                DynVal::with_value(0.0),
+                LfoShape::Sine,
+                DynVal::with_value(0.0),
+                DynVal::with_value(1.0),
+                0.0,
+            );
+            zero.clock = clock;
+            for i in 0..4 {
+                assert!(zero
+                    .evaluate(0.0, &EvalContext::at(i as f64 * 0.1))
+                    .is_finite());
+            }
+        }
+    }
+}
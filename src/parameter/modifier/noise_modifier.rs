@@ -0,0 +1,120 @@
+use crate::parameter::modifier::{EvalContext, Modifier};
+use crate::parameter::DynVal;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Random values within a range, optionally smoothed (0 is plain white
+/// noise, values towards 1 move more and more slowly).
+#[derive(Clone)]
+pub struct NoiseModifier {
+    pub min: DynVal,
+    pub max: DynVal,
+    pub smooth: DynVal,
+    current: Option<f32>,
+    rng: StdRng,
+}
+
+impl NoiseModifier {
+    pub fn from_data(min: DynVal, max: DynVal, smooth: DynVal) -> Self {
+        NoiseModifier {
+            min,
+            max,
+            smooth,
+            current: None,
+            rng: StdRng::from_entropy(),
+        }
+    }
+}
+
+impl Modifier for NoiseModifier {
+    fn evaluate(&mut self, _: f32, ctx: &EvalContext) -> f32 {
+        let min = self.min.evaluate_numerical_in(ctx);
+        let max = self.max.evaluate_numerical_in(ctx);
+        let smooth = self.smooth.evaluate_numerical_in(ctx).clamp(0.0, 0.999);
+
+        let target = if (min - max).abs() < f32::EPSILON {
+            min
+        } else {
+            self.rng.gen_range(min.min(max)..min.max(max))
+        };
+
+        let next = if let Some(cur) = self.current {
+            cur + (target - cur) * (1.0 - smooth)
+        } else {
+            target
+        };
+        self.current = Some(next);
+        next
+    }
+
+    fn shake(&mut self, factor: f32) {
+        self.min.shake(factor);
+        self.max.shake(factor);
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    // the same noise every time, for a given seed
+    fn noise(min: f32, max: f32, smooth: f32, seed: u64) -> NoiseModifier {
+        NoiseModifier {
+            rng: StdRng::seed_from_u64(seed),
+            ..NoiseModifier::from_data(
+                DynVal::with_value(min),
+                DynVal::with_value(max),
+                DynVal::with_value(smooth),
+            )
+        }
+    }
+
+    fn run(noise: &mut NoiseModifier, steps: usize) -> Vec<f32> {
+        (0..steps)
+            .map(|_| noise.evaluate(0.0, &EvalContext::default()))
+            .collect()
+    }
+
+    // mean distance between subsequent values
+    fn mean_step(vals: &[f32]) -> f32 {
+        vals.windows(2).map(|w| (w[1] - w[0]).abs()).sum::<f32>() / (vals.len() - 1) as f32
+    }
+
+    #[test]
+    fn test_noise_range() {
+        let white = run(&mut noise(100.0, 200.0, 0.0, 42), 1000);
+        assert!(white.iter().all(|v| (100.0..200.0).contains(v)));
+        // spread over the whole range
+        assert!(white.iter().any(|v| *v < 110.0));
+        assert!(white.iter().any(|v| *v > 190.0));
+
+        // same seed, same noise
+        assert_eq!(white, run(&mut noise(100.0, 200.0, 0.0, 42), 1000));
+
+        // min and max can be given in any order
+        let swapped = run(&mut noise(200.0, 100.0, 0.0, 42), 1000);
+        assert!(swapped.iter().all(|v| (100.0..200.0).contains(v)));
+
+        // no range, no noise
+        assert!(run(&mut noise(3.0, 3.0, 0.5, 42), 10)
+            .iter()
+            .all(|v| *v == 3.0));
+    }
+
+    #[test]
+    fn test_noise_continuity() {
+        let white = run(&mut noise(100.0, 200.0, 0.0, 7), 1000);
+        let smooth = run(&mut noise(100.0, 200.0, 0.9, 7), 1000);
+
+        // smoothing stays in range ...
+        assert!(smooth.iter().all(|v| (100.0..200.0).contains(v)));
+        // ... never moves more than a tenth of the range per step ...
+        assert!(smooth
+            .windows(2)
+            .all(|w| (w[1] - w[0]).abs() <= 10.0 + 0.001));
+        // ... and moves a lot less than white noise
+        assert!(mean_step(&smooth) < mean_step(&white) * 0.25);
+    }
+}
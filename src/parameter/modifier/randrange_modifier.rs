@@ -1,4 +1,4 @@
-use crate::parameter::modifier::Modifier;
+use crate::parameter::modifier::{EvalContext, Modifier};
 use crate::parameter::DynVal;
 use rand::Rng;
 
@@ -15,9 +15,9 @@ impl RandRangeModifier {
 }
 
 impl Modifier for RandRangeModifier {
-    fn evaluate(&mut self, _: f32) -> f32 {
-        let min = self.min.evaluate_numerical();
-        let max = self.max.evaluate_numerical();
+    fn evaluate(&mut self, _: f32, ctx: &EvalContext) -> f32 {
+        let min = self.min.evaluate_numerical_in(ctx);
+        let max = self.max.evaluate_numerical_in(ctx);
         let mut rng = rand::thread_rng();
         if (min - max).abs() < f32::EPSILON {
             // min == max
@@ -0,0 +1,62 @@
+use crate::parameter::modifier::{EvalContext, Modifier};
+use crate::parameter::DynVal;
+
+/// Sample the inner value every n evaluations and hold it in between.
+#[derive(Clone)]
+pub struct SampleAndHoldModifier {
+    pub inner: DynVal,
+    pub every: DynVal,
+    count: usize,
+    current: f32,
+}
+
+impl SampleAndHoldModifier {
+    pub fn from_data(inner: DynVal, every: DynVal) -> Self {
+        SampleAndHoldModifier {
+            inner,
+            every,
+            count: 0,
+            current: 0.0,
+        }
+    }
+}
+
+impl Modifier for SampleAndHoldModifier {
+    fn evaluate(&mut self, _: f32, ctx: &EvalContext) -> f32 {
+        if self.count == 0 {
+            self.current = self.inner.evaluate_numerical_in(ctx);
+        }
+        let every = (self.every.evaluate_numerical_in(ctx) as usize).max(1);
+        self.count = (self.count + 1) % every;
+        self.current
+    }
+
+    fn shake(&mut self, factor: f32) {
+        self.inner.shake(factor);
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::parameter::modifier::sequence_modifier::{SequenceMode, SequenceModifier};
+
+    #[test]
+    fn test_sample_and_hold() {
+        let inner = DynVal {
+            val: 0.0,
+            static_val: 0.0,
+            modifier: Some(Box::new(SequenceModifier::from_data(
+                vec![DynVal::with_value(1.0), DynVal::with_value(2.0)],
+                SequenceMode::Cycle,
+            ))),
+        };
+        let mut sh = SampleAndHoldModifier::from_data(inner, DynVal::with_value(3.0));
+        let res: Vec<f32> = (0..7)
+            .map(|_| sh.evaluate(0.0, &EvalContext::default()))
+            .collect();
+        assert_eq!(res, vec![1.0, 1.0, 1.0, 2.0, 2.0, 2.0, 1.0]);
+    }
+}
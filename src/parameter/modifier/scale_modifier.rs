@@ -0,0 +1,173 @@
+use crate::builtin_types::{BuiltinGlobalParameters, ConfigParameter, GlobalParameters};
+use crate::music_theory::Scale;
+use crate::parameter::modifier::tuning_modifier::active_tuning;
+use crate::parameter::modifier::{EvalContext, Modifier};
+use crate::parameter::{DynVal, ParameterValue};
+use std::sync;
+
+/// the global scale, or the default (c major) if none has been set
+pub fn active_scale(global_parameters: &GlobalParameters) -> sync::Arc<Scale> {
+    if let Some(r) = global_parameters.get(&BuiltinGlobalParameters::GlobalScale) {
+        if let ConfigParameter::Scale(s) = r.value() {
+            return sync::Arc::clone(s);
+        }
+    }
+    sync::Arc::new(Scale::default())
+}
+
+/// the scale of the generator, if it has one, the global scale otherwise
+pub fn context_scale(ctx: &EvalContext, global_parameters: &GlobalParameters) -> sync::Arc<Scale> {
+    ctx.scale
+        .clone()
+        .unwrap_or_else(|| active_scale(global_parameters))
+}
+
+/// Snaps the (modified) value to the scale of the generator,
+/// or the global scale.
+#[derive(Clone)]
+pub struct QuantizeModifier {
+    pub inner: Option<Box<dyn Modifier + Send + Sync>>,
+    pub global_parameters: sync::Arc<GlobalParameters>,
+}
+
+impl Modifier for QuantizeModifier {
+    fn evaluate(&mut self, input: f32, ctx: &EvalContext) -> f32 {
+        let val = if let Some(m) = self.inner.as_mut() {
+            m.evaluate(input, ctx)
+        } else {
+            input
+        };
+        context_scale(ctx, &self.global_parameters)
+            .quantize(val, &active_tuning(&self.global_parameters))
+    }
+
+    fn shake(&mut self, factor: f32) {
+        if let Some(m) = self.inner.as_mut() {
+            m.shake(factor);
+        }
+    }
+}
+
+/// A scale degree, resolved against the scale of the generator
+/// (or the global scale) every time it's evaluated.
+#[derive(Clone)]
+pub struct DegreeModifier {
+    pub degree: DynVal,
+    pub octave: DynVal,
+    pub global_parameters: sync::Arc<GlobalParameters>,
+}
+
+impl Modifier for DegreeModifier {
+    fn evaluate(&mut self, _: f32, ctx: &EvalContext) -> f32 {
+        let degree = self.degree.evaluate_numerical_in(ctx).round() as i32;
+        let octave = self.octave.evaluate_numerical_in(ctx).round() as i32;
+        context_scale(ctx, &self.global_parameters).degree(
+            degree,
+            octave,
+            &active_tuning(&self.global_parameters),
+        )
+    }
+
+    fn shake(&mut self, factor: f32) {
+        self.degree.shake(factor);
+    }
+}
+
+/// make a (pitch) parameter snap to the active scale,
+/// only works for scalar values so far
+pub fn quantize_parameter(
+    par: &mut ParameterValue,
+    global_parameters: &sync::Arc<GlobalParameters>,
+) {
+    if let ParameterValue::Scalar(val) = par {
+        val.modifier = Some(Box::new(QuantizeModifier {
+            inner: val.modifier.take(),
+            global_parameters: sync::Arc::clone(global_parameters),
+        }));
+    } else {
+        println!("can only quantize scalar values so far ...");
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_quantize_and_degree() {
+        let globals = sync::Arc::new(GlobalParameters::new());
+
+        let mut par = ParameterValue::Scalar(DynVal::with_value(460.0));
+        quantize_parameter(&mut par, &globals);
+
+        let mut deg = DynVal {
+            val: 0.0,
+            static_val: 0.0,
+            modifier: Some(Box::new(DegreeModifier {
+                degree: DynVal::with_value(3.0),
+                octave: DynVal::with_value(0.0),
+                global_parameters: sync::Arc::clone(&globals),
+            })),
+        };
+
+        if let ParameterValue::Scalar(val) = &mut par {
+            // c major, a4 is the nearest
+            assert!((val.evaluate_numerical() - 440.0).abs() < 0.01);
+            // e4
+            assert!((deg.evaluate_numerical() - 329.63).abs() < 0.01);
+
+            // switch live
+            globals.insert(
+                BuiltinGlobalParameters::GlobalScale,
+                ConfigParameter::Scale(sync::Arc::new(Scale::from_name("minor", "a").unwrap())),
+            );
+            assert!((val.evaluate_numerical() - 440.0).abs() < 0.01);
+            // c5
+            assert!((deg.evaluate_numerical() - 523.25).abs() < 0.01);
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_generator_scale() {
+        let globals = sync::Arc::new(GlobalParameters::new());
+
+        let mut par = ParameterValue::Scalar(DynVal::with_value(356.0));
+        quantize_parameter(&mut par, &globals);
+
+        let mut deg = DynVal {
+            val: 0.0,
+            static_val: 0.0,
+            modifier: Some(Box::new(DegreeModifier {
+                degree: DynVal::with_value(3.0),
+                octave: DynVal::with_value(0.0),
+                global_parameters: sync::Arc::clone(&globals),
+            })),
+        };
+
+        let pentatonic = EvalContext {
+            time: 0.0,
+            scale: Some(sync::Arc::new(
+                Scale::from_name("minor-pentatonic", "a").unwrap(),
+            )),
+        };
+        let dorian = EvalContext {
+            time: 0.0,
+            scale: Some(sync::Arc::new(Scale::from_name("dorian", "d").unwrap())),
+        };
+
+        if let ParameterValue::Scalar(val) = &mut par {
+            // f4 in the global c major, e4 in the generator's scale
+            assert!((val.evaluate_numerical() - 349.23).abs() < 0.01);
+            assert!((val.evaluate_numerical_in(&pentatonic) - 329.63).abs() < 0.01);
+        } else {
+            panic!();
+        }
+        // e4 in c major, f4 in d dorian
+        assert!((deg.evaluate_numerical() - 329.63).abs() < 0.01);
+        assert!((deg.evaluate_numerical_in(&dorian) - 349.23).abs() < 0.01);
+    }
+}
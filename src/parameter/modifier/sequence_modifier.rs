@@ -0,0 +1,118 @@
+use crate::parameter::modifier::{EvalContext, Modifier};
+use crate::parameter::DynVal;
+use rand::Rng;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SequenceMode {
+    Cycle,
+    PingPong,
+    Random,
+}
+
+impl SequenceMode {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "pingpong" => SequenceMode::PingPong,
+            "random" => SequenceMode::Random,
+            _ => SequenceMode::Cycle,
+        }
+    }
+}
+
+/// Step through a list of values, one per evaluation.
+#[derive(Clone)]
+pub struct SequenceModifier {
+    pub values: Vec<DynVal>,
+    pub mode: SequenceMode,
+    idx: usize,
+    forward: bool,
+}
+
+impl SequenceModifier {
+    pub fn from_data(values: Vec<DynVal>, mode: SequenceMode) -> Self {
+        SequenceModifier {
+            values,
+            mode,
+            idx: 0,
+            forward: true,
+        }
+    }
+}
+
+impl Modifier for SequenceModifier {
+    fn evaluate(&mut self, input: f32, ctx: &EvalContext) -> f32 {
+        let len = self.values.len();
+        if len == 0 {
+            return input;
+        }
+
+        if self.mode == SequenceMode::Random {
+            self.idx = rand::thread_rng().gen_range(0..len);
+        }
+
+        let cur = self.values[self.idx.min(len - 1)].evaluate_numerical_in(ctx);
+
+        match self.mode {
+            SequenceMode::Cycle => {
+                self.idx = (self.idx + 1) % len;
+            }
+            SequenceMode::PingPong => {
+                if len > 1 {
+                    if self.forward && self.idx + 1 >= len {
+                        self.forward = false;
+                    } else if !self.forward && self.idx == 0 {
+                        self.forward = true;
+                    }
+                    if self.forward {
+                        self.idx += 1;
+                    } else {
+                        self.idx -= 1;
+                    }
+                }
+            }
+            SequenceMode::Random => {}
+        }
+
+        cur
+    }
+
+    fn shake(&mut self, factor: f32) {
+        for v in self.values.iter_mut() {
+            v.shake(factor);
+        }
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_sequence_modes() {
+        let values = vec![
+            DynVal::with_value(100.0),
+            DynVal::with_value(200.0),
+            DynVal::with_value(300.0),
+        ];
+
+        let mut cycle = SequenceModifier::from_data(values.clone(), SequenceMode::Cycle);
+        let res: Vec<f32> = (0..5)
+            .map(|_| cycle.evaluate(0.0, &EvalContext::default()))
+            .collect();
+        assert_eq!(res, vec![100.0, 200.0, 300.0, 100.0, 200.0]);
+
+        let mut pingpong = SequenceModifier::from_data(values.clone(), SequenceMode::PingPong);
+        let res: Vec<f32> = (0..7)
+            .map(|_| pingpong.evaluate(0.0, &EvalContext::default()))
+            .collect();
+        assert_eq!(res, vec![100.0, 200.0, 300.0, 200.0, 100.0, 200.0, 300.0]);
+
+        let mut random = SequenceModifier::from_data(values, SequenceMode::Random);
+        for _ in 0..20 {
+            let v = random.evaluate(0.0, &EvalContext::default());
+            assert!(v == 100.0 || v == 200.0 || v == 300.0);
+        }
+    }
+}
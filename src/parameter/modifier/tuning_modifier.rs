@@ -0,0 +1,122 @@
+use crate::builtin_types::{BuiltinGlobalParameters, ConfigParameter, GlobalParameters};
+use crate::music_theory::{self, Tuning};
+use crate::parameter::modifier::{EvalContext, Modifier};
+use crate::parameter::DynVal;
+use std::sync;
+
+/// the global tuning, or equal temperament if none has been set
+pub fn active_tuning(global_parameters: &GlobalParameters) -> sync::Arc<Tuning> {
+    if let Some(r) = global_parameters.get(&BuiltinGlobalParameters::GlobalTuning) {
+        if let ConfigParameter::Tuning(t) = r.value() {
+            return sync::Arc::clone(t);
+        }
+    }
+    // init on first attempt, so the default isn't built on every evaluation
+    if let ConfigParameter::Tuning(t) = global_parameters
+        .entry(BuiltinGlobalParameters::GlobalTuning)
+        .or_insert(ConfigParameter::Tuning(sync::Arc::new(Tuning::default())))
+        .value()
+    {
+        sync::Arc::clone(t)
+    } else {
+        sync::Arc::new(Tuning::default())
+    }
+}
+
+/// A (midi) key, resolved against the active tuning
+/// every time it's evaluated.
+#[derive(Clone)]
+pub struct NoteModifier {
+    pub key: DynVal,
+    pub global_parameters: sync::Arc<GlobalParameters>,
+}
+
+impl Modifier for NoteModifier {
+    fn evaluate(&mut self, _: f32, ctx: &EvalContext) -> f32 {
+        active_tuning(&self.global_parameters).key_freq(self.key.evaluate_numerical_in(ctx))
+    }
+
+    fn shake(&mut self, factor: f32) {
+        self.key.shake(factor);
+    }
+}
+
+/// The factor to transpose by a number of steps in the active tuning.
+#[derive(Clone)]
+pub struct TransposeModifier {
+    pub steps: f32,
+    pub global_parameters: sync::Arc<GlobalParameters>,
+}
+
+impl Modifier for TransposeModifier {
+    fn evaluate(&mut self, _: f32, _: &EvalContext) -> f32 {
+        active_tuning(&self.global_parameters).step_factor(self.steps)
+    }
+
+    fn shake(&mut self, _: f32) {}
+}
+
+/// frequency parameter from a (midi) key
+pub fn key_parameter(key: DynVal, global_parameters: &sync::Arc<GlobalParameters>) -> DynVal {
+    let mut key = key;
+    let val = active_tuning(global_parameters).key_freq(key.evaluate_numerical());
+    DynVal {
+        val,
+        static_val: val,
+        modifier: Some(Box::new(NoteModifier {
+            key,
+            global_parameters: sync::Arc::clone(global_parameters),
+        })),
+    }
+}
+
+/// frequency parameter from a note name, like 'a4
+pub fn note_parameter(note: &str, global_parameters: &sync::Arc<GlobalParameters>) -> DynVal {
+    let key = music_theory::to_key(music_theory::from_string(note));
+    key_parameter(DynVal::with_value(key as f32), global_parameters)
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_switch_tuning() {
+        let globals = sync::Arc::new(GlobalParameters::new());
+        let mut c4 = note_parameter("c4", &globals);
+        let mut fifth = DynVal {
+            val: 0.0,
+            static_val: 0.0,
+            modifier: Some(Box::new(TransposeModifier {
+                steps: 7.0,
+                global_parameters: sync::Arc::clone(&globals),
+            })),
+        };
+
+        assert!((c4.evaluate_numerical() - 261.63).abs() < 0.01);
+        assert!((fifth.evaluate_numerical() - 1.4983).abs() < 0.0001);
+        // the default is only built once
+        assert!(sync::Arc::ptr_eq(
+            &active_tuning(&globals),
+            &active_tuning(&globals)
+        ));
+
+        let mut just = Tuning::just();
+        just.mapping.ref_freq = 432.0;
+        globals.insert(
+            BuiltinGlobalParameters::GlobalTuning,
+            ConfigParameter::Tuning(sync::Arc::new(just)),
+        );
+
+        assert!((c4.evaluate_numerical() - 259.2).abs() < 0.01);
+
+        globals.insert(
+            BuiltinGlobalParameters::GlobalTuning,
+            ConfigParameter::Tuning(sync::Arc::new(Tuning::equal(19))),
+        );
+        // 7 of 19 steps
+        assert!((fifth.evaluate_numerical() - 2f32.powf(7.0 / 19.0)).abs() < 0.0001);
+    }
+}
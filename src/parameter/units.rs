@@ -0,0 +1,82 @@
+use crate::builtin_types::GlobalParameters;
+use crate::parameter::modifier::tuning_modifier::note_parameter;
+use crate::parameter::DynVal;
+use ruffbox_synth::building_blocks::SynthParameterLabel;
+use std::sync;
+
+// Unit conversions for parameter values. Everything is converted to
+// the units the synth expects (Hz, linear gain, milliseconds) before
+// it ends up in an event, so arithmetic between events works on
+// plain numbers.
+
+/// decibels to linear gain
+pub fn db_to_gain(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+/// a fraction of a whole note (like 1/16) in beats,
+/// a beat being a quarter note
+pub fn fraction_to_beats(fraction: f32) -> f32 {
+    4.0 * fraction
+}
+
+/// parameters that take a duration, and thus also beats
+pub fn is_time_label(label: SynthParameterLabel) -> bool {
+    label == SynthParameterLabel::Duration
+        || label == SynthParameterLabel::Attack
+        || label == SynthParameterLabel::Decay
+        || label == SynthParameterLabel::Sustain
+        || label == SynthParameterLabel::Release
+}
+
+/// parameters that take a level, and thus also decibels
+pub fn is_level_label(label: SynthParameterLabel) -> bool {
+    label == SynthParameterLabel::EnvelopeLevel
+        || label == SynthParameterLabel::OscillatorAmplitude
+        || label == SynthParameterLabel::AttackPeakLevel
+}
+
+/// parameters that take a frequency, and thus also note names
+pub fn is_frequency_label(label: SynthParameterLabel) -> bool {
+    label == SynthParameterLabel::PitchFrequency
+        || label == SynthParameterLabel::LowpassCutoffFrequency
+        || label == SynthParameterLabel::HighpassCutoffFrequency
+        || label == SynthParameterLabel::PeakFrequency
+}
+
+/// a note name like 'a4, if the parameter takes a frequency
+pub fn note_value(
+    label: SynthParameterLabel,
+    symbol: &str,
+    global_parameters: &sync::Arc<GlobalParameters>,
+) -> Option<DynVal> {
+    if is_frequency_label(label) {
+        Some(note_parameter(symbol, global_parameters))
+    } else {
+        None
+    }
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_units() {
+        assert!((db_to_gain(0.0) - 1.0).abs() < 0.0001);
+        assert!((db_to_gain(-6.0) - 0.501).abs() < 0.001);
+        assert_eq!(fraction_to_beats(1.0 / 16.0), 0.25);
+        assert_eq!(fraction_to_beats(3.0 / 4.0), 3.0);
+        assert!(is_time_label(SynthParameterLabel::Release));
+        assert!(!is_time_label(SynthParameterLabel::EnvelopeLevel));
+        assert!(is_level_label(SynthParameterLabel::EnvelopeLevel));
+        assert!(!is_level_label(SynthParameterLabel::Peak1Gain));
+
+        let globals = sync::Arc::new(GlobalParameters::new());
+        let a4 = note_value(SynthParameterLabel::LowpassCutoffFrequency, "a4", &globals);
+        assert!((a4.unwrap().static_val - 440.0).abs() < 0.01);
+        assert!(note_value(SynthParameterLabel::EnvelopeLevel, "a4", &globals).is_none());
+    }
+}
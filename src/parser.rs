@@ -1,6 +1,9 @@
 use crate::event::{ControlEvent, Event};
+use crate::event_helpers::map_parameter;
 use crate::generator::Generator;
 use crate::markov_sequence_generator::Rule;
+use crate::parameter::modifier::beats_modifier::beats_parameter;
+use crate::parameter::units;
 use crate::parameter::{DynVal, ParameterValue};
 use crate::session::SyncContext;
 use crate::{Command, GeneratorProcessorOrModifier, GlobalParameters, PartProxy};
@@ -12,9 +15,9 @@ use nom::{
     character::{is_alphanumeric, is_newline, is_space},
     combinator::{cut, map, map_res, recognize},
     error::{context, ErrorKind, VerboseError, VerboseErrorKind},
-    multi::many0,
+    multi::{many0, separated_list0},
     number::complete::float,
-    sequence::{delimited, preceded, tuple},
+    sequence::{delimited, preceded, separated_pair, terminated, tuple},
     Err, IResult, Parser,
 };
 use parking_lot::Mutex;
@@ -27,13 +30,16 @@ pub mod eval;
 
 /// These are the basic building blocks of our casual lisp language.
 /// You might notice that there's no lists in this lisp ... not sure
-/// what to call it in that case ...
+/// what to call it in that case ... (except for quoted lists of symbols)
 #[derive(Debug)]
 pub enum Atom {
     Float(f32),
     String(String),
     Keyword(String),
     Symbol(String),
+    SymbolList(Vec<String>),
+    Fraction(f32), // like 1/16, which might be a duration in beats
+    Decibel(f32),  // like -6db, which might be a level
     Boolean(bool),
     Function(String),
 }
@@ -57,6 +63,7 @@ pub enum BuiltIn {
     GeneratorProcessorOrModifierList(Vec<GeneratorProcessorOrModifier>),
     GeneratorModifierList(Vec<GeneratorProcessorOrModifier>),
     SoundEvent(Event),
+    SoundEventList(Vec<Event>),
     Parameter(DynVal),
     Modulator(ParameterValue),
     Matrix(ParameterValue),
@@ -83,6 +90,7 @@ impl fmt::Debug for BuiltIn {
             }
             BuiltIn::GeneratorModifierList(_) => write!(f, "BuiltIn::GeneratorModifierList(..)"),
             BuiltIn::SoundEvent(_) => write!(f, "BuiltIn::SoundEvent(..)"),
+            BuiltIn::SoundEventList(_) => write!(f, "BuiltIn::SoundEventList(..)"),
             BuiltIn::Parameter(_) => write!(f, "BuiltIn::Parameter(..)"),
             BuiltIn::Modulator(_) => write!(f, "BuiltIn::Modulator(..)"),
             BuiltIn::Vector(_) => write!(f, "BuiltIn::Vector(..)"),
@@ -96,6 +104,7 @@ impl fmt::Debug for BuiltIn {
 pub enum EvaluatedExpr {
     Float(f32),
     Symbol(String),
+    SymbolList(Vec<String>),
     Keyword(String),
     String(String),
     Boolean(bool),
@@ -108,6 +117,7 @@ impl fmt::Debug for EvaluatedExpr {
         match self {
             EvaluatedExpr::Float(fl) => write!(f, "EvaluatedExpr::Float({})", fl),
             EvaluatedExpr::Symbol(s) => write!(f, "EvaluatedExpr::Symbol({})", s),
+            EvaluatedExpr::SymbolList(s) => write!(f, "EvaluatedExpr::SymbolList({:?})", s),
             EvaluatedExpr::Keyword(k) => write!(f, "EvaluatedExpr::Keyword({})", k),
             EvaluatedExpr::String(s) => write!(f, "EvaluatedExpr::String({})", s),
             EvaluatedExpr::Boolean(b) => write!(f, "EvaluatedExpr::Boolean({})", b),
@@ -117,25 +127,118 @@ impl fmt::Debug for EvaluatedExpr {
     }
 }
 
+pub type EvalFunction = fn(
+    &FunctionMap,
+    &mut Vec<EvaluatedExpr>,
+    &sync::Arc<GlobalParameters>,
+    &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    OutputMode,
+) -> Option<EvaluatedExpr>;
+
+/// What kind of thing a DSL function returns, used by the
+/// editor to highlight and complete it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum FunctionCategory {
+    Session,
+    Constructor,
+    Command,
+    Event,
+    Sample,
+    Parameter,
+    Modulator,
+    Dynpar,
+    Processor,
+    Composition,
+    Modifier,
+    Arithmetic,
+}
+
+/// The signature of a DSL function, written the way it's called, i.e.
+/// "(cyc 'name \"pattern\" :dur :rep)". Optional positional arguments
+/// are in brackets, "..." marks a variable number of arguments.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FunctionInfo {
+    pub category: FunctionCategory,
+    pub name: String,
+    pub signature: String,
+}
+
+impl FunctionInfo {
+    /// the signature is made from the name and the arguments,
+    /// i.e. "cyc" and "'name \"pattern\" :dur :rep"
+    pub fn new(category: FunctionCategory, name: &str, args: &str) -> Self {
+        let signature = if args.is_empty() {
+            format!("({})", name)
+        } else {
+            format!("({} {})", name, args)
+        };
+        FunctionInfo {
+            category,
+            name: name.to_string(),
+            signature,
+        }
+    }
+
+    fn tokens(&self) -> impl Iterator<Item = &str> {
+        self.signature
+            .trim_start_matches('(')
+            .trim_end_matches(')')
+            .split_whitespace()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// the keyword arguments, without the colon
+    pub fn keywords(&self) -> Vec<&str> {
+        self.tokens().filter_map(|t| t.strip_prefix(':')).collect()
+    }
+
+    /// minimum and maximum number of positional arguments,
+    /// no maximum if the function takes a variable number
+    pub fn arity(&self) -> (usize, Option<usize>) {
+        let mut min = 0;
+        let mut max = Some(0);
+        for t in self.tokens().skip(1).take_while(|t| !t.starts_with(':')) {
+            if t.trim_end_matches(']').ends_with("...") {
+                max = None;
+            } else if let Some(m) = max.as_mut() {
+                *m += 1;
+            }
+            if !t.starts_with('[') && !t.ends_with("...") {
+                min += 1;
+            }
+        }
+        (min, max)
+    }
+}
+
 pub struct FunctionMap {
-    pub fmap: HashMap<
-        String,
-        fn(
-            &FunctionMap,
-            &mut Vec<EvaluatedExpr>,
-            &sync::Arc<GlobalParameters>,
-            &sync::Arc<Mutex<SampleAndWavematrixSet>>,
-            OutputMode,
-        ) -> Option<EvaluatedExpr>,
-    >,
+    pub fmap: HashMap<String, EvalFunction>,
+    pub info: HashMap<String, FunctionInfo>,
 }
 
 impl FunctionMap {
     pub fn new() -> Self {
         FunctionMap {
             fmap: HashMap::new(),
+            info: HashMap::new(),
         }
     }
+
+    /// register a function along with its arguments
+    pub fn register(
+        &mut self,
+        category: FunctionCategory,
+        name: &str,
+        args: &str,
+        fun: EvalFunction,
+    ) {
+        self.fmap.insert(name.to_string(), fun);
+        self.info
+            .insert(name.to_string(), FunctionInfo::new(category, name, args));
+    }
 }
 
 /// valid chars for a string
@@ -198,6 +301,24 @@ pub fn parse_symbol(i: &str) -> IResult<&str, Atom, VerboseError<&str>> {
     )(i)
 }
 
+/// a quoted list of symbols, like '(i iv v i)
+pub fn parse_symbol_list(i: &str) -> IResult<&str, Atom, VerboseError<&str>> {
+    map(
+        context(
+            "symbol list",
+            delimited(
+                tag("'("),
+                preceded(
+                    multispace0,
+                    separated_list0(multispace1, take_while1(valid_function_name_char)),
+                ),
+                preceded(multispace0, char(')')),
+            ),
+        ),
+        |syms: Vec<&str>| Atom::SymbolList(syms.iter().map(|s| s.to_string()).collect()),
+    )(i)
+}
+
 /// function names are language constructs that contain allowed function name chars
 fn parse_function(i: &str) -> IResult<&str, Atom, VerboseError<&str>> {
     map(
@@ -224,13 +345,40 @@ pub fn parse_float(i: &str) -> IResult<&str, Atom, VerboseError<&str>> {
     }
 }
 
+/// decibels like -6db are plain numbers, except for levels,
+/// where they're converted to linear gain (see `eval_decibel`)
+fn parse_decibel(i: &str) -> IResult<&str, Atom, VerboseError<&str>> {
+    map(
+        context("decibel", terminated(float, tag("db"))),
+        Atom::Decibel,
+    )(i)
+}
+
+/// fractions like 1/16 are plain numbers, except for durations,
+/// where they're fractions of a whole note (see `eval_beats`)
+fn parse_fraction(i: &str) -> IResult<&str, Atom, VerboseError<&str>> {
+    map_res(
+        context("fraction", separated_pair(float, char('/'), float)),
+        |(n, d): (f32, f32)| {
+            if d == 0.0 {
+                Err("division by zero")
+            } else {
+                Ok(Atom::Fraction(n / d))
+            }
+        },
+    )(i)
+}
+
 /// parse all the atoms
 fn parse_constant(i: &str) -> IResult<&str, Expr, VerboseError<&str>> {
     map(
         alt((
             parse_boolean,
+            parse_decibel,
+            parse_fraction,
             parse_float,
             parse_keyword,
+            parse_symbol_list,
             parse_symbol,
             parse_string,
             parse_function,
@@ -282,6 +430,75 @@ pub fn parse_expr(i: &str) -> IResult<&str, Expr, VerboseError<&str>> {
     alt((parse_application, parse_constant))(i)
 }
 
+/// Why evaluation failed, and where, as a byte range
+/// in the source, if that could be determined.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EvalError {
+    pub message: String,
+    pub span: Option<(usize, usize)>,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+// keeps count of the applications, so the one that
+// failed can be found in the source later
+#[derive(Default)]
+struct EvalTrace {
+    applications: usize,
+    failure: Option<(usize, String)>,
+}
+
+impl EvalTrace {
+    // only the innermost failure is interesting
+    fn fail(&mut self, application: usize, message: String) {
+        if self.failure.is_none() {
+            self.failure = Some((application, message));
+        }
+    }
+}
+
+/// A fraction following a duration keyword, like `:dur 1/16`, is a
+/// fraction of a whole note, which is converted to a duration in
+/// milliseconds that follows the tempo. This is the only place where
+/// fractions are treated as beats, everywhere else they're numbers.
+fn eval_beats(
+    previous: Option<&Expr>,
+    expr: &Expr,
+    globals: &sync::Arc<GlobalParameters>,
+) -> Option<EvaluatedExpr> {
+    if let (Some(Expr::Constant(Atom::Keyword(k))), Expr::Constant(Atom::Fraction(f))) =
+        (previous, expr)
+    {
+        if units::is_time_label(map_parameter(k)) {
+            return Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(beats_parameter(
+                DynVal::with_value(units::fraction_to_beats(*f)),
+                globals,
+            ))));
+        }
+    }
+    None
+}
+
+/// Decibels following a level keyword, like `:lvl -6db`, or as the
+/// argument of a level parameter, like `(lvl -6db)`, are converted to
+/// linear gain. Everywhere else they're numbers, like fractions.
+fn eval_decibel(previous: Option<&Expr>, expr: &Expr) -> Option<EvaluatedExpr> {
+    if let (
+        Some(Expr::Constant(Atom::Keyword(p) | Atom::Function(p))),
+        Expr::Constant(Atom::Decibel(db)),
+    ) = (previous, expr)
+    {
+        if units::is_level_label(map_parameter(p)) {
+            return Some(EvaluatedExpr::Float(units::db_to_gain(*db)));
+        }
+    }
+    None
+}
+
 /// This one reduces the abstract syntax tree ...
 pub fn eval_expression(
     e: &Expr,
@@ -289,55 +506,267 @@ pub fn eval_expression(
     globals: &sync::Arc<GlobalParameters>,
     sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
     out_mode: OutputMode,
+) -> Option<EvaluatedExpr> {
+    eval_expression_traced(
+        e,
+        functions,
+        globals,
+        sample_set,
+        out_mode,
+        &mut EvalTrace::default(),
+    )
+}
+
+fn eval_expression_traced(
+    e: &Expr,
+    functions: &FunctionMap,
+    globals: &sync::Arc<GlobalParameters>,
+    sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    out_mode: OutputMode,
+    trace: &mut EvalTrace,
 ) -> Option<EvaluatedExpr> {
     match e {
         Expr::Constant(c) => Some(match c {
             Atom::Float(f) => EvaluatedExpr::Float(*f),
             Atom::Symbol(s) => EvaluatedExpr::Symbol(s.to_string()),
+            Atom::SymbolList(s) => EvaluatedExpr::SymbolList(s.clone()),
+            Atom::Fraction(f) => EvaluatedExpr::Float(*f),
+            Atom::Decibel(db) => EvaluatedExpr::Float(*db),
             Atom::Keyword(k) => EvaluatedExpr::Keyword(k.to_string()),
             Atom::String(s) => EvaluatedExpr::String(s.to_string()),
             Atom::Boolean(b) => EvaluatedExpr::Boolean(*b),
             Atom::Function(f) => EvaluatedExpr::FunctionName(f.to_string()),
         }),
         Expr::Application(head, tail) => {
+            let application = trace.applications;
+            trace.applications += 1;
             if let Some(EvaluatedExpr::FunctionName(f)) =
-                eval_expression(head, functions, globals, sample_set, out_mode)
+                eval_expression_traced(head, functions, globals, sample_set, out_mode, trace)
             {
                 // check if we have this function ...
                 if functions.fmap.contains_key(&f) {
                     let mut reduced_tail = tail
                         .iter()
-                        .map(|expr| eval_expression(expr, functions, globals, sample_set, out_mode))
+                        .enumerate()
+                        .map(|(i, expr)| {
+                            // the first argument follows the function name
+                            let previous = i.checked_sub(1).map(|p| &tail[p]).or(Some(&**head));
+                            if let Some(beats) = eval_beats(previous, expr, globals) {
+                                return Some(beats);
+                            }
+                            if let Some(gain) = eval_decibel(previous, expr) {
+                                return Some(gain);
+                            }
+                            eval_expression_traced(
+                                expr, functions, globals, sample_set, out_mode, trace,
+                            )
+                        })
                         .collect::<Option<Vec<EvaluatedExpr>>>()?;
                     // push function name
                     reduced_tail.insert(0, EvaluatedExpr::FunctionName(f.clone()));
-                    functions.fmap[&f](functions, &mut reduced_tail, globals, sample_set, out_mode)
+                    let res = functions.fmap[&f](
+                        functions,
+                        &mut reduced_tail,
+                        globals,
+                        sample_set,
+                        out_mode,
+                    );
+                    if res.is_none() {
+                        trace.fail(
+                            application,
+                            format!("can't evaluate {}, check the arguments", f),
+                        );
+                    }
+                    res
                 } else {
+                    trace.fail(application, format!("unknown function {}", f));
                     None
                 }
             } else {
+                trace.fail(application, "not a function".to_string());
                 None
             }
         }
     }
 }
 
+/// byte range of the nth application, counting opening parens
+/// (but not those of quoted symbol lists or in strings)
+fn application_span(src: &str, n: usize) -> Option<(usize, usize)> {
+    let mut count = 0;
+    let mut start = None;
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut prev = ' ';
+    for (i, c) in src.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '(' if !in_string && prev != '\'' => {
+                if start.is_some() {
+                    depth += 1;
+                } else if count == n {
+                    start = Some(i);
+                    depth = 1;
+                }
+                count += 1;
+            }
+            '(' if !in_string => {
+                // symbol lists are closed like applications
+                if start.is_some() {
+                    depth += 1;
+                }
+            }
+            ')' if !in_string && start.is_some() => {
+                depth -= 1;
+                if depth == 0 {
+                    return start.map(|s| (s, i + 1));
+                }
+            }
+            _ => {}
+        }
+        prev = c;
+    }
+    // unbalanced, mark everything from the start
+    start.map(|s| (s, src.len()))
+}
+
+/// byte range of the token at the offset, or the last char at the end
+fn token_span(src: &str, offset: usize) -> Option<(usize, usize)> {
+    if src.is_empty() {
+        None
+    } else if offset >= src.len() {
+        src.char_indices().last().map(|(i, _)| (i, src.len()))
+    } else {
+        let len = src[offset..]
+            .char_indices()
+            .skip(1)
+            .find(|(_, c)| c.is_whitespace() || *c == '(' || *c == ')')
+            .map_or(src.len() - offset, |(i, _)| i);
+        Some((offset, offset + len))
+    }
+}
+
+/// Byte ranges of the top-level forms in the source, in order,
+/// so a whole piece can be evaluated one form after another.
+/// Comments are skipped, an unclosed form reaches to the end.
+pub fn split_forms(src: &str) -> Vec<(usize, usize)> {
+    let mut forms = Vec::new();
+    let mut start = None;
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut in_comment = false;
+    let mut escaped = false;
+
+    for (i, c) in src.char_indices() {
+        if in_comment {
+            in_comment = c != '\n';
+        } else if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else {
+            match c {
+                '"' => {
+                    in_string = true;
+                    start.get_or_insert(i);
+                }
+                '(' | '[' | '{' => {
+                    depth += 1;
+                    start.get_or_insert(i);
+                }
+                ')' | ']' | '}' => {
+                    if depth > 1 {
+                        depth -= 1;
+                    } else {
+                        // a stray closing paren is a form of its own,
+                        // so evaluating it shows the error
+                        depth = 0;
+                        forms.push((start.take().unwrap_or(i), i + 1));
+                    }
+                }
+                _ if depth > 0 => in_comment = c == ';',
+                ';' => {
+                    in_comment = true;
+                    if let Some(s) = start.take() {
+                        forms.push((s, i));
+                    }
+                }
+                _ if c.is_whitespace() => {
+                    if let Some(s) = start.take() {
+                        forms.push((s, i));
+                    }
+                }
+                _ => {
+                    start.get_or_insert(i);
+                }
+            }
+        }
+    }
+    if let Some(s) = start {
+        forms.push((s, src.len()));
+    }
+    forms
+}
+
 pub fn eval_from_str(
     src: &str,
     functions: &FunctionMap,
     globals: &sync::Arc<GlobalParameters>,
     sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
     out_mode: OutputMode,
-) -> Result<EvaluatedExpr, String> {
-    // preprocessing - remove all comments ...
+) -> Result<EvaluatedExpr, EvalError> {
+    // preprocessing - blank out all comments, so that
+    // error positions still match the source ...
     let re = Regex::new(r";[^\n]+\n").unwrap();
-    let src_nocomment = re.replace_all(src, "\n");
-    parse_expr(&src_nocomment)
-        .map_err(|e: nom::Err<VerboseError<&str>>| format!("{:#?}", e))
-        .and_then(|(_, exp)| {
-            eval_expression(&exp, functions, globals, sample_set, out_mode)
-                .ok_or_else(|| "eval failed".to_string())
-        })
+    let src_nocomment = re.replace_all(src, |caps: &regex::Captures| {
+        format!("{}\n", " ".repeat(caps[0].len() - 1))
+    });
+    let (_, exp) =
+        parse_expr(&src_nocomment).map_err(|e: nom::Err<VerboseError<&str>>| match e {
+            Err::Error(v) | Err::Failure(v) => {
+                // the contexts tell what the parser was looking for
+                let expected: Vec<&str> = v
+                    .errors
+                    .iter()
+                    .filter_map(|(_, kind)| match kind {
+                        VerboseErrorKind::Context(c) => Some(*c),
+                        _ => None,
+                    })
+                    .collect();
+                EvalError {
+                    message: if expected.is_empty() {
+                        "can't parse".to_string()
+                    } else {
+                        format!("can't parse, expected {}", expected.join(" in "))
+                    },
+                    span: v.errors.first().and_then(|(rest, _)| {
+                        token_span(&src_nocomment, src_nocomment.len() - rest.len())
+                    }),
+                }
+            }
+            Err::Incomplete(_) => EvalError {
+                message: "can't parse, incomplete input".to_string(),
+                span: None,
+            },
+        })?;
+
+    let mut trace = EvalTrace::default();
+    eval_expression_traced(&exp, functions, globals, sample_set, out_mode, &mut trace).ok_or_else(
+        || match trace.failure {
+            Some((application, message)) => EvalError {
+                message,
+                span: application_span(&src_nocomment, application),
+            },
+            None => EvalError {
+                message: "eval failed".to_string(),
+                span: None,
+            },
+        },
+    )
 }
 
 #[cfg(test)]
@@ -458,6 +887,81 @@ mod tests {
         assert!(!matches!(parse_symbol(":test"), Ok(("", Atom::Symbol(_)))));
     }
 
+    #[test]
+    fn test_parse_symbol_list() {
+        if let Ok(("", Atom::SymbolList(s))) = parse_symbol_list("'(i iv  v i)") {
+            assert_eq!(s, vec!["i", "iv", "v", "i"]);
+        } else {
+            panic!();
+        }
+        assert!(matches!(
+            parse_constant("'()"),
+            Ok(("", Expr::Constant(Atom::SymbolList(_))))
+        ));
+        assert!(matches!(
+            parse_expr("(prog 'a '(i iv v i) :key 'a)"),
+            Ok(("", Expr::Application(_, _)))
+        ));
+    }
+
+    #[test]
+    fn test_parse_units() {
+        assert!(matches!(parse_decibel("-6db"), Ok(("", Atom::Decibel(db))) if db == -6.0));
+        assert!(matches!(
+            parse_constant("1/16"),
+            Ok(("", Expr::Constant(Atom::Fraction(f)))) if f == 0.0625
+        ));
+        assert!(parse_fraction("1/0").is_err());
+        assert!(matches!(
+            parse_expr("(saw 100 :lvl -6db :dur 1/8)"),
+            Ok(("", Expr::Application(_, _)))
+        ));
+        // plain numbers still work
+        assert!(matches!(
+            parse_constant("16"),
+            Ok(("", Expr::Constant(Atom::Float(_))))
+        ));
+    }
+
+    #[test]
+    fn test_eval_decibel() {
+        let mut functions = FunctionMap::new();
+        // both just return their last argument
+        functions.register(
+            FunctionCategory::Event,
+            "f",
+            "value...",
+            |_, tail, _, _, _| tail.pop(),
+        );
+        functions.register(
+            FunctionCategory::Parameter,
+            "lvl",
+            "value",
+            |_, tail, _, _, _| tail.pop(),
+        );
+        let globals = sync::Arc::new(GlobalParameters::new());
+        let sample_set = sync::Arc::new(Mutex::new(SampleAndWavematrixSet::new()));
+        let eval = |src: &str| match eval_from_str(
+            src,
+            &functions,
+            &globals,
+            &sample_set,
+            OutputMode::Stereo,
+        ) {
+            Ok(EvaluatedExpr::Float(f)) => f,
+            _ => panic!("{} didn't evaluate to a number", src),
+        };
+
+        // levels get linear gain ...
+        assert!((eval("(f :lvl -6db)") - 0.501).abs() < 0.001);
+        assert!((eval("(f :gain 0db)") - 1.0).abs() < 0.001);
+        assert!((eval("(lvl -6db)") - 0.501).abs() < 0.001);
+        // ... everything else gets the number
+        assert_eq!(eval("(f :freq -6db)"), -6.0);
+        assert_eq!(eval("(f -6db)"), -6.0);
+        assert_eq!(eval("(f -6db 0db)"), 0.0);
+    }
+
     #[test]
     fn test_parse_keyword() {
         assert!(matches!(parse_keyword(":test"), Ok(("", Atom::Keyword(_)))));
@@ -504,6 +1008,23 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_split_forms() {
+        let src =
+            ";; intro (not a form)\n(sx 'a #t ; (comment)\n  (cyc 'b \"bd )\"))\n\n'x (ctx 'c #t\n";
+        let forms: Vec<&str> = split_forms(src).iter().map(|(s, e)| &src[*s..*e]).collect();
+        assert_eq!(
+            forms,
+            vec![
+                "(sx 'a #t ; (comment)\n  (cyc 'b \"bd )\"))",
+                "'x",
+                "(ctx 'c #t\n"
+            ]
+        );
+        assert_eq!(split_forms("(a)) (b)"), vec![(0, 3), (3, 4), (5, 8)]);
+        assert!(split_forms("  ;; nothing\n").is_empty());
+    }
+
     #[test]
     fn test_parse_expr() {
         assert!(matches!(
@@ -630,4 +1151,113 @@ mod tests {
             panic!();
         }
     }
+
+    #[test]
+    fn test_function_info() {
+        let info = FunctionInfo::new(
+            FunctionCategory::Constructor,
+            "cyc",
+            "'name \"pattern\" :dur :rep :events",
+        );
+        assert_eq!(info.name(), "cyc");
+        assert_eq!(info.signature, "(cyc 'name \"pattern\" :dur :rep :events)");
+        assert_eq!(info.keywords(), vec!["dur", "rep", "events"]);
+        assert_eq!(info.arity(), (2, Some(2)));
+
+        let info = FunctionInfo::new(FunctionCategory::Modifier, "haste", "n factor [gen...]");
+        assert_eq!(info.arity(), (2, None));
+
+        let info = FunctionInfo::new(
+            FunctionCategory::Dynpar,
+            "bounce",
+            "min max [steps] :period",
+        );
+        assert_eq!(info.arity(), (2, Some(3)));
+
+        let mut functions = FunctionMap::new();
+        functions.register(
+            FunctionCategory::Command,
+            "bpm",
+            "value",
+            |_, _, _, _, _| None,
+        );
+        assert!(functions.fmap.contains_key("bpm"));
+        assert_eq!(functions.info["bpm"].category, FunctionCategory::Command);
+    }
+
+    #[test]
+    fn test_eval_error_span() {
+        let mut functions = FunctionMap::new();
+        functions.register(
+            FunctionCategory::Constructor,
+            "gen",
+            "'name event...",
+            |_, tail, _, _, _| {
+                if tail.len() > 1 {
+                    Some(EvaluatedExpr::Boolean(true))
+                } else {
+                    None
+                }
+            },
+        );
+        let globals = sync::Arc::new(GlobalParameters::new());
+        let sample_set = sync::Arc::new(Mutex::new(SampleAndWavematrixSet::new()));
+
+        // unknown function, after a comment
+        let src = "(gen 'a ;; comment\n (gen 'b) (foo 1))";
+        let err = eval_from_str(src, &functions, &globals, &sample_set, OutputMode::Stereo)
+            .err()
+            .unwrap();
+        assert_eq!(err.message, "unknown function foo");
+        let (from, to) = err.span.unwrap();
+        assert_eq!(&src[from..to], "(foo 1)");
+
+        // failing function, quoted lists aren't applications
+        let src = "(gen '(a b) (gen))";
+        let err = eval_from_str(src, &functions, &globals, &sample_set, OutputMode::Stereo)
+            .err()
+            .unwrap();
+        let (from, to) = err.span.unwrap();
+        assert_eq!(&src[from..to], "(gen)");
+
+        // missing paren
+        let src = "(gen 'a (gen 'b)";
+        let err = eval_from_str(src, &functions, &globals, &sample_set, OutputMode::Stereo)
+            .err()
+            .unwrap();
+        assert!(err.message.contains("closing paren"));
+        assert!(err.span.is_some());
+    }
+
+    #[test]
+    fn test_rec_stems_need_multichannel() {
+        let mut functions = FunctionMap::new();
+        functions.register(
+            FunctionCategory::Command,
+            "rec",
+            ":stems",
+            eval::commands::start_recording,
+        );
+        let globals = sync::Arc::new(GlobalParameters::new());
+        let sample_set = sync::Arc::new(Mutex::new(SampleAndWavematrixSet::new()));
+
+        let src = "(rec :stems #t)";
+        assert!(eval_from_str(src, &functions, &globals, &sample_set, OutputMode::Stereo).is_err());
+        assert!(eval_from_str(
+            src,
+            &functions,
+            &globals,
+            &sample_set,
+            OutputMode::EightChannel
+        )
+        .is_ok());
+        assert!(eval_from_str(
+            "(rec)",
+            &functions,
+            &globals,
+            &sample_set,
+            OutputMode::Stereo
+        )
+        .is_ok());
+    }
 }
@@ -1,6 +1,10 @@
 use std::collections::HashMap;
 
 use crate::builtin_types::*;
+use crate::generator_link::{GeneratorLink, LinkAction, LinkTrigger};
+use crate::messages;
+use crate::music_theory::Tuning;
+use crate::parameter::modifier::EvalContext;
 use crate::parameter::*;
 
 use std::collections::BTreeSet;
@@ -8,6 +12,8 @@ use std::collections::BTreeSet;
 use ruffbox_synth::building_blocks::SynthParameterLabel;
 
 use crate::parser::{BuiltIn, EvaluatedExpr, FunctionMap};
+use crate::real_time_streaming::RecordingOptions;
+use crate::session::SyncContext;
 use crate::{OutputMode, SampleAndWavematrixSet};
 use parking_lot::Mutex;
 use std::sync;
@@ -133,6 +139,70 @@ pub fn load_sample_as_wavematrix(
     }
 }
 
+pub fn capture_wavematrix(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..).skip(1);
+
+    // first arg is the key
+    let key = if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.next() {
+        s
+    } else {
+        return None;
+    };
+
+    // like with freeze, buffers are counted starting at 1
+    let mut source = BufferSource::Live(0);
+    let mut method = "zerocrossing_fixed_stretch_inverse".to_string();
+    let mut matrix_size: Option<(usize, usize)> = None;
+    let mut start: f32 = 0.0;
+
+    while let Some(c) = tail_drain.next() {
+        if let EvaluatedExpr::Keyword(k) = c {
+            match k.as_str() {
+                "from-live" => {
+                    if let Some(EvaluatedExpr::Float(f)) = tail_drain.next() {
+                        source = BufferSource::Live((f as usize).saturating_sub(1));
+                    }
+                }
+                "from-freeze" => {
+                    if let Some(EvaluatedExpr::Float(f)) = tail_drain.next() {
+                        source = BufferSource::Freeze((f as usize).saturating_sub(1));
+                    }
+                }
+                "start" => {
+                    if let Some(EvaluatedExpr::Float(f)) = tail_drain.next() {
+                        start = f;
+                    }
+                }
+                "method" => {
+                    if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.next() {
+                        method = s;
+                    }
+                }
+                "size" => {
+                    if let Some(EvaluatedExpr::Float(x)) = tail_drain.next() {
+                        if let Some(EvaluatedExpr::Float(y)) = tail_drain.next() {
+                            matrix_size = Some((x as usize, y as usize));
+                        }
+                    }
+                }
+                _ => println!("{}", k),
+            }
+        }
+    }
+
+    matrix_size.map(|size| {
+        EvaluatedExpr::BuiltIn(BuiltIn::Command(Command::CaptureWavematrix(
+            key, source, method, size, start,
+        )))
+    })
+}
+
 pub fn freeze_buffer(
     _: &FunctionMap,
     tail: &mut Vec<EvaluatedExpr>,
@@ -189,6 +259,7 @@ pub fn load_sample(
     let mut path: String = "".to_string();
     let mut set: String = "".to_string();
     let mut downmix_stereo = false;
+    let mut max_duration = None;
 
     while let Some(c) = tail_drain.next() {
         if collect_keywords {
@@ -221,13 +292,18 @@ pub fn load_sample(
                         downmix_stereo = !b;
                     }
                 }
+                "max-dur" => {
+                    if let Some(EvaluatedExpr::Float(f)) = tail_drain.next() {
+                        max_duration = Some(f as usize);
+                    }
+                }
                 _ => println!("{}", k),
             }
         }
     }
 
     Some(EvaluatedExpr::BuiltIn(BuiltIn::Command(
-        Command::LoadSample(set, keywords, path, downmix_stereo),
+        Command::LoadSample(set, keywords, path, downmix_stereo, max_duration),
     )))
 }
 
@@ -246,16 +322,25 @@ pub fn load_sample_sets(
     };
 
     let mut downmix_stereo = false;
-    if let Some(EvaluatedExpr::Keyword(k)) = tail_drain.next() {
-        if k == "use-stereo" {
-            if let Some(EvaluatedExpr::Boolean(b)) = tail_drain.next() {
-                downmix_stereo = !b
+    let mut max_duration = None;
+    while let Some(EvaluatedExpr::Keyword(k)) = tail_drain.next() {
+        match k.as_str() {
+            "use-stereo" => {
+                if let Some(EvaluatedExpr::Boolean(b)) = tail_drain.next() {
+                    downmix_stereo = !b
+                }
             }
+            "max-dur" => {
+                if let Some(EvaluatedExpr::Float(f)) = tail_drain.next() {
+                    max_duration = Some(f as usize);
+                }
+            }
+            _ => println!("{}", k),
         }
     }
 
     Some(EvaluatedExpr::BuiltIn(BuiltIn::Command(
-        Command::LoadSampleSets(path, downmix_stereo),
+        Command::LoadSampleSets(path, downmix_stereo, max_duration),
     )))
 }
 
@@ -274,19 +359,62 @@ pub fn load_sample_set(
     };
 
     let mut downmix_stereo = false;
-    if let Some(EvaluatedExpr::Keyword(k)) = tail_drain.next() {
-        if k == "use-stereo" {
-            if let Some(EvaluatedExpr::Boolean(b)) = tail_drain.next() {
-                downmix_stereo = !b
+    let mut max_duration = None;
+    while let Some(EvaluatedExpr::Keyword(k)) = tail_drain.next() {
+        match k.as_str() {
+            "use-stereo" => {
+                if let Some(EvaluatedExpr::Boolean(b)) = tail_drain.next() {
+                    downmix_stereo = !b
+                }
+            }
+            "max-dur" => {
+                if let Some(EvaluatedExpr::Float(f)) = tail_drain.next() {
+                    max_duration = Some(f as usize);
+                }
             }
+            _ => println!("{}", k),
         }
     }
 
     Some(EvaluatedExpr::BuiltIn(BuiltIn::Command(
-        Command::LoadSampleSet(path, downmix_stereo),
+        Command::LoadSampleSet(path, downmix_stereo, max_duration),
     )))
 }
 
+pub fn unload_sample_set(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..).skip(1);
+    if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.next() {
+        Some(EvaluatedExpr::BuiltIn(BuiltIn::Command(
+            Command::UnloadSampleSet(s),
+        )))
+    } else {
+        None
+    }
+}
+
+pub fn reload_sample_set(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..).skip(1);
+    if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.next() {
+        Some(EvaluatedExpr::BuiltIn(BuiltIn::Command(
+            Command::ReloadSampleSet(s),
+        )))
+    } else {
+        None
+    }
+}
+
 pub fn tmod(
     _: &FunctionMap,
     tail: &mut Vec<EvaluatedExpr>,
@@ -353,6 +481,72 @@ pub fn default_duration(
     )))
 }
 
+pub fn tuning(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..).skip(1);
+
+    // either a scala file or a builtin tuning
+    let first = tail_drain.next();
+
+    let mut kbm = None;
+    let mut reference = None;
+    let mut edo = None;
+    while let Some(EvaluatedExpr::Keyword(k)) = tail_drain.next() {
+        match (k.as_str(), tail_drain.next()) {
+            ("kbm", Some(EvaluatedExpr::String(s))) => kbm = Some(s),
+            ("ref", Some(EvaluatedExpr::Float(f))) => reference = Some(f),
+            ("edo", Some(EvaluatedExpr::Float(f))) => edo = Some(f as usize),
+            _ => {}
+        }
+    }
+
+    match first {
+        Some(EvaluatedExpr::String(path)) => Some(EvaluatedExpr::BuiltIn(BuiltIn::Command(
+            Command::LoadTuning(path, kbm, reference),
+        ))),
+        Some(EvaluatedExpr::Symbol(name)) => {
+            let mut tuning = if let Some(n) = edo {
+                Tuning::equal(n)
+            } else if let Some(t) = Tuning::from_name(&name) {
+                t
+            } else {
+                println!("unknown tuning: {}", name);
+                return None;
+            };
+            if let Some(r) = reference {
+                tuning.mapping.ref_freq = r;
+            }
+            Some(EvaluatedExpr::BuiltIn(BuiltIn::Command(Command::Tuning(
+                tuning,
+            ))))
+        }
+        _ => None,
+    }
+}
+
+/// (global 'density 0.6)
+/// set a user-defined global value, i.e. for the when processor
+pub fn set_global(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..).skip(1);
+    match (tail_drain.next(), tail_drain.next()) {
+        (Some(EvaluatedExpr::Symbol(name)), Some(EvaluatedExpr::Float(f))) => Some(
+            EvaluatedExpr::BuiltIn(BuiltIn::Command(Command::SetGlobal(name, f))),
+        ),
+        _ => None,
+    }
+}
+
 pub fn globres(
     _: &FunctionMap,
     tail: &mut Vec<EvaluatedExpr>,
@@ -584,7 +778,10 @@ pub fn once(
     for c in tail_drain {
         match c {
             EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(mut e)) => {
-                sound_events.push(e.get_static());
+                sound_events.push(e.get_static(&EvalContext::default()));
+            }
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(mut l)) => {
+                sound_events.extend(l.iter_mut().map(|e| e.get_static(&EvalContext::default())));
             }
             EvaluatedExpr::BuiltIn(BuiltIn::ControlEvent(c)) => control_events.push(c),
             _ => {}
@@ -641,29 +838,78 @@ pub fn start_recording(
     tail: &mut Vec<EvaluatedExpr>,
     _: &sync::Arc<GlobalParameters>,
     _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
-    _: OutputMode,
+    output_mode: OutputMode,
 ) -> Option<EvaluatedExpr> {
-    let mut tail_drain = tail.drain(..).skip(1);
-    let prefix = if let Some(EvaluatedExpr::String(s)) = tail_drain.next() {
-        Some(s)
+    let mut tail_drain = tail.drain(..).skip(1).peekable();
+    let prefix = if let Some(EvaluatedExpr::String(_)) = tail_drain.peek() {
+        if let Some(EvaluatedExpr::String(s)) = tail_drain.next() {
+            Some(s)
+        } else {
+            None
+        }
     } else {
         None
     };
 
-    let mut rec_input = false;
+    let mut options = RecordingOptions::default();
     while let Some(c) = tail_drain.next() {
         if let EvaluatedExpr::Keyword(k) = c {
-            if k.as_str() == "input" {
-                // default is zero ...
-                if let Some(EvaluatedExpr::Boolean(b)) = tail_drain.next() {
-                    rec_input = b;
+            match k.as_str() {
+                "input" => {
+                    // default is zero ...
+                    if let Some(EvaluatedExpr::Boolean(b)) = tail_drain.next() {
+                        options.rec_input = b;
+                    }
                 }
+                "stems" => {
+                    if let Some(EvaluatedExpr::Boolean(b)) = tail_drain.next() {
+                        options.stems = b;
+                    }
+                }
+                "format" => {
+                    if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.next() {
+                        options.flac = s.as_str() == "flac";
+                    }
+                }
+                "bits" => {
+                    if let Some(EvaluatedExpr::Float(f)) = tail_drain.next() {
+                        options.bits = match f as u16 {
+                            16 => 16,
+                            24 => 24,
+                            _ => 32,
+                        };
+                    }
+                }
+                "split-time" => {
+                    // seconds
+                    if let Some(EvaluatedExpr::Float(f)) = tail_drain.next() {
+                        options.split_time = Some(f);
+                    }
+                }
+                "split-size" => {
+                    // megabytes
+                    if let Some(EvaluatedExpr::Float(f)) = tail_drain.next() {
+                        options.split_size = Some((f * 1024.0 * 1024.0) as u64);
+                    }
+                }
+                _ => {}
             }
         }
     }
 
+    // the contexts are mixed inside the synth, so stems can only
+    // be made from output channel pairs
+    if options.stems && output_mode == OutputMode::Stereo {
+        messages::warning(
+            "can't record stems in stereo, as contexts aren't recorded separately. \
+             Stems are one file per output channel pair, so they need multichannel output"
+                .to_string(),
+        );
+        return None;
+    }
+
     Some(EvaluatedExpr::BuiltIn(BuiltIn::Command(
-        Command::StartRecording(prefix, rec_input),
+        Command::StartRecording(prefix, options),
     )))
 }
 
@@ -678,3 +924,117 @@ pub fn stop_recording(
         Command::StopRecording,
     )))
 }
+
+pub fn save_last(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..).skip(1);
+
+    // seconds, default is one minute
+    let seconds = if let Some(EvaluatedExpr::Float(f)) = tail_drain.next() {
+        f
+    } else {
+        60.0
+    };
+
+    let prefix = if let Some(EvaluatedExpr::String(s)) = tail_drain.next() {
+        Some(s)
+    } else {
+        None
+    };
+
+    Some(EvaluatedExpr::BuiltIn(BuiltIn::Command(Command::SaveLast(
+        seconds, prefix,
+    ))))
+}
+
+/// (link 'drums :on 'bd :advance 'bass)
+/// (link 'drums :on-state 'x :start 'answer)
+/// links fire when the source plays an event with the given tag,
+/// or enters the given state. Contexts to start can be given as part
+/// names or as (sx ...), advanced generators only move when triggered
+/// unless :follow is #f
+pub fn link(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..).skip(1);
+
+    let source = if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.next() {
+        s
+    } else {
+        println!("link needs a source generator");
+        return None;
+    };
+
+    let mut trigger = None;
+    let mut actions = Vec::new();
+    let mut follow = true;
+
+    while let Some(EvaluatedExpr::Keyword(k)) = tail_drain.next() {
+        match (k.as_str(), tail_drain.next()) {
+            ("on", Some(EvaluatedExpr::Symbol(s))) => trigger = Some(LinkTrigger::Tag(s)),
+            ("on-state", Some(EvaluatedExpr::Symbol(s))) => trigger = Some(LinkTrigger::State(s)),
+            ("advance", Some(EvaluatedExpr::Symbol(s))) => actions.push(LinkAction::Advance(s)),
+            ("start", Some(EvaluatedExpr::Symbol(s))) => {
+                // start a part as a context of the same name
+                actions.push(LinkAction::Start(SyncContext {
+                    name: s.clone(),
+                    generators: Vec::new(),
+                    part_proxies: vec![PartProxy::Proxy(s, Vec::new())],
+                    sync_to: None,
+                    active: true,
+                    shift: 0,
+                    block_tags: BTreeSet::new(),
+                    solo_tags: BTreeSet::new(),
+                }))
+            }
+            ("start", Some(EvaluatedExpr::BuiltIn(BuiltIn::SyncContext(ctx)))) => {
+                actions.push(LinkAction::Start(ctx))
+            }
+            ("follow", Some(EvaluatedExpr::Boolean(b))) => follow = b,
+            _ => println!("ignored link argument {}", k),
+        }
+    }
+
+    if let Some(trigger) = trigger {
+        if actions.is_empty() {
+            println!("link without anything to advance or start");
+            return None;
+        }
+        Some(EvaluatedExpr::BuiltIn(BuiltIn::Command(Command::Link(
+            GeneratorLink {
+                source,
+                trigger,
+                actions,
+                follow,
+            },
+        ))))
+    } else {
+        println!("link needs a trigger, :on or :on-state");
+        None
+    }
+}
+
+/// (unlink 'drums)
+pub fn unlink(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..).skip(1);
+    if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.next() {
+        Some(EvaluatedExpr::BuiltIn(BuiltIn::Command(Command::Unlink(s))))
+    } else {
+        None
+    }
+}
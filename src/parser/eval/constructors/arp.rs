@@ -0,0 +1,253 @@
+use crate::builtin_types::*;
+use crate::event::*;
+use crate::generator::Generator;
+use crate::markov_sequence_generator::MarkovSequenceGenerator;
+use crate::parameter::modifier::tuning_modifier::active_tuning;
+use crate::parameter::*;
+use crate::parser::eval::events::chord::{chord_events, chord_keys, default_template};
+
+use ruffbox_synth::building_blocks::SynthParameterLabel;
+use std::collections::{BTreeSet, HashMap};
+use std::sync;
+use vom_rs::pfa::{Pfa, Rule};
+
+use crate::parser::{BuiltIn, EvaluatedExpr, FunctionMap};
+use crate::{OutputMode, SampleAndWavematrixSet};
+use parking_lot::Mutex;
+
+fn transition(dur: &DynVal) -> Event {
+    let mut dur_ev = Event::with_name("transition".to_string());
+    dur_ev.params.insert(
+        SynthParameterLabel::Duration,
+        ParameterValue::Scalar(dur.clone()),
+    );
+    dur_ev
+}
+
+/// the default duration from the global parameters
+pub fn default_duration(global_parameters: &sync::Arc<GlobalParameters>) -> DynVal {
+    if let ConfigParameter::Numeric(d) = global_parameters
+        .entry(BuiltinGlobalParameters::DefaultDuration)
+        .or_insert(ConfigParameter::Numeric(200.0))
+        .value()
+    {
+        DynVal::with_value(*d)
+    } else {
+        unreachable!()
+    }
+}
+
+/// Build a generator from a list of steps, each of which might hold several
+/// events. If `random` is set, every step can follow every other step,
+/// otherwise the steps are played as a loop.
+pub fn step_generator(
+    name: String,
+    mut steps: Vec<Vec<SourceEvent>>,
+    dur: DynVal,
+    random: bool,
+) -> Generator {
+    let mut event_mapping = HashMap::<char, Vec<SourceEvent>>::new();
+    let mut duration_mapping = HashMap::<(char, char), Event>::new();
+    let mut rules = Vec::new();
+
+    let labels: Vec<char> = (0..steps.len())
+        .map(|i| std::char::from_u32('1' as u32 + i as u32).unwrap())
+        .collect();
+
+    for (label, step) in labels.iter().zip(steps.drain(..)) {
+        event_mapping.insert(*label, step);
+    }
+
+    if random && labels.len() > 1 {
+        let prob = 1.0 / (labels.len() - 1) as f32;
+        for src in labels.iter() {
+            for sym in labels.iter().filter(|l| *l != src) {
+                rules.push(Rule {
+                    source: vec![*src],
+                    symbol: *sym,
+                    probability: prob,
+                });
+                duration_mapping.insert((*src, *sym), transition(&dur));
+            }
+        }
+    } else if !labels.is_empty() {
+        for (i, src) in labels.iter().enumerate() {
+            let sym = labels[(i + 1) % labels.len()];
+            rules.push(Rule {
+                source: vec![*src],
+                symbol: sym,
+                probability: 1.0,
+            });
+            duration_mapping.insert((*src, sym), transition(&dur));
+        }
+    }
+
+    let mut id_tags = BTreeSet::new();
+    id_tags.insert(name.clone());
+
+    Generator {
+        id_tags,
+        root_generator: MarkovSequenceGenerator {
+            name,
+            // don't remove orphans here because the first state is technically
+            // "orphan"
+            generator: Pfa::<char>::infer_from_rules(&mut rules, true),
+            event_mapping,
+            duration_mapping,
+            modified: true,
+            symbol_ages: HashMap::new(),
+            default_duration: dur.static_val as u64,
+            last_transition: None,
+            last_symbol: None,
+        },
+        processors: Vec::new(),
+        time_mods: Vec::new(),
+        keep_root: false,
+    }
+}
+
+/// (arp 'name 'c4 'maj7 :mode 'updown :dur 125 :oct 2 :inv 1 (saw 0 :lpf 300))
+/// arpeggiate a chord, modes are 'up, 'down, 'updown, 'downup and 'random
+pub fn arp(
+    functions: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    global_parameters: &sync::Arc<GlobalParameters>,
+    sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    out_mode: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..).skip(1).peekable();
+
+    // name is the first symbol
+    let name = if let Some(EvaluatedExpr::Symbol(n)) = tail_drain.next() {
+        n
+    } else {
+        "".to_string()
+    };
+
+    let root = if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.next() {
+        s
+    } else {
+        return None;
+    };
+
+    let quality = if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.peek() {
+        let q = s.clone();
+        tail_drain.next();
+        q
+    } else {
+        "maj".to_string()
+    };
+
+    let mut mode = "up".to_string();
+    let mut dur = default_duration(global_parameters);
+    let mut octaves = 1;
+    let mut inversion = 0;
+    let mut template = None;
+
+    while let Some(c) = tail_drain.next() {
+        match c {
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(e)) => template = Some(e),
+            EvaluatedExpr::Keyword(k) => match k.as_str() {
+                "mode" => {
+                    if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.next() {
+                        mode = s;
+                    }
+                }
+                "dur" => match tail_drain.next() {
+                    Some(EvaluatedExpr::Float(n)) => dur = DynVal::with_value(n),
+                    Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(p))) => dur = p,
+                    _ => {}
+                },
+                "oct" => {
+                    if let Some(EvaluatedExpr::Float(n)) = tail_drain.next() {
+                        octaves = (n as usize).max(1);
+                    }
+                }
+                "inv" => {
+                    if let Some(EvaluatedExpr::Float(n)) = tail_drain.next() {
+                        inversion = n as usize;
+                    }
+                }
+                _ => println!("ignored keyword {}", k),
+            },
+            _ => println! {"ignored"},
+        }
+    }
+
+    let template = if let Some(t) = template {
+        t
+    } else {
+        default_template(functions, global_parameters, sample_set, out_mode)?
+    };
+
+    let tuning = active_tuning(global_parameters);
+    let chord = chord_keys(&root, &quality, inversion, &tuning)?;
+    let period = tuning.keys_per_period() as f32;
+    let mut up = Vec::new();
+    for o in 0..octaves {
+        up.extend(chord.iter().map(|k| k + period * o as f32));
+    }
+    let mut down = up.clone();
+    down.reverse();
+
+    let keys = match mode.as_str() {
+        "down" => down,
+        "updown" => {
+            let inner = down.len().saturating_sub(1).max(1);
+            up.iter().chain(down[1..inner].iter()).copied().collect()
+        }
+        "downup" => {
+            let inner = up.len().saturating_sub(1).max(1);
+            down.iter().chain(up[1..inner].iter()).copied().collect()
+        }
+        _ => up,
+    };
+
+    let steps = chord_events(&template, &keys, 0.0, global_parameters)
+        .into_iter()
+        .map(|ev| vec![SourceEvent::Sound(ev)])
+        .collect();
+
+    Some(EvaluatedExpr::BuiltIn(BuiltIn::Generator(step_generator(
+        name,
+        steps,
+        dur,
+        mode == "random",
+    ))))
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::parser::*;
+
+    #[test]
+    fn test_eval_arp() {
+        let snippet = "(arp 'ar 'c4 'maj7 :mode 'updown :dur 125)";
+        let mut functions = FunctionMap::new();
+        let sample_set = sync::Arc::new(Mutex::new(SampleAndWavematrixSet::new()));
+
+        functions
+            .fmap
+            .insert("arp".to_string(), eval::constructors::arp::arp);
+
+        let globals = sync::Arc::new(GlobalParameters::new());
+
+        match eval_from_str(
+            snippet,
+            &functions,
+            &globals,
+            &sample_set,
+            OutputMode::Stereo,
+        ) {
+            Ok(EvaluatedExpr::BuiltIn(BuiltIn::Generator(g))) => {
+                // c e g b g e
+                assert_eq!(g.root_generator.event_mapping.len(), 6);
+                assert_eq!(g.root_generator.default_duration, 125);
+                assert!(g.id_tags.contains("ar"));
+            }
+            _ => panic!(),
+        }
+    }
+}
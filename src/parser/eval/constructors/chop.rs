@@ -58,6 +58,9 @@ pub fn chop(
             EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(e)) => {
                 events.push(e);
             }
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                events.extend(l);
+            }
             EvaluatedExpr::Keyword(k) => match k.as_str() {
                 "dur" => match tail_drain.next() {
                     Some(EvaluatedExpr::Float(n)) => {
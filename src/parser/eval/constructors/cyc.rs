@@ -92,6 +92,10 @@ pub fn cyc(
                     collected_evs.push(SourceEvent::Sound(e));
                     continue;
                 }
+                EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                    collected_evs.extend(l.into_iter().map(SourceEvent::Sound));
+                    continue;
+                }
                 EvaluatedExpr::BuiltIn(BuiltIn::ControlEvent(e)) => {
                     collected_evs.push(SourceEvent::Control(e));
                     continue;
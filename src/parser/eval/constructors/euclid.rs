@@ -0,0 +1,479 @@
+use crate::builtin_types::*;
+use crate::event::*;
+use crate::generator::Generator;
+use crate::markov_sequence_generator::MarkovSequenceGenerator;
+use crate::parameter::*;
+
+use ruffbox_synth::building_blocks::SynthParameterLabel;
+use std::collections::{BTreeSet, HashMap};
+use std::sync;
+use vom_rs::pfa::{Pfa, Rule};
+
+use crate::parser::{BuiltIn, EvaluatedExpr, FunctionMap};
+use crate::{OutputMode, SampleAndWavematrixSet};
+use parking_lot::Mutex;
+
+/// Distribute onsets as evenly as possible over a number of steps,
+/// using Bjorklund's algorithm, so the patterns start where the
+/// usual tables (Toussaint) start them, i.e. (5 16) is x..x..x..x..x....
+pub fn euclidean_pattern(onsets: usize, steps: usize) -> Vec<bool> {
+    let onsets = onsets.min(steps);
+    if onsets == 0 || onsets == steps {
+        return vec![onsets > 0; steps];
+    }
+
+    let mut heads = vec![vec![true]; onsets];
+    let mut remainders = vec![vec![false]; steps - onsets];
+
+    // append remainders to the heads until at most one is left
+    while remainders.len() > 1 {
+        let pairs = heads.len().min(remainders.len());
+        let rest = if heads.len() > pairs {
+            heads.split_off(pairs)
+        } else {
+            remainders.split_off(pairs)
+        };
+        for (head, remainder) in heads.iter_mut().zip(remainders.drain(..)) {
+            head.extend(remainder);
+        }
+        remainders = rest;
+    }
+
+    heads.into_iter().chain(remainders).flatten().collect()
+}
+
+// the options shared by the rhythm constructors
+struct RhythmOptions {
+    dur: DynVal,
+    rotation: usize,
+    repetition_chance: f32,
+    randomize_chance: f32,
+    max_repetitions: f32,
+    keep_root: bool,
+}
+
+// collect events and keyword options, the numeric arguments
+// (onsets/density and steps) have been taken already
+fn collect_rhythm_args(
+    tail_drain: &mut std::iter::Skip<std::vec::Drain<EvaluatedExpr>>,
+    global_parameters: &sync::Arc<GlobalParameters>,
+) -> (Vec<SourceEvent>, RhythmOptions) {
+    let mut events = Vec::new();
+    let mut options = RhythmOptions {
+        dur: if let ConfigParameter::Numeric(d) = global_parameters
+            .entry(BuiltinGlobalParameters::DefaultDuration)
+            .or_insert(ConfigParameter::Numeric(200.0))
+            .value()
+        {
+            DynVal::with_value(*d)
+        } else {
+            unreachable!()
+        },
+        rotation: 0,
+        repetition_chance: 0.0,
+        randomize_chance: 0.0,
+        max_repetitions: 0.0,
+        keep_root: false,
+    };
+
+    while let Some(c) = tail_drain.next() {
+        match c {
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(e)) => events.push(SourceEvent::Sound(e)),
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                events.extend(l.into_iter().map(SourceEvent::Sound))
+            }
+            EvaluatedExpr::BuiltIn(BuiltIn::ControlEvent(e)) => {
+                events.push(SourceEvent::Control(e))
+            }
+            EvaluatedExpr::Keyword(k) => match k.as_str() {
+                "dur" => match tail_drain.next() {
+                    Some(EvaluatedExpr::Float(n)) => {
+                        options.dur = DynVal::with_value(n);
+                    }
+                    Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(p))) => {
+                        options.dur = p;
+                    }
+                    _ => {}
+                },
+                "rot" => {
+                    if let Some(EvaluatedExpr::Float(n)) = tail_drain.next() {
+                        options.rotation = n as usize;
+                    }
+                }
+                "rep" => {
+                    if let Some(EvaluatedExpr::Float(n)) = tail_drain.next() {
+                        options.repetition_chance = n;
+                    }
+                }
+                "rnd" => {
+                    if let Some(EvaluatedExpr::Float(n)) = tail_drain.next() {
+                        options.randomize_chance = n;
+                    }
+                }
+                "max-rep" => {
+                    if let Some(EvaluatedExpr::Float(n)) = tail_drain.next() {
+                        options.max_repetitions = n;
+                    }
+                }
+                "keep" => {
+                    if let Some(EvaluatedExpr::Boolean(b)) = tail_drain.next() {
+                        options.keep_root = b;
+                    }
+                }
+                _ => println!("{}", k),
+            },
+            _ => println! {"ignored"},
+        }
+    }
+
+    (events, options)
+}
+
+// Build the generator from states, their events and their successors
+// (with probabilities). Repetitions are handled like in the other
+// loop-style constructors.
+fn rhythm_generator(
+    name: String,
+    mut states: Vec<(char, Vec<SourceEvent>, Vec<(char, f32)>)>,
+    options: RhythmOptions,
+) -> Generator {
+    let mut event_mapping = HashMap::<char, Vec<SourceEvent>>::new();
+    let mut duration_mapping = HashMap::<(char, char), Event>::new();
+
+    let pfa = if !options.keep_root {
+        let mut rules = Vec::new();
+        let rep = options.repetition_chance / 100.0;
+
+        for (label, evs, successors) in states.drain(..) {
+            event_mapping.insert(label, evs);
+
+            let mut dur_ev = Event::with_name("transition".to_string());
+            dur_ev.params.insert(
+                SynthParameterLabel::Duration,
+                ParameterValue::Scalar(options.dur.clone()),
+            );
+
+            if rep > 0.0 {
+                // repetition rule
+                rules.push(Rule {
+                    source: vec![label],
+                    symbol: label,
+                    probability: rep,
+                });
+                duration_mapping.insert((label, label), dur_ev.clone());
+
+                // endless repetition allowed per default ...
+                if options.max_repetitions >= 2.0 {
+                    for (next, prob) in successors.iter() {
+                        rules.push(Rule {
+                            source: vec![label; options.max_repetitions as usize],
+                            symbol: *next,
+                            probability: *prob,
+                        });
+                    }
+                }
+            }
+
+            for (next, prob) in successors.iter() {
+                rules.push(Rule {
+                    source: vec![label],
+                    symbol: *next,
+                    probability: prob * (1.0 - rep),
+                });
+                duration_mapping.insert((label, *next), dur_ev.clone());
+            }
+        }
+
+        // don't remove orphans here because the first state is technically
+        // "orphan"
+        let mut tmp = Pfa::<char>::infer_from_rules(&mut rules, true);
+
+        if options.randomize_chance > 0.0 {
+            tmp.randomize_edges(options.randomize_chance, options.randomize_chance);
+            tmp.rebalance();
+        }
+        tmp
+    } else {
+        Pfa::<char>::new()
+    };
+
+    let mut id_tags = BTreeSet::new();
+    id_tags.insert(name.clone());
+
+    Generator {
+        id_tags,
+        root_generator: MarkovSequenceGenerator {
+            name,
+            generator: pfa,
+            event_mapping,
+            duration_mapping,
+            modified: true,
+            symbol_ages: HashMap::new(),
+            default_duration: options.dur.static_val as u64,
+            last_transition: None,
+            last_symbol: None,
+        },
+        processors: Vec::new(),
+        time_mods: Vec::new(),
+        keep_root: options.keep_root,
+    }
+}
+
+fn silence() -> Vec<SourceEvent> {
+    vec![SourceEvent::Sound(Event::with_name_and_operation(
+        "silence".to_string(),
+        EventOperation::Replace,
+    ))]
+}
+
+fn label(idx: usize) -> char {
+    std::char::from_u32('1' as u32 + idx as u32).unwrap()
+}
+
+/// (euclid 'name 5 16 (bd) :rot 2 :dur 125)
+/// a loop with evenly distributed onsets, the rest are silences
+pub fn euclid(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    global_parameters: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..).skip(1);
+
+    // name is the first symbol
+    let name = if let Some(EvaluatedExpr::Symbol(n)) = tail_drain.next() {
+        n
+    } else {
+        "".to_string()
+    };
+
+    let onsets = if let Some(EvaluatedExpr::Float(n)) = tail_drain.next() {
+        n as usize
+    } else {
+        return None;
+    };
+
+    let steps = if let Some(EvaluatedExpr::Float(n)) = tail_drain.next() {
+        n as usize
+    } else {
+        return None;
+    };
+
+    if steps == 0 {
+        return None;
+    }
+
+    let (events, options) = collect_rhythm_args(&mut tail_drain, global_parameters);
+
+    let mut pattern = euclidean_pattern(onsets, steps);
+    pattern.rotate_left(options.rotation % steps);
+
+    let states = pattern
+        .iter()
+        .enumerate()
+        .map(|(i, onset)| {
+            (
+                label(i),
+                if *onset { events.clone() } else { silence() },
+                vec![(label((i + 1) % steps), 1.0)],
+            )
+        })
+        .collect();
+
+    Some(EvaluatedExpr::BuiltIn(BuiltIn::Generator(
+        rhythm_generator(name, states, options),
+    )))
+}
+
+/// (density 'name 0.4 16 (hh))
+/// every step is an onset with the given probability, each
+/// step has an onset and a silence state to choose from
+pub fn density(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    global_parameters: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..).skip(1);
+
+    // name is the first symbol
+    let name = if let Some(EvaluatedExpr::Symbol(n)) = tail_drain.next() {
+        n
+    } else {
+        "".to_string()
+    };
+
+    let density = if let Some(EvaluatedExpr::Float(n)) = tail_drain.next() {
+        n.clamp(0.0, 1.0)
+    } else {
+        return None;
+    };
+
+    let steps = if let Some(EvaluatedExpr::Float(n)) = tail_drain.next() {
+        n as usize
+    } else {
+        return None;
+    };
+
+    if steps == 0 {
+        return None;
+    }
+
+    let (events, options) = collect_rhythm_args(&mut tail_drain, global_parameters);
+
+    // onset states are even, silence states odd
+    let mut states = Vec::new();
+    for i in 0..steps {
+        let next = (i + 1) % steps;
+        let mut successors = Vec::new();
+        if density > 0.0 {
+            successors.push((label(2 * next), density));
+        }
+        if density < 1.0 {
+            successors.push((label(2 * next + 1), 1.0 - density));
+        }
+        states.push((label(2 * i), events.clone(), successors.clone()));
+        states.push((label(2 * i + 1), silence(), successors));
+    }
+
+    Some(EvaluatedExpr::BuiltIn(BuiltIn::Generator(
+        rhythm_generator(name, states, options),
+    )))
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::parser::*;
+
+    fn pattern(p: &str) -> Vec<bool> {
+        p.chars().map(|c| c == 'x').collect()
+    }
+
+    fn eval_rhythm(snippet: &str) -> Generator {
+        let mut functions = FunctionMap::new();
+        let sample_set = sync::Arc::new(Mutex::new(SampleAndWavematrixSet::new()));
+
+        functions
+            .fmap
+            .insert("euclid".to_string(), eval::constructors::euclid::euclid);
+        functions
+            .fmap
+            .insert("density".to_string(), eval::constructors::euclid::density);
+        functions
+            .fmap
+            .insert("saw".to_string(), eval::events::sound::sound);
+
+        let globals = sync::Arc::new(GlobalParameters::new());
+
+        match eval_from_str(
+            snippet,
+            &functions,
+            &globals,
+            &sample_set,
+            OutputMode::Stereo,
+        ) {
+            Ok(EvaluatedExpr::BuiltIn(BuiltIn::Generator(g))) => g,
+            _ => panic!(),
+        }
+    }
+
+    fn is_onset(gen: &Generator, label: char) -> bool {
+        gen.root_generator.event_mapping[&label]
+            .iter()
+            .any(|ev| matches!(ev, SourceEvent::Sound(e) if e.name != "silence"))
+    }
+
+    // the onsets of the states the generator passes through
+    fn play(gen: &mut Generator, steps: usize) -> Vec<bool> {
+        let globals = sync::Arc::new(GlobalParameters::new());
+        (0..steps)
+            .map(|_| {
+                gen.current_transition(&globals, 0.0);
+                let label = gen.root_generator.last_symbol.unwrap();
+                is_onset(gen, label)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_eval_euclid() {
+        let mut gen = eval_rhythm("(euclid 'eu 5 16 (saw 100) :dur 125)");
+        assert_eq!(gen.root_generator.event_mapping.len(), 16);
+        assert_eq!(gen.root_generator.default_duration, 125);
+
+        // the states are laid out in the canonical order ...
+        let onsets: Vec<bool> = (0..16).map(|i| is_onset(&gen, label(i))).collect();
+        assert_eq!(onsets, pattern("x..x..x..x..x..."));
+
+        // ... and played in a loop, so any 16 steps have 5 onsets
+        let played = play(&mut gen, 48);
+        for bar in played.chunks(16) {
+            assert_eq!(bar.iter().filter(|o| **o).count(), 5);
+        }
+
+        // rotated by two steps
+        let gen = eval_rhythm("(euclid 'eu 5 16 (saw 100) :rot 2)");
+        let onsets: Vec<bool> = (0..16).map(|i| is_onset(&gen, label(i))).collect();
+        assert_eq!(onsets, pattern(".x..x..x..x...x."));
+    }
+
+    #[test]
+    fn test_eval_euclid_rep_keep() {
+        // without :rep, no state follows itself
+        let gen = eval_rhythm("(euclid 'eu 3 8 (saw 100))");
+        assert!((0..8).all(|i| !gen
+            .root_generator
+            .duration_mapping
+            .contains_key(&(label(i), label(i)))));
+
+        // with :rep, every state can repeat
+        let gen = eval_rhythm("(euclid 'eu 3 8 (saw 100) :rep 50 :max-rep 2)");
+        assert!((0..8).all(|i| gen
+            .root_generator
+            .duration_mapping
+            .contains_key(&(label(i), label(i)))));
+
+        // :keep leaves the structure to the generator that's already running
+        let gen = eval_rhythm("(euclid 'eu 3 8 (saw 100) :keep #t)");
+        assert!(gen.keep_root);
+        assert!(gen.root_generator.event_mapping.is_empty());
+    }
+
+    #[test]
+    fn test_eval_density() {
+        let mut gen = eval_rhythm("(density 'de 1.0 8 (saw 100))");
+        // an onset and a silence state per step
+        assert_eq!(gen.root_generator.event_mapping.len(), 16);
+        // (the first step is wherever the generator starts)
+        assert!(play(&mut gen, 32)[1..].iter().all(|o| *o));
+
+        let mut gen = eval_rhythm("(density 'de 0.0 8 (saw 100))");
+        assert!(play(&mut gen, 32)[1..].iter().all(|o| !*o));
+
+        // roughly half of the steps have onsets
+        let mut gen = eval_rhythm("(density 'de 0.5 8 (saw 100))");
+        let onsets = play(&mut gen, 1000).iter().filter(|o| **o).count();
+        assert!(onsets > 350 && onsets < 650);
+    }
+
+    #[test]
+    fn test_euclidean_pattern() {
+        let tresillo: Vec<bool> = "x..x..x.".chars().map(|c| c == 'x').collect();
+        assert_eq!(euclidean_pattern(3, 8), tresillo);
+        assert_eq!(euclidean_pattern(5, 16).iter().filter(|o| **o).count(), 5);
+        // the canonical phase, not a rotation of it
+        assert_eq!(euclidean_pattern(5, 16), pattern("x..x..x..x..x..."));
+        assert_eq!(euclidean_pattern(5, 8), pattern("x.xx.xx."));
+        assert_eq!(euclidean_pattern(7, 12), pattern("x.xx.x.xx.x."));
+        assert_eq!(euclidean_pattern(2, 5), pattern("x.x.."));
+        assert_eq!(euclidean_pattern(4, 4), vec![true; 4]);
+        assert_eq!(euclidean_pattern(0, 4), vec![false; 4]);
+        // can't have more onsets than steps
+        assert_eq!(euclidean_pattern(6, 4), vec![true; 4]);
+    }
+}
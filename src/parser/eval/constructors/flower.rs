@@ -77,6 +77,10 @@ pub fn flower(
                     collected_evs.push(SourceEvent::Sound(e));
                     continue;
                 }
+                EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                    collected_evs.extend(l.into_iter().map(SourceEvent::Sound));
+                    continue;
+                }
                 EvaluatedExpr::BuiltIn(BuiltIn::ControlEvent(e)) => {
                     collected_evs.push(SourceEvent::Control(e));
                     continue;
@@ -107,6 +111,9 @@ pub fn flower(
                 EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(e)) => {
                     final_vec.push(SourceEvent::Sound(e));
                 }
+                EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                    final_vec.extend(l.into_iter().map(SourceEvent::Sound));
+                }
                 EvaluatedExpr::BuiltIn(BuiltIn::ControlEvent(e)) => {
                     final_vec.push(SourceEvent::Control(e));
                 }
@@ -153,6 +160,9 @@ pub fn flower(
                             EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(e)) => {
                                 final_vec.push(SourceEvent::Sound(e));
                             }
+                            EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                                final_vec.extend(l.into_iter().map(SourceEvent::Sound));
+                            }
                             EvaluatedExpr::BuiltIn(BuiltIn::ControlEvent(e)) => {
                                 final_vec.push(SourceEvent::Control(e));
                             }
@@ -73,6 +73,10 @@ pub fn friendship(
                     collected_evs.push(SourceEvent::Sound(e));
                     continue;
                 }
+                EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                    collected_evs.extend(l.into_iter().map(SourceEvent::Sound));
+                    continue;
+                }
                 EvaluatedExpr::BuiltIn(BuiltIn::ControlEvent(e)) => {
                     collected_evs.push(SourceEvent::Control(e));
                     continue;
@@ -103,6 +107,9 @@ pub fn friendship(
                 EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(e)) => {
                     final_vec.push(SourceEvent::Sound(e));
                 }
+                EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                    final_vec.extend(l.into_iter().map(SourceEvent::Sound));
+                }
                 EvaluatedExpr::BuiltIn(BuiltIn::ControlEvent(e)) => {
                     final_vec.push(SourceEvent::Control(e));
                 }
@@ -162,6 +169,9 @@ pub fn friendship(
                             EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(e)) => {
                                 final_vec.push(SourceEvent::Sound(e));
                             }
+                            EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                                final_vec.extend(l.into_iter().map(SourceEvent::Sound));
+                            }
                             EvaluatedExpr::BuiltIn(BuiltIn::ControlEvent(e)) => {
                                 final_vec.push(SourceEvent::Control(e));
                             }
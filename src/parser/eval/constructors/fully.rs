@@ -70,6 +70,10 @@ pub fn fully(
                     collected_evs.push(SourceEvent::Sound(e));
                     continue;
                 }
+                EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                    collected_evs.extend(l.into_iter().map(SourceEvent::Sound));
+                    continue;
+                }
                 EvaluatedExpr::BuiltIn(BuiltIn::ControlEvent(e)) => {
                     collected_evs.push(SourceEvent::Control(e));
                     continue;
@@ -101,6 +105,14 @@ pub fn fully(
                 let final_vec = vec![SourceEvent::Sound(e)];
                 final_mapping.insert(next_char, final_vec);
             }
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                // all events in one state, i.e. a chord
+                let next_char: char = std::char::from_u32(last_char as u32 + 1).unwrap();
+                last_char = next_char;
+                labels.push(next_char);
+                let final_vec = l.into_iter().map(SourceEvent::Sound).collect();
+                final_mapping.insert(next_char, final_vec);
+            }
             EvaluatedExpr::BuiltIn(BuiltIn::ControlEvent(e)) => {
                 let next_char: char = std::char::from_u32(last_char as u32 + 1).unwrap();
                 last_char = next_char;
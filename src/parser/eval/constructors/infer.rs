@@ -120,6 +120,10 @@ pub fn infer(
                     ev_vec.push(SourceEvent::Sound(e));
                     continue;
                 }
+                EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                    ev_vec.extend(l.into_iter().map(SourceEvent::Sound));
+                    continue;
+                }
                 EvaluatedExpr::BuiltIn(BuiltIn::ControlEvent(e)) => {
                     ev_vec.push(SourceEvent::Control(e));
                     continue;
@@ -70,6 +70,10 @@ pub fn learn(
                     ev_vec.push(SourceEvent::Sound(e));
                     continue;
                 }
+                EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                    ev_vec.extend(l.into_iter().map(SourceEvent::Sound));
+                    continue;
+                }
                 EvaluatedExpr::BuiltIn(BuiltIn::ControlEvent(e)) => {
                     ev_vec.push(SourceEvent::Control(e));
                     continue;
@@ -53,6 +53,12 @@ pub fn linear(
                 dur_vec.push(dur.clone());
                 continue;
             }
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                // all events on one step, i.e. a chord
+                ev_vecs.push(l.into_iter().map(SourceEvent::Sound).collect());
+                dur_vec.push(dur.clone());
+                continue;
+            }
             EvaluatedExpr::BuiltIn(BuiltIn::ControlEvent(e)) => {
                 ev_vecs.push(vec![SourceEvent::Control(e)]);
                 dur_vec.push(dur.clone());
@@ -61,6 +67,10 @@ pub fn linear(
             EvaluatedExpr::Float(f) => {
                 *dur_vec.last_mut().unwrap() = DynVal::with_value(f);
             }
+            // durations in beats, like (beats 0.25)
+            EvaluatedExpr::BuiltIn(BuiltIn::Parameter(p)) => {
+                *dur_vec.last_mut().unwrap() = p;
+            }
             _ => println! {"ignored"},
         }
     }
@@ -56,6 +56,12 @@ pub fn a_loop(
                 dur_vec.push(dur.clone());
                 continue;
             }
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                // all events on one step, i.e. a chord
+                ev_vecs.push(l.into_iter().map(SourceEvent::Sound).collect());
+                dur_vec.push(dur.clone());
+                continue;
+            }
             EvaluatedExpr::BuiltIn(BuiltIn::ControlEvent(e)) => {
                 ev_vecs.push(vec![SourceEvent::Control(e)]);
                 dur_vec.push(dur.clone());
@@ -68,6 +74,14 @@ pub fn a_loop(
                     dur_vec.push(DynVal::with_value(f));
                 }
             }
+            // durations in beats, like (beats 0.25)
+            EvaluatedExpr::BuiltIn(BuiltIn::Parameter(p)) => {
+                if !dur_vec.is_empty() {
+                    *dur_vec.last_mut().unwrap() = p;
+                } else {
+                    dur_vec.push(p);
+                }
+            }
             EvaluatedExpr::Keyword(k) => {
                 if k == "keep" {
                     if let Some(EvaluatedExpr::Boolean(b)) = tail_drain.next() {
@@ -1,5 +1,7 @@
+pub mod arp;
 pub mod chop;
 pub mod cyc;
+pub mod euclid;
 pub mod facts;
 pub mod flower;
 pub mod friendship;
@@ -9,4 +11,5 @@ pub mod learn;
 pub mod linear;
 pub mod r#loop;
 pub mod nuc;
+pub mod prog;
 pub mod stages;
@@ -52,6 +52,9 @@ pub fn nuc(
     while let Some(c) = tail_drain.next() {
         match c {
             EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(e)) => ev_vec.push(SourceEvent::Sound(e)),
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                ev_vec.extend(l.into_iter().map(SourceEvent::Sound))
+            }
             EvaluatedExpr::BuiltIn(BuiltIn::ControlEvent(c)) => {
                 ev_vec.push(SourceEvent::Control(c))
             }
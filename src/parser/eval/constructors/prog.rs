@@ -0,0 +1,171 @@
+use crate::builtin_types::*;
+use crate::event::*;
+use crate::music_theory::{self, Scale};
+use crate::parameter::modifier::tuning_modifier::active_tuning;
+use crate::parameter::*;
+use crate::parser::eval::constructors::arp::{default_duration, step_generator};
+use crate::parser::eval::events::chord::{chord_events, default_template};
+
+use std::sync;
+
+use crate::parser::{BuiltIn, EvaluatedExpr, FunctionMap};
+use crate::{OutputMode, SampleAndWavematrixSet};
+use parking_lot::Mutex;
+
+/// (prog 'name '(i iv V7 i) :key 'a 'minor :dur 800 :oct 0 :inv 1 :spread 0.5 (saw 0))
+/// a loop over chords on the degrees of a key, one chord per step,
+/// uppercase numerals are major chords, lowercase ones minor
+pub fn prog(
+    functions: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    global_parameters: &sync::Arc<GlobalParameters>,
+    sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    out_mode: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..).skip(1).peekable();
+
+    // name is the first symbol
+    let name = if let Some(EvaluatedExpr::Symbol(n)) = tail_drain.next() {
+        n
+    } else {
+        "".to_string()
+    };
+
+    // the numerals can be given as a quoted list or as plain symbols
+    let mut numerals = Vec::new();
+    while let Some(c) = tail_drain.peek() {
+        match c {
+            EvaluatedExpr::SymbolList(l) => numerals.extend(l.iter().cloned()),
+            EvaluatedExpr::Symbol(s) => numerals.push(s.clone()),
+            _ => break,
+        }
+        tail_drain.next();
+    }
+
+    let mut root = "c".to_string();
+    let mut mode = "major".to_string();
+    let mut dur = default_duration(global_parameters);
+    let mut octave = 0;
+    let mut inversion = 0;
+    let mut spread = 0.0;
+    let mut template = None;
+
+    while let Some(c) = tail_drain.next() {
+        match c {
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(e)) => template = Some(e),
+            EvaluatedExpr::Keyword(k) => match k.as_str() {
+                "key" => {
+                    if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.next() {
+                        root = s;
+                    }
+                    if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.peek() {
+                        mode = s.clone();
+                        tail_drain.next();
+                    }
+                }
+                "dur" => match tail_drain.next() {
+                    Some(EvaluatedExpr::Float(n)) => dur = DynVal::with_value(n),
+                    Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(p))) => dur = p,
+                    _ => {}
+                },
+                "oct" => {
+                    if let Some(EvaluatedExpr::Float(n)) = tail_drain.next() {
+                        octave = n as i32;
+                    }
+                }
+                "inv" => {
+                    if let Some(EvaluatedExpr::Float(n)) = tail_drain.next() {
+                        inversion = n as usize;
+                    }
+                }
+                "spread" => {
+                    if let Some(EvaluatedExpr::Float(n)) = tail_drain.next() {
+                        spread = n;
+                    }
+                }
+                _ => println!("ignored keyword {}", k),
+            },
+            _ => println! {"ignored"},
+        }
+    }
+
+    let scale = if let Some(s) = Scale::from_name(&mode, &root) {
+        s
+    } else {
+        println!("unknown scale {}", mode);
+        return None;
+    };
+
+    let template = if let Some(t) = template {
+        t
+    } else {
+        default_template(functions, global_parameters, sample_set, out_mode)?
+    };
+
+    let tuning = active_tuning(global_parameters);
+    let mut steps = Vec::new();
+    for numeral in numerals.iter() {
+        if let Some((degree, intervals)) = music_theory::roman_numeral(numeral) {
+            let root = scale.chord_keys(degree, 1, octave, &tuning)[0];
+            let keys = music_theory::invert(
+                &intervals.iter().map(|i| root + i).collect::<Vec<f32>>(),
+                inversion,
+                &tuning,
+            );
+            steps.push(
+                chord_events(&template, &keys, spread, global_parameters)
+                    .into_iter()
+                    .map(SourceEvent::Sound)
+                    .collect(),
+            );
+        } else {
+            println!("can't read numeral {}", numeral);
+        }
+    }
+
+    if steps.is_empty() {
+        return None;
+    }
+
+    Some(EvaluatedExpr::BuiltIn(BuiltIn::Generator(step_generator(
+        name, steps, dur, false,
+    ))))
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::parser::*;
+
+    #[test]
+    fn test_eval_prog() {
+        let snippet = "(prog 'pr '(i iv V7 I) :key 'a 'minor :dur 400)";
+        let mut functions = FunctionMap::new();
+        let sample_set = sync::Arc::new(Mutex::new(SampleAndWavematrixSet::new()));
+
+        functions
+            .fmap
+            .insert("prog".to_string(), eval::constructors::prog::prog);
+
+        let globals = sync::Arc::new(GlobalParameters::new());
+
+        match eval_from_str(
+            snippet,
+            &functions,
+            &globals,
+            &sample_set,
+            OutputMode::Stereo,
+        ) {
+            Ok(EvaluatedExpr::BuiltIn(BuiltIn::Generator(g))) => {
+                let mapping = &g.root_generator.event_mapping;
+                assert_eq!(mapping.len(), 4);
+                assert_eq!(mapping[&'1'].len(), 3);
+                // the dominant seventh has four notes
+                assert_eq!(mapping[&'3'].len(), 4);
+                assert_eq!(g.root_generator.default_duration, 400);
+            }
+            _ => panic!(),
+        }
+    }
+}
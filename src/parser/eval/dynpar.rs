@@ -1,7 +1,18 @@
 use crate::parameter::{
-    modifier::bounce_modifier::BounceModifier, modifier::brownian_modifier::BrownianModifier,
-    modifier::envelope_modifier::EnvelopeModifier, modifier::randrange_modifier::RandRangeModifier,
-    DynVal,
+    modifier::beats_modifier::beats_parameter,
+    modifier::bounce_modifier::BounceModifier,
+    modifier::brownian_modifier::BrownianModifier,
+    modifier::choose_modifier::ChooseModifier,
+    modifier::clock::Clock,
+    modifier::envelope_modifier::EnvelopeModifier,
+    modifier::lfo_step_modifier::{LfoShape, LfoStepModifier},
+    modifier::noise_modifier::NoiseModifier,
+    modifier::randrange_modifier::RandRangeModifier,
+    modifier::sample_and_hold_modifier::SampleAndHoldModifier,
+    modifier::scale_modifier::DegreeModifier,
+    modifier::sequence_modifier::{SequenceMode, SequenceModifier},
+    modifier::tuning_modifier::{key_parameter, note_parameter},
+    units, DynVal,
 };
 
 use crate::parser::{BuiltIn, EvaluatedExpr, FunctionMap};
@@ -51,6 +62,18 @@ fn find_keyword_bool(
     }
 }
 
+/// :clock 'time or :clock 'beats, steps otherwise
+fn find_keyword_clock(
+    raw_params: &HashMap<String, EvaluatedExpr>,
+    global_parameters: &sync::Arc<GlobalParameters>,
+) -> Clock {
+    if let Some(EvaluatedExpr::Symbol(s)) = raw_params.get("clock") {
+        Clock::from_name(s, global_parameters)
+    } else {
+        Clock::Steps
+    }
+}
+
 fn get_next_param(tail_drain: &mut std::vec::Drain<EvaluatedExpr>, default: f32) -> DynVal {
     if let Some(b) = tail_drain.next() {
         match b {
@@ -66,7 +89,7 @@ fn get_next_param(tail_drain: &mut std::vec::Drain<EvaluatedExpr>, default: f32)
 pub fn bounce(
     _: &FunctionMap,
     tail: &mut Vec<EvaluatedExpr>,
-    _: &sync::Arc<GlobalParameters>,
+    global_parameters: &sync::Arc<GlobalParameters>,
     _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
     _: OutputMode,
 ) -> Option<EvaluatedExpr> {
@@ -77,7 +100,13 @@ pub fn bounce(
     let max = get_next_param(&mut tail_drain, 0.0);
 
     let keyword_params = get_keyword_params(&mut tail_drain);
-    let steps = find_keyword_param(&keyword_params, "steps", 128.0);
+    let clock = find_keyword_clock(&keyword_params, global_parameters);
+    // the period is given in the unit of the clock
+    let steps = if keyword_params.contains_key("period") {
+        find_keyword_param(&keyword_params, "period", 128.0)
+    } else {
+        find_keyword_param(&keyword_params, "steps", 128.0)
+    };
 
     //println!("{:?} {:?} {:?}", min, max, steps);
 
@@ -89,6 +118,7 @@ pub fn bounce(
             max,
             steps,
             step_count: 0.0,
+            clock,
         })),
     })))
 }
@@ -248,3 +278,268 @@ pub fn randrange(
         modifier: Some(Box::new(RandRangeModifier::from_data(min, max))),
     })))
 }
+
+/// a scale degree, resolved against the active scale
+pub fn degree(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    global_parameters: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..);
+    tail_drain.next();
+
+    let degree = get_next_param(&mut tail_drain, 1.0);
+
+    let keyword_params = get_keyword_params(&mut tail_drain);
+    let octave = find_keyword_param(&keyword_params, "oct", 0.0);
+
+    Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(DynVal {
+        val: 0.0,
+        static_val: 0.0,
+        modifier: Some(Box::new(DegreeModifier {
+            degree,
+            octave,
+            global_parameters: sync::Arc::clone(global_parameters),
+        })),
+    })))
+}
+
+/// (seq 100 200 300 :mode 'pingpong), modes are 'cycle, 'pingpong and 'random
+pub fn seq(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..);
+    tail_drain.next();
+
+    let mut values = Vec::new();
+    let mut mode = SequenceMode::Cycle;
+
+    while let Some(c) = tail_drain.next() {
+        match c {
+            EvaluatedExpr::Float(f) => values.push(DynVal::with_value(f)),
+            EvaluatedExpr::BuiltIn(BuiltIn::Parameter(p)) => values.push(p),
+            EvaluatedExpr::Keyword(k) => {
+                if k == "mode" {
+                    if let Some(EvaluatedExpr::Symbol(m)) = tail_drain.next() {
+                        mode = SequenceMode::from_name(&m);
+                    }
+                }
+            }
+            _ => {} // ignore
+        }
+    }
+
+    Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(DynVal {
+        val: 0.0,
+        static_val: 0.0,
+        modifier: Some(Box::new(SequenceModifier::from_data(values, mode))),
+    })))
+}
+
+/// (choose 100 200 300 :weights 1 2 1)
+pub fn choose(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..);
+    tail_drain.next();
+
+    let mut values = Vec::new();
+    let mut weights = Vec::new();
+    let mut collect_weights = false;
+
+    for c in tail_drain {
+        match c {
+            EvaluatedExpr::Float(f) => {
+                if collect_weights {
+                    weights.push(f);
+                } else {
+                    values.push(DynVal::with_value(f));
+                }
+            }
+            EvaluatedExpr::BuiltIn(BuiltIn::Parameter(p)) => values.push(p),
+            EvaluatedExpr::Keyword(k) => {
+                collect_weights = k == "weights" || k == "w";
+            }
+            _ => {} // ignore
+        }
+    }
+
+    Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(DynVal {
+        val: 0.0,
+        static_val: 0.0,
+        modifier: Some(Box::new(ChooseModifier::from_data(values, &weights))),
+    })))
+}
+
+/// sample-and-hold, (sh (randr 0 1) :every 4)
+pub fn sample_and_hold(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..);
+    tail_drain.next();
+
+    let inner = get_next_param(&mut tail_drain, 0.0);
+
+    let keyword_params = get_keyword_params(&mut tail_drain);
+    let every = find_keyword_param(&keyword_params, "every", 4.0);
+
+    Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(DynVal {
+        val: 0.0,
+        static_val: 0.0,
+        modifier: Some(Box::new(SampleAndHoldModifier::from_data(inner, every))),
+    })))
+}
+
+/// (noise 0 1 :smooth 0.2)
+pub fn noise(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..);
+    tail_drain.next();
+
+    let min = get_next_param(&mut tail_drain, 0.0);
+    let max = get_next_param(&mut tail_drain, 1.0);
+
+    let keyword_params = get_keyword_params(&mut tail_drain);
+    let smooth = find_keyword_param(&keyword_params, "smooth", 0.0);
+
+    Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(DynVal {
+        val: 0.0,
+        static_val: 0.0,
+        modifier: Some(Box::new(NoiseModifier::from_data(min, max, smooth))),
+    })))
+}
+
+/// a step-clocked oscillator,
+/// (lfo-step :period 16 :shape 'sine :min 100 :max 1000 :phase 0.25),
+/// shapes are 'sine, 'tri, 'saw, 'rsaw and 'sqr,
+/// with :clock 'time or 'beats the period is given in milliseconds or beats
+pub fn lfo_step(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    global_parameters: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..);
+    tail_drain.next();
+
+    let keyword_params = get_keyword_params(&mut tail_drain);
+    let period = find_keyword_param(&keyword_params, "period", 16.0);
+    let min = find_keyword_param(&keyword_params, "min", 0.0);
+    let max = find_keyword_param(&keyword_params, "max", 1.0);
+    let phase = find_keyword_param(&keyword_params, "phase", 0.0).evaluate_numerical();
+    let shape = if let Some(EvaluatedExpr::Symbol(s)) = keyword_params.get("shape") {
+        LfoShape::from_name(s)
+    } else {
+        LfoShape::Sine
+    };
+
+    let mut lfo = LfoStepModifier::from_data(period, shape, min, max, phase);
+    lfo.clock = find_keyword_clock(&keyword_params, global_parameters);
+
+    Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(DynVal {
+        val: 0.0,
+        static_val: 0.0,
+        modifier: Some(Box::new(lfo)),
+    })))
+}
+
+/// a frequency from a note name, (note 'a4), or a (midi) key, (note 60)
+pub fn note(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    global_parameters: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..);
+    tail_drain.next();
+
+    match tail_drain.next() {
+        Some(EvaluatedExpr::Symbol(s)) => Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(
+            note_parameter(&s, global_parameters),
+        ))),
+        Some(EvaluatedExpr::Float(f)) => Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(
+            key_parameter(DynVal::with_value(f), global_parameters),
+        ))),
+        Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(p))) => Some(EvaluatedExpr::BuiltIn(
+            BuiltIn::Parameter(key_parameter(p, global_parameters)),
+        )),
+        _ => None,
+    }
+}
+
+/// a frequency from a midi note number, (midi 60)
+pub fn midi(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    global_parameters: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..);
+    tail_drain.next();
+
+    let key = get_next_param(&mut tail_drain, 69.0);
+
+    Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(key_parameter(
+        key,
+        global_parameters,
+    ))))
+}
+
+/// a duration in beats, (beats 0.25), follows the tempo
+pub fn beats(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    global_parameters: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..);
+    tail_drain.next();
+
+    let beats = get_next_param(&mut tail_drain, 1.0);
+
+    Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(beats_parameter(
+        beats,
+        global_parameters,
+    ))))
+}
+
+/// a linear gain from decibels, (db -6)
+pub fn db(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..);
+    tail_drain.next();
+
+    if let Some(EvaluatedExpr::Float(f)) = tail_drain.next() {
+        Some(EvaluatedExpr::Float(units::db_to_gain(f)))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,244 @@
+use crate::event::Event;
+use crate::music_theory;
+use crate::parameter::modifier::tuning_modifier::{active_tuning, key_parameter};
+use crate::parameter::{DynVal, ParameterValue};
+use crate::parser::eval::events::sound::sound;
+use crate::parser::{BuiltIn, EvaluatedExpr, FunctionMap};
+use crate::{GlobalParameters, OutputMode, SampleAndWavematrixSet};
+use parking_lot::Mutex;
+use ruffbox_synth::building_blocks::SynthParameterLabel;
+use std::sync;
+
+/// One copy of the template event per chord tone (given as keys),
+/// spread evenly across the stereo field.
+pub fn chord_events(
+    template: &Event,
+    keys: &[f32],
+    spread: f32,
+    global_parameters: &sync::Arc<GlobalParameters>,
+) -> Vec<Event> {
+    let mut events = Vec::new();
+    for (i, key) in keys.iter().enumerate() {
+        let mut ev = template.clone();
+        ev.params.insert(
+            SynthParameterLabel::PitchFrequency,
+            ParameterValue::Scalar(key_parameter(DynVal::with_value(*key), global_parameters)),
+        );
+        if spread != 0.0 && keys.len() > 1 {
+            let pos = -spread + 2.0 * spread * i as f32 / (keys.len() - 1) as f32;
+            ev.params.insert(
+                SynthParameterLabel::ChannelPosition,
+                ParameterValue::Scalar(DynVal::with_value(pos)),
+            );
+        }
+        events.push(ev);
+    }
+    events
+}
+
+/// The event chord tones are derived from if the user doesn't provide one.
+pub fn default_template(
+    functions: &FunctionMap,
+    global_parameters: &sync::Arc<GlobalParameters>,
+    sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    out_mode: OutputMode,
+) -> Option<Event> {
+    let mut tail = vec![
+        EvaluatedExpr::FunctionName("saw".to_string()),
+        EvaluatedExpr::Float(100.0),
+    ];
+    if let Some(EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(ev))) = sound(
+        functions,
+        &mut tail,
+        global_parameters,
+        sample_set,
+        out_mode,
+    ) {
+        Some(ev)
+    } else {
+        None
+    }
+}
+
+/// The keys of a chord on a root note, like 'c4 'min7.
+pub fn chord_keys(
+    root: &str,
+    quality: &str,
+    inversion: usize,
+    tuning: &music_theory::Tuning,
+) -> Option<Vec<f32>> {
+    let root_key = music_theory::to_key(music_theory::from_string(root)) as f32;
+    if let Some(intervals) = music_theory::chord_intervals(quality) {
+        let keys: Vec<f32> = intervals.iter().map(|i| root_key + i).collect();
+        Some(music_theory::invert(&keys, inversion, tuning))
+    } else {
+        println!("unknown chord {}", quality);
+        None
+    }
+}
+
+/// (chord 'saw 'c4 'min7 :inv 1 :spread 0.5 :lpf 1000)
+/// all other keyword arguments are passed on to the sound event
+pub fn chord(
+    functions: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    global_parameters: &sync::Arc<GlobalParameters>,
+    sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    out_mode: OutputMode,
+) -> Option<EvaluatedExpr> {
+    let mut tail_drain = tail.drain(..).skip(1).peekable();
+
+    let synth = if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.next() {
+        s
+    } else {
+        return None;
+    };
+
+    let root = if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.next() {
+        s
+    } else {
+        return None;
+    };
+
+    let quality = if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.peek() {
+        let q = s.clone();
+        tail_drain.next();
+        q
+    } else {
+        "maj".to_string()
+    };
+
+    let mut inversion = 0;
+    let mut spread = 0.0;
+
+    // the sound event gets everything else
+    let mut sound_tail = vec![
+        EvaluatedExpr::FunctionName(synth),
+        EvaluatedExpr::Symbol(root.clone()),
+    ];
+
+    while let Some(c) = tail_drain.next() {
+        match c {
+            EvaluatedExpr::Keyword(k) if k == "inv" => {
+                if let Some(EvaluatedExpr::Float(f)) = tail_drain.next() {
+                    inversion = f as usize;
+                }
+            }
+            EvaluatedExpr::Keyword(k) if k == "spread" => {
+                if let Some(EvaluatedExpr::Float(f)) = tail_drain.next() {
+                    spread = f;
+                }
+            }
+            _ => sound_tail.push(c),
+        }
+    }
+
+    let keys = chord_keys(
+        &root,
+        &quality,
+        inversion,
+        &active_tuning(global_parameters),
+    )?;
+
+    if let Some(EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(template))) = sound(
+        functions,
+        &mut sound_tail,
+        global_parameters,
+        sample_set,
+        out_mode,
+    ) {
+        Some(EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(
+            chord_events(&template, &keys, spread, global_parameters),
+        )))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::parameter::modifier::EvalContext;
+    use crate::parser::*;
+
+    #[test]
+    fn test_eval_chord() {
+        let snippet = "(chord 'saw 'c4 'min7 :inv 1 :spread 0.5 :lvl 0.3)";
+        let mut functions = FunctionMap::new();
+        let sample_set = sync::Arc::new(Mutex::new(SampleAndWavematrixSet::new()));
+
+        functions
+            .fmap
+            .insert("chord".to_string(), eval::events::chord::chord);
+
+        let globals = sync::Arc::new(GlobalParameters::new());
+
+        match eval_from_str(
+            snippet,
+            &functions,
+            &globals,
+            &sample_set,
+            OutputMode::Stereo,
+        ) {
+            Ok(EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(mut evs))) => {
+                assert_eq!(evs.len(), 4);
+                let freqs: Vec<f32> = evs
+                    .iter_mut()
+                    .map(|ev| {
+                        if let ruffbox_synth::building_blocks::SynthParameterValue::ScalarF32(f) =
+                            ev.get_static(&EvalContext::default()).params
+                                [&SynthParameterLabel::PitchFrequency]
+                        {
+                            f
+                        } else {
+                            0.0
+                        }
+                    })
+                    .collect();
+                // first inversion, eb4 g4 bb4 c5
+                assert!((freqs[0] - 311.13).abs() < 0.01);
+                assert!((freqs[3] - 523.25).abs() < 0.01);
+                assert!(matches!(
+                    evs[0].get_static(&EvalContext::default()).params[&SynthParameterLabel::ChannelPosition],
+                    ruffbox_synth::building_blocks::SynthParameterValue::ScalarF32(p) if p == -0.5
+                ));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_chord_in_constructor() {
+        // a chord is one state, with all its notes
+        let snippet = "(fully 'f (chord 'saw 'c4 'maj) (chord 'saw 'a3 'min7))";
+        let mut functions = FunctionMap::new();
+        let sample_set = sync::Arc::new(Mutex::new(SampleAndWavematrixSet::new()));
+
+        functions
+            .fmap
+            .insert("chord".to_string(), eval::events::chord::chord);
+        functions
+            .fmap
+            .insert("fully".to_string(), eval::constructors::fully::fully);
+
+        let globals = sync::Arc::new(GlobalParameters::new());
+
+        match eval_from_str(
+            snippet,
+            &functions,
+            &globals,
+            &sample_set,
+            OutputMode::Stereo,
+        ) {
+            Ok(EvaluatedExpr::BuiltIn(BuiltIn::Generator(g))) => {
+                let mapping = &g.root_generator.event_mapping;
+                assert_eq!(mapping.len(), 2);
+                let mut sizes: Vec<usize> = mapping.values().map(|evs| evs.len()).collect();
+                sizes.sort();
+                assert_eq!(sizes, vec![3, 4]);
+            }
+            _ => panic!(),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub mod chord;
 pub mod control;
 pub mod modulators;
 pub mod parameters;
@@ -1,17 +1,17 @@
 use crate::event::{Event, EventOperation};
-use crate::music_theory;
-use crate::parameter::{DynVal, ParameterValue};
+use crate::parameter::modifier::scale_modifier::quantize_parameter;
+use crate::parameter::modifier::tuning_modifier::{key_parameter, TransposeModifier};
+use crate::parameter::{units, DynVal, ParameterValue};
 use crate::parser::{BuiltIn, EvaluatedExpr, FunctionMap};
 use crate::{GlobalParameters, OutputMode, SampleAndWavematrixSet};
 use parking_lot::Mutex;
 use ruffbox_synth::building_blocks::SynthParameterLabel;
 use std::sync;
 
-#[allow(clippy::excessive_precision)]
 pub fn transpose(
     _: &FunctionMap,
     tail: &mut Vec<EvaluatedExpr>,
-    _: &sync::Arc<GlobalParameters>,
+    global_parameters: &sync::Arc<GlobalParameters>,
     _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
     _: OutputMode,
 ) -> Option<EvaluatedExpr> {
@@ -21,22 +21,24 @@ pub fn transpose(
         let mut ev =
             Event::with_name_and_operation("ratefreq".to_string(), EventOperation::Multiply);
 
-        let factor = if n < 0.0 {
-            1.0 / f32::powf(1.05946309436, -n) // twelfth root of two, no complex scales so far ...
-        } else if n > 0.0 {
-            f32::powf(1.05946309436, n)
-        } else {
-            1.0
+        // steps of the active tuning
+        let factor = DynVal {
+            val: 1.0,
+            static_val: 1.0,
+            modifier: Some(Box::new(TransposeModifier {
+                steps: n,
+                global_parameters: sync::Arc::clone(global_parameters),
+            })),
         };
 
         ev.params.insert(
             SynthParameterLabel::PlaybackRate,
-            ParameterValue::Scalar(DynVal::with_value(factor)),
+            ParameterValue::Scalar(factor.clone()),
         );
 
         ev.params.insert(
             SynthParameterLabel::PitchFrequency,
-            ParameterValue::Scalar(DynVal::with_value(factor)),
+            ParameterValue::Scalar(factor),
         );
         Some(EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(ev)))
     } else {
@@ -47,11 +49,11 @@ pub fn transpose(
 pub fn parameter(
     _: &FunctionMap,
     tail: &mut Vec<EvaluatedExpr>,
-    _: &sync::Arc<GlobalParameters>,
+    global_parameters: &sync::Arc<GlobalParameters>,
     _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
     _: OutputMode,
 ) -> Option<EvaluatedExpr> {
-    let mut tail_drain = tail.drain(..);
+    let mut tail_drain = tail.drain(..).peekable();
 
     // get function name, check which parameter we're dealing with
     if let Some(EvaluatedExpr::FunctionName(f)) = tail_drain.next() {
@@ -82,24 +84,38 @@ pub fn parameter(
                             ParameterValue::Scalar(pl)
                         }
                         EvaluatedExpr::BuiltIn(BuiltIn::Modulator(m)) => m,
-                        EvaluatedExpr::Symbol(s)
-                            if param_key == SynthParameterLabel::PitchFrequency
-                                || param_key == SynthParameterLabel::LowpassCutoffFrequency
-                                || param_key == SynthParameterLabel::HighpassCutoffFrequency
-                                || param_key == SynthParameterLabel::PeakFrequency =>
-                        {
-                            ParameterValue::Scalar(DynVal::with_value(music_theory::to_freq(
-                                music_theory::from_string(&s),
-                                music_theory::Tuning::EqualTemperament,
-                            )))
-                        }
                         EvaluatedExpr::Symbol(s) => {
-                            // jump out if the user entered garbage ...
-                            crate::parser::eval::events::sound::map_symbolic_param_value(&s)?
+                            if let Some(n) = units::note_value(param_key, &s, global_parameters) {
+                                ParameterValue::Scalar(n)
+                            } else {
+                                // jump out if the user entered garbage ...
+                                crate::parser::eval::events::sound::map_symbolic_param_value(&s)?
+                            }
                         }
                         _ => ParameterValue::Scalar(DynVal::with_value(0.5)), // should be save ...
                     },
                 );
+                // notes are resolved against the active tuning
+                if param_key == SynthParameterLabel::PitchNote
+                    && matches!(ev.op, EventOperation::Replace)
+                {
+                    if let Some(ParameterValue::Scalar(key)) = ev.params.remove(&param_key) {
+                        ev.params.insert(
+                            SynthParameterLabel::PitchFrequency,
+                            ParameterValue::Scalar(key_parameter(key, global_parameters)),
+                        );
+                    }
+                }
+                // pitch parameters can be snapped to the active scale,
+                // leave anything but :quant for what follows
+                if matches!(tail_drain.peek(), Some(EvaluatedExpr::Keyword(k)) if k == "quant") {
+                    tail_drain.next();
+                    if let Some(EvaluatedExpr::Boolean(true)) = tail_drain.next() {
+                        if let Some(p) = ev.params.get_mut(&SynthParameterLabel::PitchFrequency) {
+                            quantize_parameter(p, global_parameters);
+                        }
+                    }
+                }
                 //println!("{:?}", ev);
                 Some(EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(ev)))
             } else {
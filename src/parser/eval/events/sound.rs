@@ -1,7 +1,8 @@
 use crate::event::{Event, EventOperation};
 use crate::event_helpers::map_parameter;
-use crate::music_theory;
-use crate::parameter::{DynVal, ParameterValue};
+use crate::parameter::modifier::scale_modifier::quantize_parameter;
+use crate::parameter::modifier::tuning_modifier::{key_parameter, note_parameter};
+use crate::parameter::{units, DynVal, ParameterValue};
 use crate::parser::{BuiltIn, EvaluatedExpr, FunctionMap};
 use crate::{GlobalParameters, OutputMode, SampleAndWavematrixSet};
 use parking_lot::Mutex;
@@ -104,9 +105,26 @@ fn collect_param_value(
     }
 }
 
+// note names for frequency parameters, like :lpf 'a4,
+// everything else is collected as usual
+fn collect_unit_value(
+    label: SynthParameterLabel,
+    tail_drain: &mut std::iter::Peekable<std::vec::Drain<EvaluatedExpr>>,
+    global_parameters: &sync::Arc<GlobalParameters>,
+) -> ParameterValue {
+    if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.peek() {
+        if let Some(n) = units::note_value(label, s, global_parameters) {
+            tail_drain.next();
+            return ParameterValue::Scalar(n);
+        }
+    }
+    collect_param_value(tail_drain)
+}
+
 fn get_pitch_param(
     ev: &mut Event,
     tail_drain: &mut std::iter::Peekable<std::vec::Drain<EvaluatedExpr>>,
+    global_parameters: &sync::Arc<GlobalParameters>,
 ) {
     // first arg is always freq ...
     ev.params.insert(
@@ -116,10 +134,7 @@ fn get_pitch_param(
             Some(EvaluatedExpr::Float(n)) => ParameterValue::Scalar(DynVal::with_value(n)),
             Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(pl))) => ParameterValue::Scalar(pl),
             Some(EvaluatedExpr::Symbol(s)) => {
-                ParameterValue::Scalar(DynVal::with_value(music_theory::to_freq(
-                    music_theory::from_string(&s),
-                    music_theory::Tuning::EqualTemperament,
-                )))
+                ParameterValue::Scalar(note_parameter(&s, global_parameters))
             }
             _ => ParameterValue::Scalar(DynVal::with_value(100.0)),
         },
@@ -268,7 +283,7 @@ fn nofilter_defaults(ev: &mut Event) {
 pub fn sound(
     _: &FunctionMap,
     tail: &mut Vec<EvaluatedExpr>,
-    _: &sync::Arc<GlobalParameters>,
+    global_parameters: &sync::Arc<GlobalParameters>,
     sample_set_sync: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
     _: OutputMode,
 ) -> Option<EvaluatedExpr> {
@@ -289,80 +304,80 @@ pub fn sound(
         "sine" => {
             let mut ev =
                 Event::with_name_and_operation("sine".to_string(), EventOperation::Replace);
-            get_pitch_param(&mut ev, &mut tail_drain);
+            get_pitch_param(&mut ev, &mut tail_drain, global_parameters);
             nofilter_defaults(&mut ev);
             ev
         }
         "tri" => {
             let mut ev = Event::with_name_and_operation("tri".to_string(), EventOperation::Replace);
-            get_pitch_param(&mut ev, &mut tail_drain);
+            get_pitch_param(&mut ev, &mut tail_drain, global_parameters);
             synth_defaults(&mut ev);
             ev
         }
         "saw" => {
             let mut ev = Event::with_name_and_operation("saw".to_string(), EventOperation::Replace);
-            get_pitch_param(&mut ev, &mut tail_drain);
+            get_pitch_param(&mut ev, &mut tail_drain, global_parameters);
             synth_defaults(&mut ev);
             ev
         }
         "fmsaw" => {
             let mut ev =
                 Event::with_name_and_operation("fmsaw".to_string(), EventOperation::Replace);
-            get_pitch_param(&mut ev, &mut tail_drain);
+            get_pitch_param(&mut ev, &mut tail_drain, global_parameters);
             synth_defaults(&mut ev);
             ev
         }
         "fmsqr" => {
             let mut ev =
                 Event::with_name_and_operation("fmsqr".to_string(), EventOperation::Replace);
-            get_pitch_param(&mut ev, &mut tail_drain);
+            get_pitch_param(&mut ev, &mut tail_drain, global_parameters);
             synth_defaults(&mut ev);
             ev
         }
         "fmtri" => {
             let mut ev =
                 Event::with_name_and_operation("fmtri".to_string(), EventOperation::Replace);
-            get_pitch_param(&mut ev, &mut tail_drain);
+            get_pitch_param(&mut ev, &mut tail_drain, global_parameters);
             synth_defaults(&mut ev);
             ev
         }
         "wsaw" => {
             let mut ev =
                 Event::with_name_and_operation("wsaw".to_string(), EventOperation::Replace);
-            get_pitch_param(&mut ev, &mut tail_drain);
+            get_pitch_param(&mut ev, &mut tail_drain, global_parameters);
             synth_defaults(&mut ev);
             ev
         }
         "sqr" => {
             let mut ev = Event::with_name_and_operation("sqr".to_string(), EventOperation::Replace);
-            get_pitch_param(&mut ev, &mut tail_drain);
+            get_pitch_param(&mut ev, &mut tail_drain, global_parameters);
             synth_defaults(&mut ev);
             ev
         }
         "cub" => {
             let mut ev = Event::with_name_and_operation("cub".to_string(), EventOperation::Replace);
-            get_pitch_param(&mut ev, &mut tail_drain);
+            get_pitch_param(&mut ev, &mut tail_drain, global_parameters);
             nofilter_defaults(&mut ev);
             ev
         }
         "risset" => {
             let mut ev =
                 Event::with_name_and_operation("risset".to_string(), EventOperation::Replace);
-            get_pitch_param(&mut ev, &mut tail_drain);
+            get_pitch_param(&mut ev, &mut tail_drain, global_parameters);
             synth_defaults(&mut ev);
             ev
         }
         "wtab" => {
             let mut ev =
                 Event::with_name_and_operation("wavetable".to_string(), EventOperation::Replace);
-            get_pitch_param(&mut ev, &mut tail_drain);
+            get_pitch_param(&mut ev, &mut tail_drain, global_parameters);
             synth_defaults(&mut ev);
             ev
         }
         "wmat" => {
             let mut ev =
                 Event::with_name_and_operation("wavematrix".to_string(), EventOperation::Replace);
-            get_pitch_param(&mut ev, &mut tail_drain);
+            get_pitch_param(&mut ev, &mut tail_drain, global_parameters);
             synth_defaults(&mut ev);
             ev
         }
@@ -401,6 +416,48 @@ pub fn sound(
 
             ev // return event
         }
+        "stream" => {
+            // long files that are streamed from disk,
+            // found by keywords (including the set name)
+            let mut keyword_set: HashSet<String> = HashSet::new();
+            while let Some(EvaluatedExpr::Symbol(s)) = tail_drain.peek() {
+                keyword_set.insert(s.to_string());
+                tail_drain.next();
+            }
+
+            let sample_set = sample_set_sync.lock();
+            let stream_info = if let Some(info) = sample_set.stream_keys(&keyword_set) {
+                info
+            } else {
+                println!("can't find stream {:?}", keyword_set);
+                return None;
+            };
+
+            let mut ev = Event::with_name("stream".to_string());
+            for k in stream_info.key.iter() {
+                ev.tags.insert(k.to_string());
+            }
+
+            ev.params.insert(
+                SynthParameterLabel::SampleBufferNumber,
+                ParameterValue::Scalar(DynVal::with_value(stream_info.bufnum as f32)),
+            );
+            // start is given in milliseconds here
+            ev.params.insert(
+                SynthParameterLabel::PlaybackStart,
+                ParameterValue::Scalar(DynVal::with_value(0.0)),
+            );
+            ev.params.insert(
+                SynthParameterLabel::EnvelopeLevel,
+                ParameterValue::Scalar(DynVal::with_value(0.5)),
+            );
+            ev.params.insert(
+                SynthParameterLabel::ChannelPosition,
+                ParameterValue::Scalar(DynVal::with_value(0.00)),
+            );
+
+            ev // return event
+        }
         _ => {
             // check if it's a sample event
             let sample_set = sample_set_sync.lock();
@@ -459,6 +516,13 @@ pub fn sound(
                 ev.tags.insert(s.clone());
                 tail_drain.next();
             }
+        } else if k == "quant" {
+            // snap the pitch to the active scale
+            if let Some(EvaluatedExpr::Boolean(true)) = tail_drain.next() {
+                if let Some(p) = ev.params.get_mut(&SynthParameterLabel::PitchFrequency) {
+                    quantize_parameter(p, global_parameters);
+                }
+            }
         } else if k == "wm" {
             // wavematrix lookup
             if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.peek() {
@@ -476,9 +540,27 @@ pub fn sound(
                 ev.params
                     .insert(map_parameter(&k), collect_param_value(&mut tail_drain));
             }
+        } else if map_parameter(&k) == SynthParameterLabel::PitchNote {
+            // notes are resolved against the active tuning
+            if let Some(EvaluatedExpr::Symbol(s)) = tail_drain.peek() {
+                let freq = note_parameter(s, global_parameters);
+                tail_drain.next();
+                ev.params.insert(
+                    SynthParameterLabel::PitchFrequency,
+                    ParameterValue::Scalar(freq),
+                );
+            } else if let ParameterValue::Scalar(key) = collect_param_value(&mut tail_drain) {
+                ev.params.insert(
+                    SynthParameterLabel::PitchFrequency,
+                    ParameterValue::Scalar(key_parameter(key, global_parameters)),
+                );
+            }
         } else {
-            ev.params
-                .insert(map_parameter(&k), collect_param_value(&mut tail_drain));
+            let label = map_parameter(&k);
+            ev.params.insert(
+                label,
+                collect_unit_value(label, &mut tail_drain, global_parameters),
+            );
         }
     }
 
@@ -489,6 +571,7 @@ pub fn sound(
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
+    use crate::parameter::modifier::EvalContext;
     use crate::parser::*;
 
     #[test]
@@ -522,4 +605,141 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_eval_stream() {
+        let snippet = "(stream 'rain :start 30000)";
+        let mut functions = FunctionMap::new();
+        let sample_set = sync::Arc::new(Mutex::new(SampleAndWavematrixSet::new()));
+
+        let mut key = HashSet::new();
+        key.insert("rain".to_string());
+        sample_set.lock().insert_stream(
+            "field".to_string(),
+            crate::sample_set::SampleInfo {
+                key,
+                bufnum: 0,
+                duration: 300000,
+                path: "field/rain.flac".to_string(),
+                downmix_stereo: true,
+                max_duration: None,
+            },
+        );
+
+        functions
+            .fmap
+            .insert("stream".to_string(), eval::events::sound::sound);
+
+        let globals = sync::Arc::new(GlobalParameters::new());
+
+        match eval_from_str(
+            snippet,
+            &functions,
+            &globals,
+            &sample_set,
+            OutputMode::Stereo,
+        ) {
+            Ok(EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(mut ev))) => {
+                assert_eq!(ev.name, "stream");
+                assert!(ev.tags.contains("field"));
+                let st = ev.get_static(&EvalContext::default());
+                assert!(matches!(
+                    st.params[&SynthParameterLabel::PlaybackStart],
+                    ruffbox_synth::building_blocks::SynthParameterValue::ScalarF32(s) if s == 30000.0
+                ));
+            }
+            _ => assert!(false),
+        }
+    }
+    #[test]
+    fn test_eval_quant() {
+        let snippet = "(saw 460 :quant #t)";
+        let mut functions = FunctionMap::new();
+        let sample_set = sync::Arc::new(Mutex::new(SampleAndWavematrixSet::new()));
+
+        functions
+            .fmap
+            .insert("saw".to_string(), eval::events::sound::sound);
+
+        let globals = sync::Arc::new(GlobalParameters::new());
+
+        match eval_from_str(
+            snippet,
+            &functions,
+            &globals,
+            &sample_set,
+            OutputMode::Stereo,
+        ) {
+            Ok(EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(mut ev))) => {
+                let st = ev.get_static(&EvalContext::default());
+                // snapped to a4, as the default scale is c major
+                assert!(matches!(
+                    st.params[&SynthParameterLabel::PitchFrequency],
+                    ruffbox_synth::building_blocks::SynthParameterValue::ScalarF32(f) if (f - 440.0).abs() < 0.01
+                ));
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_eval_units() {
+        let snippet = "(saw 'a4 :lvl -6db :lpf 'a5 :dur 1/16)";
+        let mut functions = FunctionMap::new();
+        let sample_set = sync::Arc::new(Mutex::new(SampleAndWavematrixSet::new()));
+
+        functions
+            .fmap
+            .insert("saw".to_string(), eval::events::sound::sound);
+
+        let globals = sync::Arc::new(GlobalParameters::new());
+
+        match eval_from_str(
+            snippet,
+            &functions,
+            &globals,
+            &sample_set,
+            OutputMode::Stereo,
+        ) {
+            Ok(EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(mut ev))) => {
+                let st = ev.get_static(&EvalContext::default());
+                assert!(matches!(
+                    st.params[&SynthParameterLabel::EnvelopeLevel],
+                    ruffbox_synth::building_blocks::SynthParameterValue::ScalarF32(f) if (f - 0.501).abs() < 0.001
+                ));
+                assert!(matches!(
+                    st.params[&SynthParameterLabel::LowpassCutoffFrequency],
+                    ruffbox_synth::building_blocks::SynthParameterValue::ScalarF32(f) if (f - 880.0).abs() < 0.01
+                ));
+                // a sixteenth is a quarter of the default duration
+                assert!(matches!(
+                    st.params[&SynthParameterLabel::Duration],
+                    ruffbox_synth::building_blocks::SynthParameterValue::ScalarF32(f) if (f - 50.0).abs() < 0.01
+                ));
+            }
+            _ => panic!(),
+        }
+
+        // fractions are only beats for durations, elsewhere they're numbers
+        match eval_from_str(
+            "(saw 100 :lvl 1/2 :atk 1/8)",
+            &functions,
+            &globals,
+            &sample_set,
+            OutputMode::Stereo,
+        ) {
+            Ok(EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(mut ev))) => {
+                let st = ev.get_static(&EvalContext::default());
+                assert!(matches!(
+                    st.params[&SynthParameterLabel::EnvelopeLevel],
+                    ruffbox_synth::building_blocks::SynthParameterValue::ScalarF32(f) if f == 0.5
+                ));
+                assert!(matches!(
+                    st.params[&SynthParameterLabel::Attack],
+                    ruffbox_synth::building_blocks::SynthParameterValue::ScalarF32(f) if (f - 100.0).abs() < 0.01
+                ));
+            }
+            _ => panic!(),
+        }
+    }
 }
@@ -4,9 +4,15 @@ mod exhibit;
 mod inhibit;
 mod lifemodel;
 mod pear;
+mod scale;
+mod when;
 
 use crate::builtin_types::*;
+use crate::event::Event;
 use crate::generator_processor::GeneratorProcessor;
+use crate::parameter::{hash_dynval, hash_parameter};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::sync;
 
 use crate::parser::{BuiltIn, EvaluatedExpr, FunctionMap};
@@ -65,6 +71,16 @@ pub fn eval_every(
     eval_generator_processor(every::collect_every, tail)
 }
 
+pub fn eval_when(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    eval_generator_processor(when::collect_when, tail)
+}
+
 pub fn eval_lifemodel(
     _: &FunctionMap,
     tail: &mut Vec<EvaluatedExpr>,
@@ -75,6 +91,137 @@ pub fn eval_lifemodel(
     eval_generator_processor(lifemodel::collect_lifemodel, tail)
 }
 
+pub fn eval_scale(
+    _: &FunctionMap,
+    tail: &mut Vec<EvaluatedExpr>,
+    _: &sync::Arc<GlobalParameters>,
+    _: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    _: OutputMode,
+) -> Option<EvaluatedExpr> {
+    // without anything to process, it's the global scale
+    if tail.len() <= 1 + scale::scale_arg_count(tail) {
+        scale::scale_from_args(tail)
+            .map(|s| EvaluatedExpr::BuiltIn(BuiltIn::Command(Command::Scale(s))))
+    } else {
+        eval_generator_processor(scale::collect_scale, tail)
+    }
+}
+
+// the structure of an evaluated argument, so that processors with
+// the same arguments end up with the same identity
+fn hash_expr<H: Hasher>(e: &EvaluatedExpr, state: &mut H) {
+    std::mem::discriminant(e).hash(state);
+    match e {
+        EvaluatedExpr::Float(f) => f.to_bits().hash(state),
+        EvaluatedExpr::Symbol(s)
+        | EvaluatedExpr::Keyword(s)
+        | EvaluatedExpr::String(s)
+        | EvaluatedExpr::FunctionName(s) => s.hash(state),
+        EvaluatedExpr::SymbolList(l) => l.hash(state),
+        EvaluatedExpr::Boolean(b) => b.hash(state),
+        EvaluatedExpr::BuiltIn(b) => {
+            std::mem::discriminant(b).hash(state);
+            match b {
+                BuiltIn::SoundEvent(ev) => hash_event(ev, state),
+                BuiltIn::SoundEventList(evs) => evs.iter().for_each(|ev| hash_event(ev, state)),
+                BuiltIn::ControlEvent(ev) => ev.tags.hash(state),
+                BuiltIn::Parameter(p) => hash_dynval(p, state),
+                BuiltIn::Modulator(p) | BuiltIn::Matrix(p) | BuiltIn::Vector(p) => {
+                    hash_parameter(p, state)
+                }
+                BuiltIn::Generator(g) => g.id_tags.hash(state),
+                BuiltIn::GeneratorProcessorOrModifier(gpom) => hash_gpom(gpom, state),
+                BuiltIn::GeneratorProcessorOrModifierList(l)
+                | BuiltIn::GeneratorModifierList(l) => {
+                    l.iter().for_each(|gpom| hash_gpom(gpom, state))
+                }
+                // nothing a processor would take as an argument
+                _ => {}
+            }
+        }
+    }
+}
+
+fn hash_event<H: Hasher>(ev: &Event, state: &mut H) {
+    ev.name.hash(state);
+    ev.tags.hash(state);
+    std::mem::discriminant(&ev.op).hash(state);
+    // the parameters are unordered, so combine them in an order-independent way
+    let mut params: u64 = 0;
+    for (label, val) in ev.params.iter() {
+        let mut param_state = DefaultHasher::new();
+        label.hash(&mut param_state);
+        hash_parameter(val, &mut param_state);
+        params = params.wrapping_add(param_state.finish());
+    }
+    params.hash(state);
+}
+
+fn hash_gpom<H: Hasher>(gpom: &GeneratorProcessorOrModifier, state: &mut H) {
+    match gpom {
+        GeneratorProcessorOrModifier::GeneratorProcessor(gp) => gp.get_id().hash(state),
+        GeneratorProcessorOrModifier::GeneratorModifierFunction((fun, pos_args, named_args)) => {
+            (*fun as usize).hash(state);
+            pos_args.iter().for_each(|arg| hash_config(arg, state));
+            let mut named: u64 = 0;
+            for (name, arg) in named_args.iter() {
+                let mut arg_state = DefaultHasher::new();
+                name.hash(&mut arg_state);
+                hash_config(arg, &mut arg_state);
+                named = named.wrapping_add(arg_state.finish());
+            }
+            named.hash(state);
+        }
+    }
+}
+
+fn hash_config<H: Hasher>(param: &ConfigParameter, state: &mut H) {
+    std::mem::discriminant(param).hash(state);
+    match param {
+        ConfigParameter::Numeric(f) => f.to_bits().hash(state),
+        ConfigParameter::Dynamic(d) => hash_dynval(d, state),
+        ConfigParameter::Symbolic(s) => s.hash(state),
+        _ => {}
+    }
+}
+
+// an explicit :id 'name, otherwise the processor type and a hash of
+// its arguments, which helps to find the matching processor when
+// transferring the state
+fn processor_id(tail: &mut Vec<EvaluatedExpr>) -> String {
+    if let Some(pos) = tail
+        .iter()
+        .position(|e| matches!(e, EvaluatedExpr::Keyword(k) if k == "id"))
+    {
+        if let Some(EvaluatedExpr::Symbol(_)) = tail.get(pos + 1) {
+            if let EvaluatedExpr::Symbol(s) = tail.remove(pos + 1) {
+                tail.remove(pos);
+                return s;
+            }
+        }
+    }
+
+    let (kind, args) = match tail.split_first() {
+        Some((EvaluatedExpr::FunctionName(f), args)) => (f.as_str(), args),
+        _ => ("", &tail[..]),
+    };
+    let mut state = DefaultHasher::new();
+    for arg in args.iter() {
+        hash_expr(arg, &mut state);
+    }
+    format!("{}-{:016x}", kind, state.finish())
+}
+
+fn collect_with_id(
+    collector: Collector,
+    tail: &mut Vec<EvaluatedExpr>,
+) -> Box<dyn GeneratorProcessor + Send> {
+    let id = processor_id(tail);
+    let mut proc = collector(tail);
+    proc.set_id(id);
+    proc
+}
+
 // store list of genProcs in a vec if there's no root gen ???
 fn eval_generator_processor(
     collector: Collector,
@@ -83,7 +230,7 @@ fn eval_generator_processor(
     let last = tail.pop();
     Some(match last {
         Some(EvaluatedExpr::BuiltIn(BuiltIn::Generator(mut g))) => {
-            g.processors.push(collector(tail));
+            g.processors.push(collect_with_id(collector, tail));
             EvaluatedExpr::BuiltIn(BuiltIn::Generator(g))
         }
         Some(EvaluatedExpr::Symbol(s)) => {
@@ -95,28 +242,30 @@ fn eval_generator_processor(
                     tail.push(prev.unwrap()); // push back for further processing
                     tail.push(EvaluatedExpr::Symbol(s));
                     EvaluatedExpr::BuiltIn(BuiltIn::GeneratorProcessorOrModifier(
-                        GeneratorProcessorOrModifier::GeneratorProcessor(collector(tail)),
+                        GeneratorProcessorOrModifier::GeneratorProcessor(collect_with_id(
+                            collector, tail,
+                        )),
                     ))
                 }
                 _ => {
                     tail.push(prev.unwrap()); // push back for further processing
                     EvaluatedExpr::BuiltIn(BuiltIn::PartProxy(PartProxy::Proxy(
                         s,
-                        vec![GeneratorProcessorOrModifier::GeneratorProcessor(collector(
-                            tail,
-                        ))],
+                        vec![GeneratorProcessorOrModifier::GeneratorProcessor(
+                            collect_with_id(collector, tail),
+                        )],
                     )))
                 }
             }
         }
         Some(EvaluatedExpr::BuiltIn(BuiltIn::PartProxy(PartProxy::Proxy(s, mut proxy_mods)))) => {
-            proxy_mods.push(GeneratorProcessorOrModifier::GeneratorProcessor(collector(
-                tail,
-            )));
+            proxy_mods.push(GeneratorProcessorOrModifier::GeneratorProcessor(
+                collect_with_id(collector, tail),
+            ));
             EvaluatedExpr::BuiltIn(BuiltIn::PartProxy(PartProxy::Proxy(s, proxy_mods)))
         }
         Some(EvaluatedExpr::BuiltIn(BuiltIn::ProxyList(mut l))) => {
-            let gp = collector(tail);
+            let gp = collect_with_id(collector, tail);
             let mut pdrain = l.drain(..);
             let mut new_list = Vec::new();
             while let Some(PartProxy::Proxy(s, mut proxy_mods)) = pdrain.next() {
@@ -126,7 +275,7 @@ fn eval_generator_processor(
             EvaluatedExpr::BuiltIn(BuiltIn::ProxyList(new_list))
         }
         Some(EvaluatedExpr::BuiltIn(BuiltIn::GeneratorList(mut gl))) => {
-            let gp = collector(tail);
+            let gp = collect_with_id(collector, tail);
             for gen in gl.iter_mut() {
                 gen.processors.push(gp.clone());
             }
@@ -147,34 +296,114 @@ fn eval_generator_processor(
                         ),
                     ));
                     EvaluatedExpr::BuiltIn(BuiltIn::GeneratorProcessorOrModifier(
-                        GeneratorProcessorOrModifier::GeneratorProcessor(collector(tail)),
+                        GeneratorProcessorOrModifier::GeneratorProcessor(collect_with_id(
+                            collector, tail,
+                        )),
                     ))
                 }
                 _ => EvaluatedExpr::BuiltIn(BuiltIn::GeneratorProcessorOrModifierList(vec![
                     gp,
-                    GeneratorProcessorOrModifier::GeneratorProcessor(collector(tail)),
+                    GeneratorProcessorOrModifier::GeneratorProcessor(collect_with_id(
+                        collector, tail,
+                    )),
                 ])),
             }
         }
         Some(EvaluatedExpr::BuiltIn(BuiltIn::GeneratorProcessorOrModifierList(mut l))) => {
-            l.push(GeneratorProcessorOrModifier::GeneratorProcessor(collector(
-                tail,
-            )));
+            l.push(GeneratorProcessorOrModifier::GeneratorProcessor(
+                collect_with_id(collector, tail),
+            ));
             EvaluatedExpr::BuiltIn(BuiltIn::GeneratorProcessorOrModifierList(l))
         }
         // pure modifier lists are handled differently
         Some(EvaluatedExpr::BuiltIn(BuiltIn::GeneratorModifierList(ml))) => {
             tail.push(EvaluatedExpr::BuiltIn(BuiltIn::GeneratorModifierList(ml)));
             EvaluatedExpr::BuiltIn(BuiltIn::GeneratorProcessorOrModifier(
-                GeneratorProcessorOrModifier::GeneratorProcessor(collector(tail)),
+                GeneratorProcessorOrModifier::GeneratorProcessor(collect_with_id(collector, tail)),
             ))
         }
         Some(l) => {
             tail.push(l);
             EvaluatedExpr::BuiltIn(BuiltIn::GeneratorProcessorOrModifier(
-                GeneratorProcessorOrModifier::GeneratorProcessor(collector(tail)),
+                GeneratorProcessorOrModifier::GeneratorProcessor(collect_with_id(collector, tail)),
             ))
         }
         None => return None,
     })
 }
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+    use crate::generator_processor::GeneratorProcessorState;
+    use crate::parameter::{DynVal, ParameterValue};
+    use crate::parser::eval::constructors::arp::step_generator;
+    use ruffbox_synth::building_blocks::SynthParameterLabel;
+
+    fn every_tail(name: &str, freq: f32) -> Vec<EvaluatedExpr> {
+        let mut ev = Event::with_name("sqr".to_string());
+        ev.params.insert(
+            SynthParameterLabel::PitchFrequency,
+            ParameterValue::Scalar(DynVal::with_value(freq)),
+        );
+        vec![
+            EvaluatedExpr::FunctionName(name.to_string()),
+            EvaluatedExpr::Keyword("n".to_string()),
+            EvaluatedExpr::Float(4.0),
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(ev)),
+        ]
+    }
+
+    fn step_count(proc: &(dyn GeneratorProcessor + Send)) -> usize {
+        if let GeneratorProcessorState::Count(c) = proc.get_state() {
+            c
+        } else {
+            0
+        }
+    }
+
+    #[test]
+    fn test_processor_id() {
+        let low = collect_with_id(every::collect_every, &mut every_tail("every", 100.0));
+        let high = collect_with_id(every::collect_every, &mut every_tail("every", 200.0));
+        // the same arguments give the same identity ...
+        let low_again = collect_with_id(every::collect_every, &mut every_tail("every", 100.0));
+        assert_eq!(low.get_id(), low_again.get_id());
+        // ... only differing in the event arguments doesn't ...
+        assert_ne!(low.get_id(), high.get_id());
+        // ... and neither does the type of processor
+        assert_ne!(
+            processor_id(&mut every_tail("every", 100.0)),
+            processor_id(&mut every_tail("pear", 100.0))
+        );
+
+        let mut old = step_generator(
+            "gen".to_string(),
+            vec![Vec::new()],
+            DynVal::with_value(100.0),
+            false,
+        );
+        old.processors.push(low);
+        old.processors.push(high);
+        old.processors[0].set_state(GeneratorProcessorState::Count(3));
+        old.processors[1].set_state(GeneratorProcessorState::Count(11));
+
+        // re-evaluated with the processors swapped
+        let mut new = old.clone();
+        new.processors.clear();
+        new.processors.push(collect_with_id(
+            every::collect_every,
+            &mut every_tail("every", 200.0),
+        ));
+        new.processors.push(collect_with_id(
+            every::collect_every,
+            &mut every_tail("every", 100.0),
+        ));
+        new.transfer_state(&old);
+
+        assert_eq!(step_count(new.processors[0].as_ref()), 11);
+        assert_eq!(step_count(new.processors[1].as_ref()), 3);
+    }
+}
@@ -6,14 +6,13 @@ use crate::parameter::DynVal;
 use crate::parser::{BuiltIn, EvaluatedExpr};
 
 pub fn collect_every(tail: &mut Vec<EvaluatedExpr>) -> Box<dyn GeneratorProcessor + Send> {
-    let mut tail_drain = tail.drain(..);
-    tail_drain.next(); // skip function name
+    let mut tail_drain = tail.drain(..).skip(1).peekable(); // skip function name
 
     let mut proc = EveryProcessor::new();
 
     let mut last_filters = Vec::new();
 
-    let mut cur_step = DynVal::with_value(1.0); // if nothing is specified, it's always applied
+    let mut cur_step = EveryCondition::with_n(DynVal::with_value(1.0)); // if nothing is specified, it's always applied
     let mut gen_mod_funs = Vec::new();
     let mut events = Vec::new();
     let mut collect_filters = false;
@@ -38,6 +37,10 @@ pub fn collect_every(tail: &mut Vec<EvaluatedExpr>) -> Box<dyn GeneratorProcesso
                 events.push(e);
                 collect_filters = false;
             }
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                events.extend(l);
+                collect_filters = false;
+            }
             EvaluatedExpr::Symbol(s) => {
                 if collect_filters {
                     last_filters.push(s)
@@ -71,7 +74,7 @@ pub fn collect_every(tail: &mut Vec<EvaluatedExpr>) -> Box<dyn GeneratorProcesso
                         // collect new filters
                         collect_filters = true;
                     }
-                    "n" => {
+                    "n" | "pattern" => {
                         if !events.is_empty() || !gen_mod_funs.is_empty() {
                             let mut n_mods = Vec::new();
                             n_mods.append(&mut gen_mod_funs);
@@ -92,15 +95,31 @@ pub fn collect_every(tail: &mut Vec<EvaluatedExpr>) -> Box<dyn GeneratorProcesso
                                 n_mods,
                             ));
                         }
-                        // grab new probability
-                        cur_step = match tail_drain.next() {
-                            Some(EvaluatedExpr::Float(f)) => DynVal::with_value(f),
-                            Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(p))) => p,
-                            _ => DynVal::with_value(1.0),
-                        };
+                        if k == "pattern" {
+                            // grab new step pattern, i.e. 1 0 0 1
+                            let mut pattern = Vec::new();
+                            while let Some(EvaluatedExpr::Float(f)) = tail_drain.peek() {
+                                pattern.push(*f != 0.0);
+                                tail_drain.next();
+                            }
+                            cur_step = EveryCondition::with_pattern(pattern);
+                        } else {
+                            // grab new step
+                            cur_step = EveryCondition::with_n(match tail_drain.next() {
+                                Some(EvaluatedExpr::Float(f)) => DynVal::with_value(f),
+                                Some(EvaluatedExpr::BuiltIn(BuiltIn::Parameter(p))) => p,
+                                _ => DynVal::with_value(1.0),
+                            });
+                        }
 
                         collect_filters = false;
                     }
+                    "offset" => {
+                        // shifts the current step or pattern
+                        if let Some(EvaluatedExpr::Float(f)) = tail_drain.next() {
+                            cur_step.offset = f as usize;
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -34,6 +34,12 @@ pub fn collect_inhibit(tail: &mut Vec<EvaluatedExpr>) -> Box<dyn GeneratorProces
                     collect_filters = false;
                 }
             }
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                evs.extend(l);
+                if collect_filters {
+                    collect_filters = false;
+                }
+            }
             EvaluatedExpr::Keyword(k) => {
                 match k.as_str() {
                     "p" => {
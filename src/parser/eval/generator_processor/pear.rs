@@ -24,6 +24,12 @@ pub fn collect_pear(tail: &mut Vec<EvaluatedExpr>) -> Box<dyn GeneratorProcessor
                     collect_filters = false;
                 }
             }
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                evs.extend(l);
+                if collect_filters {
+                    collect_filters = false;
+                }
+            }
             EvaluatedExpr::Keyword(k) => {
                 match k.as_str() {
                     "p" => {
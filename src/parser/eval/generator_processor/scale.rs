@@ -0,0 +1,54 @@
+use crate::generator_processor::*;
+use crate::music_theory::Scale;
+use crate::parameter::ParameterValue;
+use crate::parser::{BuiltIn, EvaluatedExpr};
+use rust_music_theory::note::PitchClass;
+
+/// the number of leading arguments that describe the scale,
+/// either name or steps, and an optional root
+pub fn scale_arg_count(tail: &[EvaluatedExpr]) -> usize {
+    match tail.get(1) {
+        Some(EvaluatedExpr::Symbol(_)) | Some(EvaluatedExpr::BuiltIn(BuiltIn::Vector(_))) => {
+            match tail.get(2) {
+                // anything else might be a part name
+                Some(EvaluatedExpr::Symbol(s)) if PitchClass::from_str(s).is_some() => 2,
+                _ => 1,
+            }
+        }
+        _ => 0,
+    }
+}
+
+/// scale from name or steps, and an optional root
+pub fn scale_from_args(tail: &mut Vec<EvaluatedExpr>) -> Option<Scale> {
+    let count = scale_arg_count(tail);
+    let mut tail_drain = tail.drain(..).skip(1).take(count); // skip function name
+
+    let first = tail_drain.next();
+    let root = if let Some(EvaluatedExpr::Symbol(r)) = tail_drain.next() {
+        r
+    } else {
+        "c".to_string()
+    };
+
+    match first {
+        Some(EvaluatedExpr::Symbol(name)) => {
+            let scale = Scale::from_name(&name, &root);
+            if scale.is_none() {
+                println!("unknown scale: {}", name);
+            }
+            scale
+        }
+        Some(EvaluatedExpr::BuiltIn(BuiltIn::Vector(ParameterValue::Vector(mut steps)))) => {
+            let steps: Vec<f32> = steps.iter_mut().map(|s| s.evaluate_numerical()).collect();
+            Some(Scale::from_period_steps(&steps, &root))
+        }
+        _ => None,
+    }
+}
+
+pub fn collect_scale(tail: &mut Vec<EvaluatedExpr>) -> Box<dyn GeneratorProcessor + Send> {
+    Box::new(ScaleProcessor::new(
+        scale_from_args(tail).unwrap_or_default(),
+    ))
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use crate::builtin_types::*;
+use crate::event::Event;
+use crate::generator_processor::*;
+use crate::parser::{BuiltIn, EvaluatedExpr};
+
+type GenModFunWithArgs = (
+    crate::generator::GenModFun,
+    Vec<ConfigParameter>,
+    HashMap<String, ConfigParameter>,
+);
+
+// save the current context, if there's something to be applied
+fn save_context(
+    proc: &mut WhenProcessor,
+    conditions: &mut Vec<WhenCondition>,
+    filters: &mut Vec<String>,
+    events: &mut Vec<Event>,
+    gen_mod_funs: &mut Vec<GenModFunWithArgs>,
+) {
+    if events.is_empty() && gen_mod_funs.is_empty() {
+        return;
+    }
+
+    let mut n_filters = Vec::new();
+    n_filters.append(filters);
+    if n_filters.is_empty() {
+        n_filters.push("".to_string());
+    }
+
+    let mut filtered_events = HashMap::new();
+    filtered_events.insert(n_filters, (true, std::mem::take(events)));
+
+    proc.things_to_be_applied.push((
+        std::mem::take(conditions),
+        filtered_events,
+        std::mem::take(gen_mod_funs),
+    ));
+}
+
+pub fn collect_when(tail: &mut Vec<EvaluatedExpr>) -> Box<dyn GeneratorProcessor + Send> {
+    Box::new(collect_when_processor(tail))
+}
+
+/// (when :after 64 (shrink) :global 'density :gt 0.5 :first 8 (bd))
+/// all conditions before some events or modifiers need to hold
+fn collect_when_processor(tail: &mut Vec<EvaluatedExpr>) -> WhenProcessor {
+    let mut tail_drain = tail.drain(..).skip(1).peekable(); // skip function name
+
+    let mut proc = WhenProcessor::new();
+
+    let mut conditions = Vec::new();
+    let mut last_filters = Vec::new();
+    let mut gen_mod_funs = Vec::new();
+    let mut events = Vec::new();
+    let mut collect_filters = false;
+
+    while let Some(c) = tail_drain.next() {
+        match c {
+            EvaluatedExpr::BuiltIn(BuiltIn::GeneratorProcessorOrModifier(
+                GeneratorProcessorOrModifier::GeneratorModifierFunction(gmf),
+            )) => {
+                gen_mod_funs.push(gmf);
+                collect_filters = false;
+            }
+            EvaluatedExpr::BuiltIn(BuiltIn::GeneratorModifierList(mut ml)) => {
+                for gpom in ml.drain(..) {
+                    if let GeneratorProcessorOrModifier::GeneratorModifierFunction(gmf) = gpom {
+                        gen_mod_funs.push(gmf);
+                    }
+                }
+                collect_filters = false;
+            }
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(e)) => {
+                events.push(e);
+                collect_filters = false;
+            }
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEventList(l)) => {
+                events.extend(l);
+                collect_filters = false;
+            }
+            EvaluatedExpr::Symbol(s) => {
+                if collect_filters {
+                    last_filters.push(s)
+                }
+            }
+            EvaluatedExpr::Keyword(k) => {
+                if k == "for" {
+                    if !events.is_empty() || !gen_mod_funs.is_empty() {
+                        // the conditions stay valid for the next filters
+                        let mut cur_conditions = conditions.clone();
+                        save_context(
+                            &mut proc,
+                            &mut cur_conditions,
+                            &mut last_filters,
+                            &mut events,
+                            &mut gen_mod_funs,
+                        );
+                    } else {
+                        last_filters.clear();
+                    }
+                    collect_filters = true;
+                    continue;
+                }
+
+                // a condition after something to be applied starts a new context
+                save_context(
+                    &mut proc,
+                    &mut conditions,
+                    &mut last_filters,
+                    &mut events,
+                    &mut gen_mod_funs,
+                );
+                collect_filters = false;
+
+                match k.as_str() {
+                    "after" => {
+                        if let Some(EvaluatedExpr::Float(f)) = tail_drain.next() {
+                            conditions.push(WhenCondition::After(f as usize));
+                        }
+                    }
+                    "first" => {
+                        if let Some(EvaluatedExpr::Float(f)) = tail_drain.next() {
+                            conditions.push(WhenCondition::First(f as usize));
+                        }
+                    }
+                    "between" => {
+                        if let (Some(EvaluatedExpr::Float(a)), Some(EvaluatedExpr::Float(b))) =
+                            (tail_drain.next(), tail_drain.next())
+                        {
+                            conditions.push(WhenCondition::Between(a as usize, b as usize));
+                        }
+                    }
+                    "bar-phase" => {
+                        if let Some(EvaluatedExpr::Float(from)) = tail_drain.next() {
+                            // without an end, until the end of the bar
+                            let to = if let Some(EvaluatedExpr::Float(to)) = tail_drain.peek() {
+                                let to = *to;
+                                tail_drain.next();
+                                to
+                            } else {
+                                1.0
+                            };
+                            conditions.push(WhenCondition::BarPhase(from, to));
+                        }
+                    }
+                    "global" => {
+                        if let (
+                            Some(EvaluatedExpr::Symbol(name)),
+                            Some(EvaluatedExpr::Keyword(cmp)),
+                            Some(EvaluatedExpr::Float(val)),
+                        ) = (tail_drain.next(), tail_drain.next(), tail_drain.next())
+                        {
+                            if let Some(cmp) = Comparison::from_name(&cmp) {
+                                conditions.push(WhenCondition::Global(name, cmp, val));
+                            } else {
+                                println!("unknown comparison {}", cmp);
+                            }
+                        }
+                    }
+                    _ => println!("unknown condition {}", k),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // save last context
+    save_context(
+        &mut proc,
+        &mut conditions,
+        &mut last_filters,
+        &mut events,
+        &mut gen_mod_funs,
+    );
+
+    proc
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_collect_when_conditions() {
+        let mut tail = vec![
+            EvaluatedExpr::FunctionName("when".to_string()),
+            EvaluatedExpr::Keyword("after".to_string()),
+            EvaluatedExpr::Float(64.0),
+            EvaluatedExpr::Keyword("global".to_string()),
+            EvaluatedExpr::Symbol("density".to_string()),
+            EvaluatedExpr::Keyword("gt".to_string()),
+            EvaluatedExpr::Float(0.5),
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(Event::with_name("bd".to_string()))),
+            EvaluatedExpr::Keyword("bar-phase".to_string()),
+            EvaluatedExpr::Float(0.75),
+            EvaluatedExpr::BuiltIn(BuiltIn::SoundEvent(Event::with_name("sn".to_string()))),
+        ];
+
+        let proc = collect_when_processor(&mut tail);
+        assert_eq!(proc.things_to_be_applied.len(), 2);
+        assert_eq!(
+            proc.things_to_be_applied[0].0,
+            vec![
+                WhenCondition::After(64),
+                WhenCondition::Global("density".to_string(), Comparison::Greater, 0.5)
+            ]
+        );
+        assert_eq!(
+            proc.things_to_be_applied[1].0,
+            vec![WhenCondition::BarPhase(0.75, 1.0)]
+        );
+    }
+}
@@ -1,6 +1,10 @@
 use crossbeam::channel::Receiver;
 use crossbeam::channel::Sender;
 use hound;
+
+use crate::flac_writer::FlacWriter;
+use crate::messages;
+use parking_lot::Mutex;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
 use std::{sync, thread};
@@ -71,6 +75,8 @@ pub struct RecordingControl<const MAX: usize, const NCHAN: usize> {
     pub catch_in: Option<Catch<MAX, NCHAN>>,
     pub catch_in_handle: Option<CatchHandle<MAX, NCHAN>>,
     pub samplerate: u32, // assume output and input have the same samplerate
+    pub retro_buffer: Option<sync::Arc<Mutex<RetroBuffer>>>, // always-on buffer, if enabled
+    pub retro_handle: Option<CatchHandle<MAX, NCHAN>>,
 }
 
 pub fn stop_writer_thread<const MAX: usize, const NCHAN: usize>(
@@ -80,10 +86,132 @@ pub fn stop_writer_thread<const MAX: usize, const NCHAN: usize>(
     handle.handle.unwrap().join().unwrap()
 }
 
+/// How and where to record.
+#[derive(Clone, Debug)]
+pub struct RecordingOptions {
+    pub rec_input: bool,
+    pub stems: bool, // one file per output channel pair
+    pub flac: bool,
+    pub bits: u16,               // 16, 24 or 32 (float, wav only)
+    pub split_time: Option<f32>, // seconds
+    pub split_size: Option<u64>, // bytes
+}
+
+impl Default for RecordingOptions {
+    fn default() -> Self {
+        RecordingOptions {
+            rec_input: false,
+            stems: false,
+            flac: false,
+            bits: 32,
+            split_time: None,
+            split_size: None,
+        }
+    }
+}
+
+enum SampleWriter {
+    Wav(hound::WavWriter<std::io::BufWriter<std::fs::File>>, u16),
+    Flac(FlacWriter),
+}
+
+impl SampleWriter {
+    fn create(path: &str, channels: usize, samplerate: u32, options: &RecordingOptions) -> Self {
+        if options.flac {
+            // flac doesn't do float
+            let bits = if options.bits == 16 { 16 } else { 24 };
+            SampleWriter::Flac(FlacWriter::create(path, channels, samplerate, bits).unwrap())
+        } else {
+            let spec = hound::WavSpec {
+                channels: channels as u16,
+                sample_rate: samplerate,
+                bits_per_sample: options.bits,
+                sample_format: if options.bits == 32 {
+                    hound::SampleFormat::Float
+                } else {
+                    hound::SampleFormat::Int
+                },
+            };
+            SampleWriter::Wav(hound::WavWriter::create(path, spec).unwrap(), options.bits)
+        }
+    }
+
+    fn write_frame(&mut self, frame: &[f32]) {
+        match self {
+            SampleWriter::Wav(writer, 32) => {
+                for s in frame {
+                    writer.write_sample(*s).unwrap();
+                }
+            }
+            SampleWriter::Wav(writer, bits) => {
+                let max_val = ((1 << (*bits - 1)) - 1) as f32;
+                for s in frame {
+                    writer
+                        .write_sample((s.clamp(-1.0, 1.0) * max_val) as i32)
+                        .unwrap();
+                }
+            }
+            SampleWriter::Flac(writer) => writer.write_frame(frame).unwrap(),
+        }
+    }
+
+    fn bytes_written(&self) -> u64 {
+        match self {
+            SampleWriter::Wav(writer, bits) => {
+                writer.len() as u64 * (*bits as u64 / 8) + 44 // header
+            }
+            SampleWriter::Flac(writer) => writer.bytes_written(),
+        }
+    }
+
+    fn finalize(self) {
+        match self {
+            SampleWriter::Wav(writer, _) => writer.finalize().unwrap(),
+            SampleWriter::Flac(writer) => writer.finalize().unwrap(),
+        }
+    }
+}
+
+/// The channel groups to write to separate files, either all channels
+/// in one file or, for stems, one file per output channel pair
+/// (stems aren't accepted in stereo, see `start_recording`).
+fn channel_groups(nchan: usize, stems: bool) -> Vec<Vec<usize>> {
+    if stems && nchan > 2 {
+        (0..nchan)
+            .step_by(2)
+            .map(|c| (c..(c + 2).min(nchan)).collect())
+            .collect()
+    } else {
+        vec![(0..nchan).collect()]
+    }
+}
+
+/// The file name for a channel group and file part.
+/// The path is given without extension.
+fn recording_file_name(
+    path: &str,
+    group: &[usize],
+    stems: bool,
+    part: usize,
+    options: &RecordingOptions,
+) -> String {
+    let mut name = path.to_string();
+    if stems {
+        let chans: Vec<String> = group.iter().map(|c| (c + 1).to_string()).collect();
+        name.push_str(&format!("_ch{}", chans.join("-")));
+    }
+    if part > 1 {
+        name.push_str(&format!("_part{}", part));
+    }
+    name.push_str(if options.flac { ".flac" } else { ".wav" });
+    name
+}
+
 pub fn start_writer_thread<const MAX: usize, const NCHAN: usize>(
     catch: Catch<MAX, NCHAN>,
     samplerate: u32,
     path: String,
+    options: RecordingOptions,
 ) -> CatchHandle<MAX, NCHAN> {
     let write_interval = catch.write_interval_ms;
     let running = sync::Arc::new(AtomicBool::new(true));
@@ -96,29 +224,60 @@ pub fn start_writer_thread<const MAX: usize, const NCHAN: usize>(
     let handle = Some(
         builder
             .spawn(move || {
-                let spec = hound::WavSpec {
-                    channels: NCHAN as u16, // record with global number of channels
-                    sample_rate: samplerate,
-                    bits_per_sample: 32, // 32bit float is fixed
-                    sample_format: hound::SampleFormat::Float,
+                let groups = channel_groups(NCHAN, options.stems);
+                let stems = groups.len() > 1;
+                let split_frames = options.split_time.map(|t| (t * samplerate as f32) as usize);
+
+                let mut part = 1;
+                let create_writers = |part: usize| -> Vec<SampleWriter> {
+                    groups
+                        .iter()
+                        .map(|g| {
+                            let file_name = recording_file_name(&path, g, stems, part, &options);
+                            println!("recording to {}", file_name);
+                            SampleWriter::create(&file_name, g.len(), samplerate, &options)
+                        })
+                        .collect()
                 };
 
+                let mut writers = create_writers(part);
+                let mut frames_written = 0;
+                let mut frame = [0.0; NCHAN];
+
                 let mut logical_time = 0.0;
                 let start_time = Instant::now();
 
-                let mut writer = hound::WavWriter::create(path, spec).unwrap();
-
                 while running2.load(Ordering::SeqCst) {
                     for mut stream_item in catch.catch_q.try_iter() {
                         for s in 0..stream_item.size {
-                            for ch in 0..NCHAN {
-                                writer.write_sample(stream_item.buffer[ch][s]).unwrap();
+                            for (ch, f) in frame.iter_mut().enumerate() {
+                                *f = stream_item.buffer[ch][s];
+                            }
+                            // groups are contiguous
+                            for (group, writer) in groups.iter().zip(writers.iter_mut()) {
+                                writer.write_frame(&frame[group[0]..group[0] + group.len()]);
                             }
                         }
+                        frames_written += stream_item.size;
                         stream_item.size = 0;
                         catch.return_q.send(stream_item).unwrap();
                     }
 
+                    // continue in a new file if the limits are reached
+                    let split_by_time = split_frames.map(|l| frames_written >= l).unwrap_or(false);
+                    let split_by_size = options
+                        .split_size
+                        .map(|l| writers.iter().any(|w| w.bytes_written() >= l))
+                        .unwrap_or(false);
+                    if split_by_time || split_by_size {
+                        for writer in writers.drain(..) {
+                            writer.finalize();
+                        }
+                        part += 1;
+                        writers = create_writers(part);
+                        frames_written = 0;
+                    }
+
                     let cur = start_time.elapsed().as_secs_f64();
                     let mut diff = cur - logical_time;
                     if diff < 0.0 {
@@ -129,6 +288,10 @@ pub fn start_writer_thread<const MAX: usize, const NCHAN: usize>(
                     thread::sleep(Duration::from_secs_f64(write_interval - diff));
                 }
 
+                for writer in writers.drain(..) {
+                    writer.finalize();
+                }
+
                 catch
             })
             .unwrap(),
@@ -137,6 +300,266 @@ pub fn start_writer_thread<const MAX: usize, const NCHAN: usize>(
     CatchHandle { handle, running }
 }
 
+/// An always-on ring buffer that holds the most recent output,
+/// so the good moments can still be saved after they happened.
+pub struct RetroBuffer {
+    buffers: Vec<Vec<f32>>,
+    idx: usize,
+    filled: usize,
+    written: u64, // frames written ever, to tell which ones are still there
+    pub samplerate: u32,
+}
+
+// how many frames to copy from the retro buffer at once when saving,
+// so the retro thread doesn't wait for long
+const RETRO_COPY_CHUNK: usize = 8192;
+
+impl RetroBuffer {
+    pub fn new(channels: usize, len: usize, samplerate: u32) -> Self {
+        RetroBuffer {
+            buffers: vec![vec![0.0; len]; channels],
+            idx: 0,
+            filled: 0,
+            written: 0,
+            samplerate,
+        }
+    }
+
+    fn write_frame(&mut self, frame: &[f32]) {
+        let len = self.buffers.first().map(|b| b.len()).unwrap_or(0);
+        if len == 0 {
+            return;
+        }
+        for (buf, s) in self.buffers.iter_mut().zip(frame.iter()) {
+            buf[self.idx] = *s;
+        }
+        self.idx += 1;
+        if self.idx >= len {
+            self.idx = 0;
+        }
+        if self.filled < len {
+            self.filled += 1;
+        }
+        self.written += 1;
+    }
+
+    /// where the last frames start (counted in frames written ever),
+    /// and how many of them there are so far
+    fn span(&self, frames: usize) -> (u64, usize) {
+        let frames = frames.min(self.filled);
+        (self.written - frames as u64, frames)
+    }
+
+    /// Append frames to the content (per channel), starting at a position
+    /// from `span`. Returns false if they've been overwritten in the meantime.
+    fn copy_into(&self, from: u64, frames: usize, content: &mut [Vec<f32>]) -> bool {
+        if from < self.written - self.filled as u64 || from + frames as u64 > self.written {
+            return false;
+        }
+        for (buf, c) in self.buffers.iter().zip(content.iter_mut()) {
+            let len = buf.len() as u64;
+            c.extend((from..from + frames as u64).map(|pos| buf[(pos % len) as usize]));
+        }
+        true
+    }
+
+    /// the last frames (per channel, oldest first), limited to
+    /// what's actually been recorded so far
+    pub fn last(&self, frames: usize) -> Vec<Vec<f32>> {
+        let frames = frames.min(self.filled);
+        if frames == 0 {
+            return vec![Vec::new(); self.buffers.len()];
+        }
+        self.buffers
+            .iter()
+            .map(|buf| {
+                let len = buf.len();
+                let start = (self.idx + len - frames) % len;
+                let mut content = Vec::with_capacity(frames);
+                if start + frames <= len {
+                    content.extend_from_slice(&buf[start..start + frames]);
+                } else {
+                    content.extend_from_slice(&buf[start..]);
+                    content.extend_from_slice(&buf[..start + frames - len]);
+                }
+                content
+            })
+            .collect()
+    }
+}
+
+/// Keeps the retro buffer up to date. Runs until it's stopped
+/// with `stop_writer_thread`.
+pub fn start_retro_thread<const MAX: usize, const NCHAN: usize>(
+    catch: Catch<MAX, NCHAN>,
+    retro: sync::Arc<Mutex<RetroBuffer>>,
+) -> CatchHandle<MAX, NCHAN> {
+    let write_interval = catch.write_interval_ms;
+    let running = sync::Arc::new(AtomicBool::new(true));
+    let running2 = running.clone();
+
+    let builder = thread::Builder::new().name("retro_buffer".to_string());
+
+    let handle = Some(
+        builder
+            .spawn(move || {
+                let mut frame = [0.0; NCHAN];
+                while running2.load(Ordering::SeqCst) {
+                    for mut stream_item in catch.catch_q.try_iter() {
+                        {
+                            let mut retro = retro.lock();
+                            for s in 0..stream_item.size {
+                                for (ch, f) in frame.iter_mut().enumerate() {
+                                    *f = stream_item.buffer[ch][s];
+                                }
+                                retro.write_frame(&frame);
+                            }
+                        }
+                        stream_item.size = 0;
+                        catch.return_q.send(stream_item).unwrap();
+                    }
+                    thread::sleep(Duration::from_secs_f64(write_interval));
+                }
+                catch
+            })
+            .unwrap(),
+    );
+
+    CatchHandle { handle, running }
+}
+
+/// Write the last seconds of the retro buffer to a file,
+/// in the background. The path is given without extension.
+pub fn save_retro(
+    retro: &sync::Arc<Mutex<RetroBuffer>>,
+    seconds: f32,
+    path: String,
+    options: RecordingOptions,
+) {
+    let retro = sync::Arc::clone(retro);
+    thread::spawn(move || {
+        // copy first, so the buffer isn't blocked while writing, and copy
+        // in chunks, so the retro thread can keep up with the audio thread
+        // in between (otherwise it runs out of blocks and drops some)
+        let (from, frames, samplerate, channels) = {
+            let retro = retro.lock();
+            let (from, frames) = retro.span((seconds * retro.samplerate as f32) as usize);
+            (from, frames, retro.samplerate, retro.buffers.len())
+        };
+        let mut content: Vec<Vec<f32>> =
+            (0..channels).map(|_| Vec::with_capacity(frames)).collect();
+        let mut copied = 0;
+        while copied < frames {
+            let chunk = RETRO_COPY_CHUNK.min(frames - copied);
+            if !retro
+                .lock()
+                .copy_into(from + copied as u64, chunk, &mut content)
+            {
+                messages::warning(
+                    "the retro buffer moved on while saving, the file is cut short".to_string(),
+                );
+                break;
+            }
+            copied += chunk;
+        }
+
+        let frames = copied;
+        if frames == 0 {
+            println!("nothing to save yet ...");
+            return;
+        }
+
+        let groups = channel_groups(content.len(), options.stems);
+        let stems = groups.len() > 1;
+        for group in groups.iter() {
+            let file_name = recording_file_name(&path, group, stems, 1, &options);
+            let mut writer = SampleWriter::create(&file_name, group.len(), samplerate, &options);
+            let mut frame = vec![0.0; group.len()];
+            for s in 0..frames {
+                for (f, ch) in frame.iter_mut().zip(group.iter()) {
+                    *f = content[*ch][s];
+                }
+                writer.write_frame(&frame);
+            }
+            writer.finalize();
+            println!(
+                "saved last {:.1} seconds to {}",
+                frames as f32 / samplerate as f32,
+                file_name
+            );
+        }
+    });
+}
+
+/// Peak and RMS level per channel since the last reading,
+/// and the most recent output for an oscilloscope.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeterReading {
+    pub peak: Vec<f32>,
+    pub rms: Vec<f32>,
+    /// how many frames the levels are taken from
+    pub frames: usize,
+    /// per channel, oldest first
+    pub scope: Vec<Vec<f32>>,
+}
+
+/// The receiving end of a stream that's thrown in the audio callback
+/// just like the recordings. Nothing waits for anything: if nobody reads
+/// for a while, the audio thread skips blocks until there's room again.
+pub struct MeterTap<const MAX: usize, const NCHAN: usize> {
+    catch: Catch<MAX, NCHAN>,
+    scope: RetroBuffer,
+    scope_frames: usize,
+}
+
+impl<const MAX: usize, const NCHAN: usize> MeterTap<MAX, NCHAN> {
+    pub fn new(catch: Catch<MAX, NCHAN>, samplerate: u32, scope_frames: usize) -> Self {
+        MeterTap {
+            catch,
+            scope: RetroBuffer::new(NCHAN, scope_frames, samplerate),
+            scope_frames,
+        }
+    }
+
+    /// Go through the blocks that came in since the last reading.
+    pub fn read(&mut self) -> MeterReading {
+        let mut peak = [0.0_f32; NCHAN];
+        let mut square_sum = [0.0_f32; NCHAN];
+        let mut frames = 0;
+        let mut frame = [0.0; NCHAN];
+
+        for mut stream_item in self.catch.catch_q.try_iter() {
+            for s in 0..stream_item.size {
+                for (ch, f) in frame.iter_mut().enumerate() {
+                    *f = stream_item.buffer[ch][s];
+                    peak[ch] = peak[ch].max(f.abs());
+                    square_sum[ch] += *f * *f;
+                }
+                self.scope.write_frame(&frame);
+            }
+            frames += stream_item.size;
+            stream_item.size = 0;
+            self.catch.return_q.send(stream_item).unwrap();
+        }
+
+        MeterReading {
+            peak: peak.to_vec(),
+            rms: square_sum
+                .iter()
+                .map(|s| {
+                    if frames > 0 {
+                        (s / frames as f32).sqrt()
+                    } else {
+                        0.0
+                    }
+                })
+                .collect(),
+            frames,
+            scope: self.scope.last(self.scope_frames),
+        }
+    }
+}
+
 pub fn init_real_time_stream<const MAX: usize, const NCHAN: usize>(
     block_interval_ms: f64,
     write_interval_ms: f64,
@@ -190,8 +613,8 @@ mod tests {
     #[test]
     fn test_real_time_stream() {
         let (throw, catch) = init_real_time_stream::<512, 2>(0.003, 0.1);
-        let path = "megra_recording.wav".to_string();
-        let handle = start_writer_thread(catch, 44100, path);
+        let path = "megra_recording".to_string();
+        let handle = start_writer_thread(catch, 44100, path, RecordingOptions::default());
 
         let mut buf: [[f32; 512]; 2] = [[1.0; 512]; 2];
 
@@ -208,4 +631,86 @@ mod tests {
 
         stop_writer_thread(handle);
     }
+
+    #[test]
+    fn test_recording_file_names() {
+        let options = RecordingOptions {
+            flac: true,
+            ..Default::default()
+        };
+
+        let groups = channel_groups(8, true);
+        assert_eq!(groups.len(), 4);
+        assert_eq!(groups[3], vec![6, 7]);
+        assert_eq!(channel_groups(2, false), vec![vec![0, 1]]);
+
+        // there's nothing to split in stereo
+        assert_eq!(channel_groups(2, true), vec![vec![0, 1]]);
+
+        assert_eq!(
+            recording_file_name("rec", &groups[1], true, 1, &options),
+            "rec_ch3-4.flac"
+        );
+        assert_eq!(
+            recording_file_name("rec", &[0, 1], false, 2, &RecordingOptions::default()),
+            "rec_part2.wav"
+        );
+    }
+
+    #[test]
+    fn test_retro_buffer() {
+        let mut retro = RetroBuffer::new(2, 4, 44100);
+        retro.write_frame(&[1.0, -1.0]);
+        retro.write_frame(&[2.0, -2.0]);
+
+        // only what's been written so far
+        assert_eq!(retro.last(10), vec![vec![1.0, 2.0], vec![-1.0, -2.0]]);
+
+        for i in 3..7 {
+            retro.write_frame(&[i as f32, -(i as f32)]);
+        }
+
+        assert_eq!(retro.last(10)[0], vec![3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(retro.last(3)[1], vec![-4.0, -5.0, -6.0]);
+        // copying in chunks while the buffer keeps going ...
+        let (from, frames) = retro.span(2);
+        assert_eq!(frames, 2);
+        let mut content = vec![Vec::new(); 2];
+        assert!(retro.copy_into(from, 1, &mut content));
+        retro.write_frame(&[7.0, -7.0]);
+        assert!(retro.copy_into(from + 1, 1, &mut content));
+        assert_eq!(content, vec![vec![5.0, 6.0], vec![-5.0, -6.0]]);
+
+        // ... as long as the frames haven't been overwritten
+        for i in 8..12 {
+            retro.write_frame(&[i as f32, -(i as f32)]);
+        }
+        assert!(!retro.copy_into(from, 1, &mut content));
+        // nor can anything be copied that hasn't been written yet
+        let (from, _) = retro.span(1);
+        assert!(!retro.copy_into(from, 2, &mut content));
+    }
+
+    #[test]
+    fn test_meter_tap() {
+        let (throw, catch) = init_real_time_stream::<4, 2>(0.01, 0.1);
+        let mut tap = MeterTap::new(catch, 44100, 6);
+
+        throw.write_samples(&[[0.5, -0.5, 0.5, -0.5], [0.0, 0.0, 1.5, 0.0]], 4);
+        throw.write_samples(&[[0.5, -0.5, 0.5, -0.5], [0.0; 4]], 4);
+
+        let reading = tap.read();
+        assert_eq!(reading.frames, 8);
+        assert_eq!(reading.peak, vec![0.5, 1.5]);
+        assert_eq!(reading.rms[0], 0.5);
+        assert_eq!(reading.scope[0], vec![0.5, -0.5, 0.5, -0.5, 0.5, -0.5]);
+        assert_eq!(reading.scope[1], vec![1.5, 0.0, 0.0, 0.0, 0.0, 0.0]);
+
+        // nothing new, but the scope keeps showing the last output
+        let reading = tap.read();
+        assert_eq!(reading.frames, 0);
+        assert_eq!(reading.peak, vec![0.0, 0.0]);
+        assert_eq!(reading.rms, vec![0.0, 0.0]);
+        assert_eq!(reading.scope[1].len(), 6);
+    }
 }
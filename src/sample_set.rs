@@ -2,10 +2,20 @@ use crate::parameter::DynVal;
 use rand::seq::SliceRandom;
 use std::collections::{HashMap, HashSet};
 
+/// samples are played back up to this length (in ms) unless
+/// specified otherwise for the file or set
+pub const DEFAULT_MAX_SAMPLE_DURATION: usize = 10000;
+
+/// files longer than this (in ms) are streamed from disk by default
+pub const DEFAULT_STREAM_THRESHOLD: usize = 60000;
+
 pub struct SampleInfo {
     pub key: HashSet<String>,
     pub bufnum: usize,
     pub duration: usize, // duration in ms ..
+    pub path: String,    // keep the source file to be able to reload ...
+    pub downmix_stereo: bool,
+    pub max_duration: Option<usize>, // per-file duration cap, if any ...
 }
 
 impl SampleInfo {
@@ -23,6 +33,13 @@ impl SampleInfo {
 pub struct SampleAndWavematrixSet {
     subsets: HashMap<String, Vec<SampleInfo>>,
     wavematrices: HashMap<String, Vec<Vec<DynVal>>>,
+    // long files that are streamed from disk instead of being
+    // loaded into a ruffbox buffer, the bufnum is the stream id,
+    // which maps to the path in stream_paths
+    streams: HashMap<String, Vec<SampleInfo>>,
+    stream_paths: HashMap<usize, String>,
+    next_stream_id: usize,
+    pub stream_threshold: usize, // files longer than this (in ms) will be streamed
 }
 
 impl Default for SampleAndWavematrixSet {
@@ -36,6 +53,10 @@ impl SampleAndWavematrixSet {
         SampleAndWavematrixSet {
             subsets: HashMap::new(),
             wavematrices: HashMap::new(),
+            streams: HashMap::new(),
+            stream_paths: HashMap::new(),
+            next_stream_id: 0,
+            stream_threshold: DEFAULT_STREAM_THRESHOLD,
         }
     }
 
@@ -47,15 +68,86 @@ impl SampleAndWavematrixSet {
         self.wavematrices.get(key)
     }
 
-    pub fn insert(&mut self, set: String, keyword_set: HashSet<String>, bufnum: usize, dur: usize) {
+    pub fn insert(&mut self, set: String, info: SampleInfo) {
+        self.subsets.entry(set).or_insert_with(Vec::new).push(info);
+    }
+
+    /// register a file to be streamed from disk, returns the stream id
+    pub fn insert_stream(&mut self, set: String, mut info: SampleInfo) -> usize {
+        let id = self.next_stream_id;
+        self.next_stream_id += 1;
+        self.stream_paths.insert(id, info.path.clone());
+        info.bufnum = id;
+        // so streams can be found by set name, too
+        info.key.insert(set.clone());
+        self.streams.entry(set).or_insert_with(Vec::new).push(info);
+        id
+    }
+
+    pub fn stream_path(&self, id: usize) -> Option<&String> {
+        self.stream_paths.get(&id)
+    }
+
+    /// find a stream whose keywords are a superset of the given ones
+    pub fn stream_keys(&self, keywords: &HashSet<String>) -> Option<&SampleInfo> {
+        let choice: Vec<&SampleInfo> = self
+            .streams
+            .values()
+            .flatten()
+            .filter(|i| i.matches(keywords))
+            .collect();
+        choice.choose(&mut rand::thread_rng()).copied()
+    }
+
+    /// remove an entire set, returns the sample infos so they can be re-loaded if needed.
+    /// The ruffbox buffers of the removed samples stay reserved (ruffbox can't free them),
+    /// so running generators that still refer to them keep playing the old samples.
+    pub fn remove_set(&mut self, set: &str) -> Option<Vec<SampleInfo>> {
+        let samples = self.subsets.remove(set);
+        let streams = self.streams.remove(set);
+        if let Some(streams) = streams.as_ref() {
+            for info in streams.iter() {
+                self.stream_paths.remove(&info.bufnum);
+            }
+        }
+        match (samples, streams) {
+            (Some(mut samples), Some(mut streams)) => {
+                samples.append(&mut streams);
+                Some(samples)
+            }
+            (Some(samples), None) => Some(samples),
+            (None, streams) => streams,
+        }
+    }
+
+    /// remove a single sample (identified by its source file) from a set
+    pub fn remove_sample(&mut self, set: &str, path: &str) -> Option<SampleInfo> {
+        if let Some(subset) = self.subsets.get_mut(set) {
+            if let Some(idx) = subset.iter().position(|i| i.path == path) {
+                return Some(subset.remove(idx));
+            }
+        }
+        if let Some(subset) = self.streams.get_mut(set) {
+            if let Some(idx) = subset.iter().position(|i| i.path == path) {
+                let info = subset.remove(idx);
+                self.stream_paths.remove(&info.bufnum);
+                return Some(info);
+            }
+        }
+        None
+    }
+
+    /// The loading options of a set (downmix and maximum duration), taken
+    /// from the samples that are in it, so files added later are loaded
+    /// the same way.
+    pub fn set_options(&self, set: &str) -> Option<(bool, Option<usize>)> {
         self.subsets
-            .entry(set)
-            .or_insert_with(Vec::new)
-            .push(SampleInfo {
-                key: keyword_set,
-                bufnum,
-                duration: dur,
-            });
+            .get(set)
+            .into_iter()
+            .chain(self.streams.get(set))
+            .flatten()
+            .next()
+            .map(|i| (i.downmix_stereo, i.max_duration))
     }
 
     pub fn exists_not_empty(&self, set: &str) -> bool {
@@ -94,3 +186,101 @@ impl SampleAndWavematrixSet {
             .map(|subset| subset.choose(&mut rand::thread_rng()).unwrap())
     }
 }
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn info(bufnum: usize, dur: usize, path: &str) -> SampleInfo {
+        SampleInfo {
+            key: HashSet::new(),
+            bufnum,
+            duration: dur,
+            path: path.to_string(),
+            downmix_stereo: true,
+            max_duration: None,
+        }
+    }
+
+    #[test]
+    fn test_remove_sample_and_set() {
+        let mut set = SampleAndWavematrixSet::new();
+        set.insert("bd".to_string(), info(0, 200, "bd/bd1.flac"));
+        set.insert("bd".to_string(), info(1, 300, "bd/bd2.flac"));
+        set.insert("sn".to_string(), info(2, 300, "sn/sn1.flac"));
+
+        let removed = set.remove_sample("bd", "bd/bd1.flac").unwrap();
+        assert_eq!(removed.bufnum, 0);
+        assert!(set.remove_sample("bd", "bd/bd1.flac").is_none());
+        assert_eq!(set.pos("bd", 0).unwrap().bufnum, 1);
+
+        let removed_set = set.remove_set("bd").unwrap();
+        assert_eq!(removed_set.len(), 1);
+        assert_eq!(removed_set[0].bufnum, 1);
+        assert!(!set.exists_not_empty("bd"));
+        assert!(set.remove_set("bd").is_none());
+        // other sets stay untouched
+        assert!(set.exists_not_empty("sn"));
+    }
+
+    #[test]
+    fn test_streams() {
+        let mut set = SampleAndWavematrixSet::new();
+        let mut rain = info(0, 120000, "field/rain.flac");
+        rain.key.insert("rain".to_string());
+        let mut wind = info(0, 300000, "field/wind.flac");
+        wind.key.insert("wind".to_string());
+
+        assert_eq!(set.insert_stream("field".to_string(), rain), 0);
+        assert_eq!(set.insert_stream("field".to_string(), wind), 1);
+
+        let mut kw = HashSet::new();
+        kw.insert("wind".to_string());
+        let found = set.stream_keys(&kw).unwrap();
+        assert_eq!(found.bufnum, 1);
+        assert_eq!(set.stream_path(found.bufnum).unwrap(), "field/wind.flac");
+
+        // streams aren't sample events ...
+        assert!(!set.exists_not_empty("field"));
+
+        // but can be found by set name
+        kw.insert("field".to_string());
+        assert!(set.stream_keys(&kw).is_some());
+
+        kw.insert("thunder".to_string());
+        assert!(set.stream_keys(&kw).is_none());
+
+        // removed streams can't be found anymore, ids aren't re-used
+        assert_eq!(
+            set.remove_sample("field", "field/rain.flac")
+                .unwrap()
+                .bufnum,
+            0
+        );
+        assert!(set.stream_path(0).is_none());
+        let rain = info(0, 120000, "field/rain.flac");
+        assert_eq!(set.insert_stream("field".to_string(), rain), 2);
+
+        assert_eq!(set.remove_set("field").unwrap().len(), 2);
+        assert!(set.stream_path(1).is_none() && set.stream_path(2).is_none());
+        assert!(set.stream_keys(&HashSet::new()).is_none());
+    }
+
+    #[test]
+    fn test_set_options() {
+        let mut set = SampleAndWavematrixSet::new();
+        let mut bd = info(0, 200, "bd/bd1.flac");
+        bd.downmix_stereo = false;
+        bd.max_duration = Some(500);
+        set.insert("bd".to_string(), bd);
+        assert_eq!(set.set_options("bd"), Some((false, Some(500))));
+
+        // streamed sets have options, too
+        set.insert_stream("field".to_string(), info(0, 120000, "field/rain.flac"));
+        assert_eq!(set.set_options("field"), Some((true, None)));
+
+        assert!(set.set_options("sn").is_none());
+    }
+}
@@ -1,6 +1,9 @@
 use crate::builtin_types::*;
 use crate::generator::Generator;
+use crate::generator_link::LinkStore;
+use crate::live_buffer::LiveBufferMirror;
 use crate::session::{OutputMode, Session, SyncMode};
+use crate::stream_player::StreamControl;
 use crate::visualizer_client::VisualizerClient;
 use parking_lot::Mutex;
 use ruffbox_synth::ruffbox::RuffboxControls;
@@ -35,11 +38,15 @@ pub struct SchedulerData<const BUFSIZE: usize, const NCHAN: usize> {
     pub session: sync::Arc<Mutex<Session<BUFSIZE, NCHAN>>>,
     pub parts_store: sync::Arc<Mutex<PartsStore>>,
     pub visualizer_client: Option<sync::Arc<VisualizerClient>>,
+    pub stream_control: Option<sync::Arc<StreamControl<BUFSIZE, NCHAN>>>,
+    pub live_buffer: Option<sync::Arc<LiveBufferMirror>>,
     pub global_parameters: sync::Arc<GlobalParameters>,
     pub output_mode: OutputMode,
     pub sync_mode: SyncMode,
     pub block_tags: BTreeSet<String>,
     pub solo_tags: BTreeSet<String>,
+    pub links: LinkStore,
+    pub advances_seen: u64, // the last advance from a link this generator played
 }
 
 impl<const BUFSIZE: usize, const NCHAN: usize> SchedulerData<BUFSIZE, NCHAN> {
@@ -85,11 +92,15 @@ impl<const BUFSIZE: usize, const NCHAN: usize> SchedulerData<BUFSIZE, NCHAN> {
             session: sync::Arc::clone(session),
             parts_store: sync::Arc::clone(parts_store),
             visualizer_client: vca,
+            stream_control: old.stream_control.clone(),
+            live_buffer: old.live_buffer.clone(),
             global_parameters: sync::Arc::clone(&old.global_parameters),
             output_mode: old.output_mode,
             sync_mode: old.sync_mode,
             block_tags: block_tags.clone(),
             solo_tags: solo_tags.clone(),
+            links: sync::Arc::clone(&old.links),
+            advances_seen: old.advances_seen,
         }
     }
 
@@ -125,11 +136,15 @@ impl<const BUFSIZE: usize, const NCHAN: usize> SchedulerData<BUFSIZE, NCHAN> {
             session: sync::Arc::clone(session),
             parts_store: sync::Arc::clone(parts_store),
             visualizer_client: vca,
+            stream_control: old.stream_control.clone(),
+            live_buffer: old.live_buffer.clone(),
             global_parameters: sync::Arc::clone(&old.global_parameters),
             output_mode: old.output_mode,
             sync_mode: old.sync_mode,
             block_tags: block_tags.clone(),
             solo_tags: solo_tags.clone(),
+            links: sync::Arc::clone(&old.links),
+            advances_seen: old.advances_seen,
         }
     }
 
@@ -150,6 +165,9 @@ impl<const BUFSIZE: usize, const NCHAN: usize> SchedulerData<BUFSIZE, NCHAN> {
         // get logical time since start from ruffbox
         let stream_time = ruffbox.get_now();
         let vca;
+        let sca;
+        let lba;
+        let links;
         {
             let sess = session.lock();
             vca = if let Some(vc) = &sess.visualizer_client {
@@ -157,6 +175,9 @@ impl<const BUFSIZE: usize, const NCHAN: usize> SchedulerData<BUFSIZE, NCHAN> {
             } else {
                 None
             };
+            sca = sess.stream_control.clone();
+            lba = sess.live_buffer.clone();
+            links = sync::Arc::clone(&sess.links);
         }
         SchedulerData {
             start_time: Instant::now(),
@@ -171,11 +192,15 @@ impl<const BUFSIZE: usize, const NCHAN: usize> SchedulerData<BUFSIZE, NCHAN> {
             session: sync::Arc::clone(session),
             parts_store: sync::Arc::clone(parts_store),
             visualizer_client: vca,
+            stream_control: sca,
+            live_buffer: lba,
             global_parameters: sync::Arc::clone(global_parameters),
             output_mode,
             sync_mode,
             block_tags: block_tags.clone(),
             solo_tags: solo_tags.clone(),
+            links,
+            advances_seen: 0,
         }
     }
 }
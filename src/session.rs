@@ -10,12 +10,16 @@ use crate::builtin_types::{
     GlobalParameters, Part, PartProxy, PartsStore,
 };
 use crate::commands;
-use crate::event::InterpretableEvent;
+use crate::event::{InterpretableEvent, StaticEvent};
 use crate::event_helpers::*;
 use crate::generator::Generator;
+use crate::generator_link::{self, LinkStore, Links};
+use crate::live_buffer::LiveBufferMirror;
+use crate::parameter::modifier::EvalContext;
 use crate::parameter::*;
 use crate::real_time_streaming;
 use crate::scheduler::{Scheduler, SchedulerData};
+use crate::stream_player::{self, StreamControl};
 use crate::visualizer_client::VisualizerClient;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -50,6 +54,18 @@ pub struct SyncContext {
     pub solo_tags: BTreeSet<String>,
 }
 
+/// What's known about a generator, for the editor to show.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneratorStatus {
+    pub id_tags: BTreeSet<String>,
+    pub context: Option<String>,
+    pub running: bool,
+    pub state: Option<String>,
+    pub last_symbol: Option<char>,
+    pub muted: bool,
+    pub soloed: bool,
+}
+
 pub struct Session<const BUFSIZE: usize, const NCHAN: usize> {
     pub schedulers: HashMap<
         BTreeSet<String>,
@@ -61,6 +77,9 @@ pub struct Session<const BUFSIZE: usize, const NCHAN: usize> {
     contexts: HashMap<String, BTreeSet<BTreeSet<String>>>,
     pub visualizer_client: Option<sync::Arc<VisualizerClient>>,
     pub rec_control: Option<real_time_streaming::RecordingControl<BUFSIZE, NCHAN>>,
+    pub stream_control: Option<sync::Arc<StreamControl<BUFSIZE, NCHAN>>>,
+    pub live_buffer: Option<sync::Arc<LiveBufferMirror>>,
+    pub links: LinkStore,
 }
 
 // basically a bfs on a dag !
@@ -114,14 +133,64 @@ fn resolve_proxy(parts_store: &PartsStore, proxy: PartProxy, generators: &mut Ve
     }
 }
 
+// the tags only this generator has, otherwise soloing a generator
+// would keep the others in the same context audible
+fn solo_tags_for<'a>(
+    id_tags: &BTreeSet<String>,
+    all: impl Iterator<Item = &'a BTreeSet<String>>,
+) -> BTreeSet<String> {
+    let mut unique = id_tags.clone();
+    for other in all.filter(|other| *other != id_tags) {
+        unique = unique.difference(other).cloned().collect();
+    }
+    if unique.is_empty() {
+        id_tags.clone()
+    } else {
+        unique
+    }
+}
+
 //////////////////////////////////////
 // THE MAIN TIME RECURSION LOOP!!!  //
 //////////////////////////////////////
 
+// how often a generator that follows a link checks back, in seconds
+const LINK_FOLLOWER_POLL: f64 = 0.01;
+
 // yes, here it is ... the evaluation function ...
 // or better, the inside part of the time iteration
 fn eval_loop<const BUFSIZE: usize, const NCHAN: usize>(
     data: &mut SchedulerData<BUFSIZE, NCHAN>,
+) -> (f64, bool, bool) {
+    // advances queued by links, played at the time of the generator
+    // that fired them (unless that's already passed), keeping the own time
+    let own_time = data.stream_time;
+    let mut sync = false;
+    let mut end_state = false;
+    let advances = generator_link::take_advances(
+        &data.links,
+        &data.generator.id_tags,
+        &mut data.advances_seen,
+    );
+    for time in advances {
+        data.stream_time = time.max(own_time);
+        let (_, step_sync, step_end) = eval_step(data);
+        sync |= step_sync;
+        end_state |= step_end;
+    }
+    data.stream_time = own_time;
+
+    // generators that follow a link only move when they're advanced,
+    // in the meantime, just keep the time running
+    if end_state || generator_link::is_follower(&data.links, &data.generator.id_tags) {
+        return (LINK_FOLLOWER_POLL, sync, end_state);
+    }
+    let (time, step_sync, step_end) = eval_step(data);
+    (time, sync || step_sync, step_end)
+}
+
+fn eval_step<const BUFSIZE: usize, const NCHAN: usize>(
+    data: &mut SchedulerData<BUFSIZE, NCHAN>,
 ) -> (f64, bool, bool) {
     // global tempo modifier, allows us to do weird stuff with the
     // global tempo ...
@@ -134,7 +203,7 @@ fn eval_loop<const BUFSIZE: usize, const NCHAN: usize>(
         .or_insert(ConfigParameter::Dynamic(DynVal::with_value(1.0))) // init on first attempt
         .value_mut()
     {
-        tmod = global_tmod.evaluate_numerical() as f64;
+        tmod = global_tmod.evaluate_numerical_in(&EvalContext::at(data.stream_time)) as f64;
     }
 
     if let ConfigParameter::Dynamic(global_latency) = data
@@ -143,7 +212,7 @@ fn eval_loop<const BUFSIZE: usize, const NCHAN: usize>(
         .or_insert(ConfigParameter::Dynamic(DynVal::with_value(0.05))) // init on first attempt
         .value_mut()
     {
-        latency = global_latency.evaluate_numerical() as f64;
+        latency = global_latency.evaluate_numerical_in(&EvalContext::at(data.stream_time)) as f64;
     }
 
     if let Some(vc) = &data.visualizer_client {
@@ -157,9 +226,13 @@ fn eval_loop<const BUFSIZE: usize, const NCHAN: usize>(
         }
     }
 
+    // time-based modifiers follow the logical time of this scheduler,
+    // i.e. the time the events will be played at
+    let logical_time = data.stream_time + latency;
+
     let time = if let SynthParameterValue::ScalarF32(t) = data
         .generator
-        .current_transition(&data.global_parameters)
+        .current_transition(&data.global_parameters, logical_time)
         .params[&SynthParameterLabel::Duration]
     {
         (t * 0.001) as f64 * tmod
@@ -168,7 +241,9 @@ fn eval_loop<const BUFSIZE: usize, const NCHAN: usize>(
     };
 
     // retrieve the current events
-    let mut events = data.generator.current_events(&data.global_parameters);
+    let mut events = data
+        .generator
+        .current_events(&data.global_parameters, logical_time);
     //if events.is_empty() {
     //    println!("really no events");
     //}
@@ -216,6 +291,19 @@ fn eval_loop<const BUFSIZE: usize, const NCHAN: usize>(
                     continue;
                 }
 
+                // streams are played outside of ruffbox
+                if s.name == "stream" {
+                    if let Some(sc) = &data.stream_control {
+                        let (id, params) = stream_player::stream_parameters(
+                            s,
+                            data.stream_time + latency,
+                            data.output_mode,
+                        );
+                        sc.start_stream(id, params);
+                    }
+                    continue;
+                }
+
                 let mut bufnum: usize = 0;
                 if let Some(SynthParameterValue::ScalarUsize(b)) =
                     s.params.get(&SynthParameterLabel::SampleBufferNumber)
@@ -283,7 +371,12 @@ fn eval_loop<const BUFSIZE: usize, const NCHAN: usize>(
                                 println!("a command (load part)");
                             }
                             Command::FreezeBuffer(freezbuf, inbuf) => {
-                                commands::freeze_buffer(&data.ruffbox, freezbuf, inbuf);
+                                commands::freeze_buffer(
+                                    &data.ruffbox,
+                                    &data.live_buffer,
+                                    freezbuf,
+                                    inbuf,
+                                );
                                 println!("freeze buffer");
                             }
                             Command::Tmod(p) => {
@@ -295,9 +388,26 @@ fn eval_loop<const BUFSIZE: usize, const NCHAN: usize>(
                                     v,
                                 );
                             }
+                            Command::SetGlobal(name, v) => {
+                                commands::set_global_variable(&data.global_parameters, name, v);
+                            }
+                            Command::Link(l) => {
+                                if let Err(e) = generator_link::add_link(&data.links, l) {
+                                    println!("{}", e);
+                                }
+                            }
+                            Command::Unlink(name) => {
+                                generator_link::remove_links(&data.links, &name);
+                            }
                             Command::GlobalRuffboxParams(mut m) => {
                                 commands::set_global_ruffbox_parameters(&data.ruffbox, &mut m);
                             }
+                            Command::Scale(s) => {
+                                commands::set_global_scale(&data.global_parameters, s);
+                            }
+                            Command::Tuning(t) => {
+                                commands::set_global_tuning(&data.global_parameters, t);
+                            }
                             Command::Clear => {
                                 let session2 = sync::Arc::clone(&data.session);
                                 let parts_store2 = sync::Arc::clone(&data.parts_store);
@@ -329,6 +439,45 @@ fn eval_loop<const BUFSIZE: usize, const NCHAN: usize>(
         }
     }
 
+    // fire the links this generator is the source of
+    let played: Vec<&StaticEvent> = events
+        .iter()
+        .filter_map(|ev| match ev {
+            InterpretableEvent::Sound(s) if s.name != "silence" => Some(s),
+            _ => None,
+        })
+        .collect();
+    let state = data.generator.root_generator.last_symbol;
+    let started = generator_link::fire_links(
+        &data.links,
+        &data.generator.id_tags,
+        state,
+        &played,
+        data.stream_time,
+    );
+    if !started.is_empty() {
+        // starting a context locks the session and might need this very
+        // scheduler, which is locked right now, so (like clearing) it's
+        // done on its own thread
+        let session2 = sync::Arc::clone(&data.session);
+        let ruffbox2 = sync::Arc::clone(&data.ruffbox);
+        let parts_store2 = sync::Arc::clone(&data.parts_store);
+        let global_parameters2 = sync::Arc::clone(&data.global_parameters);
+        let output_mode = data.output_mode;
+        thread::spawn(move || {
+            for mut ctx in started {
+                Session::handle_context(
+                    &mut ctx,
+                    &session2,
+                    &ruffbox2,
+                    &parts_store2,
+                    &global_parameters2,
+                    output_mode,
+                );
+            }
+        });
+    }
+
     (time, sync, end_state)
 }
 // END INNER MAIN SCHEDULER FUNCTION ...
@@ -340,6 +489,9 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Session<BUFSIZE, NCHAN> {
             contexts: HashMap::new(),
             visualizer_client: None,
             rec_control: None,
+            stream_control: None,
+            live_buffer: None,
+            links: sync::Arc::new(Mutex::new(Links::default())),
         }
     }
 
@@ -871,6 +1023,11 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Session<BUFSIZE, NCHAN> {
         {
             let mut sess = session.lock();
             sched_prox = sess.schedulers.remove(gen_name);
+            // a stopped generator doesn't belong to its context anymore,
+            // so it'll be started again once the context is re-evaluated
+            for gens in sess.contexts.values_mut() {
+                gens.remove(gen_name);
+            }
             if let Some(c) = &sess.visualizer_client {
                 c.clear(gen_name);
             }
@@ -894,6 +1051,119 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Session<BUFSIZE, NCHAN> {
         }
     }
 
+    /// A snapshot of all running generators and the contexts
+    /// they belong to, sorted by context and tags.
+    pub fn generator_status(
+        session: &sync::Arc<Mutex<Session<BUFSIZE, NCHAN>>>,
+    ) -> Vec<GeneratorStatus> {
+        // don't hold the session while waiting for the schedulers
+        let (running, contexts) = {
+            let sess = session.lock();
+            let running: Vec<_> = sess
+                .schedulers
+                .iter()
+                .map(|(id_tags, (_, data))| (id_tags.clone(), sync::Arc::clone(data)))
+                .collect();
+            (running, sess.contexts.clone())
+        };
+
+        let context_of = |id_tags: &BTreeSet<String>| {
+            contexts
+                .iter()
+                .find(|(_, gens)| gens.contains(id_tags))
+                .map(|(name, _)| name.clone())
+        };
+
+        let mut status = Vec::new();
+        for (id_tags, data) in running {
+            let data = data.lock();
+            let root = &data.generator.root_generator;
+            status.push(GeneratorStatus {
+                context: context_of(&id_tags),
+                running: true,
+                state: root
+                    .generator
+                    .current_state
+                    .and_then(|s| root.generator.labels.get(&s))
+                    .map(|label| label.iter().collect()),
+                last_symbol: root.last_symbol,
+                muted: id_tags.is_subset(&data.block_tags),
+                soloed: !data.solo_tags.is_empty() && !data.solo_tags.is_disjoint(&id_tags),
+                id_tags,
+            });
+        }
+
+        // context members that aren't running (anymore)
+        for (name, gens) in contexts.iter() {
+            for id_tags in gens.iter() {
+                if !status.iter().any(|s| &s.id_tags == id_tags) {
+                    status.push(GeneratorStatus {
+                        id_tags: id_tags.clone(),
+                        context: Some(name.clone()),
+                        running: false,
+                        state: None,
+                        last_symbol: None,
+                        muted: false,
+                        soloed: false,
+                    });
+                }
+            }
+        }
+
+        status.sort_by(|a, b| (&a.context, &a.id_tags).cmp(&(&b.context, &b.id_tags)));
+        status
+    }
+
+    /// Mute or unmute a running generator by blocking its own tags.
+    /// Re-evaluating the generator resets the block tags to
+    /// whatever its context says.
+    pub fn mute_generator(
+        session: &sync::Arc<Mutex<Session<BUFSIZE, NCHAN>>>,
+        id_tags: &BTreeSet<String>,
+        mute: bool,
+    ) {
+        let data = session
+            .lock()
+            .schedulers
+            .get(id_tags)
+            .map(|(_, data)| sync::Arc::clone(data));
+
+        if let Some(data) = data {
+            let mut data = data.lock();
+            if mute {
+                data.block_tags.extend(id_tags.iter().cloned());
+            } else {
+                for tag in id_tags.iter() {
+                    data.block_tags.remove(tag);
+                }
+            }
+        }
+    }
+
+    /// Solo a running generator, or clear all solos.
+    pub fn solo_generator(
+        session: &sync::Arc<Mutex<Session<BUFSIZE, NCHAN>>>,
+        id_tags: &BTreeSet<String>,
+        solo: bool,
+    ) {
+        let all: Vec<_> = session
+            .lock()
+            .schedulers
+            .iter()
+            .map(|(id_tags, (_, data))| (id_tags.clone(), sync::Arc::clone(data)))
+            .collect();
+
+        let solo_tags = if solo {
+            solo_tags_for(id_tags, all.iter().map(|(other, _)| other))
+        } else {
+            BTreeSet::new()
+        };
+
+        for (_, data) in all {
+            data.lock().solo_tags = solo_tags.clone();
+        }
+    }
+
     pub fn stop_generators(
         session: &sync::Arc<Mutex<Session<BUFSIZE, NCHAN>>>,
         gen_names: &[BTreeSet<String>],
@@ -961,7 +1231,34 @@ impl<const BUFSIZE: usize, const NCHAN: usize> Session<BUFSIZE, NCHAN> {
 
         sess.schedulers = HashMap::new();
         sess.contexts = HashMap::new();
+        generator_link::clear_links(&sess.links);
         let mut ps = parts_store.lock();
         *ps = HashMap::new();
     }
 }
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn tags(t: &[&str]) -> BTreeSet<String> {
+        t.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_solo_tags() {
+        let troll = tags(&["ga", "troll"]);
+        let beat = tags(&["ga", "beat"]);
+        let ga = tags(&["ga"]);
+
+        // the context name is shared, so only solo the generator name
+        let all = vec![troll.clone(), beat.clone()];
+        assert_eq!(solo_tags_for(&troll, all.iter()), tags(&["troll"]));
+
+        // nothing unique left, use what's there
+        let all = vec![ga.clone(), beat];
+        assert_eq!(solo_tags_for(&ga, all.iter()), ga);
+    }
+}
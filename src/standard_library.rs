@@ -1,298 +1,455 @@
-use crate::parser::{eval, FunctionMap};
+use crate::parser::{eval, FunctionCategory::*, FunctionMap};
+
+// keyword arguments all sound events understand
+pub const SOUND_KEYWORDS: &str = ":freq :note :lvl :gain :atk :dec :sus :rel :dur :pos :lpf :lpq :lpd :hpf :hpq :pff :pfq :pfg :pw :rev :del :tags";
+// additional keyword arguments for sample events
+pub const SAMPLE_KEYWORDS: &str = ":rate :start :loop";
 
 /**
  * This is where all the "frontend" functions (that is, the DSL functions)
  * are defined and bound to their Rust equivalents ...  
+ * The arguments are used by the editor for completion and highlighting,
+ * the keywords have to match what the functions actually parse (see the test below).
  */
 pub fn define_standard_library() -> FunctionMap {
     let mut standard_library = FunctionMap::new();
     // session
-    standard_library.fmap.insert("sx".to_string(), eval::session::sync_context::sync_context);
+    standard_library.register(Session, "sx", "'name active gen... :sync :shift :solo :block", eval::session::sync_context::sync_context);
 
     // constructors
-    standard_library.fmap.insert("nuc".to_string(), eval::constructors::nuc::nuc);
-    standard_library.fmap.insert("fully".to_string(), eval::constructors::fully::fully);
-    standard_library.fmap.insert("friendship".to_string(), eval::constructors::friendship::friendship);
-    standard_library.fmap.insert("lin".to_string(), eval::constructors::linear::linear);
-    standard_library.fmap.insert("linear".to_string(), eval::constructors::linear::linear);
-    standard_library.fmap.insert("loop".to_string(), eval::constructors::r#loop::a_loop);
-    standard_library.fmap.insert("chop".to_string(), eval::constructors::chop::chop);
-    standard_library.fmap.insert("infer".to_string(), eval::constructors::infer::infer);
-    standard_library.fmap.insert("rule".to_string(), eval::constructors::infer::rule);
-    standard_library.fmap.insert("learn".to_string(), eval::constructors::learn::learn);
-    standard_library.fmap.insert("cyc".to_string(), eval::constructors::cyc::cyc);
-    standard_library.fmap.insert("flower".to_string(), eval::constructors::flower::flower);
-    standard_library.fmap.insert("stages".to_string(), eval::constructors::stages::stages);
-    standard_library.fmap.insert("facts".to_string(), eval::constructors::facts::facts);
+    standard_library.register(Constructor, "nuc", "'name event... :dur :keep", eval::constructors::nuc::nuc);
+    standard_library.register(Constructor, "fully", "'name event... :dur :keep", eval::constructors::fully::fully);
+    standard_library.register(Constructor, "friendship", "'name :center :friends :events :dur :rep :max-rep :rnd :keep", eval::constructors::friendship::friendship);
+    standard_library.register(Constructor, "lin", "'name event [dur]...", eval::constructors::linear::linear);
+    standard_library.register(Constructor, "linear", "'name event [dur]...", eval::constructors::linear::linear);
+    standard_library.register(Constructor, "loop", "'name event [dur]... :keep", eval::constructors::r#loop::a_loop);
+    standard_library.register(Constructor, "chop", "'name slices event... :dur :rep :max-rep :rnd :keep", eval::constructors::chop::chop);
+    standard_library.register(Constructor, "infer", "'name :events :rules :dur :keep", eval::constructors::infer::infer);
+    standard_library.register(Constructor, "rule", "'source 'destination probability [duration]", eval::constructors::infer::rule);
+    standard_library.register(Constructor, "learn", "'name :events :sample :bound :epsilon :size :autosilence :dur :keep", eval::constructors::learn::learn);
+    standard_library.register(Constructor, "cyc", "'name \"pattern\" :dur :events :map :rep :max-rep :rnd :keep", eval::constructors::cyc::cyc);
+    standard_library.register(Constructor, "flower", "'name :pistil :petals :layers :events :dur :rep :max-rep :rnd :keep", eval::constructors::flower::flower);
+    standard_library.register(Constructor, "stages", "'name event... :pprev :pnext :cyc :dur :rnd :keep", eval::constructors::stages::stages);
+    standard_library.register(Constructor, "facts", "'name fact... :rnd :keep", eval::constructors::facts::facts);
+    standard_library.register(Constructor, "arp", "'name chord :mode :oct :inv :dur", eval::constructors::arp::arp);
+    standard_library.register(Constructor, "prog", "'name chord... :key :oct :inv :spread :dur", eval::constructors::prog::prog);
+    standard_library.register(Constructor, "euclid", "'name onsets steps event... :rot :dur :rep :max-rep :rnd :keep", eval::constructors::euclid::euclid);
+    standard_library.register(Constructor, "density", "'name density steps event... :rot :dur :rep :max-rep :rnd :keep", eval::constructors::euclid::density);
 
     // commands
-    standard_library.fmap.insert("defpart".to_string(), eval::commands::load_part);
-    standard_library.fmap.insert("freeze".to_string(), eval::commands::freeze_buffer);
-    standard_library.fmap.insert("load-sample".to_string(), eval::commands::load_sample);
-    standard_library.fmap.insert("load-wavematrix".to_string(), eval::commands::load_sample_as_wavematrix);
-    standard_library.fmap.insert("load-sample-sets".to_string(), eval::commands::load_sample_sets);
-    standard_library.fmap.insert("load-sample-set".to_string(), eval::commands::load_sample_set);
-    standard_library.fmap.insert("tmod".to_string(), eval::commands::tmod);
-    standard_library.fmap.insert("latency".to_string(), eval::commands::latency);
-    standard_library.fmap.insert("bpm".to_string(), eval::commands::bpm);
-    standard_library.fmap.insert("default-duration".to_string(), eval::commands::default_duration);
-    standard_library.fmap.insert("globres".to_string(), eval::commands::globres);
-    standard_library.fmap.insert("global-resources".to_string(), eval::commands::globres);
-    standard_library.fmap.insert("reverb".to_string(), eval::commands::reverb);
-    standard_library.fmap.insert("delay".to_string(), eval::commands::delay);
-    standard_library.fmap.insert("export-dot".to_string(), eval::commands::export_dot);
-    standard_library.fmap.insert("once".to_string(), eval::commands::once);
-    standard_library.fmap.insert("step-part".to_string(), eval::commands::step_part);
-    standard_library.fmap.insert("clear".to_string(), eval::commands::clear);
-    standard_library.fmap.insert("connect-visualizer".to_string(), eval::commands::connect_visualizer);
-    standard_library.fmap.insert("rec".to_string(), eval::commands::start_recording);
-    standard_library.fmap.insert("stop-rec".to_string(), eval::commands::stop_recording);
-    standard_library.fmap.insert("midi-callback".to_string(), eval::commands::define_midi_callback);
+    standard_library.register(Command, "defpart", "'name gen...", eval::commands::load_part);
+    standard_library.register(Command, "freeze", "buffer :in", eval::commands::freeze_buffer);
+    standard_library.register(Command, "load-sample", ":set :path :keywords :use-stereo :max-dur", eval::commands::load_sample);
+    standard_library.register(Command, "load-wavematrix", ":key :path :method :size :start", eval::commands::load_sample_as_wavematrix);
+    standard_library.register(Command, "capture-wavematrix", "'name :from-live :from-freeze :method :size :start", eval::commands::capture_wavematrix);
+    standard_library.register(Command, "load-sample-sets", "\"path\" :use-stereo :max-dur", eval::commands::load_sample_sets);
+    standard_library.register(Command, "load-sample-set", "\"path\" :use-stereo :max-dur", eval::commands::load_sample_set);
+    standard_library.register(Command, "unload-sample-set", "'set", eval::commands::unload_sample_set);
+    standard_library.register(Command, "reload-sample-set", "'set", eval::commands::reload_sample_set);
+    standard_library.register(Command, "tmod", "param", eval::commands::tmod);
+    standard_library.register(Command, "latency", "seconds", eval::commands::latency);
+    standard_library.register(Command, "bpm", "value", eval::commands::bpm);
+    standard_library.register(Command, "default-duration", "ms", eval::commands::default_duration);
+    standard_library.register(Command, "globres", "value", eval::commands::globres);
+    standard_library.register(Command, "global-resources", "value", eval::commands::globres);
+    standard_library.register(Command, "global", "'name value", eval::commands::set_global);
+    standard_library.register(Command, "link", "'source :on :on-state :advance :start :follow", eval::commands::link);
+    standard_library.register(Command, "unlink", "'source", eval::commands::unlink);
+    standard_library.register(Command, "reverb", ":mix :roomsize :damp", eval::commands::reverb);
+    standard_library.register(Command, "delay", ":mix :t :r :fb :damp-freq", eval::commands::delay);
+    standard_library.register(Command, "export-dot", "\"file\" :part :live", eval::commands::export_dot);
+    standard_library.register(Command, "once", "event...", eval::commands::once);
+    standard_library.register(Command, "step-part", "'name", eval::commands::step_part);
+    standard_library.register(Command, "clear", "", eval::commands::clear);
+    standard_library.register(Command, "connect-visualizer", "", eval::commands::connect_visualizer);
+    standard_library.register(Command, "rec", "\"prefix\" :input :stems :format :bits :split-time :split-size", eval::commands::start_recording);
+    standard_library.register(Command, "stop-rec", "", eval::commands::stop_recording);
+    standard_library.register(Command, "tuning", "\"file.scl\"|'name :kbm :ref :edo", eval::commands::tuning);
+    standard_library.register(Command, "save-last", "[seconds] [\"prefix\"]", eval::commands::save_last);
+    standard_library.register(Command, "midi-callback", "key command", eval::commands::define_midi_callback);
     
     // control event
-    standard_library.fmap.insert("ctrl".to_string(), eval::events::control::control);
+    standard_library.register(Event, "ctrl", "command...", eval::events::control::control);
 
     // parameter structs
-    standard_library.fmap.insert("vec".to_string(), eval::structs::vec);
-    standard_library.fmap.insert("mat".to_string(), eval::structs::mat);
+    standard_library.register(Parameter, "vec", "value...", eval::structs::vec);
+    standard_library.register(Parameter, "mat", "row...", eval::structs::mat);
     
     // sound events (sample events are added as needed)
-    standard_library.fmap.insert("risset".to_string(), eval::events::sound::sound);
-    standard_library.fmap.insert("saw".to_string(), eval::events::sound::sound);
-    standard_library.fmap.insert("wsaw".to_string(), eval::events::sound::sound);
-    standard_library.fmap.insert("fmsaw".to_string(), eval::events::sound::sound);
-    standard_library.fmap.insert("fmsqr".to_string(), eval::events::sound::sound);
-    standard_library.fmap.insert("fmtri".to_string(), eval::events::sound::sound);
-    standard_library.fmap.insert("sqr".to_string(), eval::events::sound::sound);
-    standard_library.fmap.insert("cub".to_string(), eval::events::sound::sound);
-    standard_library.fmap.insert("tri".to_string(), eval::events::sound::sound);
-    standard_library.fmap.insert("sine".to_string(), eval::events::sound::sound);
-    standard_library.fmap.insert("~".to_string(), eval::events::sound::sound);
-    standard_library.fmap.insert("silence".to_string(), eval::events::sound::sound);
-    standard_library.fmap.insert("feedr".to_string(), eval::events::sound::sound);
-    standard_library.fmap.insert("freezr".to_string(), eval::events::sound::sound);
-    standard_library.fmap.insert("wtab".to_string(), eval::events::sound::sound);
-    standard_library.fmap.insert("wmat".to_string(), eval::events::sound::sound);
-    standard_library.fmap.insert("white".to_string(), eval::events::sound::sound);
-    standard_library.fmap.insert("brown".to_string(), eval::events::sound::sound);
+    standard_library.register(Event, "chord", "name... :inv :spread", eval::events::chord::chord);
+    standard_library.register(Event, "risset", &format!("[freq] {}", SOUND_KEYWORDS), eval::events::sound::sound);
+    standard_library.register(Event, "saw", &format!("[freq] {}", SOUND_KEYWORDS), eval::events::sound::sound);
+    standard_library.register(Event, "wsaw", &format!("[freq] {}", SOUND_KEYWORDS), eval::events::sound::sound);
+    standard_library.register(Event, "fmsaw", &format!("[freq] {}", SOUND_KEYWORDS), eval::events::sound::sound);
+    standard_library.register(Event, "fmsqr", &format!("[freq] {}", SOUND_KEYWORDS), eval::events::sound::sound);
+    standard_library.register(Event, "fmtri", &format!("[freq] {}", SOUND_KEYWORDS), eval::events::sound::sound);
+    standard_library.register(Event, "sqr", &format!("[freq] {}", SOUND_KEYWORDS), eval::events::sound::sound);
+    standard_library.register(Event, "cub", &format!("[freq] {}", SOUND_KEYWORDS), eval::events::sound::sound);
+    standard_library.register(Event, "tri", &format!("[freq] {}", SOUND_KEYWORDS), eval::events::sound::sound);
+    standard_library.register(Event, "sine", &format!("[freq] {}", SOUND_KEYWORDS), eval::events::sound::sound);
+    standard_library.register(Event, "~", "", eval::events::sound::sound);
+    standard_library.register(Event, "silence", "[dur]", eval::events::sound::sound);
+    standard_library.register(Event, "feedr", &format!("buffer {} {}", SOUND_KEYWORDS, SAMPLE_KEYWORDS), eval::events::sound::sound);
+    standard_library.register(Event, "freezr", &format!("buffer {} {}", SOUND_KEYWORDS, SAMPLE_KEYWORDS), eval::events::sound::sound);
+    standard_library.register(Event, "wtab", &format!("{} :wt", SOUND_KEYWORDS), eval::events::sound::sound);
+    standard_library.register(Event, "wmat", &format!("{} :wm :ti", SOUND_KEYWORDS), eval::events::sound::sound);
+    standard_library.register(Event, "white", &format!("{}", SOUND_KEYWORDS), eval::events::sound::sound);
+    standard_library.register(Event, "brown", &format!("{}", SOUND_KEYWORDS), eval::events::sound::sound);
+    standard_library.register(Event, "stream", &format!("'keyword... {} {}", SOUND_KEYWORDS, SAMPLE_KEYWORDS), eval::events::sound::sound);
 
     // modulators
-    standard_library.fmap.insert("lfo~".to_string(), eval::events::modulators::lfo_modulator);
-    standard_library.fmap.insert("lfsaw~".to_string(), eval::events::modulators::lfsaw_modulator);
-    standard_library.fmap.insert("lfrsaw~".to_string(), eval::events::modulators::lfrsaw_modulator);
-    standard_library.fmap.insert("lfsqr~".to_string(), eval::events::modulators::lfsquare_modulator);
-    standard_library.fmap.insert("lftri~".to_string(), eval::events::modulators::lftri_modulator);
-    standard_library.fmap.insert("linramp~".to_string(), eval::events::modulators::lin_ramp_modulator);
-    standard_library.fmap.insert("logramp~".to_string(), eval::events::modulators::log_ramp_modulator);
-    standard_library.fmap.insert("expramp~".to_string(), eval::events::modulators::exp_ramp_modulator);
-    standard_library.fmap.insert("env~".to_string(), eval::events::modulators::multi_point_envelope_modulator);
+    standard_library.register(Modulator, "lfo~", ":i :f :p :r :amp :add :op", eval::events::modulators::lfo_modulator);
+    standard_library.register(Modulator, "lfsaw~", ":i :f :p :r :amp :add :op", eval::events::modulators::lfsaw_modulator);
+    standard_library.register(Modulator, "lfrsaw~", ":i :f :p :r :amp :add :op", eval::events::modulators::lfrsaw_modulator);
+    standard_library.register(Modulator, "lfsqr~", ":i :f :pw :r :amp :add :op", eval::events::modulators::lfsquare_modulator);
+    standard_library.register(Modulator, "lftri~", ":i :f :p :r :amp :add :op", eval::events::modulators::lftri_modulator);
+    standard_library.register(Modulator, "linramp~", "from to :t :op", eval::events::modulators::lin_ramp_modulator);
+    standard_library.register(Modulator, "logramp~", "from to :t :op", eval::events::modulators::log_ramp_modulator);
+    standard_library.register(Modulator, "expramp~", "from to :t :op", eval::events::modulators::exp_ramp_modulator);
+    standard_library.register(Modulator, "env~", ":l :t :ty :loop :op", eval::events::modulators::multi_point_envelope_modulator);
     
     // parameter events
 
     // symbolic type paramerters
-    standard_library.fmap.insert("lpt".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("hpt".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("atkt".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("dect".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("relt".to_string(), eval::events::parameters::parameter);
-    
-    standard_library.fmap.insert("pitch".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pitch-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pitch-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pitch-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pitch-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("freq".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("freq-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("freq-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("freq-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("freq-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("lvl".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("lvl-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("lvl-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("lvl-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("lvl-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("lpf".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("lpf-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("lpf-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("lpf-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("lpf-div".to_string(), eval::events::parameters::parameter);
-    
-    standard_library.fmap.insert("lpd".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("lpd-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("lpd-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("lpd-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("lpd-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("lpq".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("lpq-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("lpq-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("lpq-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("lpq-div".to_string(), eval::events::parameters::parameter);
-    
-    standard_library.fmap.insert("pff".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pff-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pff-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pff-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pff-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("pfq".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pfq-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pfq-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pfq-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pfq-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("pfg".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pfg-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pfg-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pfg-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pfg-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("hpf".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("hpf-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("hpf-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("hpf-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("hpf-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("hpq".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("hpq-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("hpq-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("hpq-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("hpq-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("atk".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("atk-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("atk-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("atk-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("atk-div".to_string(), eval::events::parameters::parameter);
+    standard_library.register(Parameter, "lpt", "type", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "hpt", "type", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "atkt", "type", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "dect", "type", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "relt", "type", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "pitch", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pitch-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pitch-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pitch-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pitch-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "freq", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "freq-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "freq-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "freq-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "freq-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "lvl", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "lvl-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "lvl-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "lvl-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "lvl-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "lpf", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "lpf-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "lpf-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "lpf-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "lpf-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "lpd", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "lpd-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "lpd-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "lpd-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "lpd-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "lpq", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "lpq-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "lpq-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "lpq-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "lpq-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "pff", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pff-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pff-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pff-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pff-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "pfq", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pfq-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pfq-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pfq-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pfq-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "pfg", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pfg-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pfg-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pfg-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pfg-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "hpf", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "hpf-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "hpf-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "hpf-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "hpf-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "hpq", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "hpq-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "hpq-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "hpq-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "hpq-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "atk", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "atk-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "atk-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "atk-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "atk-div", "value", eval::events::parameters::parameter);
 
     // attack peak
-    standard_library.fmap.insert("atkp".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("atkp-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("atkp-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("atkp-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("atkp-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("sus".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("sus-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("sus-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("sus-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("sus-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("dec".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("dec-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("dec-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("dec-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("dec-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("rel".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("rel-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("rel-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("rel-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("rel-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("pos".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pos-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pos-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pos-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pos-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("dur".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("dur-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("dur-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("dur-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("dur-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("del".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("del-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("del-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("del-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("del-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("rev".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("rev-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("rev-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("rev-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("rev-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("pw".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pw-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pw-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pw-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("pw-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("start".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("start-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("start-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("start-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("start-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("rate".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("rate-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("rate-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("rate-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("rate-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("gain".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("gain-add".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("gain-mul".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("gain-sub".to_string(), eval::events::parameters::parameter);
-    standard_library.fmap.insert("gain-div".to_string(), eval::events::parameters::parameter);
-
-    standard_library.fmap.insert("transpose".to_string(), eval::events::parameters::transpose);
-    standard_library.fmap.insert("tpo".to_string(), eval::events::parameters::transpose);
+    standard_library.register(Parameter, "atkp", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "atkp-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "atkp-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "atkp-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "atkp-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "sus", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "sus-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "sus-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "sus-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "sus-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "dec", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "dec-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "dec-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "dec-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "dec-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "rel", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "rel-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "rel-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "rel-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "rel-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "pos", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pos-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pos-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pos-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pos-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "dur", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "dur-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "dur-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "dur-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "dur-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "del", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "del-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "del-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "del-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "del-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "rev", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "rev-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "rev-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "rev-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "rev-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "pw", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pw-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pw-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pw-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "pw-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "start", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "start-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "start-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "start-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "start-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "rate", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "rate-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "rate-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "rate-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "rate-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "gain", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "gain-add", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "gain-mul", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "gain-sub", "value", eval::events::parameters::parameter);
+    standard_library.register(Parameter, "gain-div", "value", eval::events::parameters::parameter);
+
+    standard_library.register(Parameter, "transpose", "steps event...", eval::events::parameters::transpose);
+    standard_library.register(Parameter, "tpo", "steps event...", eval::events::parameters::transpose);
 
     // dynpars
-    standard_library.fmap.insert("bounce".to_string(), eval::dynpar::bounce);
-    standard_library.fmap.insert("brownian".to_string(), eval::dynpar::brownian);
-    standard_library.fmap.insert("randr".to_string(), eval::dynpar::randrange);
-    standard_library.fmap.insert("env".to_string(), eval::dynpar::env);
-    standard_library.fmap.insert("fade".to_string(), eval::dynpar::fade);
+    standard_library.register(Dynpar, "bounce", "min max :steps :period :clock", eval::dynpar::bounce);
+    standard_library.register(Dynpar, "brownian", "min max :step :start :wrap", eval::dynpar::brownian);
+    standard_library.register(Dynpar, "randr", "min max", eval::dynpar::randrange);
+    standard_library.register(Dynpar, "env", ":v :values :s :steps :repeat", eval::dynpar::env);
+    standard_library.register(Dynpar, "fade", "from to :steps", eval::dynpar::fade);
+    standard_library.register(Dynpar, "degree", "degree :oct", eval::dynpar::degree);
+    standard_library.register(Dynpar, "seq", "value... :mode", eval::dynpar::seq);
+    standard_library.register(Dynpar, "choose", "value... :w :weights", eval::dynpar::choose);
+    standard_library.register(Dynpar, "sh", "value :every", eval::dynpar::sample_and_hold);
+    standard_library.register(Dynpar, "noise", "min max :smooth", eval::dynpar::noise);
+    standard_library.register(Dynpar, "lfo-step", ":min :max :period :phase :shape :clock", eval::dynpar::lfo_step);
+    standard_library.register(Dynpar, "note", "'name", eval::dynpar::note);
+    standard_library.register(Dynpar, "midi", "key", eval::dynpar::midi);
+    standard_library.register(Dynpar, "beats", "count", eval::dynpar::beats);
+    standard_library.register(Dynpar, "db", "value", eval::dynpar::db);
 
     // generator processors
-    standard_library.fmap.insert("pear".to_string(), eval::generator_processor::eval_pear);
-    standard_library.fmap.insert("apple".to_string(), eval::generator_processor::eval_apple);
-    standard_library.fmap.insert("every".to_string(), eval::generator_processor::eval_every);
-    standard_library.fmap.insert("life".to_string(), eval::generator_processor::eval_lifemodel);
-    standard_library.fmap.insert("inhibit".to_string(), eval::generator_processor::eval_inhibit);
-    standard_library.fmap.insert("exhibit".to_string(), eval::generator_processor::eval_exhibit);
+    standard_library.register(Processor, "pear", "[gen...] :p :for", eval::generator_processor::eval_pear);
+    standard_library.register(Processor, "apple", "[gen...] :p", eval::generator_processor::eval_apple);
+    standard_library.register(Processor, "every", "[gen...] :n :offset :pattern :id", eval::generator_processor::eval_every);
+    standard_library.register(Processor, "when", "[gen...] :after :first :between :bar-phase :global :for :id", eval::generator_processor::eval_when);
+    standard_library.register(Processor, "scale", "'mode ['root] [gen...]", eval::generator_processor::eval_scale);
+    standard_library.register(Processor, "life", "[gen...] :global-contrib :local-resources :cost :apoptosis :autophagia :durs :method :keep :rnd :solidify :solidify-len :id", eval::generator_processor::eval_lifemodel);
+    standard_library.register(Processor, "inhibit", "[gen...] :p :for", eval::generator_processor::eval_inhibit);
+    standard_library.register(Processor, "exhibit", "[gen...] :p :for", eval::generator_processor::eval_exhibit);
 
     // composition
-    standard_library.fmap.insert("cmp".to_string(), eval::compose::compose);
-    standard_library.fmap.insert("compose".to_string(), eval::compose::compose);
-    standard_library.fmap.insert("ls".to_string(), eval::generator_list::generator_list);
-    standard_library.fmap.insert("list".to_string(), eval::generator_list::generator_list);
+    standard_library.register(Composition, "cmp", "processor... gen", eval::compose::compose);
+    standard_library.register(Composition, "compose", "processor... gen", eval::compose::compose);
+    standard_library.register(Composition, "ls", "gen...", eval::generator_list::generator_list);
+    standard_library.register(Composition, "list", "gen...", eval::generator_list::generator_list);
 
     // multiplyer
-    standard_library.fmap.insert("xspread".to_string(), eval::multiplyer::eval_xspread);
-    standard_library.fmap.insert("xdup".to_string(), eval::multiplyer::eval_xdup);
+    standard_library.register(Composition, "xspread", "processor... gen", eval::multiplyer::eval_xspread);
+    standard_library.register(Composition, "xdup", "processor... gen", eval::multiplyer::eval_xdup);
     
     // generator modifiers
-    standard_library.fmap.insert("haste".to_string(), eval::generator_modifier::eval_haste);
-    standard_library.fmap.insert("relax".to_string(), eval::generator_modifier::eval_relax);
-    standard_library.fmap.insert("grow".to_string(), eval::generator_modifier::eval_grow);
-    standard_library.fmap.insert("grown".to_string(), eval::generator_modifier::eval_grown);
-    standard_library.fmap.insert("shrink".to_string(), eval::generator_modifier::eval_shrink);
-    standard_library.fmap.insert("solidify".to_string(), eval::generator_modifier::eval_solidify);
-    standard_library.fmap.insert("blur".to_string(), eval::generator_modifier::eval_blur);
-    standard_library.fmap.insert("sharpen".to_string(), eval::generator_modifier::eval_sharpen);
-    standard_library.fmap.insert("shake".to_string(), eval::generator_modifier::eval_shake);
-    standard_library.fmap.insert("skip".to_string(), eval::generator_modifier::eval_skip);
-    standard_library.fmap.insert("rewind".to_string(), eval::generator_modifier::eval_rewind);
-    standard_library.fmap.insert("rnd".to_string(), eval::generator_modifier::eval_rnd);
-    standard_library.fmap.insert("rep".to_string(), eval::generator_modifier::eval_rep);
-    standard_library.fmap.insert("reverse".to_string(), eval::generator_modifier::eval_reverse);
-    standard_library.fmap.insert("keep".to_string(), eval::generator_modifier::eval_keep);
+    standard_library.register(Modifier, "haste", "n factor [gen...]", eval::generator_modifier::eval_haste);
+    standard_library.register(Modifier, "relax", "n factor [gen...]", eval::generator_modifier::eval_relax);
+    standard_library.register(Modifier, "grow", "factor [gen...] :method", eval::generator_modifier::eval_grow);
+    standard_library.register(Modifier, "grown", "n factor [gen...] :method", eval::generator_modifier::eval_grown);
+    standard_library.register(Modifier, "shrink", "[gen...]", eval::generator_modifier::eval_shrink);
+    standard_library.register(Modifier, "solidify", "factor [gen...]", eval::generator_modifier::eval_solidify);
+    standard_library.register(Modifier, "blur", "factor [gen...]", eval::generator_modifier::eval_blur);
+    standard_library.register(Modifier, "sharpen", "factor [gen...]", eval::generator_modifier::eval_sharpen);
+    standard_library.register(Modifier, "shake", "factor [gen...]", eval::generator_modifier::eval_shake);
+    standard_library.register(Modifier, "skip", "n [gen...]", eval::generator_modifier::eval_skip);
+    standard_library.register(Modifier, "rewind", "n [gen...]", eval::generator_modifier::eval_rewind);
+    standard_library.register(Modifier, "rnd", "chance [gen...]", eval::generator_modifier::eval_rnd);
+    standard_library.register(Modifier, "rep", "chance max-rep [gen...]", eval::generator_modifier::eval_rep);
+    standard_library.register(Modifier, "reverse", "[gen...]", eval::generator_modifier::eval_reverse);
+    standard_library.register(Modifier, "keep", "[gen...]", eval::generator_modifier::eval_keep);
 
     // arithmetic
-    standard_library.fmap.insert("add".to_string(), eval::arithmetic::add);
-    standard_library.fmap.insert("mul".to_string(), eval::arithmetic::mul);
-    standard_library.fmap.insert("sub".to_string(), eval::arithmetic::sub);
-    standard_library.fmap.insert("div".to_string(), eval::arithmetic::div);
-    standard_library.fmap.insert("mod".to_string(), eval::arithmetic::modulo);
-    standard_library.fmap.insert("pow".to_string(), eval::arithmetic::pow);
+    standard_library.register(Arithmetic, "add", "value...", eval::arithmetic::add);
+    standard_library.register(Arithmetic, "mul", "value...", eval::arithmetic::mul);
+    standard_library.register(Arithmetic, "sub", "value...", eval::arithmetic::sub);
+    standard_library.register(Arithmetic, "div", "value...", eval::arithmetic::div);
+    standard_library.register(Arithmetic, "mod", "value...", eval::arithmetic::modulo);
+    standard_library.register(Arithmetic, "pow", "value...", eval::arithmetic::pow);
 
     standard_library
 }
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    /// the body of a function, so keywords parsed by its neighbours don't count
+    fn function_source(source: &'static str, fun: &str) -> &'static str {
+        let start = source.find(&format!("fn {}(", fun)).unwrap_or(source.len());
+        let end = source[start..].find("\n}").map(|e| start + e).unwrap_or(source.len());
+        &source[start..end]
+    }
+
+    /// the sources that parse the keywords of a function
+    fn keyword_sources(name: &str) -> Vec<&'static str> {
+        let processors = include_str!("parser/eval/generator_processor.rs");
+        let commands = include_str!("parser/eval/commands.rs");
+        let dynpars = include_str!("parser/eval/dynpar.rs");
+        let clock = function_source(dynpars, "find_keyword_clock");
+        match name {
+            "nuc" => vec![include_str!("parser/eval/constructors/nuc.rs")],
+            "fully" => vec![include_str!("parser/eval/constructors/fully.rs")],
+            "friendship" => vec![include_str!("parser/eval/constructors/friendship.rs")],
+            "lin" | "linear" => vec![include_str!("parser/eval/constructors/linear.rs")],
+            "loop" => vec![include_str!("parser/eval/constructors/loop.rs")],
+            "chop" => vec![include_str!("parser/eval/constructors/chop.rs")],
+            "infer" | "rule" => vec![include_str!("parser/eval/constructors/infer.rs")],
+            "learn" => vec![include_str!("parser/eval/constructors/learn.rs")],
+            "cyc" => vec![include_str!("parser/eval/constructors/cyc.rs")],
+            "flower" => vec![include_str!("parser/eval/constructors/flower.rs")],
+            "stages" => vec![include_str!("parser/eval/constructors/stages.rs")],
+            "facts" => vec![include_str!("parser/eval/constructors/facts.rs")],
+            "arp" => vec![include_str!("parser/eval/constructors/arp.rs")],
+            "prog" => vec![include_str!("parser/eval/constructors/prog.rs")],
+            "euclid" | "density" => vec![include_str!("parser/eval/constructors/euclid.rs")],
+            "pear" => vec![processors, include_str!("parser/eval/generator_processor/pear.rs")],
+            "apple" => vec![processors, include_str!("parser/eval/generator_processor/apple.rs")],
+            "every" => vec![processors, include_str!("parser/eval/generator_processor/every.rs")],
+            "when" => vec![processors, include_str!("parser/eval/generator_processor/when.rs")],
+            "scale" => vec![processors, include_str!("parser/eval/generator_processor/scale.rs")],
+            "life" => vec![processors, include_str!("parser/eval/generator_processor/lifemodel.rs")],
+            "inhibit" => vec![processors, include_str!("parser/eval/generator_processor/inhibit.rs")],
+            "exhibit" => vec![processors, include_str!("parser/eval/generator_processor/exhibit.rs")],
+            "freeze" => vec![function_source(commands, "freeze_buffer")],
+            "load-sample" => vec![function_source(commands, "load_sample")],
+            "load-wavematrix" => vec![function_source(commands, "load_sample_as_wavematrix")],
+            "capture-wavematrix" => vec![function_source(commands, "capture_wavematrix")],
+            "load-sample-sets" => vec![function_source(commands, "load_sample_sets")],
+            "load-sample-set" => vec![function_source(commands, "load_sample_set")],
+            "tuning" => vec![function_source(commands, "tuning")],
+            "link" => vec![function_source(commands, "link")],
+            "reverb" => vec![function_source(commands, "reverb")],
+            "delay" => vec![function_source(commands, "delay")],
+            "export-dot" => vec![function_source(commands, "export_dot")],
+            "rec" => vec![function_source(commands, "start_recording")],
+            "bounce" => vec![function_source(dynpars, "bounce"), clock],
+            "brownian" => vec![function_source(dynpars, "brownian")],
+            "env" => vec![function_source(dynpars, "env")],
+            "fade" => vec![function_source(dynpars, "fade")],
+            "degree" => vec![function_source(dynpars, "degree")],
+            "seq" => vec![function_source(dynpars, "seq")],
+            "choose" => vec![function_source(dynpars, "choose")],
+            "sh" => vec![function_source(dynpars, "sample_and_hold")],
+            "noise" => vec![function_source(dynpars, "noise")],
+            "lfo-step" => vec![function_source(dynpars, "lfo_step"), clock],
+            _ => Vec::new(),
+        }
+    }
+
+    /// the source without comments, so keywords that are commented out don't count
+    fn strip_comments(source: &str) -> String {
+        let mut stripped = String::new();
+        let mut rest = source;
+        while let Some(start) = rest.find("/*") {
+            stripped.push_str(&rest[..start]);
+            rest = rest[start..].find("*/").map(|end| &rest[start + end + 2..]).unwrap_or("");
+        }
+        stripped.push_str(rest);
+        stripped
+            .lines()
+            .map(|l| l.split("//").next().unwrap_or(""))
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_advertised_keywords_are_parsed() {
+        let functions = define_standard_library();
+        for (name, info) in functions.info.iter() {
+            let checked = match info.category {
+                Constructor | Processor => true,
+                // most commands and dynpars don't take any keywords
+                Command | Dynpar => !info.keywords().is_empty(),
+                _ => false,
+            };
+            if !checked {
+                continue;
+            }
+            let sources: Vec<String> = keyword_sources(name).into_iter().map(strip_comments).collect();
+            assert!(!sources.is_empty(), "no source for {}", name);
+            for kw in info.keywords() {
+                let quoted = format!("\"{}\"", kw);
+                assert!(
+                    sources.iter().any(|s| s.contains(&quoted)),
+                    "{} advertises :{} but doesn't parse it",
+                    name,
+                    kw
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_strip_comments() {
+        let source = "\"dur\" => {}\n//\"events\" => {}\n/*\"map\" => {}*/ \"keep\" => {} // \"rep\"";
+        let stripped = strip_comments(source);
+        assert!(stripped.contains("\"dur\""));
+        assert!(stripped.contains("\"keep\""));
+        assert!(!stripped.contains("\"events\""));
+        assert!(!stripped.contains("\"map\""));
+        assert!(!stripped.contains("\"rep\""));
+    }
+}
@@ -0,0 +1,865 @@
+use crossbeam::channel::{Receiver, Sender, TryRecvError};
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use std::{sync, thread};
+
+use ruffbox_synth::building_blocks::{SynthParameterLabel, SynthParameterValue};
+
+use crate::event::StaticEvent;
+use crate::messages;
+use crate::real_time_streaming::StreamItem;
+use crate::sample_set::SampleAndWavematrixSet;
+use crate::session::OutputMode;
+
+// number of blocks the reader pool decodes ahead of the audio thread
+const STREAM_PREFILL: usize = 64;
+
+// fade times, to avoid clicks when starting in the middle of a file
+// or cutting it off
+const FADE_IN_MS: f32 = 5.0;
+const FADE_OUT_MS: f32 = 10.0;
+
+// the maximum number of concurrent streams, so the audio thread never has
+// to allocate ... if more are started, the oldest one is stopped
+const MAX_VOICES: usize = 32;
+
+// how long the reader pool waits for something to do, in milliseconds
+const POOL_IDLE_MS: u64 = 2;
+
+// how many samples before the offset are skipped per round for files
+// that can't seek (flac), so the other streams get their turn in between
+const OFFSET_CHUNK: usize = 16384;
+
+/// The parameters of a single stream, as taken from the stream event.
+pub struct StreamParameters {
+    pub start_time: f64,     // logical (ruffbox) time to start at, in seconds
+    pub offset: f32,         // where to start in the file, in ms
+    pub length: Option<f32>, // how long to play, in ms, until end of file if none
+    pub level: f32,
+    pub position: f32, // channel position, 0.0 to NCHAN (stereo needs to be translated)
+}
+
+/// The audio thread side of a stream.
+pub struct StreamVoice<const BUFSIZE: usize, const NCHAN: usize> {
+    start_time: f64,
+    block_q: Receiver<StreamItem<BUFSIZE, NCHAN>>,
+    return_q: Sender<StreamItem<BUFSIZE, NCHAN>>,
+    current: Option<StreamItem<BUFSIZE, NCHAN>>,
+    idx: usize,
+    // set by the reader pool to fade the voice out right away
+    stop: sync::Arc<AtomicBool>,
+    finished: bool,
+}
+
+/// Mixes the active streams into the output. Lives in the audio callback
+/// and is called once for every block ruffbox produces, so it can keep
+/// track of the logical time on its own.
+pub struct StreamMixer<const BUFSIZE: usize, const NCHAN: usize> {
+    new_voices: Receiver<StreamVoice<BUFSIZE, NCHAN>>,
+    // finished voices go back to the reader pool, so nothing
+    // is freed on the audio thread
+    finished_voices: Sender<StreamVoice<BUFSIZE, NCHAN>>,
+    voices: Vec<StreamVoice<BUFSIZE, NCHAN>>,
+    now: f64,
+    samplerate: f64,
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> StreamMixer<BUFSIZE, NCHAN> {
+    pub fn mix(&mut self, block: &mut [[f32; BUFSIZE]; NCHAN]) {
+        while self.voices.len() < MAX_VOICES {
+            if let Ok(voice) = self.new_voices.try_recv() {
+                self.voices.push(voice);
+            } else {
+                break;
+            }
+        }
+
+        for voice in self.voices.iter_mut() {
+            // stopped voices fade out within this block
+            let stopping = voice.stop.load(Ordering::Relaxed);
+
+            // sample-accurate start
+            let mut offset = 0;
+            if voice.start_time > self.now {
+                offset = ((voice.start_time - self.now) * self.samplerate) as usize;
+                if offset >= BUFSIZE {
+                    // stopped before it even started
+                    voice.finished = stopping;
+                    continue;
+                }
+            }
+
+            for s in offset..BUFSIZE {
+                let exhausted = if let Some(item) = &voice.current {
+                    voice.idx >= item.size
+                } else {
+                    true
+                };
+
+                if exhausted {
+                    if let Some(item) = voice.current.take() {
+                        voice.return_q.try_send(item).ok();
+                    }
+                    match voice.block_q.try_recv() {
+                        Ok(item) => {
+                            voice.current = Some(item);
+                            voice.idx = 0;
+                        }
+                        // reader can't keep up, skip ...
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) => {
+                            voice.finished = true;
+                            break;
+                        }
+                    }
+                }
+
+                if let Some(item) = &voice.current {
+                    let gain = if stopping {
+                        (BUFSIZE - s) as f32 / BUFSIZE as f32
+                    } else {
+                        1.0
+                    };
+                    for (ch, out) in block.iter_mut().enumerate() {
+                        out[s] += item.buffer[ch][voice.idx] * gain;
+                    }
+                    voice.idx += 1;
+                }
+            }
+
+            if stopping {
+                voice.finished = true;
+            }
+        }
+
+        let mut v = 0;
+        while v < self.voices.len() {
+            if self.voices[v].finished {
+                let voice = self.voices.swap_remove(v);
+                // only fails if the reader pool is gone
+                self.finished_voices.try_send(voice).ok();
+            } else {
+                v += 1;
+            }
+        }
+        self.now += BUFSIZE as f64 / self.samplerate;
+    }
+}
+
+enum StreamRequest {
+    Start(usize, String, StreamParameters),
+    StopAll,
+}
+
+/// The control side of the stream player, hands the streams
+/// to the reader pool.
+pub struct StreamControl<const BUFSIZE: usize, const NCHAN: usize> {
+    requests: Sender<StreamRequest>,
+    sample_set: sync::Arc<Mutex<SampleAndWavematrixSet>>,
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> StreamControl<BUFSIZE, NCHAN> {
+    /// start streaming the file registered under the given id
+    pub fn start_stream(&self, id: usize, params: StreamParameters) {
+        let path = if let Some(p) = self.sample_set.lock().stream_path(id) {
+            p.clone()
+        } else {
+            messages::warning(format!("can't find stream {}", id));
+            return;
+        };
+
+        if self
+            .requests
+            .try_send(StreamRequest::Start(id, path, params))
+            .is_err()
+        {
+            messages::warning("too many streams at once, skipping".to_string());
+        }
+    }
+
+    /// stop all running streams
+    pub fn stop_all(&self) {
+        self.requests.send(StreamRequest::StopAll).ok();
+    }
+}
+
+/// The reader pool side of a stream.
+struct ActiveStream<const BUFSIZE: usize, const NCHAN: usize> {
+    id: usize,
+    reader: FrameReader,
+    params: StreamParameters,
+    frame: Vec<f32>,
+    fade_in: usize,
+    fade_out: usize,
+    length: Option<usize>,
+    frames_played: usize,
+    block_q: Sender<StreamItem<BUFSIZE, NCHAN>>,
+    return_q: Receiver<StreamItem<BUFSIZE, NCHAN>>,
+    stop: sync::Arc<AtomicBool>,
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> ActiveStream<BUFSIZE, NCHAN> {
+    /// Decode into the blocks the voice returned. Returns whether there
+    /// was anything to do and whether the stream is done.
+    fn fill(&mut self) -> (bool, bool) {
+        // work through the offset first, one chunk per round
+        if self.reader.seeking() {
+            return (true, !self.reader.advance_offset());
+        }
+
+        let mut busy = false;
+        loop {
+            let mut item = match self.return_q.try_recv() {
+                Ok(item) => item,
+                Err(TryRecvError::Empty) => return (busy, false),
+                // the voice is gone
+                Err(TryRecvError::Disconnected) => return (busy, true),
+            };
+            busy = true;
+
+            let mut done = false;
+            item.size = 0;
+            for s in 0..BUFSIZE {
+                for ch_buf in item.buffer.iter_mut() {
+                    ch_buf[s] = 0.0;
+                }
+
+                if let Some(l) = self.length {
+                    if self.frames_played >= l {
+                        done = true;
+                        break;
+                    }
+                }
+
+                if !self.reader.next_frame(&mut self.frame) {
+                    done = true;
+                    break;
+                }
+
+                // simple linear fades
+                let mut gain = self.params.level;
+                if self.frames_played < self.fade_in {
+                    gain *= self.frames_played as f32 / self.fade_in as f32;
+                }
+                if let Some(l) = self.length {
+                    let left = l - self.frames_played;
+                    if left < self.fade_out {
+                        gain *= left as f32 / self.fade_out as f32;
+                    }
+                }
+
+                spread(&self.frame, self.params.position, gain, &mut item.buffer, s);
+
+                self.frames_played += 1;
+                item.size += 1;
+            }
+
+            if item.size > 0 && self.block_q.send(item).is_err() {
+                return (busy, true);
+            }
+            if done {
+                return (busy, true);
+            }
+        }
+    }
+}
+
+/// Decodes all running streams on a single thread, so starting
+/// a stream doesn't start a thread, and the number of streams
+/// is limited.
+struct ReaderPool<const BUFSIZE: usize, const NCHAN: usize> {
+    streams: Vec<ActiveStream<BUFSIZE, NCHAN>>,
+    new_voices: Sender<StreamVoice<BUFSIZE, NCHAN>>,
+    samplerate: f32,
+}
+
+impl<const BUFSIZE: usize, const NCHAN: usize> ReaderPool<BUFSIZE, NCHAN> {
+    fn start(&mut self, id: usize, path: String, params: StreamParameters) {
+        let reader = if let Some(r) = FrameReader::open(&path, params.offset, self.samplerate) {
+            r
+        } else {
+            messages::warning(format!("can't stream {}", path));
+            return;
+        };
+
+        // streams without a length play until the same file is started
+        // again, otherwise they'd pile up ...
+        if params.length.is_none() {
+            let mut i = 0;
+            while i < self.streams.len() {
+                if self.streams[i].id == id && self.streams[i].length.is_none() {
+                    self.stop(i);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        if self.streams.len() >= MAX_VOICES {
+            self.stop(0);
+        }
+
+        let (block_tx, block_rx) = crossbeam::channel::bounded(STREAM_PREFILL);
+        let (return_tx, return_rx) = crossbeam::channel::bounded(STREAM_PREFILL);
+
+        for _ in 0..STREAM_PREFILL {
+            return_tx
+                .send(StreamItem::<BUFSIZE, NCHAN> {
+                    buffer: [[0.0; BUFSIZE]; NCHAN],
+                    size: 0,
+                })
+                .unwrap();
+        }
+
+        let stop = sync::Arc::new(AtomicBool::new(false));
+        let mut stream = ActiveStream {
+            id,
+            frame: vec![0.0; reader.channels],
+            reader,
+            fade_in: (FADE_IN_MS * 0.001 * self.samplerate) as usize,
+            fade_out: (FADE_OUT_MS * 0.001 * self.samplerate) as usize,
+            length: params
+                .length
+                .map(|l| (l * 0.001 * self.samplerate) as usize),
+            params,
+            frames_played: 0,
+            block_q: block_tx,
+            return_q: return_rx,
+            stop: sync::Arc::clone(&stop),
+        };
+
+        // decode ahead before the voice starts, so the start isn't skipped
+        let (_, done) = stream.fill();
+
+        let voice = StreamVoice {
+            start_time: stream.params.start_time,
+            block_q: block_rx,
+            return_q: return_tx,
+            current: None,
+            idx: 0,
+            stop,
+            finished: false,
+        };
+
+        if !done {
+            self.streams.push(stream);
+        } else {
+            // short enough to be decoded already, dropping the
+            // sender lets the voice know there's nothing more
+            drop(stream);
+        }
+
+        if self.new_voices.try_send(voice).is_err() {
+            // the mixer doesn't take any more voices, drop the stream again
+            if !done {
+                self.streams.pop();
+            }
+        }
+    }
+
+    fn stop(&mut self, idx: usize) {
+        let stream = self.streams.remove(idx);
+        stream.stop.store(true, Ordering::Relaxed);
+    }
+
+    fn stop_all(&mut self) {
+        while !self.streams.is_empty() {
+            self.stop(0);
+        }
+    }
+
+    /// decode for all running streams, returns whether there was anything to do
+    fn fill(&mut self) -> bool {
+        let mut busy = false;
+        let mut i = 0;
+        while i < self.streams.len() {
+            let (stream_busy, done) = self.streams[i].fill();
+            busy |= stream_busy;
+            if done {
+                // dropping the sender lets the voice know we're finished ...
+                self.streams.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        busy
+    }
+
+    fn handle(&mut self, request: StreamRequest) {
+        match request {
+            StreamRequest::Start(id, path, params) => self.start(id, path, params),
+            StreamRequest::StopAll => self.stop_all(),
+        }
+    }
+
+    fn run(
+        mut self,
+        requests: Receiver<StreamRequest>,
+        finished_voices: Receiver<StreamVoice<BUFSIZE, NCHAN>>,
+    ) {
+        loop {
+            let busy = self.fill();
+            // the voices the mixer is done with are freed here
+            finished_voices.try_iter().for_each(drop);
+
+            let request = if busy {
+                requests.try_recv().map_err(|e| e.is_disconnected())
+            } else {
+                requests
+                    .recv_timeout(Duration::from_millis(POOL_IDLE_MS))
+                    .map_err(|e| e.is_disconnected())
+            };
+            match request {
+                Ok(request) => {
+                    self.handle(request);
+                    for request in requests.try_iter() {
+                        self.handle(request);
+                    }
+                }
+                // the control side is gone
+                Err(true) => return,
+                Err(false) => {}
+            }
+        }
+    }
+}
+
+/// distribute a frame to the output channels
+fn spread<const BUFSIZE: usize, const NCHAN: usize>(
+    frame: &[f32],
+    position: f32,
+    gain: f32,
+    buffer: &mut [[f32; BUFSIZE]; NCHAN],
+    idx: usize,
+) {
+    let lower = position.floor().max(0.0) as usize;
+    if frame.len() == 1 {
+        // equal-power panning between two adjacent channels
+        let frac = position - position.floor();
+        let angle = frac * std::f32::consts::FRAC_PI_2;
+        buffer[lower % NCHAN][idx] += frame[0] * gain * angle.cos();
+        buffer[(lower + 1) % NCHAN][idx] += frame[0] * gain * angle.sin();
+    } else {
+        // multi-channel files are laid out starting at the lower channel
+        for (ch, s) in frame.iter().enumerate() {
+            buffer[(lower + ch) % NCHAN][idx] += s * gain;
+        }
+    }
+}
+
+/// The interleaved samples of a flac file, decoded block by block,
+/// as claxon's own sample iterator only borrows the reader.
+struct FlacSamples {
+    reader: claxon::FlacReader<std::fs::File>,
+    buffer: Vec<i32>, // one channel after another
+    block_size: usize,
+    channels: usize,
+    idx: usize,
+}
+
+impl Iterator for FlacSamples {
+    type Item = i32;
+
+    fn next(&mut self) -> Option<i32> {
+        if self.idx >= self.block_size * self.channels {
+            let buffer = std::mem::take(&mut self.buffer);
+            let block = self.reader.blocks().read_next_or_eof(buffer).ok()??;
+            self.block_size = block.duration() as usize;
+            self.channels = block.channels() as usize;
+            self.buffer = block.into_buffer();
+            self.idx = 0;
+            if self.block_size == 0 {
+                return None;
+            }
+        }
+        let (frame, ch) = (self.idx / self.channels, self.idx % self.channels);
+        self.idx += 1;
+        Some(self.buffer[ch * self.block_size + frame])
+    }
+}
+
+/// Decodes a file frame by frame, converting to the output samplerate.
+struct FrameReader {
+    samples: Box<dyn Iterator<Item = f32> + Send>,
+    channels: usize,
+    step: f64,
+    frac: f64,
+    prev: Vec<f32>,
+    next: Vec<f32>,
+    exhausted: bool,
+    to_skip: usize, // samples before the offset that still need to be skipped
+    primed: bool,
+}
+
+impl FrameReader {
+    fn open(path: &str, offset: f32, samplerate: f32) -> Option<Self> {
+        let lc_path = path.to_lowercase();
+        let mut to_skip = 0;
+        let (samples, channels, file_samplerate): (Box<dyn Iterator<Item = f32> + Send>, _, _) =
+            if lc_path.trim().ends_with(".wav") {
+                let mut reader = hound::WavReader::open(path).ok()?;
+                let spec = reader.spec();
+                let start_frame =
+                    ((offset * 0.001 * spec.sample_rate as f32) as u32).min(reader.duration());
+                reader.seek(start_frame).ok()?;
+                let samples: Box<dyn Iterator<Item = f32> + Send> = match spec.sample_format {
+                    hound::SampleFormat::Float => {
+                        Box::new(reader.into_samples::<f32>().map(|x| x.unwrap_or(0.0)))
+                    }
+                    hound::SampleFormat::Int => {
+                        let max_val = (i32::MAX >> (32 - spec.bits_per_sample)) as f32;
+                        Box::new(
+                            reader
+                                .into_samples::<i32>()
+                                .map(move |x| x.unwrap_or(0) as f32 / max_val),
+                        )
+                    }
+                };
+                (samples, spec.channels as usize, spec.sample_rate as f32)
+            } else if lc_path.trim().ends_with(".flac") {
+                let reader = claxon::FlacReader::open(path).ok()?;
+                let info = reader.streaminfo();
+                let max_val = (i32::MAX >> (32 - info.bits_per_sample)) as f32;
+                // flac can't seek, so the samples before the offset are skipped
+                // bit by bit once the stream runs (see advance_offset)
+                to_skip =
+                    (offset * 0.001 * info.sample_rate as f32) as usize * info.channels as usize;
+                let samples: Box<dyn Iterator<Item = f32> + Send> = Box::new(
+                    FlacSamples {
+                        reader,
+                        buffer: Vec::new(),
+                        block_size: 0,
+                        channels: 0,
+                        idx: 0,
+                    }
+                    .map(move |x| x as f32 / max_val),
+                );
+                (samples, info.channels as usize, info.sample_rate as f32)
+            } else {
+                return None;
+            };
+
+        let mut reader = FrameReader {
+            samples,
+            channels,
+            step: file_samplerate as f64 / samplerate as f64,
+            frac: 0.0,
+            prev: vec![0.0; channels],
+            next: vec![0.0; channels],
+            exhausted: false,
+            to_skip,
+            primed: false,
+        };
+
+        // nothing left to play after the offset
+        if to_skip == 0 && !reader.prime() {
+            return None;
+        }
+
+        Some(reader)
+    }
+
+    /// read the first two frames to interpolate between,
+    /// returns false if there's nothing to play
+    fn prime(&mut self) -> bool {
+        if !self.read_next() {
+            return false;
+        }
+        self.prev.copy_from_slice(&self.next);
+        if !self.read_next() {
+            self.next.copy_from_slice(&self.prev);
+            self.exhausted = true;
+        }
+        self.primed = true;
+        true
+    }
+
+    /// whether the reader is still working through the offset
+    fn seeking(&self) -> bool {
+        !self.primed
+    }
+
+    /// Skip a chunk of the samples before the offset, so a long offset
+    /// doesn't hold up the other streams in the pool. Returns false if
+    /// there's nothing left to play after the offset.
+    fn advance_offset(&mut self) -> bool {
+        let n = self.to_skip.min(OFFSET_CHUNK);
+        self.to_skip -= n;
+        if n > 0 && self.samples.nth(n - 1).is_none() {
+            return false;
+        }
+        if self.to_skip == 0 {
+            return self.prime();
+        }
+        true
+    }
+
+    fn read_next(&mut self) -> bool {
+        for n in self.next.iter_mut() {
+            if let Some(s) = self.samples.next() {
+                *n = s;
+            } else {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// linear interpolation, which should be good enough for the
+    /// usual 44.1 vs. 48k mismatch
+    fn next_frame(&mut self, frame: &mut [f32]) -> bool {
+        while self.frac >= 1.0 {
+            if self.exhausted {
+                return false;
+            }
+            self.prev.copy_from_slice(&self.next);
+            if !self.read_next() {
+                // still play the last frame
+                self.next.copy_from_slice(&self.prev);
+                self.exhausted = true;
+            }
+            self.frac -= 1.0;
+        }
+        for (ch, f) in frame.iter_mut().enumerate().take(self.channels) {
+            *f = self.prev[ch] + (self.next[ch] - self.prev[ch]) * self.frac as f32;
+        }
+        self.frac += self.step;
+        true
+    }
+}
+
+/// get stream id and parameters from a stream event
+pub fn stream_parameters(
+    ev: &StaticEvent,
+    start_time: f64,
+    output_mode: OutputMode,
+) -> (usize, StreamParameters) {
+    let get = |label| {
+        if let Some(SynthParameterValue::ScalarF32(v)) = ev.params.get(&label) {
+            Some(*v)
+        } else {
+            None
+        }
+    };
+
+    let id = if let Some(SynthParameterValue::ScalarUsize(b)) =
+        ev.params.get(&SynthParameterLabel::SampleBufferNumber)
+    {
+        *b
+    } else {
+        0
+    };
+
+    let mut position = get(SynthParameterLabel::ChannelPosition).unwrap_or(0.0);
+    if output_mode == OutputMode::Stereo {
+        position = ((position + 1.0) * 0.5).clamp(0.0, 1.0);
+    }
+
+    (
+        id,
+        StreamParameters {
+            start_time,
+            offset: get(SynthParameterLabel::PlaybackStart).unwrap_or(0.0),
+            length: get(SynthParameterLabel::Sustain),
+            level: get(SynthParameterLabel::EnvelopeLevel).unwrap_or(0.5),
+            position,
+        },
+    )
+}
+
+/// create the control and audio thread sides of the stream player,
+/// and start the reader pool
+pub fn init_stream_player<const BUFSIZE: usize, const NCHAN: usize>(
+    sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>,
+    samplerate: f32,
+) -> (StreamControl<BUFSIZE, NCHAN>, StreamMixer<BUFSIZE, NCHAN>) {
+    let (voice_tx, voice_rx) = crossbeam::channel::bounded(MAX_VOICES);
+    let (finished_tx, finished_rx) = crossbeam::channel::bounded(MAX_VOICES);
+    let (request_tx, request_rx) = crossbeam::channel::bounded(MAX_VOICES);
+
+    let pool = ReaderPool {
+        streams: Vec::new(),
+        new_voices: voice_tx,
+        samplerate,
+    };
+
+    thread::Builder::new()
+        .name("stream_readers".to_string())
+        .spawn(move || pool.run(request_rx, finished_rx))
+        .unwrap();
+
+    let control = StreamControl {
+        requests: request_tx,
+        sample_set: sync::Arc::clone(sample_set),
+    };
+
+    let mixer = StreamMixer {
+        new_voices: voice_rx,
+        finished_voices: finished_tx,
+        voices: Vec::with_capacity(MAX_VOICES),
+        now: 0.0,
+        samplerate: samplerate as f64,
+    };
+
+    (control, mixer)
+}
+
+// TEST TEST TEST
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    // a mono file with a constant signal, in the temp dir
+    fn write_test_file(name: &str, frames: usize) -> String {
+        let path = std::env::temp_dir()
+            .join(name)
+            .to_str()
+            .unwrap()
+            .to_string();
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 44100,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..frames {
+            writer.write_sample(1.0_f32).unwrap();
+        }
+        writer.finalize().unwrap();
+        path
+    }
+
+    fn register(sample_set: &sync::Arc<Mutex<SampleAndWavematrixSet>>, path: &str) -> usize {
+        sample_set.lock().insert_stream(
+            "test".to_string(),
+            crate::sample_set::SampleInfo {
+                key: std::collections::HashSet::new(),
+                bufnum: 0,
+                duration: 1000,
+                path: path.to_string(),
+                downmix_stereo: true,
+                max_duration: None,
+            },
+        )
+    }
+
+    fn params(offset: f32) -> StreamParameters {
+        StreamParameters {
+            start_time: 0.0,
+            offset,
+            length: None,
+            level: 1.0,
+            position: 0.0,
+        }
+    }
+
+    // wait for the reader pool to hand the voice over
+    fn receive_voice<const BUFSIZE: usize, const NCHAN: usize>(
+        mixer: &mut StreamMixer<BUFSIZE, NCHAN>,
+    ) {
+        let voice = mixer
+            .new_voices
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap();
+        mixer.voices.push(voice);
+    }
+
+    #[test]
+    fn test_stream_player() {
+        let path = write_test_file("megra_stream_test.wav", 44100);
+        let sample_set = sync::Arc::new(Mutex::new(SampleAndWavematrixSet::new()));
+        let id = register(&sample_set, &path);
+
+        let (control, mut mixer) = init_stream_player::<512, 2>(&sample_set, 44100.0);
+        control.start_stream(id, params(500.0));
+        // the rest of the file fits into the blocks decoded ahead
+        receive_voice(&mut mixer);
+
+        let mut played = 0;
+        for _ in 0..100 {
+            let mut block = [[0.0; 512]; 2];
+            mixer.mix(&mut block);
+            played += block[0].iter().filter(|s| **s > 0.0).count();
+        }
+
+        // half a second left after the offset, position 0.0 is all left channel ...
+        assert_eq!(played, 22050 - 1);
+        assert!(mixer.voices.is_empty());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_stream_restart() {
+        let path = write_test_file("megra_stream_restart_test.wav", 441000);
+        let sample_set = sync::Arc::new(Mutex::new(SampleAndWavematrixSet::new()));
+        let id = register(&sample_set, &path);
+
+        let (control, mut mixer) = init_stream_player::<512, 2>(&sample_set, 44100.0);
+        control.start_stream(id, params(0.0));
+        receive_voice(&mut mixer);
+        mixer.mix(&mut [[0.0; 512]; 2]);
+        assert_eq!(mixer.voices.len(), 1);
+
+        // starting the same file again stops the first one,
+        // which fades out within the next block
+        control.start_stream(id, params(0.0));
+        receive_voice(&mut mixer);
+        mixer.mix(&mut [[0.0; 512]; 2]);
+        assert_eq!(mixer.voices.len(), 1);
+        assert!(!mixer.voices[0].stop.load(Ordering::Relaxed));
+
+        // requests are handled in order, so once the next voice
+        // is there, the others have been stopped
+        control.stop_all();
+        control.start_stream(id, params(0.0));
+        receive_voice(&mut mixer);
+        mixer.mix(&mut [[0.0; 512]; 2]);
+        assert_eq!(mixer.voices.len(), 1);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_flac_offset() {
+        // a ramp, so we can tell where playback starts
+        let path = std::env::temp_dir()
+            .join("megra_stream_offset_test.flac")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let frames = 44100 * 2;
+        let mut writer = crate::flac_writer::FlacWriter::create(&path, 1, 44100, 24).unwrap();
+        for i in 0..frames {
+            writer.write_frame(&[i as f32 / frames as f32]).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        // the offset is skipped bit by bit, not on opening
+        let mut reader = FrameReader::open(&path, 1500.0, 44100.0).unwrap();
+        assert!(reader.seeking());
+        let mut rounds = 0;
+        while reader.seeking() {
+            assert!(reader.advance_offset());
+            rounds += 1;
+        }
+        assert_eq!(rounds, (44100 * 3 / 2) / OFFSET_CHUNK + 1);
+
+        let mut frame = vec![0.0];
+        assert!(reader.next_frame(&mut frame));
+        assert!((frame[0] - 0.75).abs() < 0.001);
+
+        // nothing left after the offset
+        let mut reader = FrameReader::open(&path, 3000.0, 44100.0).unwrap();
+        while reader.seeking() {
+            if !reader.advance_offset() {
+                break;
+            }
+        }
+        assert!(reader.seeking());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}